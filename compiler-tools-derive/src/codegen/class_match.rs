@@ -8,7 +8,12 @@ pub(crate) fn gen_class_match(tokens_to_parse: &[TokenParseData], enum_ident: &I
     for info in tokens_to_parse {
         let ident = &info.ident;
 
-        if info.has_target {
+        if !info.capture_fields.is_empty() {
+            let fields = info.capture_fields_pattern();
+            matches.push(quote! {
+                (#enum_ident::#ident #fields, #enum_ident::#ident #fields) => true,
+            })
+        } else if info.has_target {
             //todo: what to do with its a parsed target that doesn't impl Display?
             matches.push(quote! {
                 (#enum_ident::#ident(_), #enum_ident::#ident(_)) => true,
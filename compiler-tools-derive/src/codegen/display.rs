@@ -3,12 +3,23 @@ use quote::quote;
 
 use crate::{TokenParseData, flatten};
 
-pub(crate) fn gen_display(tokens_to_parse: &[TokenParseData], enum_ident: &Ident) -> TokenStream {
+pub(crate) fn gen_display(tokens_to_parse: &[TokenParseData], enum_ident: &Ident, bytes: bool) -> TokenStream {
     let mut display_fields = vec![];
     for info in tokens_to_parse {
         let ident = &info.ident;
 
-        if info.has_target {
+        if !info.capture_fields.is_empty() {
+            // The fields don't add up to the matched text, so this displays like a variant without any.
+            let ident_str = format!("{}", ident);
+            let fields = info.capture_fields_pattern();
+            display_fields.push(quote! {
+                #enum_ident::#ident #fields => write!(f, "{}", #ident_str),
+            })
+        } else if info.has_target && bytes && !info.target_needs_parse {
+            display_fields.push(quote! {
+                #enum_ident::#ident(x) => write!(f, "{}", ::std::string::String::from_utf8_lossy(x)),
+            })
+        } else if info.has_target {
             //todo: what to do with its a parsed target that doesn't impl Display?
             display_fields.push(quote! {
                 #enum_ident::#ident(x) => write!(f, "{}", x),
@@ -3,24 +3,32 @@ use std::collections::BTreeMap;
 use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 
-use crate::{TokenParseData, construct_variant, flatten};
+use crate::{Candidate, TokenParseData, codegen::span::gen_span, emit_token, flatten, gen_captures, text_literal};
 
 pub(crate) fn gen_full_regex(
     tokens_to_parse: &[TokenParseData],
+    mode: u32,
     conflicts: &BTreeMap<(Ident, String), Vec<(Ident, String)>>,
     enum_ident: &Ident,
-    parse_fns: &mut BTreeMap<usize, Vec<TokenStream>>,
+    bytes: bool,
+    candidates: &mut BTreeMap<usize, Vec<Candidate>>,
 ) -> Result<(), TokenStream> {
-    for (token_index, item) in tokens_to_parse.iter().enumerate() {
+    // A `bytes` tokenizer matches with the `regex` crate's `&[u8]` engine.
+    let (regex_mod, input, as_text) = if bytes {
+        (quote! { ::compiler_tools::regex::bytes }, quote! { [u8] }, quote! { as_bytes })
+    } else {
+        (quote! { ::compiler_tools::regex }, quote! { str }, quote! { as_str })
+    };
+    for (token_index, item) in tokens_to_parse.iter().enumerate().filter(|(_, x)| x.in_mode(mode)) {
         for regex in &item.regexes {
-            let key = (item.ident.clone(), regex.clone());
-            let regex = format!("\\A(?:{})", regex);
+            let key = (item.ident.clone(), regex.text.clone());
+            let regex = format!("\\A(?:{})", regex.text);
 
             let fn_ident = format_ident!("parse_r_{}", item.ident);
             let regex_fn = quote! {
-                fn #fn_ident(from: &str) -> Option<(&str, &str)> {
-                    static REGEX: ::std::sync::OnceLock<::compiler_tools::regex::Regex> = ::std::sync::OnceLock::new();
-                    let regex = REGEX.get_or_init(|| ::compiler_tools::regex::Regex::new(#regex).unwrap());
+                fn #fn_ident(from: &#input) -> Option<(&#input, &#input)> {
+                    static REGEX: ::std::sync::OnceLock<#regex_mod::Regex> = ::std::sync::OnceLock::new();
+                    let regex = REGEX.get_or_init(|| #regex_mod::Regex::new(#regex).unwrap());
                     if let Some(matching) = regex.find(from) {
                         assert_eq!(matching.start(), 0);
                         Some((&from[..matching.end()], &from[matching.end()..]))
@@ -30,59 +38,62 @@ pub(crate) fn gen_full_regex(
                 }
             };
 
-            let constructed = construct_variant(item, enum_ident);
-
-            let span = quote! {
-                ::compiler_tools::Span {
-                    line_start: self.line,
-                    col_start: self.col,
-                    line_stop: {
-                        self.line += passed.chars().filter(|x| *x == '\n').count() as u64;
-                        self.line
-                    },
-                    //todo: handle utf8 better with newline seeking here
-                    col_stop: if let Some(trailing_bytes) = passed.as_bytes().iter().rev().position(|x| *x == b'\n') {
-                        // `trailing_bytes` is the number of bytes after the last newline,
-                        // which is the column on the final line.
-                        self.col = trailing_bytes as u64;
-                        self.col
-                    } else {
-                        self.col += passed.len() as u64;
-                        self.col
-                    },
+            let emit = emit_token(item, enum_ident, bytes);
+            let captures = if item.capture_fields.is_empty() {
+                quote! {}
+            } else {
+                // Validated by `gen_mode`.
+                let parsed = ::regex::bytes::Regex::new(&regex).unwrap();
+                let names: Vec<_> = parsed.capture_names().collect();
+                let fields = gen_captures(item, &key.1, &names, |group| quote! { groups.as_ref().and_then(|x| x.get(#group)).map(|x| x.#as_text()) })?;
+                let captures_fn = format_ident!("captures_r_{}", item.ident);
+                quote! {
+                    let captures = {
+                        fn #captures_fn(from: &#input) -> Option<#regex_mod::Captures<'_>> {
+                            static REGEX: ::std::sync::OnceLock<#regex_mod::Regex> = ::std::sync::OnceLock::new();
+                            REGEX.get_or_init(|| #regex_mod::Regex::new(#regex).unwrap()).captures(from)
+                        }
+                        let groups = #captures_fn(self.inner);
+                        #fields
+                    };
                 }
             };
 
+            let span = gen_span(true, bytes);
+
             let conflicts = conflicts.get(&key).cloned().unwrap_or_default();
             let mut conflict_resolutions = vec![];
             for (ident, literal) in conflicts {
                 let subitem = tokens_to_parse.iter().find(|x| x.ident == ident).expect("missing subitem");
-                let constructed = construct_variant(subitem, enum_ident);
+                let emit = emit_token(subitem, enum_ident, bytes);
+                let literal = text_literal(&literal, bytes);
 
                 conflict_resolutions.push(quote! {
-                    #literal => return Some(::compiler_tools::Spanned {
-                        token: #constructed,
-                        span,
-                    }),
+                    #literal => {
+                        #emit
+                    }
                 })
             }
             let conflict_resolutions = flatten(conflict_resolutions);
 
-            parse_fns.entry(token_index).or_default().push(quote! {
-                {
-                    #regex_fn
-                    if let Some((passed, remaining)) = #fn_ident(self.inner) {
-                        let span = #span;
-                        self.inner = remaining;
-                        match passed {
-                            #conflict_resolutions
-                            passed => return Some(::compiler_tools::Spanned {
-                                token: #constructed,
-                                span,
-                            }),
+            candidates.entry(token_index).or_default().push(Candidate {
+                matcher: quote! {
+                    {
+                        #regex_fn
+                        #fn_ident(self.inner).map(|(passed, remaining)| (#token_index, passed, remaining))
+                    }
+                },
+                emit: quote! {
+                    let span = #span;
+                    #captures
+                    self.inner = remaining;
+                    match passed {
+                        #conflict_resolutions
+                        passed => {
+                            #emit
                         }
                     }
-                }
+                },
             });
         }
     }
@@ -1,5 +1,7 @@
 pub(super) mod class_match;
 pub(super) mod display;
+pub(super) mod property_test;
 pub(super) mod simple_regex;
+pub(super) mod span;
 
 pub(super) mod full_regex;
@@ -0,0 +1,132 @@
+use std::collections::BTreeSet;
+
+use compiler_tools_regex::seeded_rng;
+use proc_macro2::{Ident, Literal, TokenStream};
+use quote::{format_ident, quote};
+
+use crate::{TokenParseData, TokenParseOptions, flatten, input_type};
+
+/// How many strings are made up from each simple regex.
+const SAMPLES: usize = 16;
+/// The most chars in a made-up string.
+const MAX_LEN: usize = 12;
+/// What the made-up strings are drawn from, the same on every run.
+const SEED: u64 = 0x2545_F491_4F6C_DD1D;
+
+/// A `#[cfg(test)]` test that lexes strings each variant's patterns match in full, checking each lexes as one token of
+/// that variant, or as nothing for a `skip` variant. The strings are made up here, with a fixed seed, so the test is the
+/// same on every run. A string spelling another variant's literal is left out, since the literal is meant to win it.
+///
+/// Only variants of the default mode with literals or simple regexes are tested: a `regex_full` or `parse_fn` can't be
+/// sampled, and a variant marked `#[token(allow_shadowed)]` is expected to lose to another.
+pub(crate) fn gen_property_test(tokens_to_parse: &[TokenParseData], enum_ident: &Ident, tokenizer_ident: &Ident, options: &TokenParseOptions) -> TokenStream {
+    let bytes = options.bytes;
+    // Samples of a bytes-mode regex are chars up to `U+00FF`, each a byte, so literals are spelled the same way.
+    let units = |literal: &String| if bytes { literal.bytes().map(char::from).collect() } else { literal.clone() };
+    let literals: Vec<(&Ident, String)> = tokens_to_parse
+        .iter()
+        .flat_map(|item| item.literals.iter().map(move |x| (&item.ident, units(x))))
+        .collect();
+    let mut checks = vec![];
+    for item in tokens_to_parse.iter().filter(|x| !x.is_illegal && !x.allow_shadowed && x.in_mode(0)) {
+        let mut samples: BTreeSet<String> = item.literals.iter().map(units).collect();
+        for pattern in &item.simple_regexes {
+            // An invalid pattern has already been reported.
+            let Ok(regex) = options.parse_simple_regex(&pattern.text) else {
+                continue;
+            };
+            samples.extend(
+                regex
+                    .samples(seeded_rng(SEED), MAX_LEN)
+                    .take(SAMPLES)
+                    .filter(|sample| !literals.iter().any(|(ident, literal)| *ident != &item.ident && literal == sample)),
+            );
+        }
+        // An empty match never makes a token.
+        samples.remove("");
+        if samples.is_empty() {
+            continue;
+        }
+        let samples = flatten(samples.iter().map(|sample| {
+            if bytes {
+                let sample = Literal::byte_string(&sample.chars().map(|c| c as u8).collect::<Vec<u8>>());
+                quote! { #sample, }
+            } else {
+                quote! { #sample, }
+            }
+        }));
+
+        let variant = &item.ident;
+        let variant_name = variant.to_string();
+        let check = if item.skip {
+            quote! {
+                assert!(matches!(&first, Ok(None)), "{:?} lexed as {} rather than being skipped as `{}`", sample, lexed, #variant_name);
+            }
+        } else {
+            let fields = if !item.capture_fields.is_empty() {
+                item.capture_fields_pattern()
+            } else if item.has_target {
+                quote! { (_) }
+            } else {
+                quote! {}
+            };
+            quote! {
+                // A payload that fails to parse still lexed as the variant.
+                let parse_failed = matches!(
+                    &first,
+                    Err(::compiler_tools::LexError { kind: ::compiler_tools::LexErrorKind::ParseFailed { variant, .. }, .. }) if variant == #variant_name
+                );
+                assert!(
+                    parse_failed || matches!(&first, Ok(Some(::compiler_tools::Spanned { token: #enum_ident::#variant #fields, .. }))),
+                    "{:?} lexed as {} rather than `{}`",
+                    sample,
+                    lexed,
+                    #variant_name
+                );
+                if !parse_failed {
+                    assert!(
+                        matches!(::compiler_tools::TokenParse::try_next(&mut tokenizer), Ok(None)),
+                        "{:?} lexed as more than one token, starting with `{}`",
+                        sample,
+                        #variant_name
+                    );
+                }
+            }
+        };
+        let input_ty = input_type(bytes);
+        checks.push(quote! {
+            let samples: &[&#input_ty] = &[#samples];
+            for sample in samples {
+                let mut tokenizer = #tokenizer_ident::new(sample);
+                let first = ::compiler_tools::TokenParse::try_next(&mut tokenizer);
+                let lexed = match &first {
+                    Ok(Some(x)) => format!("`{}`", x.token),
+                    Ok(None) => "nothing".to_string(),
+                    Err(e) => format!("an error ({})", e),
+                };
+                #check
+            }
+        });
+    }
+    let checks = flatten(checks);
+    let test_ident = format_ident!("{}_samples_lex_as_their_variants", snake_case(&enum_ident.to_string()));
+    quote! {
+        #[cfg(test)]
+        #[test]
+        fn #test_ident() {
+            #checks
+        }
+    }
+}
+
+/// `name` in snake case, as a function is named: `BinToken` becomes `bin_token`.
+fn snake_case(name: &str) -> String {
+    let mut out = String::new();
+    for (index, c) in name.chars().enumerate() {
+        if c.is_uppercase() && index > 0 {
+            out.push('_');
+        }
+        out.extend(c.to_lowercase());
+    }
+    out
+}
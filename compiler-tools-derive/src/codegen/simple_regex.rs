@@ -1,57 +1,88 @@
 use std::collections::BTreeMap;
 
+use compiler_tools_regex::{Regex as SimpleRegex, RegexSet as SimpleRegexSet};
 use proc_macro2::{Ident, TokenStream};
 use quote::{format_ident, quote};
 
-use crate::{SimpleRegexData, TokenParseData, construct_variant, flatten};
+use crate::{Candidate, SimpleRegexData, TokenParseData, codegen::span::gen_span, emit_token, flatten, gen_captures, text_literal};
 
+/// A candidate matcher reading pattern `pattern` of the tokenizer's regex set. The set is scanned once per token, on
+/// first use; `token_index` may refer to `passed`.
+pub(crate) fn gen_set_matcher(pattern: usize, token_index: TokenStream) -> TokenStream {
+    quote! {
+        scan.get_or_insert_with(|| parse_set(self.inner, None))[#pattern].map(|len| {
+            let (passed, remaining) = self.inner.split_at(len);
+            (#token_index, passed, remaining)
+        })
+    }
+}
+
+/// The patterns of the tokenizer's regex set, with a candidate reading each simple regex's result, and the index of the
+/// lit table's pattern. The set is walked first-match ([`SimpleRegexSet::generate_first_match_parser`]) outside
+/// `longest_match`, so its patterns go in in candidate order: `lit_table`, the lit table's pattern with the index of the
+/// variant it runs at, goes in after that variant's own regexes.
+///
+/// A regex with zero-width assertions can't join the set's combined DFA ([`SimpleRegexSet::combines`]), so it gets a
+/// matcher of its own instead. That scan only runs when its candidate is reached, so it's skipped once an earlier
+/// candidate has matched.
 pub(crate) fn gen_simple_regex(
     tokens_to_parse: &[TokenParseData],
-    parsed: &BTreeMap<(Ident, String), SimpleRegexData>,
+    mut parsed: BTreeMap<(Ident, String), SimpleRegexData>,
     conflicts: &BTreeMap<(Ident, String), Vec<(Ident, String)>>,
     enum_ident: &Ident,
-    parse_fns: &mut BTreeMap<usize, Vec<TokenStream>>,
-) -> Result<(), TokenStream> {
+    bytes: bool,
+    mut lit_table: Option<(usize, SimpleRegex)>,
+    candidates: &mut BTreeMap<usize, Vec<Candidate>>,
+) -> Result<(Vec<SimpleRegex>, Option<usize>), TokenStream> {
+    let mut set_patterns = vec![];
+    let mut lit_table_pattern = None;
     for (token_index, item) in tokens_to_parse.iter().enumerate() {
+        if lit_table.as_ref().is_some_and(|(index, _)| *index < token_index) {
+            lit_table_pattern = Some(set_patterns.len());
+            set_patterns.extend(lit_table.take().map(|(_, regex)| regex));
+        }
         for simple_regex in &item.simple_regexes {
-            let key = (item.ident.clone(), simple_regex.clone());
-            let parsed = &parsed.get(&key).unwrap().regex;
-            let fn_ident = format_ident!("parse_sr_{}", item.ident);
-            let parse_fn = parsed.generate_parser(fn_ident.clone());
-
-            let constructed = construct_variant(item, enum_ident);
+            let key = (item.ident.clone(), simple_regex.text.clone());
+            // A regex repeated on the same variant is already in the set.
+            let Some(SimpleRegexData {
+                regex: parsed,
+                ..
+            }) = parsed.remove(&key)
+            else {
+                continue;
+            };
 
-            let span = if parsed.could_capture_newline() {
+            let emit = emit_token(item, enum_ident, bytes);
+            let captures = if item.capture_fields.is_empty() {
+                quote! {}
+            } else {
+                let vm = parsed.pike_vm();
+                let names: Vec<_> = vm.capture_names().collect();
+                let fields = gen_captures(item, &simple_regex.text, &names, |group| quote! { groups[#group].map(|(start, end)| &passed[start..end]) })?;
+                let captures_fn = vm.generate_captures(format_ident!("capture_groups"));
+                let len = vm.captures_len();
+                // The capture pass reruns the match the set scan found, so it only fails to reproduce it on an engine
+                // bug; every group is then left unset.
                 quote! {
-                    ::compiler_tools::Span {
-                        line_start: self.line,
-                        col_start: self.col,
-                        line_stop: {
-                            self.line += passed.chars().filter(|x| *x == '\n').count() as u64;
-                            self.line
-                        },
-                        //todo: handle utf8 better with newline seeking here
-                        col_stop: if let Some(trailing_bytes) = passed.as_bytes().iter().rev().position(|x| *x == b'\n') {
-                            // `trailing_bytes` is the number of bytes after the last newline,
-                            // which is the column on the final line.
-                            self.col = trailing_bytes as u64;
-                            self.col
-                        } else {
-                            self.col += passed.len() as u64;
-                            self.col
-                        },
-                    }
+                    let captures = {
+                        #captures_fn
+                        let groups = capture_groups(self.inner, passed.len(), None).unwrap_or([None; #len]);
+                        #fields
+                    };
                 }
+            };
+
+            let span = gen_span(parsed.could_capture_newline(), bytes);
+            let matcher = if SimpleRegexSet::combines(&parsed) {
+                let pattern = set_patterns.len();
+                set_patterns.push(parsed);
+                gen_set_matcher(pattern, quote! { #token_index })
             } else {
+                let parser = parsed.generate_parser(format_ident!("parse_standalone"));
                 quote! {
-                    ::compiler_tools::Span {
-                        line_start: self.line,
-                        col_start: self.col,
-                        line_stop: self.line,
-                        col_stop: {
-                            self.col += passed.len() as u64;
-                            self.col
-                        },
+                    {
+                        #parser
+                        parse_standalone(self.inner, None).map(|(passed, remaining)| (#token_index, passed, remaining))
                     }
                 }
             };
@@ -60,37 +91,37 @@ pub(crate) fn gen_simple_regex(
             let mut conflict_resolutions = vec![];
             for (ident, literal) in conflicts {
                 let subitem = tokens_to_parse.iter().find(|x| x.ident == ident).expect("missing subitem");
-                let constructed = construct_variant(subitem, enum_ident);
+                let emit = emit_token(subitem, enum_ident, bytes);
+                let literal = text_literal(&literal, bytes);
 
                 conflict_resolutions.push(quote! {
-                    #literal => return Some(::compiler_tools::Spanned {
-                        token: #constructed,
-                        span,
-                    }),
+                    #literal => {
+                        #emit
+                    }
                 })
             }
             let conflict_resolutions = flatten(conflict_resolutions);
 
-            parse_fns.entry(token_index).or_default().push(quote! {
-                {
-                    #parse_fn
-                    // The tokenizer anchors each match at the current position, treated
-                    // as start of text/line, so no preceding char is threaded through.
-                    if let Some((passed, remaining)) = #fn_ident(self.inner, None) {
-                        let span = #span;
-                        self.inner = remaining;
-                        match passed {
-                            #conflict_resolutions
-                            passed => return Some(::compiler_tools::Spanned {
-                                token: #constructed,
-                                span,
-                            }),
+            candidates.entry(token_index).or_default().push(Candidate {
+                matcher,
+                emit: quote! {
+                    let span = #span;
+                    #captures
+                    self.inner = remaining;
+                    match passed {
+                        #conflict_resolutions
+                        passed => {
+                            #emit
                         }
                     }
-                }
+                },
             });
         }
     }
 
-    Ok(())
+    if let Some((_, regex)) = lit_table {
+        lit_table_pattern = Some(set_patterns.len());
+        set_patterns.push(regex);
+    }
+    Ok((set_patterns, lit_table_pattern))
 }
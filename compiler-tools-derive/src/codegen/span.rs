@@ -0,0 +1,108 @@
+use proc_macro2::TokenStream;
+use quote::quote;
+
+use crate::ColumnUnit;
+
+/// Emits a `::compiler_tools::Span` expression covering `passed`, advancing the tokenizer's line, column and byte offset past it.
+/// Matches that can never contain a newline skip the rescan for line breaks. `passed` is `&[u8]` for a `bytes` tokenizer.
+pub(crate) fn gen_span(could_capture_newline: bool, bytes: bool) -> TokenStream {
+    let (newlines, last_newline) = if bytes {
+        (quote! { passed.iter().filter(|x| **x == b'\n') }, quote! { passed.iter().rposition(|x| *x == b'\n') })
+    } else {
+        (quote! { passed.chars().filter(|x| *x == '\n') }, quote! { passed.rfind('\n') })
+    };
+    if could_capture_newline {
+        quote! {
+            ::compiler_tools::Span {
+                line_start: self.line,
+                col_start: self.col,
+                line_stop: {
+                    self.line += #newlines.count() as u64;
+                    self.line
+                },
+                col_stop: if let Some(newline) = #last_newline {
+                    // the column on the final line counts only the text after the last newline
+                    self.col = Self::advance_col(0, &passed[newline + 1..]);
+                    self.col
+                } else {
+                    self.col = Self::advance_col(self.col, passed);
+                    self.col
+                },
+                byte_start: self.offset,
+                byte_end: {
+                    self.offset += passed.len() as u64;
+                    self.offset
+                },
+                file: self.file,
+            }
+        }
+    } else {
+        quote! {
+            ::compiler_tools::Span {
+                line_start: self.line,
+                col_start: self.col,
+                line_stop: self.line,
+                col_stop: {
+                    self.col = Self::advance_col(self.col, passed);
+                    self.col
+                },
+                byte_start: self.offset,
+                byte_end: {
+                    self.offset += passed.len() as u64;
+                    self.offset
+                },
+                file: self.file,
+            }
+        }
+    }
+}
+
+/// Emits the tokenizer's `advance_col(col, text) -> u64`: the column after `text`, which holds no newline, starting
+/// at `col`. The `&[u8]` text of a `bytes` tokenizer counts chars and UTF-16 units of its lossy UTF-8 decoding.
+pub(crate) fn gen_advance_col(columns: ColumnUnit, tab_width: Option<u64>, bytes: bool) -> TokenStream {
+    if bytes {
+        return match (columns, tab_width) {
+            (ColumnUnit::Bytes, None) => quote! {
+                fn advance_col(col: u64, text: &[u8]) -> u64 {
+                    col + text.len() as u64
+                }
+            },
+            (ColumnUnit::Bytes, Some(tab_width)) => quote! {
+                fn advance_col(col: u64, text: &[u8]) -> u64 {
+                    text.iter().fold(col, |col, c| if *c == b'\t' { (col / #tab_width + 1) * #tab_width } else { col + 1 })
+                }
+            },
+            (_, _) => {
+                let advance_col = gen_advance_col(columns, tab_width, false);
+                quote! {
+                    fn advance_col(col: u64, text: &[u8]) -> u64 {
+                        #advance_col
+                        advance_col(col, &::std::string::String::from_utf8_lossy(text))
+                    }
+                }
+            }
+        };
+    }
+    let width = match columns {
+        ColumnUnit::Bytes => quote! { c.len_utf8() },
+        ColumnUnit::Chars => quote! { 1 },
+        ColumnUnit::Utf16 => quote! { c.len_utf16() },
+    };
+    match (columns, tab_width) {
+        (ColumnUnit::Bytes, None) => quote! {
+            fn advance_col(col: u64, text: &str) -> u64 {
+                col + text.len() as u64
+            }
+        },
+        (_, None) => quote! {
+            fn advance_col(col: u64, text: &str) -> u64 {
+                col + text.chars().map(|c| #width as u64).sum::<u64>()
+            }
+        },
+        (_, Some(tab_width)) => quote! {
+            fn advance_col(col: u64, text: &str) -> u64 {
+                text.chars().fold(col, |col, c| if c == '\t' { (col / #tab_width + 1) * #tab_width } else { col + #width as u64 })
+            }
+        },
+    }
+}
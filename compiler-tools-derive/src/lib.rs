@@ -2,41 +2,252 @@ use std::collections::{BTreeMap, HashSet};
 
 use indexmap::IndexMap;
 use proc_macro::TokenStream;
-use proc_macro2::{Ident, TokenStream as TokenStream2, TokenTree};
-use quote::{format_ident, quote, quote_spanned};
-use regex::Regex;
-use syn::{DeriveInput, Expr, ExprLit, ExprPath, Fields, FieldsUnnamed, Lifetime, Lit, Meta, Type, parse_macro_input, spanned::Spanned};
+use proc_macro2::{Ident, Literal, TokenStream as TokenStream2, TokenTree};
+use quote::{ToTokens, format_ident, quote, quote_spanned};
+use regex::{Regex, bytes::Regex as BytesRegex};
+use syn::{
+    DeriveInput, Expr, ExprLit, ExprPath, Fields, FieldsNamed, FieldsUnnamed, GenericArgument, Lifetime, Lit, Meta, PathArguments, Type, TypePath,
+    parse_macro_input, spanned::Spanned,
+};
 
 // Aliased to disambiguate from the `regex` crate's `Regex` (imported above), which
 // this file also uses for the `regex_full` path.
-use compiler_tools_regex::{Regex as SimpleRegex, flatten};
+use compiler_tools_regex::{ParseOptions, Regex as SimpleRegex, RegexError, RegexSet as SimpleRegexSet, flatten};
 
-use crate::{codegen::class_match::gen_class_match, lit_table::LitTable};
+use crate::{
+    codegen::{
+        class_match::gen_class_match,
+        property_test::gen_property_test,
+        simple_regex::gen_set_matcher,
+        span::{gen_advance_col, gen_span},
+    },
+    lit_table::LitTable,
+    pattern::Pattern,
+    shadow::warn_shadowed,
+};
 
 mod codegen;
 mod lit_table;
+mod pattern;
+mod shadow;
 
 #[proc_macro_attribute]
-pub fn token_parse(_metadata: TokenStream, input: TokenStream) -> TokenStream {
+pub fn token_parse(metadata: TokenStream, input: TokenStream) -> TokenStream {
+    let options = match TokenParseOptions::parse(metadata.into()) {
+        Ok(x) => x,
+        Err(e) => return e.into(),
+    };
     let ast = parse_macro_input!(input as DeriveInput);
-    impl_token_parse(&ast).into()
+    impl_token_parse(&ast, &options).into()
+}
+
+/// Grammar-wide settings passed as `#[token_parse(...)]` arguments.
+#[derive(Default)]
+struct TokenParseOptions {
+    /// Evaluate every variant at each position and keep the longest match, with declaration order only breaking ties.
+    /// The combined scan then runs until no pattern can extend rather than stopping once an earlier one has matched, so
+    /// a pattern that can run past the token that wins (`[a-z ]+!` beside `[a-z]+`) rescans the line on every token.
+    /// Simple regexes with zero-width assertions (`\b`, `^`, `$`) are left out of the combined scan, so each then costs a
+    /// scan of its own on every token, where otherwise it only runs when no earlier variant matched.
+    longest_match: bool,
+    /// What a span column counts.
+    columns: ColumnUnit,
+    /// Tab stop interval, if tabs advance the column to the next tab stop rather than by one unit.
+    tab_width: Option<u64>,
+    /// Lex `&[u8]` rather than `&str`: simple regexes and literals match bytes, and borrowed payloads are `&'a [u8]`.
+    bytes: bool,
+    /// Emit a `#[cfg(test)]` test lexing strings made up from each variant's patterns, which must lex as that variant.
+    property_tests: bool,
+    /// Read the intersection `(?&...)`, difference `(?-...)` and complement `~(...)` operators in simple regexes.
+    set_operators: bool,
+}
+
+#[derive(Default, Clone, Copy)]
+enum ColumnUnit {
+    #[default]
+    Bytes,
+    Chars,
+    /// UTF-16 code units, as used by LSP.
+    Utf16,
+}
+
+impl TokenParseOptions {
+    fn parse(metadata: TokenStream2) -> Result<Self, TokenStream2> {
+        let span = metadata.span();
+        let Some(attributes) = parse_attributes(metadata) else {
+            return Err(quote_spanned! {
+                span =>
+                compile_error!("invalid token_parse argument syntax");
+            });
+        };
+        let mut options = TokenParseOptions::default();
+        for (name, value) in attributes {
+            let value = value.map(|(value, _)| value);
+            match &*name {
+                "longest_match" => {
+                    if value.is_some() {
+                        return Err(quote_spanned! {
+                            span =>
+                            compile_error!("unexpected value for 'longest_match'");
+                        });
+                    }
+                    options.longest_match = true;
+                }
+                "bytes" => {
+                    if value.is_some() {
+                        return Err(quote_spanned! {
+                            span =>
+                            compile_error!("unexpected value for 'bytes'");
+                        });
+                    }
+                    options.bytes = true;
+                }
+                "property_tests" => {
+                    if value.is_some() {
+                        return Err(quote_spanned! {
+                            span =>
+                            compile_error!("unexpected value for 'property_tests'");
+                        });
+                    }
+                    options.property_tests = true;
+                }
+                "set_operators" => {
+                    if value.is_some() {
+                        return Err(quote_spanned! {
+                            span =>
+                            compile_error!("unexpected value for 'set_operators'");
+                        });
+                    }
+                    options.set_operators = true;
+                }
+                "columns" => {
+                    options.columns = match value.as_deref() {
+                        Some("bytes") => ColumnUnit::Bytes,
+                        Some("chars") => ColumnUnit::Chars,
+                        Some("utf16") => ColumnUnit::Utf16,
+                        _ => {
+                            return Err(quote_spanned! {
+                                span =>
+                                compile_error!("expected 'columns' to be one of \"bytes\", \"chars\" or \"utf16\"");
+                            });
+                        }
+                    };
+                }
+                "tab_width" => match value.and_then(|x| x.parse::<u64>().ok()).filter(|x| *x > 0) {
+                    Some(tab_width) => options.tab_width = Some(tab_width),
+                    None => {
+                        return Err(quote_spanned! {
+                            span =>
+                            compile_error!("expected 'tab_width' to be a positive integer");
+                        });
+                    }
+                },
+                _ => {
+                    return Err(quote_spanned! {
+                        span =>
+                        compile_error!("unknown token_parse argument");
+                    });
+                }
+            }
+        }
+        Ok(options)
+    }
+
+    /// Parses the text of a `regex` attribute as these options have it read.
+    fn parse_simple_regex(&self, text: &str) -> Result<SimpleRegex, RegexError> {
+        SimpleRegex::parse_with(
+            text,
+            ParseOptions {
+                bytes: self.bytes,
+                set_operators: self.set_operators,
+            },
+        )
+    }
 }
 
 struct TokenParseData {
     has_target: bool,
     target_needs_parse: bool,
+    /// Whether the payload is the `u8` an illegal token of a `bytes` tokenizer holds, rather than parsed from its text.
+    target_is_byte: bool,
+    /// The fields of a variant with named fields or more than one field, each filled from a capture group of the
+    /// matching regex.
+    capture_fields: Vec<CaptureField>,
     is_illegal: bool,
     literals: Vec<String>,
-    simple_regexes: Vec<String>,
-    regexes: Vec<String>,
+    simple_regexes: Vec<Pattern>,
+    regexes: Vec<Pattern>,
     parse_fn: Option<String>,
+    /// Indices of the lexer modes the variant is matched in; empty for the default mode only
+    modes: Vec<u32>,
+    /// Mode pushed onto the tokenizer's mode stack after the variant is lexed
+    push_mode: Option<u32>,
+    /// Whether the tokenizer's mode stack is popped after the variant is lexed
+    pop_mode: bool,
+    /// Whether matches are consumed without being returned, as trivia
+    skip: bool,
+    /// Whether the variant is `#[token(allow_shadowed)]`, so its patterns may be shadowed by an earlier variant's
+    allow_shadowed: bool,
     ident: Ident,
 }
 
-fn parse_attributes(input: TokenStream2) -> Option<IndexMap<String, Option<String>>> {
+impl TokenParseData {
+    /// Whether the variant is tried while the tokenizer is in `mode`. The illegal fallback applies in every mode.
+    pub(crate) fn in_mode(&self, mode: u32) -> bool {
+        if self.is_illegal {
+            true
+        } else if self.modes.is_empty() {
+            mode == 0
+        } else {
+            self.modes.contains(&mode)
+        }
+    }
+
+    /// The pattern ignoring a multi-field variant's fields, `(..)` or `{ .. }`.
+    pub(crate) fn capture_fields_pattern(&self) -> TokenStream2 {
+        if self.capture_fields.first().is_some_and(|x| x.name.is_some()) {
+            quote! { { .. } }
+        } else {
+            quote! { (..) }
+        }
+    }
+}
+
+/// A field of a multi-field variant.
+struct CaptureField {
+    /// The field's name, which is also the name of the capture group it is filled from. The fields of a tuple variant
+    /// are filled from groups 1, 2, ... in order.
+    name: Option<Ident>,
+    /// Whether the field is an `Option`, `None` when its group doesn't participate in the match. Otherwise such a group
+    /// reads as empty text.
+    optional: bool,
+    needs_parse: bool,
+}
+
+/// Lexer mode names, by index. Mode 0 is the default mode the tokenizer starts in.
+struct ModeTable {
+    names: Vec<String>,
+}
+
+impl ModeTable {
+    const DEFAULT: &'static str = "default";
+
+    fn intern(&mut self, name: &str) -> u32 {
+        match self.names.iter().position(|x| x == name) {
+            Some(index) => index as u32,
+            None => {
+                self.names.push(name.to_string());
+                self.names.len() as u32 - 1
+            }
+        }
+    }
+}
+
+/// Parses `name = value, name, ...` attribute arguments, keeping each value's literal token alongside it.
+fn parse_attributes(input: TokenStream2) -> Option<IndexMap<String, Option<(String, Literal)>>> {
     let mut tokens = input.into_iter();
 
-    let mut attributes = IndexMap::<String, Option<String>>::new();
+    let mut attributes = IndexMap::<String, Option<(String, Literal)>>::new();
 
     loop {
         let name = match tokens.next() {
@@ -54,16 +265,23 @@ fn parse_attributes(input: TokenStream2) -> Option<IndexMap<String, Option<Strin
             }
             Some(TokenTree::Punct(p)) if p.as_char() == '=' => {
                 let value = if let TokenTree::Literal(literal) = tokens.next()? {
-                    let lit = Lit::new(literal);
-                    Some(match lit {
+                    let lit = Lit::new(literal.clone());
+                    let value = match lit {
                         Lit::Str(s) => s.value(),
                         Lit::Char(c) => c.value().to_string(),
+                        Lit::Int(i) => i.base10_digits().to_string(),
                         _ => return None,
-                    })
+                    };
+                    Some((value, literal))
                 } else {
                     return None;
                 };
                 attributes.insert(name.to_string(), value);
+                match tokens.next() {
+                    None => break,
+                    Some(TokenTree::Punct(p)) if p.as_char() == ',' => (),
+                    _ => return None,
+                }
             }
             _ => return None,
         }
@@ -71,13 +289,180 @@ fn parse_attributes(input: TokenStream2) -> Option<IndexMap<String, Option<Strin
     Some(attributes)
 }
 
-fn construct_variant(item: &TokenParseData, enum_ident: &Ident) -> TokenStream2 {
+/// Whether a payload of type `ty` is parsed from its text with `FromStr`, rather than borrowing it as `&'a str`, or
+/// `&'a [u8]` for a `bytes` tokenizer. `lifetime` is the enum's lifetime parameter, the only one a borrowed payload may use.
+fn payload_needs_parse(ty: &Type, lifetime: Option<&Ident>, bytes: bool) -> Result<bool, &'static str> {
+    let Type::Reference(ty) = ty else {
+        return Ok(true);
+    };
+    if ty.mutability.is_some() {
+        return Err("cannot have `&mut` in TokenParse variant");
+    }
+    if !matches!(&ty.lifetime, Some(Lifetime { ident, ..}) if Some(ident) == lifetime) {
+        return Err("unexpected lifetime in TokenParse variant (use the same one as defined in enum declaration)");
+    }
+    match &*ty.elem {
+        Type::Path(path) if !bytes && is_primitive(path, "str") => Ok(false),
+        Type::Slice(slice) if bytes && matches!(&*slice.elem, Type::Path(path) if is_primitive(path, "u8")) => Ok(false),
+        _ if bytes => Err("invalid type in reference for TokenParse (only &[u8] allowed in a bytes tokenizer)"),
+        _ => Err("invalid type in reference for TokenParse (only &str allowed)"),
+    }
+}
+
+fn is_primitive(path: &TypePath, name: &str) -> bool {
+    path.qself.is_none() && path.path.segments.len() == 1 && path.path.segments.first().unwrap().ident == name
+}
+
+/// `T` if `ty` is `Option<T>`.
+fn option_inner(ty: &Type) -> Option<&Type> {
+    let Type::Path(path) = ty else {
+        return None;
+    };
+    let segment = path.path.segments.last()?;
+    if path.qself.is_some() || segment.ident != "Option" {
+        return None;
+    }
+    let PathArguments::AngleBracketed(arguments) = &segment.arguments else {
+        return None;
+    };
+    match arguments.args.first() {
+        Some(GenericArgument::Type(ty)) if arguments.args.len() == 1 => Some(ty),
+        _ => None,
+    }
+}
+
+/// A `match` parsing `text` into a payload of `variant`, returning a `ParseFailed` error from `try_next()` if it fails.
+/// The bytes of a `bytes` tokenizer must be UTF-8 to parse.
+fn parse_payload(text: TokenStream2, variant: &Ident, bytes: bool) -> TokenStream2 {
+    let variant_name = variant.to_string();
+    let fail = quote! {
+        return Err(::compiler_tools::LexError {
+            span,
+            kind: ::compiler_tools::LexErrorKind::ParseFailed {
+                variant: #variant_name.to_string(),
+                message: ::std::string::ToString::to_string(&e),
+            },
+        });
+    };
+    let parse = |text: TokenStream2| {
+        quote! {
+            match #text.parse() {
+                Ok(x) => x,
+                Err(e) => {
+                    #fail
+                }
+            }
+        }
+    };
+    if bytes {
+        let parsed = parse(quote! { text });
+        quote! {
+            match ::core::str::from_utf8(#text) {
+                Ok(text) => #parsed,
+                Err(e) => {
+                    #fail
+                }
+            }
+        }
+    } else {
+        parse(text)
+    }
+}
+
+/// The tokenizer's input type behind the `&`.
+pub(crate) fn input_type(bytes: bool) -> TokenStream2 {
+    if bytes {
+        quote! { [u8] }
+    } else {
+        quote! { str }
+    }
+}
+
+/// The literal `text` as a pattern or expression of the tokenizer's input type: a string literal, or a byte string
+/// literal for a `bytes` tokenizer.
+pub(crate) fn text_literal(text: &str, bytes: bool) -> TokenStream2 {
+    if bytes {
+        proc_macro2::Literal::byte_string(text.as_bytes()).into_token_stream()
+    } else {
+        quote! { #text }
+    }
+}
+
+/// The text of each capture field, in declaration order, as `[Option<&str>; N]`. `group(i)` is the text of capture
+/// group `i` of the match. Errors name the field that has no group in `pattern`.
+pub(crate) fn gen_captures(
+    item: &TokenParseData,
+    pattern: &str,
+    names: &[Option<&str>],
+    group: impl Fn(usize) -> TokenStream2,
+) -> Result<TokenStream2, TokenStream2> {
+    let mut fields = vec![];
+    for (index, field) in item.capture_fields.iter().enumerate() {
+        let index = match &field.name {
+            Some(name) => names.iter().position(|x| *x == Some(&*name.to_string())),
+            None => Some(index + 1).filter(|x| *x < names.len()),
+        };
+        let Some(index) = index else {
+            let message = match &field.name {
+                Some(name) => format!("regex '{}' has no capture group named '{}'", pattern, name),
+                None => format!("regex '{}' has fewer capture groups than '{}' has fields", pattern, item.ident),
+            };
+            return Err(quote_spanned! {
+                item.ident.span() =>
+                compile_error!(#message);
+            });
+        };
+        let group = group(index);
+        fields.push(quote! { #group, });
+    }
+    let fields = flatten(fields);
+    Ok(quote! { [#fields] })
+}
+
+fn construct_variant(item: &TokenParseData, enum_ident: &Ident, bytes: bool) -> TokenStream2 {
     let variant = &item.ident;
-    if item.has_target {
-        if item.target_needs_parse {
-            //TODO: emit better error for parsefail
+    if !item.capture_fields.is_empty() {
+        let empty = if bytes {
+            quote! { &[] }
+        } else {
+            quote! { "" }
+        };
+        let mut fields = vec![];
+        for (index, field) in item.capture_fields.iter().enumerate() {
+            let value = match (field.optional, field.needs_parse) {
+                (false, false) => quote! { captures[#index].unwrap_or(#empty) },
+                (false, true) => parse_payload(quote! { captures[#index].unwrap_or(#empty) }, variant, bytes),
+                (true, false) => quote! { captures[#index] },
+                (true, true) => {
+                    let parsed = parse_payload(quote! { text }, variant, bytes);
+                    quote! {
+                        match captures[#index] {
+                            Some(text) => Some(#parsed),
+                            None => None,
+                        }
+                    }
+                }
+            };
+            fields.push(match &field.name {
+                Some(name) => quote! { #name: #value, },
+                None => quote! { #value, },
+            });
+        }
+        let fields = flatten(fields);
+        if item.capture_fields[0].name.is_some() {
+            quote! { #enum_ident::#variant { #fields } }
+        } else {
+            quote! { #enum_ident::#variant(#fields) }
+        }
+    } else if item.has_target {
+        if item.target_is_byte {
             quote! {
-                #enum_ident::#variant(passed.parse().ok()?)
+                #enum_ident::#variant(passed[0])
+            }
+        } else if item.target_needs_parse {
+            let parsed = parse_payload(quote! { passed }, variant, bytes);
+            quote! {
+                #enum_ident::#variant(#parsed)
             }
         } else {
             quote! {
@@ -91,6 +476,44 @@ fn construct_variant(item: &TokenParseData, enum_ident: &Ident) -> TokenStream2
     }
 }
 
+/// Statements returning `item` from `try_next()`, with `passed` and `span` bound to its match. Applies the variant's mode
+/// transition once the token is constructed.
+///
+/// Skipped variants instead restart the lexing loop. An empty skipped match falls through as if it hadn't matched,
+/// since restarting on it would never make progress.
+pub(crate) fn emit_token(item: &TokenParseData, enum_ident: &Ident, bytes: bool) -> TokenStream2 {
+    let transition = match (item.push_mode, item.pop_mode) {
+        (Some(mode), _) => quote! { self.modes.push(#mode); },
+        (None, true) => quote! { self.modes.pop(); },
+        (None, false) => quote! {},
+    };
+    if item.skip {
+        return quote! {
+            if !passed.is_empty() {
+                #transition
+                continue;
+            }
+        };
+    }
+    let constructed = construct_variant(item, enum_ident, bytes);
+    quote! {
+        let token = #constructed;
+        #transition
+        return Ok(Some(::compiler_tools::Spanned {
+            token,
+            span,
+        }));
+    }
+}
+
+/// One way the generated `try_next()` can match a token at the current position.
+pub(crate) struct Candidate {
+    /// An `Option<(usize, &str, &str)>` expression: the matched variant's declaration index, the matched text and the remaining input.
+    matcher: TokenStream2,
+    /// Statements run with `token_index`, `passed` and `remaining` bound to a match; they return the token.
+    emit: TokenStream2,
+}
+
 struct SimpleRegexData {
     pub token_index: usize,
     pub regex: SimpleRegex,
@@ -98,10 +521,10 @@ struct SimpleRegexData {
 
 struct RegexData {
     pub token_index: usize,
-    pub regex: Regex,
+    pub regex: BytesRegex,
 }
 
-fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
+fn impl_token_parse(input: &DeriveInput, options: &TokenParseOptions) -> proc_macro2::TokenStream {
     if input.generics.params.len() > 1 || !matches!(input.generics.params.first(), None | Some(syn::GenericParam::Lifetime(_))) {
         return quote_spanned! {
             input.generics.span() =>
@@ -127,15 +550,25 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
 
     let mut tokens_to_parse = vec![];
     let mut has_illegal = false;
+    let mut modes = ModeTable {
+        names: vec![ModeTable::DEFAULT.to_string()],
+    };
     for variant in &items.variants {
         let mut parse_data = TokenParseData {
             has_target: false,
             target_needs_parse: false,
+            target_is_byte: false,
+            capture_fields: vec![],
             is_illegal: false,
             literals: vec![],
             simple_regexes: vec![],
             regexes: vec![],
             parse_fn: None,
+            modes: vec![],
+            push_mode: None,
+            pop_mode: false,
+            skip: false,
+            allow_shadowed: false,
             ident: variant.ident.clone(),
         };
 
@@ -158,7 +591,8 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
                 }
             };
             for (name, value) in attributes {
-                if name != "illegal" && value.is_none() {
+                let (value, literal) = value.unzip();
+                if !["illegal", "pop_mode", "skip", "allow_shadowed"].contains(&&*name) && value.is_none() {
                     return quote_spanned! {
                         attribute.span() =>
                         compile_error!("missing attribute value");
@@ -170,10 +604,16 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
                         parse_data.literals.push(value.unwrap());
                     }
                     "regex" => {
-                        parse_data.simple_regexes.push(value.unwrap());
+                        parse_data.simple_regexes.push(Pattern {
+                            text: value.unwrap(),
+                            literal: literal.unwrap(),
+                        });
                     }
                     "regex_full" => {
-                        parse_data.regexes.push(value.unwrap());
+                        parse_data.regexes.push(Pattern {
+                            text: value.unwrap(),
+                            literal: literal.unwrap(),
+                        });
                     }
                     "parse_fn" => {
                         if parse_data.parse_fn.is_some() {
@@ -184,6 +624,54 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
                         }
                         parse_data.parse_fn = Some(value.unwrap());
                     }
+                    "mode" => {
+                        let mode = modes.intern(&value.unwrap());
+                        if !parse_data.modes.contains(&mode) {
+                            parse_data.modes.push(mode);
+                        }
+                    }
+                    "push_mode" => {
+                        if parse_data.push_mode.is_some() || parse_data.pop_mode {
+                            return quote_spanned! {
+                                attribute.span() =>
+                                compile_error!("a token can have at most one 'push_mode' or 'pop_mode' attribute");
+                            };
+                        }
+                        parse_data.push_mode = Some(modes.intern(&value.unwrap()));
+                    }
+                    "pop_mode" => {
+                        if value.is_some() {
+                            return quote_spanned! {
+                                attribute.span() =>
+                                compile_error!("unexpected attribute value");
+                            };
+                        }
+                        if parse_data.push_mode.is_some() || parse_data.pop_mode {
+                            return quote_spanned! {
+                                attribute.span() =>
+                                compile_error!("a token can have at most one 'push_mode' or 'pop_mode' attribute");
+                            };
+                        }
+                        parse_data.pop_mode = true;
+                    }
+                    "skip" => {
+                        if value.is_some() {
+                            return quote_spanned! {
+                                attribute.span() =>
+                                compile_error!("unexpected attribute value");
+                            };
+                        }
+                        parse_data.skip = true;
+                    }
+                    "allow_shadowed" => {
+                        if value.is_some() {
+                            return quote_spanned! {
+                                attribute.span() =>
+                                compile_error!("unexpected attribute value");
+                            };
+                        }
+                        parse_data.allow_shadowed = true;
+                    }
                     "illegal" => {
                         if value.is_some() {
                             return quote_spanned! {
@@ -236,6 +724,16 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
                 input.span() =>
                 compile_error!("cannot have an 'illegal' attribute and a 'literal', 'regex', 'regex_full', or 'parse_fn' attribute");
             };
+        } else if parse_data.is_illegal && (!parse_data.modes.is_empty() || parse_data.push_mode.is_some() || parse_data.pop_mode) {
+            return quote_spanned! {
+                input.span() =>
+                compile_error!("cannot have an 'illegal' attribute and a 'mode', 'push_mode', or 'pop_mode' attribute");
+            };
+        } else if parse_data.is_illegal && parse_data.skip {
+            return quote_spanned! {
+                input.span() =>
+                compile_error!("cannot have an 'illegal' attribute and a 'skip' attribute");
+            };
         } else if !parse_data.is_illegal && !has_anything {
             return quote_spanned! {
                 input.span() =>
@@ -244,55 +742,74 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
         }
 
         match &variant.fields {
-            Fields::Named(_) => {
-                return quote_spanned! {
-                    variant.fields.span() =>
-                    compile_error!("cannot have enum struct in TokenParse variant");
-                };
-            }
             Fields::Unnamed(FieldsUnnamed {
                 unnamed,
                 ..
-            }) => {
-                if unnamed.len() != 1 {
+            }) if unnamed.len() == 1 => match payload_needs_parse(&unnamed.first().unwrap().ty, original_lifetime_param.as_ref(), options.bytes) {
+                Ok(needs_parse) => {
+                    let ty = &unnamed.first().unwrap().ty;
+                    let primitive = |name| matches!(ty, Type::Path(path) if is_primitive(path, name));
+                    // The illegal token of a `bytes` tokenizer is a single byte, which needn't be a char.
+                    if parse_data.is_illegal && options.bytes && primitive("char") {
+                        return quote_spanned! {
+                            unnamed.span() =>
+                            compile_error!("'illegal' attributed tokens of a bytes tokenizer can't be a 'char' (use 'u8' or '&[u8]')");
+                        };
+                    }
+                    parse_data.has_target = true;
+                    parse_data.target_needs_parse = needs_parse;
+                    parse_data.target_is_byte = parse_data.is_illegal && options.bytes && primitive("u8");
+                }
+                Err(message) => {
                     return quote_spanned! {
                         unnamed.span() =>
-                        compile_error!("must have single target type in TokenParse variant");
+                        compile_error!(#message);
                     };
                 }
-                let field = unnamed.first().unwrap();
-                match &field.ty {
-                    Type::Reference(ty) => {
-                        if ty.mutability.is_some() {
-                            return quote_spanned! {
-                                unnamed.span() =>
-                                compile_error!("cannot have `&mut` in TokenParse variant");
-                            };
-                        }
-                        if !matches!(&ty.lifetime, Some(Lifetime { ident, ..}) if Some(ident) == original_lifetime_param.as_ref()) {
-                            return quote_spanned! {
-                                unnamed.span() =>
-                                compile_error!("unexpected lifetime in TokenParse variant (use the same one as defined in enum declaration)");
-                            };
-                        }
-                        if let Type::Path(path) = &*ty.elem {
-                            if path.qself.is_some() || path.path.segments.len() != 1 || path.path.segments.first().unwrap().ident != "str" {
-                                return quote_spanned! {
-                                    unnamed.span() =>
-                                    compile_error!("invalid type in reference for TokenParse (only &str allowed)");
-                                };
-                            }
-                        } else {
+            },
+            Fields::Named(FieldsNamed {
+                named: fields,
+                ..
+            })
+            | Fields::Unnamed(FieldsUnnamed {
+                unnamed: fields,
+                ..
+            }) => {
+                if fields.is_empty() {
+                    return quote_spanned! {
+                        variant.fields.span() =>
+                        compile_error!("cannot have empty fields in TokenParse variant");
+                    };
+                }
+                if parse_data.is_illegal {
+                    return quote_spanned! {
+                        variant.fields.span() =>
+                        compile_error!("'illegal' attributed tokens must have a single field (usually 'char' or '&str')");
+                    };
+                }
+                if parse_data.parse_fn.is_some() || !parse_data.literals.is_empty() {
+                    return quote_spanned! {
+                        variant.fields.span() =>
+                        compile_error!("fields filled from capture groups need a 'regex' or 'regex_full' attribute, not a 'literal' or 'parse_fn'");
+                    };
+                }
+                for field in fields {
+                    let (optional, ty) = match option_inner(&field.ty) {
+                        Some(ty) => (true, ty),
+                        None => (false, &field.ty),
+                    };
+                    match payload_needs_parse(ty, original_lifetime_param.as_ref(), options.bytes) {
+                        Ok(needs_parse) => parse_data.capture_fields.push(CaptureField {
+                            name: field.ident.clone(),
+                            optional,
+                            needs_parse,
+                        }),
+                        Err(message) => {
                             return quote_spanned! {
-                                unnamed.span() =>
-                                compile_error!("invalid type in reference for TokenParse (only &str allowed)");
+                                field.ty.span() =>
+                                compile_error!(#message);
                             };
                         }
-                        parse_data.has_target = true;
-                    }
-                    _ => {
-                        parse_data.has_target = true;
-                        parse_data.target_needs_parse = true;
                     }
                 }
             }
@@ -310,106 +827,49 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
         tokens_to_parse.push(parse_data)
     }
 
-    let mut simple_regexes = BTreeMap::new();
-    for (token_index, item) in tokens_to_parse.iter().enumerate() {
-        for simple_regex in &item.simple_regexes {
-            let parsed = match SimpleRegex::parse(simple_regex) {
-                Some(x) => x,
-                None => {
-                    return quote_spanned! {
-                        item.ident.span() =>
-                        compile_error!("invalid simple regex");
-                    };
-                }
+    for (mode, name) in modes.names.iter().enumerate().skip(1) {
+        let mode = mode as u32;
+        if !tokens_to_parse.iter().any(|x| x.modes.contains(&mode)) {
+            let message = format!("mode '{}' has no tokens", name);
+            return quote_spanned! {
+                input.span() =>
+                compile_error!(#message);
             };
-            simple_regexes.insert(
-                (item.ident.clone(), simple_regex.clone()),
-                SimpleRegexData {
-                    token_index,
-                    regex: parsed,
-                },
-            );
         }
     }
 
-    let mut regexes = BTreeMap::new();
-    for (token_index, item) in tokens_to_parse.iter().enumerate() {
-        for regex in &item.regexes {
-            let modified_regex = format!("^{}", regex);
-            let parsed = match Regex::new(&modified_regex) {
-                Ok(x) => x,
-                Err(_) => {
-                    return quote_spanned! {
-                        item.ident.span() =>
-                        compile_error!("invalid simple regex");
-                    };
-                }
-            };
-            regexes.insert(
-                (item.ident.clone(), regex.clone()),
-                RegexData {
-                    token_index,
-                    regex: parsed,
-                },
-            );
+    // Each mode lexes with its own regex set, lit table and conflict resolution.
+    let mut mode_bodies = vec![];
+    let mut dots = vec![];
+    let mut warnings = vec![];
+    for mode in 0..modes.names.len() as u32 {
+        match gen_mode(&tokens_to_parse, mode, options, &input.ident, &mut warnings) {
+            Ok((body, dot)) => {
+                mode_bodies.push(body);
+                dots.push(dot);
+            }
+            Err(e) => return e,
         }
     }
-
-    // (regex ident, raw regex) => (literal ident, literal)
-    let mut simple_regex_ident_conflicts: BTreeMap<(Ident, String), Vec<(Ident, String)>> = BTreeMap::new();
-    let mut regex_ident_conflicts: BTreeMap<(Ident, String), Vec<(Ident, String)>> = BTreeMap::new();
-    let mut known_literals = HashSet::new();
-
-    let mut parse_fns: BTreeMap<usize, Vec<TokenStream2>> = BTreeMap::new();
-
-    let mut lit_table = LitTable::default();
-    for (token_index, item) in tokens_to_parse.iter().enumerate() {
-        for literal in &item.literals {
-            if !known_literals.insert(literal.clone()) {
-                return quote_spanned! {
-                    item.ident.span() =>
-                    compile_error!("conflicting literals");
-                };
-            }
-            let mut any_matched = false;
-            for ((ident, raw_regex), regex) in &simple_regexes {
-                if regex.token_index > token_index && regex.regex.matches(literal) {
-                    simple_regex_ident_conflicts
-                        .entry((ident.clone(), raw_regex.clone()))
-                        .or_default()
-                        .push((item.ident.clone(), literal.clone()));
-                    any_matched = true;
-                }
-            }
-            if any_matched {
-                continue;
-            }
-            for ((ident, raw_regex), regex) in &regexes {
-                if regex.token_index > token_index && regex.regex.is_match(literal) {
-                    regex_ident_conflicts
-                        .entry((ident.clone(), raw_regex.clone()))
-                        .or_default()
-                        .push((item.ident.clone(), literal.clone()));
-                    any_matched = true;
+    let mode_dispatch = if mode_bodies.len() == 1 {
+        mode_bodies.pop().unwrap()
+    } else {
+        let arms = flatten(mode_bodies.into_iter().enumerate().map(|(mode, body)| {
+            let mode = mode as u32;
+            quote! {
+                #mode => {
+                    #body
                 }
             }
-            if any_matched {
-                continue;
+        }));
+        quote! {
+            match self.modes.last().copied().unwrap_or(0) {
+                #arms
+                _ => unreachable!("unknown lexer mode"),
             }
-            lit_table.push(item, literal, &mut literal.chars());
         }
-    }
-
-    let lit_table_name = format_ident!("parse_lits");
-    let lit_table = lit_table.emit(&lit_table_name, &input.ident);
-
-    if let Err(e) = codegen::simple_regex::gen_simple_regex(&tokens_to_parse[..], &simple_regexes, &simple_regex_ident_conflicts, &input.ident, &mut parse_fns)
-    {
-        return e;
-    }
-    if let Err(e) = codegen::full_regex::gen_full_regex(&tokens_to_parse[..], &regex_ident_conflicts, &input.ident, &mut parse_fns) {
-        return e;
-    }
+    };
+    let mode_names = flatten(modes.names.iter().map(|name| quote! { #name, }));
 
     let lifetime_param = if has_lifetime_param {
         quote! { <'a> }
@@ -425,42 +885,78 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
     let token_ident = &input.ident;
     let vis = &input.vis;
 
-    let display_fields = codegen::display::gen_display(&tokens_to_parse[..], &input.ident);
+    let display_fields = codegen::display::gen_display(&tokens_to_parse[..], &input.ident, options.bytes);
 
     let illegal_emission = if let Some(illegal) = tokens_to_parse.iter().find(|x| x.is_illegal) {
-        let constructor = construct_variant(illegal, &input.ident);
+        let emit = emit_token(illegal, &input.ident, options.bytes);
+        let illegal_span = gen_span(true, options.bytes);
+        // The illegal token is one char, or one byte of a `bytes` tokenizer.
+        let width = if options.bytes {
+            quote! { 1 }
+        } else {
+            quote! { value.len_utf8() }
+        };
+        let first = if options.bytes {
+            quote! { self.inner.first() }
+        } else {
+            quote! { self.inner.chars().next() }
+        };
         quote! {
-            if let Some(value) = self.inner.chars().next() {
-                let span = ::compiler_tools::Span {
-                    line_start: self.line,
-                    col_start: self.col,
-                    line_stop: if value == '\n' {
-                        self.line += 1;
-                        self.line
-                    } else {
-                        self.line
-                    },
-                    col_stop: if value != '\n' {
-                        self.col += value.len_utf8() as u64;
-                        self.col
-                    } else {
-                        self.col = 0;
-                        self.col
+            if let Some(value) = #first {
+                let passed = &self.inner[..#width];
+                let span = #illegal_span;
+                self.inner = &self.inner[#width..];
+                #emit
+            } else {
+                Ok(None)
+            }
+        }
+    } else {
+        quote! {
+            if self.inner.is_empty() {
+                Ok(None)
+            } else {
+                Err(::compiler_tools::LexError {
+                    span: ::compiler_tools::Span {
+                        line_start: self.line,
+                        col_start: self.col,
+                        line_stop: self.line,
+                        col_stop: self.col,
+                        byte_start: self.offset,
+                        byte_end: self.offset,
+                        file: self.file,
                     },
-                };
-                let passed = &self.inner[..value.len_utf8()];
-                self.inner = &self.inner[value.len_utf8()..];
-                return Some(::compiler_tools::Spanned {
-                    token: #constructor,
-                    span,
+                    kind: ::compiler_tools::LexErrorKind::NoMatch,
                 })
-            } else {
-                None
+            }
+        }
+    };
+
+    let advance_col = gen_advance_col(options.columns, options.tab_width, options.bytes);
+    // Reported through `TokenParse`, for wrappers measuring text the tokenizer didn't lex.
+    let columns = match options.columns {
+        ColumnUnit::Bytes => quote! { ::compiler_tools::ColumnUnit::Bytes },
+        ColumnUnit::Chars => quote! { ::compiler_tools::ColumnUnit::Chars },
+        ColumnUnit::Utf16 => quote! { ::compiler_tools::ColumnUnit::Utf16 },
+    };
+    let tab_width = match options.tab_width {
+        Some(tab_width) => quote! { Some(#tab_width) },
+        None => quote! { None },
+    };
+    let input_ty = input_type(options.bytes);
+
+    let body = if tokens_to_parse.iter().any(|x| x.skip) {
+        quote! {
+            // Each iteration lexes one token; skipped tokens `continue` to the next.
+            loop {
+                #mode_dispatch
+                return #illegal_emission;
             }
         }
     } else {
         quote! {
-            None
+            #mode_dispatch
+            #illegal_emission
         }
     };
 
@@ -495,94 +991,13 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
     };
 
     let class_matches = gen_class_match(&tokens_to_parse[..], &input.ident);
-
-    for (token_index, token) in tokens_to_parse.iter().enumerate() {
-        if let Some(parse_fn) = &token.parse_fn {
-            let path_expr: ExprPath = match syn::parse_str(parse_fn) {
-                Ok(x) => x,
-                Err(_e) => {
-                    parse_fns
-                        .entry(token_index)
-                        .or_default()
-                        .push(quote! { compile_error!("can't parse path for parse_fn"); });
-                    continue;
-                }
-            };
-            let constructed = construct_variant(token, &input.ident);
-            parse_fns.entry(token_index).or_default().push(quote! {
-                {
-                    if let Some((passed, remaining)) = #path_expr(self.inner) {
-                        let span = ::compiler_tools::Span {
-                            line_start: self.line,
-                            col_start: self.col,
-                            line_stop: {
-                                self.line += passed.chars().filter(|x| *x == '\n').count() as u64;
-                                self.line
-                            },
-                            //todo: handle utf8 better with newline seeking here
-                            col_stop: if let Some(trailing_bytes) = passed.as_bytes().iter().rev().position(|x| *x == b'\n') {
-                                // `trailing_bytes` is the number of bytes after the last newline,
-                                // which is the column on the final line.
-                                self.col = trailing_bytes as u64;
-                                self.col
-                            } else {
-                                self.col += passed.len() as u64;
-                                self.col
-                            },
-                        };
-                        self.inner = remaining;
-                        match passed {
-                            passed => return Some(::compiler_tools::Spanned {
-                                token: #constructed,
-                                span,
-                            }),
-                        }
-                    }
-                }
-            });
-        }
-    }
-
-    let lit_table_parse = quote! {
-        match #lit_table_name(self.inner) {
-            Some((token, remaining, newlines)) => {
-                let span = ::compiler_tools::Span {
-                    line_start: self.line,
-                    col_start: self.col,
-                    line_stop: if newlines == 0 {
-                        self.line
-                    } else {
-                        self.line += newlines;
-                        self.line
-                    },
-                    col_stop: if newlines == 0 {
-                        self.col += (self.inner.len() - remaining.len()) as u64;
-                        self.col
-                    } else {
-                        //todo: handle utf8 better with newline seeking here
-                        // `trailing_bytes` is the number of bytes after the last newline in the
-                        // matched literal, which is the column on the final line.
-                        let trailing_bytes = self.inner[..self.inner.len() - remaining.len()].as_bytes().iter().rev().position(|x| *x == b'\n').expect("malformed newline state");
-                        self.col = trailing_bytes as u64;
-                        self.col
-                    },
-                };
-                self.inner = remaining;
-                return Some(::compiler_tools::Spanned {
-                    token,
-                    span,
-                });
-            },
-            None => (),
-        }
+    let warnings = flatten(warnings);
+    let property_test = if options.property_tests {
+        gen_property_test(&tokens_to_parse[..], &input.ident, &tokenizer_ident, options)
+    } else {
+        quote! {}
     };
 
-    if let Some(lit_table_index) = tokens_to_parse.iter().position(|x| !x.literals.is_empty()) {
-        parse_fns.entry(lit_table_index).or_default().push(lit_table_parse);
-    }
-
-    let parse_fns = flatten(parse_fns.into_values().flatten());
-
     quote! {
         #reinput
 
@@ -605,28 +1020,330 @@ fn impl_token_parse(input: &DeriveInput) -> proc_macro2::TokenStream {
         #vis struct #tokenizer_ident<'a> {
             line: u64,
             col: u64,
-            inner: &'a str,
+            /// Byte offset of `inner` in the input
+            offset: u64,
+            inner: &'a #input_ty,
+            file: Option<::compiler_tools::FileId>,
+            /// The stack of entered lexer modes; empty in the default mode.
+            modes: Vec<u32>,
         }
 
         impl<'a> #tokenizer_ident<'a> {
-            pub fn new(input: &'a str) -> Self {
+            const MODES: &'static [&'static str] = &[#mode_names];
+
+            pub fn new(input: &'a #input_ty) -> Self {
                 Self {
                     line: 0,
                     col: 0,
+                    offset: 0,
                     inner: input,
+                    file: None,
+                    modes: Vec::new(),
                 }
             }
+
+            /// Lexes `input`, the source of `file`, tagging every span with it.
+            pub fn with_file(file: ::compiler_tools::FileId, input: &'a #input_ty) -> Self {
+                Self {
+                    file: Some(file),
+                    ..Self::new(input)
+                }
+            }
+
+            /// The combined DFA lexer mode `mode` matches its simple regexes and literal alternation with, in Graphviz
+            /// DOT, or `None` if there is no such mode. Its accepting states name the patterns they accept by index:
+            /// each variant's simple regexes in declaration order, with the literal alternation after those of the first
+            /// variant declaring a literal. Simple regexes with zero-width assertions are matched on their own, so they
+            /// aren't in it.
+            pub fn dfa_dot(mode: &str) -> Option<&'static str> {
+                const DOTS: &[&str] = &[#(#dots),*];
+                Self::MODES.iter().position(|x| *x == mode).map(|x| DOTS[x])
+            }
+
+            /// The name of the lexer mode the next token is lexed in.
+            pub fn mode(&self) -> &'static str {
+                Self::MODES[self.modes.last().copied().unwrap_or(0) as usize]
+            }
+
+            #advance_col
         }
 
         impl<'a> ::compiler_tools::TokenParse<'a> for #tokenizer_ident<'a> {
             type Token = #token_ident #lifetime_param;
+            const COLUMNS: ::compiler_tools::ColumnUnit = #columns;
+            const TAB_WIDTH: Option<u64> = #tab_width;
 
-            #[allow(non_snake_case, unreachable_pattern, unreachable_code)]
             fn next(&mut self) -> Option<::compiler_tools::Spanned<Self::Token>> {
-                #lit_table
-                #parse_fns
-                #illegal_emission
+                ::compiler_tools::TokenParse::try_next(self).ok().flatten()
+            }
+
+            #[allow(non_snake_case, unreachable_pattern, unreachable_code, unused_variables)]
+            fn try_next(&mut self) -> Result<Option<::compiler_tools::Spanned<Self::Token>>, ::compiler_tools::LexError> {
+                #body
+            }
+        }
+
+        #property_test
+        #warnings
+    }
+}
+
+/// The body of `try_next()` for one lexer mode: every matcher of the variants in `mode`, each run at the current position.
+/// Falls through when none of them match. Also returns the Graphviz DOT of the combined DFA the mode scans with.
+fn gen_mode(
+    tokens_to_parse: &[TokenParseData],
+    mode: u32,
+    options: &TokenParseOptions,
+    enum_ident: &Ident,
+    warnings: &mut Vec<TokenStream2>,
+) -> Result<(TokenStream2, String), TokenStream2> {
+    let mut simple_regexes = BTreeMap::new();
+    for (token_index, item) in tokens_to_parse.iter().enumerate().filter(|(_, x)| x.in_mode(mode)) {
+        for simple_regex in &item.simple_regexes {
+            let parsed = match options.parse_simple_regex(&simple_regex.text) {
+                Ok(x) => x,
+                Err(e) => return Err(simple_regex.error_at(e.offset, &format!("invalid simple regex: {}", e.message))),
+            };
+            simple_regexes.insert(
+                (item.ident.clone(), simple_regex.text.clone()),
+                SimpleRegexData {
+                    token_index,
+                    regex: parsed,
+                },
+            );
+        }
+    }
+
+    let mut regexes = BTreeMap::new();
+    for (token_index, item) in tokens_to_parse.iter().enumerate().filter(|(_, x)| x.in_mode(mode)) {
+        for regex in &item.regexes {
+            let modified_regex = format!("^{}", regex.text);
+            // Literal conflicts are checked against the bytes engine either way, which agrees with the str one on UTF-8
+            // input, but a str tokenizer also needs the pattern to be valid for `&str`.
+            let parsed = match BytesRegex::new(&modified_regex) {
+                Ok(x) if options.bytes || Regex::new(&modified_regex).is_ok() => x,
+                _ => {
+                    // Report the error for the pattern as written, without the anchor.
+                    let error = if options.bytes {
+                        BytesRegex::new(&regex.text).err()
+                    } else {
+                        Regex::new(&regex.text).err()
+                    };
+                    let message = error.map_or_else(|| "invalid regex".to_string(), |e| e.to_string());
+                    return Err(quote_spanned! {
+                        regex.literal.span() =>
+                        compile_error!(#message);
+                    });
+                }
+            };
+            regexes.insert(
+                (item.ident.clone(), regex.text.clone()),
+                RegexData {
+                    token_index,
+                    regex: parsed,
+                },
+            );
+        }
+    }
+
+    // (regex ident, raw regex) => (literal ident, literal)
+    let mut simple_regex_ident_conflicts: BTreeMap<(Ident, String), Vec<(Ident, String)>> = BTreeMap::new();
+    let mut regex_ident_conflicts: BTreeMap<(Ident, String), Vec<(Ident, String)>> = BTreeMap::new();
+    let mut known_literals = HashSet::new();
+
+    let mut candidates: BTreeMap<usize, Vec<Candidate>> = BTreeMap::new();
+
+    let mut lit_table = LitTable::default();
+    for (token_index, item) in tokens_to_parse.iter().enumerate().filter(|(_, x)| x.in_mode(mode)) {
+        for literal in &item.literals {
+            if !known_literals.insert(literal.clone()) {
+                return Err(quote_spanned! {
+                    item.ident.span() =>
+                    compile_error!("conflicting literals");
+                });
+            }
+            // In longest-match mode a literal competes with every regex directly, and declaration order breaks the tie.
+            if options.longest_match {
+                lit_table.push(token_index, literal);
+                continue;
+            }
+            let mut any_matched = false;
+            for ((ident, raw_regex), regex) in &simple_regexes {
+                if regex.token_index > token_index && regex.regex.matches(literal) {
+                    simple_regex_ident_conflicts
+                        .entry((ident.clone(), raw_regex.clone()))
+                        .or_default()
+                        .push((item.ident.clone(), literal.clone()));
+                    any_matched = true;
+                }
+            }
+            if any_matched {
+                continue;
             }
+            for ((ident, raw_regex), regex) in &regexes {
+                if regex.token_index > token_index && regex.regex.is_match(literal.as_bytes()) {
+                    regex_ident_conflicts
+                        .entry((ident.clone(), raw_regex.clone()))
+                        .or_default()
+                        .push((item.ident.clone(), literal.clone()));
+                    any_matched = true;
+                }
+            }
+            if any_matched {
+                continue;
+            }
+            lit_table.push(token_index, literal);
+        }
+    }
+
+    // The lit table runs at the position of the first variant declaring a literal.
+    let lit_table_index = tokens_to_parse
+        .iter()
+        .position(|x| x.in_mode(mode) && !x.literals.is_empty())
+        .filter(|_| !lit_table.is_empty());
+    warn_shadowed(tokens_to_parse, &simple_regexes, &lit_table, lit_table_index, options, warnings);
+
+    // The simple regexes without zero-width assertions and the lit table's alternation share a combined DFA, walked on
+    // first use per token; the rest are scanned on their own.
+    let (set_patterns, lit_table_pattern) = codegen::simple_regex::gen_simple_regex(
+        tokens_to_parse,
+        simple_regexes,
+        &simple_regex_ident_conflicts,
+        enum_ident,
+        options.bytes,
+        lit_table_index.map(|index| (index, lit_table.to_regex(options.bytes))),
+        &mut candidates,
+    )?;
+    codegen::full_regex::gen_full_regex(tokens_to_parse, mode, &regex_ident_conflicts, enum_ident, options.bytes, &mut candidates)?;
+
+    for (token_index, token) in tokens_to_parse.iter().enumerate().filter(|(_, x)| x.in_mode(mode)) {
+        if let Some(parse_fn) = &token.parse_fn {
+            let path_expr: ExprPath = match syn::parse_str(parse_fn) {
+                Ok(x) => x,
+                Err(_e) => {
+                    return Err(quote_spanned! {
+                        token.ident.span() =>
+                        compile_error!("can't parse path for parse_fn");
+                    });
+                }
+            };
+            let emit = emit_token(token, enum_ident, options.bytes);
+            let span = gen_span(true, options.bytes);
+            candidates.entry(token_index).or_default().push(Candidate {
+                matcher: quote! {
+                    #path_expr(self.inner).map(|(passed, remaining)| (#token_index, passed, remaining))
+                },
+                emit: quote! {
+                    let span = #span;
+                    self.inner = remaining;
+                    #emit
+                },
+            });
+        }
+    }
+
+    let mut lit_emissions = vec![];
+    for (token_index, item) in tokens_to_parse.iter().enumerate().filter(|(_, x)| x.in_mode(mode)) {
+        if item.literals.is_empty() {
+            continue;
         }
+        let emit = emit_token(item, enum_ident, options.bytes);
+        let span = gen_span(item.literals.iter().any(|x| x.contains('\n')), options.bytes);
+        lit_emissions.push(quote! {
+            #token_index => {
+                let span = #span;
+                self.inner = remaining;
+                #emit
+            }
+        });
+    }
+    let lit_emissions = flatten(lit_emissions);
+
+    if let (Some(lit_table_index), Some(lit_table_pattern)) = (lit_table_index, lit_table_pattern) {
+        let dispatch = lit_table.emit_dispatch(options.bytes);
+        candidates.entry(lit_table_index).or_default().push(Candidate {
+            matcher: gen_set_matcher(lit_table_pattern, dispatch),
+            emit: quote! {
+                match token_index {
+                    #lit_emissions
+                    _ => unreachable!("unknown literal token index"),
+                }
+            },
+        });
     }
+
+    let set = SimpleRegexSet::from_regexes(set_patterns);
+    let dot = if options.longest_match {
+        set.to_dot()
+    } else {
+        set.first_match_graph().to_dot()
+    };
+    let set = if set.is_empty() {
+        quote! {}
+    } else {
+        // Outside `longest_match` the first matching candidate wins, so the walk can stop once an earlier pattern has.
+        let set_fn = if options.longest_match {
+            set.generate_parser(format_ident!("parse_set"))
+        } else {
+            set.generate_first_match_parser(format_ident!("parse_set"))
+        };
+        quote! {
+            #set_fn
+            let mut scan = None;
+        }
+    };
+
+    let candidates: Vec<Candidate> = candidates.into_values().flatten().collect();
+    let input_ty = input_type(options.bytes);
+    let candidates = if options.longest_match {
+        let mut matchers = vec![];
+        let mut emissions = vec![];
+        for (candidate_index, candidate) in candidates.iter().enumerate() {
+            let matcher = &candidate.matcher;
+            let emit = &candidate.emit;
+            matchers.push(quote! {
+                if let Some((token_index, passed, remaining)) = #matcher {
+                    if best.is_none_or(|(_, best_index, best_passed, _)| {
+                        passed.len() > best_passed.len() || (passed.len() == best_passed.len() && token_index < best_index)
+                    }) {
+                        best = Some((#candidate_index, token_index, passed, remaining));
+                    }
+                }
+            });
+            emissions.push(quote! {
+                #candidate_index => {
+                    #emit
+                }
+            });
+        }
+        let matchers = flatten(matchers);
+        let emissions = flatten(emissions);
+        quote! {
+            // (candidate, token index, passed, remaining)
+            let mut best: Option<(usize, usize, &'a #input_ty, &'a #input_ty)> = None;
+            #matchers
+            if let Some((candidate, token_index, passed, remaining)) = best {
+                match candidate {
+                    #emissions
+                    _ => unreachable!("unknown token candidate"),
+                }
+            }
+        }
+    } else {
+        flatten(candidates.iter().map(|candidate| {
+            let matcher = &candidate.matcher;
+            let emit = &candidate.emit;
+            quote! {
+                if let Some((token_index, passed, remaining)) = #matcher {
+                    #emit
+                }
+            }
+        }))
+    };
+
+    let body = quote! {
+        #set
+        #candidates
+    };
+    Ok((body, dot))
 }
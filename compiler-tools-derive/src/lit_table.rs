@@ -1,131 +1,86 @@
-use std::collections::BTreeMap;
-
-use proc_macro2::{Ident, TokenStream};
+use proc_macro2::TokenStream;
 use quote::quote;
 
-use crate::{TokenParseData, flatten};
+use compiler_tools_regex::{Atom, AtomRepeat, Regex as SimpleRegex, Repeat, SimpleRegexAst};
+
+use crate::{flatten, text_literal};
 
+/// The literals lexed by table lookup (everything not resolved through a regex), matched as one pattern of the
+/// tokenizer's regex set.
 #[derive(Default)]
 pub(super) struct LitTable {
-    table: BTreeMap<char, LitTable>,
-    token: Option<LitTableToken>,
+    literals: Vec<(usize, String)>,
 }
 
-pub(super) struct LitTableToken {
-    ident: Ident,
-    has_target: bool,
-    target_needs_parse: bool,
-    literal: String,
-}
+impl LitTable {
+    pub(super) fn push(&mut self, token_index: usize, literal: &str) {
+        self.literals.push((token_index, literal.to_string()));
+    }
 
-impl LitTableToken {
-    fn emit(&self, enum_ident: &Ident) -> TokenStream {
-        let variant = &self.ident;
-        let len = self.literal.len();
-        let newlines = self.literal.chars().filter(|c| *c == '\n').count() as u64;
+    pub(super) fn is_empty(&self) -> bool {
+        self.literals.is_empty()
+    }
 
-        if self.has_target {
-            if self.target_needs_parse {
-                //TODO: emit better error for parsefail
-                quote! {
-                    {
-                        let (before, after) = from.split_at(#len);
-                        from = after;
-                        (#enum_ident::#variant(before.parse().ok()?), #newlines)
-                    }
-                }
-            } else {
-                quote! {
-                    {
-                        let (before, after) = from.split_at(#len);
-                        from = after;
-                        (#enum_ident::#variant(before), #newlines)
-                    }
-                }
-            }
-        } else {
-            quote! {
-                {
-                    from = &from[#len..];
-                    (#enum_ident::#variant, #newlines)
-                }
-            }
-        }
+    /// An alternation of every literal, longest first, so its leftmost-first match is the longest literal at the input.
+    pub(super) fn to_regex(&self, bytes: bool) -> SimpleRegex {
+        let mut literals: Vec<&str> = self.literals.iter().map(|(_, literal)| &**literal).collect();
+        literals.sort_by_key(|literal| std::cmp::Reverse(literal.len()));
+        let branches = literals.into_iter().map(|literal| vec![literal_atom(literal, bytes)]).collect();
+        compile(
+            AtomRepeat {
+                atom: Atom::Alternation(branches),
+                repeat: Repeat::Once,
+                lazy: false,
+            },
+            bytes,
+        )
     }
-}
 
-impl LitTable {
-    pub(super) fn push(&mut self, item: &TokenParseData, literal: &str, remaining: &mut impl Iterator<Item = char>) {
-        match remaining.next() {
-            Some(c) => {
-                let entry = self.table.entry(c).or_default();
-                entry.push(item, literal, remaining);
+    /// Each literal as a pattern of its own, with the token index of its variant.
+    pub(super) fn literal_regexes(&self, bytes: bool) -> Vec<(usize, &str, SimpleRegex)> {
+        self.literals
+            .iter()
+            .map(|(token_index, literal)| (*token_index, &**literal, compile(literal_atom(literal, bytes), bytes)))
+            .collect()
+    }
+
+    /// A `usize` expression: the token index of the literal `passed` matched.
+    pub(super) fn emit_dispatch(&self, bytes: bool) -> TokenStream {
+        let arms = flatten(self.literals.iter().map(|(token_index, literal)| {
+            let literal = text_literal(literal, bytes);
+            quote! {
+                #literal => #token_index,
             }
-            None => {
-                self.token = Some(LitTableToken {
-                    ident: item.ident.clone(),
-                    has_target: item.has_target,
-                    target_needs_parse: item.target_needs_parse,
-                    literal: literal.to_string(),
-                })
+        }));
+        quote! {
+            match passed {
+                #arms
+                _ => unreachable!("unknown literal"),
             }
         }
     }
+}
 
-    fn emit_internal(&self, enum_ident: &Ident, pending_default: Option<&LitTableToken>) -> TokenStream {
-        if self.table.is_empty() {
-            if let Some(token) = &self.token {
-                let emitted = token.emit(enum_ident);
-                quote! {
-                    Some(#emitted)
-                }
-            } else {
-                quote! {
-                    None
-                }
-            }
+/// A literal as an atom. For a `bytes` tokenizer it is spelled as its UTF-8 bytes.
+fn literal_atom(literal: &str, bytes: bool) -> AtomRepeat {
+    AtomRepeat {
+        atom: Atom::Literal(if bytes {
+            literal.bytes().map(char::from).collect()
         } else {
-            let default = self.token.as_ref().or(pending_default);
-            let mut entries = vec![];
-            for (c, table) in &self.table {
-                let internal = table.emit_internal(enum_ident, default);
-                entries.push(quote! {
-                    Some(#c) => #internal,
-                });
-            }
-            if let Some(token) = default {
-                let emitted = token.emit(enum_ident);
-                entries.push(quote! {
-                    _ => Some(#emitted),
-                });
-            } else {
-                entries.push(quote! {
-                    _ => None,
-                });
-            }
-            let entries = flatten(entries);
-
-            quote! {
-                match iter.next() {
-                    #entries
-                }
-            }
-        }
+            literal.to_string()
+        }),
+        repeat: Repeat::Once,
+        lazy: false,
     }
+}
 
-    //TODO: straightshot optimization
-    pub(super) fn emit(&self, fn_name: &Ident, enum_ident: &Ident) -> TokenStream {
-        let internal = self.emit_internal(enum_ident, None);
-        // println!("{}", internal);
-        quote! {
-            // returns (token, remaining, newlines_skipped)
-            #[inline]
-            fn #fn_name(mut from: &str) -> Option<(#enum_ident, &str, u64)> {
-                let start = from;
-                let mut iter = from.chars();
-                let (token, newlines) = #internal?;
-                Some((token, from, newlines))
-            }
-        }
+fn compile(atom: AtomRepeat, bytes: bool) -> SimpleRegex {
+    let ast = SimpleRegexAst {
+        atoms: vec![atom],
+    };
+    if bytes {
+        SimpleRegex::from_ast_bytes(ast)
+    } else {
+        SimpleRegex::from_ast(ast)
     }
 }
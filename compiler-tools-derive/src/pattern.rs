@@ -0,0 +1,81 @@
+use proc_macro2::{Literal, TokenStream as TokenStream2};
+use quote::quote_spanned;
+
+/// The pattern of a `regex` or `regex_full` attribute, with the string literal it was written as, so an error in it can
+/// point into the literal.
+pub(crate) struct Pattern {
+    pub(crate) text: String,
+    pub(crate) literal: Literal,
+}
+
+impl Pattern {
+    /// A `compile_error!` for an error at byte `offset` of the pattern. It points at that column inside the literal
+    /// where the compiler can resolve a span within a token (currently only on nightly), and at the whole literal
+    /// otherwise. Either way the message repeats the pattern with a caret under the column.
+    pub(crate) fn error_at(&self, offset: usize, message: &str) -> TokenStream2 {
+        let source = self.literal.to_string();
+        let span = source_offset(&source, offset)
+            .and_then(|start| {
+                let len = self.text[offset..].chars().next().map_or(0, char::len_utf8);
+                let end = source_offset(&source, offset + len).unwrap_or(start);
+                self.literal.subspan(start..end.max(start + 1))
+            })
+            .unwrap_or_else(|| self.literal.span());
+        // A verbose-mode pattern can span lines; show just the one with the error.
+        let line_start = self.text[..offset].rfind('\n').map_or(0, |x| x + 1);
+        let line_end = self.text[offset..].find('\n').map_or(self.text.len(), |x| offset + x);
+        let column = self.text[line_start..offset].chars().count();
+        let message = format!("{message}\n  {}\n  {}^", &self.text[line_start..line_end], " ".repeat(column));
+        quote_spanned! {
+            span =>
+            compile_error!(#message);
+        }
+    }
+}
+
+/// The byte offset in `source`, the text of a string literal token, of byte `offset` of the string's value. `None` for
+/// a literal that isn't a string, or an offset past its end.
+fn source_offset(source: &str, offset: usize) -> Option<usize> {
+    if source.starts_with('r') {
+        // A raw string's value is its source between the quotes, verbatim.
+        return Some(source.find('"')? + 1 + offset);
+    }
+    let body = source.strip_prefix('"')?;
+    let mut chars = body.char_indices().peekable();
+    let mut value_offset = 0;
+    while let Some((at, c)) = chars.next() {
+        if value_offset == offset {
+            return Some(1 + at);
+        }
+        if c == '"' {
+            return None;
+        }
+        if c != '\\' {
+            value_offset += c.len_utf8();
+            continue;
+        }
+        match chars.next()?.1 {
+            // `\u{...}` is one char of however many bytes.
+            'u' => {
+                let mut hex = String::new();
+                for (_, c) in chars.by_ref() {
+                    match c {
+                        '{' => {}
+                        '}' => break,
+                        c => hex.push(c),
+                    }
+                }
+                value_offset += char::from_u32(u32::from_str_radix(&hex, 16).ok()?)?.len_utf8();
+            }
+            'x' => {
+                chars.next();
+                chars.next();
+                value_offset += 1;
+            }
+            // A line continuation: the newline and the whitespace after it are dropped.
+            '\n' | '\r' => while chars.next_if(|(_, c)| c.is_whitespace()).is_some() {},
+            _ => value_offset += 1,
+        }
+    }
+    None
+}
@@ -0,0 +1,97 @@
+use std::collections::BTreeMap;
+
+use compiler_tools_regex::{Priority, Regex as SimpleRegex};
+use proc_macro2::{Ident, Span, TokenStream as TokenStream2};
+use quote::quote_spanned;
+
+use crate::{SimpleRegexData, TokenParseData, TokenParseOptions, lit_table::LitTable};
+
+/// A pattern of a mode that is matched with a DFA, so it can be compared with the others.
+struct Contender<'a> {
+    /// Where the tokenizer ranks the pattern against the others: its candidate's place in the order they are tried,
+    /// or under `longest_match`, its variant's declaration order, which breaks ties.
+    rank: usize,
+    token_index: usize,
+    regex: &'a SimpleRegex,
+    /// Whether it is a literal of the lit table. Literals don't shadow each other, since the table matches the longest.
+    literal: bool,
+    span: Span,
+}
+
+/// Pushes a warning onto `warnings` for each pattern in a mode that can never match, because the pattern of an earlier
+/// variant wins on every input it matches. Only simple regexes and literals are compared: `regex_full` and `parse_fn`
+/// variants have no DFA to compare. A variant marked `#[token(allow_shadowed)]` isn't checked.
+pub(crate) fn warn_shadowed(
+    tokens_to_parse: &[TokenParseData],
+    simple_regexes: &BTreeMap<(Ident, String), SimpleRegexData>,
+    lit_table: &LitTable,
+    lit_table_index: Option<usize>,
+    options: &TokenParseOptions,
+    warnings: &mut Vec<TokenStream2>,
+) {
+    let priority = if options.longest_match { Priority::Longest } else { Priority::First };
+    let literal_regexes = lit_table.literal_regexes(options.bytes);
+    let mut contenders = vec![];
+    for ((_, raw_regex), data) in simple_regexes {
+        let item = &tokens_to_parse[data.token_index];
+        let span = item
+            .simple_regexes
+            .iter()
+            .find(|x| &x.text == raw_regex)
+            .map_or_else(|| item.ident.span(), |x| x.literal.span());
+        contenders.push(Contender {
+            rank: data.token_index,
+            token_index: data.token_index,
+            regex: &data.regex,
+            literal: false,
+            span,
+        });
+    }
+    for (token_index, _, regex) in &literal_regexes {
+        contenders.push(Contender {
+            rank: if options.longest_match {
+                *token_index
+            } else {
+                lit_table_index.unwrap_or(*token_index)
+            },
+            token_index: *token_index,
+            regex,
+            literal: true,
+            span: tokens_to_parse[*token_index].ident.span(),
+        });
+    }
+    contenders.sort_by_key(|x| x.token_index);
+
+    for later in contenders.iter().filter(|x| !tokens_to_parse[x.token_index].allow_shadowed) {
+        let shadowing = contenders
+            .iter()
+            .filter(|x| x.rank < later.rank && x.token_index != later.token_index && !(x.literal && later.literal))
+            .find_map(|earlier| Some((earlier, earlier.regex.shadows(later.regex, priority)?)));
+        if let Some((earlier, sample)) = shadowing {
+            let sample = if options.bytes {
+                format!("b\"{}\"", sample.chars().map(|c| (c as u8).escape_ascii().to_string()).collect::<String>())
+            } else {
+                format!("{sample:?}")
+            };
+            let message = format!(
+                "this {} of `{}` can never match: `{}` wins on every input it matches, e.g. {} \
+                 (allow it with `#[token(allow_shadowed)]` on the variant)",
+                if later.literal { "literal" } else { "pattern" },
+                tokens_to_parse[later.token_index].ident,
+                tokens_to_parse[earlier.token_index].ident,
+                sample,
+            );
+            // A proc macro can't emit a warning of its own on stable, so this leans on `deprecated`: the use of the
+            // constant is spanned at the pattern, which is where rustc points the warning.
+            warnings.push(quote_spanned! {
+                later.span =>
+                const _: () = {
+                    #[deprecated(note = #message)]
+                    #[allow(non_upper_case_globals)]
+                    const shadowed_pattern: () = ();
+                    shadowed_pattern
+                };
+            });
+        }
+    }
+}
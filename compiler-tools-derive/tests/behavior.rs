@@ -3,7 +3,7 @@
 //! Unlike `integration.rs` (which only smoke-tests that lexing runs), these tests
 //! assert the exact token stream, payloads, `Display` output, and span tracking.
 
-use compiler_tools::{Spanned, TokenParse};
+use compiler_tools::{LexErrorKind, SourceMap, Spanned, TokenParse, TriviaWrap};
 use compiler_tools_derive::token_parse;
 
 #[token_parse]
@@ -132,6 +132,129 @@ fn spans_single_line() {
     assert!(spans.iter().all(|s| s.span.line_start == 0 && s.span.line_stop == 0));
 }
 
+#[test]
+fn spans_carry_byte_offsets() {
+    // literal, simple regex, parse_fn and illegal paths; `é` is two bytes
+    let source = "let 'é'\n==x #";
+    let spans = lex_spans(source);
+    let slices: Vec<&str> = spans.iter().map(|s| s.span.slice(source)).collect();
+    assert_eq!(slices, vec!["let", " ", "'é'", "\n", "==", "x", " ", "#"]);
+    assert_eq!((spans[2].span.byte_start, spans[2].span.byte_end), (4, 8));
+    let merged = spans[0].span + spans[4].span;
+    assert_eq!(merged.slice(source), "let 'é'\n==");
+}
+
+#[test]
+fn spans_carry_their_file() {
+    let mut sources = SourceMap::new();
+    let a = sources.add("a", "let x");
+    let b = sources.add("b", "==\n@");
+    let mut spans = vec![];
+    for file in [a, b] {
+        let mut tokenizer = Tokenizer::with_file(file, sources.source(file));
+        while let Some(next) = tokenizer.next() {
+            spans.push(next.span);
+        }
+    }
+    let texts: Vec<(&str, &str)> = spans.iter().map(|x| (sources.name(x.file.unwrap()), sources.span_text(x).unwrap())).collect();
+    assert_eq!(texts, vec![("a", "let"), ("a", " "), ("a", "x"), ("b", "=="), ("b", "\n"), ("b", "@")]);
+    assert!(Tokenizer::new("x").next().unwrap().span.file.is_none());
+}
+
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Full<'a> {
+    #[token(regex_full = "[a-z]+")]
+    Word(&'a str),
+    #[token(regex_full = "\\s+")]
+    Ws,
+}
+
+#[test]
+fn full_regex_spans_carry_byte_offsets() {
+    let source = "ab \n cd";
+    let mut tokenizer = FullTokenizer::new(source);
+    let mut slices = vec![];
+    while let Some(next) = tokenizer.next() {
+        slices.push((next.span.slice(source), next.span.byte_start));
+    }
+    assert_eq!(slices, vec![("ab", 0), (" \n ", 2), ("cd", 5)]);
+}
+
+#[token_parse(columns = "chars", tab_width = 4)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Chars<'a> {
+    #[token(regex = "[^ \t\n]+")]
+    Word(&'a str),
+    #[token(regex = "[ \t\n]+")]
+    Ws,
+}
+
+#[token_parse(columns = "utf16")]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Utf16<'a> {
+    #[token(regex = "[^ \t\n]+")]
+    Word(&'a str),
+    #[token(regex = "[ \t\n]+")]
+    Ws,
+}
+
+#[test]
+fn columns_count_chars_with_tab_stops() {
+    let mut tokenizer = CharsTokenizer::new("é😀 a\tb\n\tc");
+    let mut cols = vec![];
+    while let Some(next) = tokenizer.next() {
+        cols.push((next.span.line_start, next.span.col_start, next.span.line_stop, next.span.col_stop));
+    }
+    // the tab after `a` (column 4) advances to the next tab stop at 8
+    assert_eq!(cols, vec![(0, 0, 0, 2), (0, 2, 0, 3), (0, 3, 0, 4), (0, 4, 0, 8), (0, 8, 0, 9), (0, 9, 1, 4), (1, 4, 1, 5)]);
+}
+
+#[test]
+fn columns_count_utf16_code_units() {
+    let mut tokenizer = Utf16Tokenizer::new("é😀 a\n😀\t");
+    let mut cols = vec![];
+    while let Some(next) = tokenizer.next() {
+        cols.push((next.span.col_start, next.span.col_stop));
+    }
+    // `😀` is two UTF-16 code units; without a tab width a tab is one
+    assert_eq!(cols, vec![(0, 3), (3, 4), (4, 5), (5, 0), (0, 2), (2, 3)]);
+}
+
+#[test]
+fn line_index_columns_match_derived_spans() {
+    use compiler_tools::{ColumnUnit, LineIndex};
+
+    fn check(source: &str, spans: Vec<compiler_tools::Span>, index: &LineIndex, unit: ColumnUnit) {
+        for span in spans {
+            let (start, end) = (span.byte_start as usize, span.byte_end as usize);
+            assert_eq!(index.line_col(start, unit), Some((span.line_start, span.col_start)), "start of {:?}", &source[start..end]);
+            assert_eq!(index.line_col(end, unit), Some((span.line_stop, span.col_stop)), "end of {:?}", &source[start..end]);
+            assert_eq!(index.offset(span.line_start, span.col_start, unit), Some(start));
+        }
+    }
+
+    let source = "é😀 a\tb\n\tc \t\td";
+    let mut tokenizer = CharsTokenizer::new(source);
+    let spans = std::iter::from_fn(|| tokenizer.next().map(|x| x.span)).collect();
+    check(source, spans, &LineIndex::new(source).with_tab_width(4), ColumnUnit::Chars);
+
+    let mut tokenizer = Utf16Tokenizer::new(source);
+    let spans = std::iter::from_fn(|| tokenizer.next().map(|x| x.span)).collect();
+    check(source, spans, &LineIndex::new(source), ColumnUnit::Utf16);
+
+    // The tokenizers report their settings, for wrappers measuring text they didn't lex.
+    assert_eq!((<CharsTokenizer as TokenParse>::COLUMNS, <CharsTokenizer as TokenParse>::TAB_WIDTH), (ColumnUnit::Chars, Some(4)));
+    assert_eq!((<Utf16Tokenizer as TokenParse>::COLUMNS, <Utf16Tokenizer as TokenParse>::TAB_WIDTH), (ColumnUnit::Utf16, None));
+
+    // A source map configured like the tokenizer indexes its files the same way.
+    let mut map = compiler_tools::SourceMap::new().with_columns(ColumnUnit::Chars).with_tab_width(4);
+    let file = map.add("a.src", source);
+    let mut tokenizer = CharsTokenizer::with_file(file, map.source(file));
+    let spans = std::iter::from_fn(|| tokenizer.next().map(|x| x.span)).collect();
+    check(source, spans, &map.line_index(file), map.columns());
+}
+
 #[test]
 fn spans_track_newlines() {
     // Whitespace containing a newline advances the line counter for following tokens.
@@ -212,3 +335,505 @@ fn no_lifetime_grammar_lexes() {
     };
     assert_eq!(toks, vec![Sym::Dot, Sym::Dot, Sym::Bad('@')]);
 }
+
+// No `illegal` fallback, so lexing can fail.
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Strict {
+    #[token(regex = "[0-9]+")]
+    Byte(u8),
+    #[token(regex = "[ \n]+")]
+    Ws,
+}
+
+#[test]
+fn try_next_distinguishes_eof_from_stuck_input() {
+    let mut tokenizer = StrictTokenizer::new("1 2");
+    assert_eq!(tokenizer.try_next().unwrap().map(|x| x.token), Some(Strict::Byte(1)));
+    assert_eq!(tokenizer.try_next().unwrap().map(|x| x.token), Some(Strict::Ws));
+    assert_eq!(tokenizer.try_next().unwrap().map(|x| x.token), Some(Strict::Byte(2)));
+    assert!(tokenizer.try_next().unwrap().is_none());
+
+    let mut tokenizer = StrictTokenizer::new("1\n x");
+    while let Ok(Some(_)) = tokenizer.try_next() {}
+    let error = tokenizer.try_next().unwrap_err();
+    assert_eq!(error.kind, LexErrorKind::NoMatch);
+    assert_eq!((error.span.line_start, error.span.col_start), (1, 1));
+    assert_eq!(error.to_string(), "no token matches input at 1:1");
+    // stuck input doesn't advance
+    assert_eq!(tokenizer.try_next().unwrap_err(), error);
+    assert!(tokenizer.next().is_none());
+}
+
+#[test]
+fn try_next_reports_payload_parse_failures() {
+    let mut tokenizer = StrictTokenizer::new("256 7");
+    let error = tokenizer.try_next().unwrap_err();
+    assert_eq!(
+        error.kind,
+        LexErrorKind::ParseFailed {
+            variant: "Byte".to_string(),
+            message: "number too large to fit in target type".to_string(),
+        }
+    );
+    assert_eq!((error.span.col_start, error.span.col_stop), (0, 3));
+    assert_eq!(error.to_string(), "failed to parse 'Byte' token at 0:0-3: number too large to fit in target type");
+    // the bad token is consumed, so lexing can resume after it
+    assert_eq!(tokenizer.try_next().unwrap().map(|x| x.token), Some(Strict::Ws));
+    assert_eq!(tokenizer.try_next().unwrap().map(|x| x.token), Some(Strict::Byte(7)));
+}
+
+// Whitespace and comments are consumed inside the tokenizer.
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Trivia<'a> {
+    #[token(regex = "[a-z]+")]
+    Ident(&'a str),
+    #[token(regex = "[ \n]+", skip)]
+    Ws,
+    #[token(regex = "#[^\n]*")]
+    #[token(skip)]
+    Comment(&'a str),
+    Semi = ";",
+    #[token(illegal)]
+    Illegal(char),
+}
+
+#[test]
+fn skipped_tokens_are_never_returned() {
+    let mut tokenizer = TriviaTokenizer::new("a # note\n  b;\n# end\n");
+    let mut out = vec![];
+    while let Some(next) = tokenizer.next() {
+        out.push((next.token, next.span.line_start, next.span.col_start, next.span.line_stop, next.span.col_stop));
+    }
+    assert_eq!(out, vec![(Trivia::Ident("a"), 0, 0, 0, 1), (Trivia::Ident("b"), 1, 2, 1, 3), (Trivia::Semi, 1, 3, 1, 4)]);
+}
+
+#[test]
+fn skipped_tokens_reach_eof_and_errors() {
+    assert!(TriviaTokenizer::new("  # only trivia").try_next().unwrap().is_none());
+    let next = TriviaTokenizer::new(" \n @").next().unwrap();
+    assert_eq!((next.token, next.span.line_start, next.span.col_start), (Trivia::Illegal('@'), 1, 1));
+}
+
+#[test]
+fn trivia_wrap_round_trips_skipped_and_ignored_tokens() {
+    // `Ws` and `Comment` are skipped by the tokenizer itself, `Semi` is ignored by the wrapper
+    let source = "# lead\na ;b # tail\n\n  c ; \n";
+    let mut wrap = TriviaWrap::new(TriviaTokenizer::new(source), source, [Trivia::Semi]);
+    let mut tokens = vec![];
+    let mut out = String::new();
+    while let Some(next) = wrap.next() {
+        next.write_source(&mut out);
+        tokens.push(next);
+    }
+    assert_eq!(out, source);
+    assert_eq!(
+        tokens.iter().map(|x| x.token.token).collect::<Vec<_>>(),
+        vec![Trivia::Ident("a"), Trivia::Ident("b"), Trivia::Ident("c")]
+    );
+    assert_eq!(tokens[0].leading.iter().map(|x| x.text).collect::<Vec<_>>(), vec!["# lead\n"]);
+    assert_eq!(tokens[0].trailing_of(&Trivia::Semi).count(), 1);
+    assert_eq!(tokens[1].trailing.iter().map(|x| x.text).collect::<Vec<_>>(), vec![" # tail\n\n  "]);
+}
+
+// A grammar lexed in longest-match mode: every variant is tried at each position and the
+// longest match wins, with declaration order only breaking ties.
+#[token_parse(longest_match)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Longest<'a> {
+    If = "if",
+    Dot = ".",
+    #[token(regex = "[a-z]+")]
+    Ident(&'a str),
+    // Only ever lexed as `Ident`, which wins the tie.
+    #[token(allow_shadowed)]
+    Else = "else",
+    #[token(regex = "[0-9]+")]
+    Int(&'a str),
+    #[token(regex = "[0-9]+\\.[0-9]+")]
+    Float(&'a str),
+    #[token(parse_fn = "compiler_tools::util::parse_str::<'\\''>")]
+    Str(&'a str),
+    #[token(regex = "'[a-z]*")]
+    Tick(&'a str),
+    #[token(regex = "[ \n]+")]
+    Ws,
+    #[token(illegal)]
+    Illegal(char),
+}
+
+fn lex_longest(input: &str) -> Vec<Longest<'_>> {
+    let mut tokenizer = LongestTokenizer::new(input);
+    let mut out = vec![];
+    while let Some(next) = tokenizer.next() {
+        out.push(next.token);
+    }
+    out
+}
+
+#[test]
+fn longest_match_prefers_later_longer_regex() {
+    // `Int` is declared first and matches "1", but `Float` matches more.
+    assert_eq!(lex_longest("1.5"), vec![Longest::Float("1.5")]);
+    assert_eq!(lex_longest("1."), vec![Longest::Int("1"), Longest::Dot]);
+}
+
+#[test]
+fn longest_match_ties_go_to_declaration_order() {
+    // `If` is declared before `Ident`, so the equal-length match is the keyword.
+    assert_eq!(lex_longest("if"), vec![Longest::If]);
+    assert_eq!(lex_longest("iffy"), vec![Longest::Ident("iffy")]);
+    // `Else` is declared after `Ident`, so the identifier wins the tie.
+    assert_eq!(lex_longest("else"), vec![Longest::Ident("else")]);
+}
+
+#[test]
+fn longest_match_spans_parse_fns() {
+    // the parse_fn consumes the whole string, beating the shorter `Tick` regex
+    assert_eq!(lex_longest("'ab'"), vec![Longest::Str("'ab'")]);
+    assert_eq!(lex_longest("'ab"), vec![Longest::Tick("'ab")]);
+}
+
+#[test]
+fn longest_match_spans_and_fallback() {
+    let mut tokenizer = LongestTokenizer::new("if\n2.25@");
+    let mut spans = vec![];
+    while let Some(next) = tokenizer.next() {
+        spans.push((next.token, next.span.line_start, next.span.col_start, next.span.line_stop, next.span.col_stop));
+    }
+    assert_eq!(
+        spans,
+        vec![
+            (Longest::If, 0, 0, 0, 2),
+            (Longest::Ws, 0, 2, 1, 0),
+            (Longest::Float("2.25"), 1, 0, 1, 4),
+            (Longest::Illegal('@'), 1, 4, 1, 5),
+        ]
+    );
+}
+
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Interp<'a> {
+    #[token(literal = "\"", push_mode = "string")]
+    #[token(mode = "default")]
+    #[token(mode = "code")]
+    Open,
+    #[token(literal = "\"", mode = "string", pop_mode)]
+    Close,
+    #[token(literal = "${", mode = "string", push_mode = "code")]
+    InterpStart,
+    #[token(regex = "[^\"$]+", mode = "string")]
+    Text(&'a str),
+    #[token(literal = "}", mode = "code", pop_mode)]
+    InterpEnd,
+    #[token(regex = "[a-z]+")]
+    #[token(mode = "default")]
+    #[token(mode = "code")]
+    Ident(&'a str),
+    #[token(regex = " +")]
+    Ws,
+    #[token(illegal)]
+    Illegal(char),
+}
+
+fn lex_interp(input: &str) -> Vec<(Interp<'_>, &'static str)> {
+    let mut tokenizer = InterpTokenizer::new(input);
+    let mut out = vec![];
+    while let Some(next) = tokenizer.next() {
+        out.push((next.token, tokenizer.mode()));
+    }
+    out
+}
+
+#[test]
+fn modes_switch_token_sets() {
+    // the same text lexes differently inside and outside the string
+    assert_eq!(
+        lex_interp("a \"a b\" b"),
+        vec![
+            (Interp::Ident("a"), "default"),
+            (Interp::Ws, "default"),
+            (Interp::Open, "string"),
+            (Interp::Text("a b"), "string"),
+            (Interp::Close, "default"),
+            (Interp::Ws, "default"),
+            (Interp::Ident("b"), "default"),
+        ]
+    );
+}
+
+#[test]
+fn modes_nest_as_a_stack() {
+    assert_eq!(
+        lex_interp("\"x${y\"z\"}\""),
+        vec![
+            (Interp::Open, "string"),
+            (Interp::Text("x"), "string"),
+            (Interp::InterpStart, "code"),
+            (Interp::Ident("y"), "code"),
+            (Interp::Open, "string"),
+            (Interp::Text("z"), "string"),
+            (Interp::Close, "code"),
+            (Interp::InterpEnd, "string"),
+            (Interp::Close, "default"),
+        ]
+    );
+}
+
+#[test]
+fn modes_share_the_illegal_fallback() {
+    // `Ws` only exists in the default mode, and `}` only in `code`
+    assert_eq!(
+        lex_interp("}\"${ }"),
+        vec![
+            (Interp::Illegal('}'), "default"),
+            (Interp::Open, "string"),
+            (Interp::InterpStart, "code"),
+            (Interp::Illegal(' '), "code"),
+            (Interp::InterpEnd, "string"),
+        ]
+    );
+}
+
+#[test]
+fn modes_expose_their_combined_dfa() {
+    let default = InterpTokenizer::dfa_dot("default").unwrap();
+    let string = InterpTokenizer::dfa_dot("string").unwrap();
+    assert!(default.starts_with("digraph {") && default.contains("start -> 0"));
+    assert_ne!(default, string);
+    assert_eq!(InterpTokenizer::dfa_dot("nope"), None);
+}
+
+// Multi-field and struct variants are filled from capture groups: tuple fields by position, named fields by name.
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Lit<'a> {
+    #[token(regex = "(?P<int>[0-9]+)\\.(?P<frac>[0-9]+)")]
+    Float { int: &'a str, frac: u32 },
+    #[token(regex = "([0-9]+)(?:e([0-9]+))?")]
+    Int(u64, Option<u32>),
+    #[token(regex = "#(?P<tag>[a-z]+)\\b")]
+    Tag { tag: &'a str },
+    #[token(regex_full = "([a-z]+)(?:@([a-z]+))?")]
+    At(&'a str, &'a str),
+    #[token(regex = " +")]
+    Ws,
+    #[token(illegal)]
+    Illegal(char),
+}
+
+fn lex_lit(input: &str) -> Vec<Lit<'_>> {
+    let mut tokenizer = LitTokenizer::new(input);
+    let mut out = vec![];
+    while let Some(next) = tokenizer.next() {
+        if next.token != Lit::Ws {
+            out.push(next.token);
+        }
+    }
+    out
+}
+
+#[test]
+fn fields_are_filled_from_capture_groups() {
+    assert_eq!(
+        lex_lit("1.50 12 3e4 #ab x@yz"),
+        vec![
+            Lit::Float {
+                int: "1",
+                frac: 50,
+            },
+            Lit::Int(12, None),
+            Lit::Int(3, Some(4)),
+            Lit::Tag {
+                tag: "ab",
+            },
+            Lit::At("x", "yz"),
+        ]
+    );
+    // a group that doesn't participate fills a non-`Option` field with empty text
+    assert_eq!(lex_lit("abc"), vec![Lit::At("abc", "")]);
+    // the `\b` after the group sees the input following the match
+    assert_eq!(lex_lit("#ab1"), vec![Lit::Illegal('#'), Lit::At("ab", ""), Lit::Int(1, None)]);
+}
+
+#[test]
+fn capture_field_parse_failures_name_the_variant() {
+    let mut tokenizer = LitTokenizer::new("1.99999999999");
+    let error = tokenizer.try_next().unwrap_err();
+    assert_eq!(
+        error.kind,
+        LexErrorKind::ParseFailed {
+            variant: "Float".to_string(),
+            message: "number too large to fit in target type".to_string(),
+        }
+    );
+    assert_eq!((error.span.col_start, error.span.col_stop), (0, 13));
+}
+
+#[test]
+fn capture_variants_display_and_match_by_class() {
+    use compiler_tools::TokenExt;
+    let float = Lit::Float {
+        int: "1",
+        frac: 5,
+    };
+    assert_eq!(float.to_string(), "Float");
+    assert_eq!(Lit::At("a", "b").to_string(), "At");
+    assert!(float.matches_class(&Lit::Float {
+        int: "2",
+        frac: 0,
+    }));
+    assert!(Lit::Int(1, None).matches_class(&Lit::Int(2, Some(3))));
+    assert!(!Lit::Int(1, None).matches_class(&float));
+}
+
+// A grammar lexed over `&[u8]`: classes match single bytes, while literals and non-ASCII
+// chars match their UTF-8 encoding.
+#[token_parse(bytes)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Bin<'a> {
+    Arrow = "→",
+    #[token(regex = "[0-9]+")]
+    Int(u32),
+    #[token(regex_full = "(?-u)\\xFE\\xFF")]
+    Bom,
+    #[token(regex = "[\\x80-\\xFF]+")]
+    High(&'a [u8]),
+    #[token(regex = "([a-z]+)=([0-9]+)")]
+    Pair(&'a [u8], u8),
+    #[token(regex = "[ \n]+")]
+    Ws,
+    #[token(illegal)]
+    Illegal(&'a [u8]),
+}
+
+fn lex_bin(input: &[u8]) -> Vec<Bin<'_>> {
+    let mut tokenizer = BinTokenizer::new(input);
+    let mut out = vec![];
+    while let Some(next) = tokenizer.next() {
+        if next.token != Bin::Ws {
+            out.push(next.token);
+        }
+    }
+    out
+}
+
+#[test]
+fn bytes_tokenizer_lexes_raw_bytes() {
+    assert_eq!(
+        lex_bin(b"12 \xFE\xFF\x80\x81 k=7 \xE2\x86\x92 @"),
+        vec![
+            Bin::Int(12),
+            Bin::Bom,
+            Bin::High(b"\x80\x81"),
+            Bin::Pair(b"k", 7),
+            Bin::Arrow,
+            Bin::Illegal(b"@"),
+        ]
+    );
+    // the arrow's encoding is all high bytes, so a longer run of them is `High`
+    assert_eq!(lex_bin("→→".as_bytes()), vec![Bin::High("→→".as_bytes())]);
+    assert_eq!(Bin::High(b"\xFFa").to_string(), "\u{FFFD}a");
+}
+
+#[test]
+fn bytes_tokenizer_spans_count_bytes() {
+    let mut tokenizer = BinTokenizer::new(b"\xFF\n\xFF\xFE 1");
+    let mut spans = vec![];
+    while let Some(next) = tokenizer.next() {
+        spans.push((next.span.line_start, next.span.col_start, next.span.byte_start, next.span.byte_end));
+    }
+    assert_eq!(spans, vec![(0, 0, 0, 1), (0, 1, 1, 2), (1, 0, 2, 4), (1, 2, 4, 5), (1, 3, 5, 6)]);
+    // parsed payloads need UTF-8 text
+    let mut tokenizer = BinTokenizer::new(b"9999999999");
+    assert!(matches!(tokenizer.try_next().unwrap_err().kind, LexErrorKind::ParseFailed { .. }));
+}
+
+// Set operators in token patterns, read under `set_operators`: a comment is `/*`, a body not
+// containing `*/`, then `*/`, and a name is any run of letters but a keyword, so it can come first.
+#[token_parse(longest_match, set_operators)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Set<'a> {
+    #[token(regex = r"/\*~((?s:.*)\*/(?s:.*))\*/")]
+    Comment(&'a str),
+    #[token(regex = "[a-z]+(?-if|else)")]
+    Name(&'a str),
+    If = "if",
+    Else = "else",
+    #[token(regex = "[ \n]+")]
+    Ws,
+    #[token(illegal)]
+    Illegal(char),
+}
+
+#[test]
+fn set_operators_shape_token_patterns() {
+    let mut tokenizer = SetTokenizer::new("if iffy /* a\n */ else */");
+    let mut tokens = vec![];
+    while let Some(next) = tokenizer.next() {
+        if next.token != Set::Ws {
+            tokens.push(next.token);
+        }
+    }
+    assert_eq!(
+        tokens,
+        vec![
+            Set::If,
+            Set::Name("iffy"),
+            Set::Comment("/* a\n */"),
+            Set::Else,
+            Set::Illegal('*'),
+            Set::Illegal('/')
+        ]
+    );
+}
+
+// `property_tests` generates `props_samples_lex_as_their_variants`, which lexes strings made up from each variant's
+// patterns: keywords must win over `Ident`, `Ws` must lex as nothing, `Pair` must fill its fields, and an `Int` too
+// large for a `u8` still lexes as `Int`.
+#[token_parse(property_tests)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Props<'a> {
+    Let = "let",
+    Arrow = "->",
+    #[token(regex = "[0-9]+")]
+    Int(u8),
+    #[token(regex = "(?P<name>[a-z]+):(?P<value>[0-9]{1,3})")]
+    Pair {
+        name: &'a str,
+        value: u16,
+    },
+    #[token(regex = "[a-z][a-z0-9_]*")]
+    Ident(&'a str),
+    #[token(regex = "[ \t]+", skip)]
+    Ws,
+    #[token(illegal)]
+    Illegal(char),
+}
+
+// The same over `&[u8]`, where the samples and the `→` literal are compared byte by byte.
+#[token_parse(bytes, property_tests)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum PropsBin<'a> {
+    Arrow = "→",
+    #[token(regex = "[0-9]+")]
+    Int(u32),
+    #[token(regex = "[a-z]+")]
+    Word(&'a [u8]),
+    #[token(regex = " +", skip)]
+    Ws,
+    #[token(illegal)]
+    Illegal(u8),
+}
+
+#[test]
+fn bytes_illegal_token_holds_the_byte() {
+    let mut tokenizer = PropsBinTokenizer::new(b"1@\xFF");
+    let mut tokens = vec![];
+    while let Some(next) = tokenizer.try_next().unwrap() {
+        tokens.push(next.token);
+    }
+    assert_eq!(tokens, vec![PropsBin::Int(1), PropsBin::Illegal(b'@'), PropsBin::Illegal(0xFF)]);
+}
@@ -30,6 +30,32 @@ pub enum TokenFull<'a> {
     CommentBlock(&'a str),
 }
 
+// `Sentence` can extend over every `Word` and `Space` after it, so it keeps walking once `Word` has won unless the
+// combined scan stops there.
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TokenOverlap<'a> {
+    #[token(regex = "[a-z]+")]
+    Word(&'a str),
+    #[token(regex = "[ \t]+")]
+    Space(&'a str),
+    #[token(regex = "[a-z ]+!")]
+    Sentence(&'a str),
+}
+
+// Under `longest_match` the combined scan runs until no pattern can extend, so it stays linear as long as no pattern
+// can run past the token that wins.
+#[token_parse(longest_match)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum TokenLongest<'a> {
+    #[token(regex = "[a-z]+")]
+    Word(&'a str),
+    #[token(regex = "[a-z]+[0-9]+")]
+    Numbered(&'a str),
+    #[token(regex = "[ \t]+")]
+    Space(&'a str),
+}
+
 fn duration_ms(duration: Duration) -> f64 {
     duration.as_secs_f64() * 1000.0
 }
@@ -51,7 +77,7 @@ fn test_regex_or() {
 fn bench_simple() {
     let idents = "test_ide123nt+".repeat(TEST_COUNT);
 
-    let mut tokenizer = TokenizerSimple::new(&*idents);
+    let mut tokenizer = TokenizerSimple::new(&idents);
     let start = Instant::now();
     for _ in 0..TEST_COUNT {
         assert!(tokenizer.next().is_some());
@@ -61,7 +87,7 @@ fn bench_simple() {
 
     let idents = "/* test * block */+".repeat(TEST_COUNT);
 
-    let mut tokenizer = TokenizerSimple::new(&*idents);
+    let mut tokenizer = TokenizerSimple::new(&idents);
     let start = Instant::now();
     for _ in 0..TEST_COUNT {
         assert!(tokenizer.next().is_some());
@@ -77,7 +103,7 @@ fn bench_simple() {
 fn bench_full() {
     let idents = "test_ide123nt+".repeat(TEST_COUNT);
 
-    let mut tokenizer = TokenizerFull::new(&*idents);
+    let mut tokenizer = TokenizerFull::new(&idents);
     let start = Instant::now();
     for _ in 0..TEST_COUNT {
         assert!(tokenizer.next().is_some());
@@ -87,11 +113,48 @@ fn bench_full() {
 
     let idents = "/* test * block */+".repeat(TEST_COUNT);
 
-    let mut tokenizer = TokenizerFull::new(&*idents);
+    let mut tokenizer = TokenizerFull::new(&idents);
+    let start = Instant::now();
+    for _ in 0..TEST_COUNT {
+        assert!(tokenizer.next().is_some());
+    }
+    let elapsed = duration_ms(start.elapsed());
+    println!("took {:.02} ms for {} idents @ {:.04} ms/ident", elapsed, TEST_COUNT, elapsed / TEST_COUNT as f64);
+}
+
+// cargo test --release --package compiler-tools-derive --test regex_bench -- bench_overlap --exact --nocapture
+// took 2.17 ms for 100000 idents @ 0.0000 ms/ident
+#[test]
+fn bench_overlap() {
+    let idents = "abc ".repeat(TEST_COUNT);
+
+    let mut tokenizer = TokenizerOverlap::new(&idents);
+    let start = Instant::now();
+    for _ in 0..TEST_COUNT {
+        assert!(matches!(tokenizer.next().map(|token| token.token), Some(TokenOverlap::Word("abc"))));
+        assert!(tokenizer.next().is_some());
+    }
+    let elapsed = duration_ms(start.elapsed());
+    println!("took {:.02} ms for {} idents @ {:.04} ms/ident", elapsed, TEST_COUNT, elapsed / TEST_COUNT as f64);
+    // Rescanning the rest of the input at every identifier takes minutes here.
+    assert!(elapsed < 10_000.0, "lexing is quadratic: took {elapsed:.02} ms");
+}
+
+// cargo test --release --package compiler-tools-derive --test regex_bench -- bench_longest --exact --nocapture
+// took 7.08 ms for 100000 idents @ 0.0001 ms/ident
+#[test]
+fn bench_longest() {
+    let idents = "abc abc1 ".repeat(TEST_COUNT);
+
+    let mut tokenizer = TokenizerLongest::new(&idents);
     let start = Instant::now();
     for _ in 0..TEST_COUNT {
+        assert!(matches!(tokenizer.next().map(|token| token.token), Some(TokenLongest::Word("abc"))));
+        assert!(tokenizer.next().is_some());
+        assert!(matches!(tokenizer.next().map(|token| token.token), Some(TokenLongest::Numbered("abc1"))));
         assert!(tokenizer.next().is_some());
     }
     let elapsed = duration_ms(start.elapsed());
     println!("took {:.02} ms for {} idents @ {:.04} ms/ident", elapsed, TEST_COUNT, elapsed / TEST_COUNT as f64);
+    assert!(elapsed < 10_000.0, "lexing is quadratic: took {elapsed:.02} ms");
 }
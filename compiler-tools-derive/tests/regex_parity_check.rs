@@ -181,3 +181,26 @@ fn z_anchor_runtime() {
     let mut t2 = ZAnchorTokenizer::new("abc");
     assert!(matches!(t2.next().unwrap().token, ZAnchor::Word("abc")));
 }
+
+// The `\b` competes with the consuming `[a-z ]*`, so this lexes with the lock-step
+// thread simulation rather than a DFA walk.
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum LockStep<'a> {
+    #[token(regex = "[a-z ]*\\bx")]
+    EndsInX(&'a str),
+    #[token(illegal)]
+    Illegal(char),
+}
+
+#[test]
+fn lock_step_runtime() {
+    let mut t = LockStepTokenizer::new("a b cd x");
+    assert!(matches!(t.next().unwrap().token, LockStep::EndsInX("a b cd x")));
+    // Only an `x` starting a word ends the token, and the last one wins.
+    let mut t2 = LockStepTokenizer::new("x ax x!");
+    assert!(matches!(t2.next().unwrap().token, LockStep::EndsInX("x ax x")));
+    // No `x` starts a word, so nothing but the illegal char lexes.
+    let mut t3 = LockStepTokenizer::new("ax");
+    assert!(matches!(t3.next().unwrap().token, LockStep::Illegal('a')));
+}
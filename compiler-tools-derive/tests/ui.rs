@@ -0,0 +1,6 @@
+//! Diagnostics the derive reports, checked against the `.stderr` files in `tests/ui`.
+
+#[test]
+fn ui() {
+    trybuild::TestCases::new().compile_fail("tests/ui/*.rs");
+}
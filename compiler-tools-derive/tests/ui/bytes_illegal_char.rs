@@ -0,0 +1,13 @@
+use compiler_tools_derive::token_parse;
+
+#[token_parse(bytes)]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Token<'a> {
+    #[token(regex = "[a-z]+")]
+    Ident(&'a [u8]),
+    // A stray byte needn't be a char.
+    #[token(illegal)]
+    Illegal(char),
+}
+
+fn main() {}
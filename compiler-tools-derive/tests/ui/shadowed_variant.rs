@@ -0,0 +1,21 @@
+// `deprecated` is how the shadowing warning reaches rustc, so denying it turns the warning into this failure.
+#![deny(deprecated)]
+
+use compiler_tools_derive::token_parse;
+
+#[token_parse]
+#[derive(PartialEq, Clone, Copy, Debug)]
+pub enum Token<'a> {
+    #[token(regex = "[a-z]+")]
+    Ident(&'a str),
+    #[token(regex = "let|fn")]
+    Keyword(&'a str),
+    // Not reported: the variant opts out.
+    #[token(regex = "[a-z]+_")]
+    #[token(allow_shadowed)]
+    Never(&'a str),
+    #[token(illegal)]
+    Illegal(char),
+}
+
+fn main() {}
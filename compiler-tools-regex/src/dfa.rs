@@ -13,13 +13,71 @@ const MAX_CP: u32 = 0x10FFFF;
 
 /// A character class as a list of inclusive codepoint ranges (kept sorted and
 /// disjoint by [`normalize`]).
-type Ranges = Vec<(u32, u32)>;
+pub(crate) type Ranges = Vec<(u32, u32)>;
 
-#[derive(Debug)]
+/// A state's edges as [`Dfa::minimize`] compares them: the chars leading into each
+/// class of states, then the other edges with their targets' classes.
+type ClassEdges = (Vec<(Ranges, u32)>, Vec<(TransitionEvent, u32)>);
+
+#[derive(Debug, Clone)]
 pub struct Dfa {
     // state => [(event, state)]
     pub transitions: BTreeMap<u32, Vec<(TransitionEvent, u32)>>,
+    /// The accepting sink of the first pattern — for a DFA built from one regex, the
+    /// only one.
     pub final_state: u32,
+    /// The accepting sink of every pattern, by pattern index. An `End` edge points at
+    /// the sink of the pattern it accepts, so a multi-pattern DFA
+    /// ([`Dfa::build_multi`]) tags each accepting state with the patterns it accepts.
+    pub final_states: Vec<u32>,
+    /// Whether walking the DFA one state at a time can miss a match. A walk takes an
+    /// assertion edge only once no consuming edge claims the lookahead, and then only
+    /// the first that holds, so it is exact only where an assertion either leaves a
+    /// state on its own or gates an accept behind every consuming edge (`.+\b`, which
+    /// the walk backs off to). Otherwise (`.*\bx`, `(?m)(?:^|a)*`, `(?:^|a+)`) the
+    /// threads have to run in lock-step, as in [`PikeVm`](crate::PikeVm).
+    pub lock_step: bool,
+    /// The state counts before and after [`Dfa::minimize`].
+    pub minimization: Minimization,
+}
+
+/// How far [`Dfa::minimize`] shrank a DFA: its state count, accepting sinks included,
+/// as the subset construction built it and once equivalent states were merged. The
+/// generated matcher and the JIT emit one arm or block per state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Minimization {
+    pub before: usize,
+    pub after: usize,
+}
+
+/// How [`Dfa::product`] combines the strings two DFAs match.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SetOperation {
+    /// The strings both match: `(?&...)`.
+    Intersection,
+    /// The strings the first matches and the second doesn't: `(?-...)`, and `~(...)`
+    /// with every string as the first.
+    Difference,
+}
+
+/// Which pattern each NFA state belongs to. A single regex's NFA is one pattern
+/// spanning every state; the union NFA behind [`Dfa::build_multi`] prefixes a root
+/// split (state 0, owned by no pattern) and lays the patterns out in contiguous,
+/// increasing state ranges.
+struct Patterns {
+    /// The first NFA state of each pattern's range.
+    starts: Vec<u32>,
+    /// The accepting NFA state of each pattern.
+    finals: Vec<u32>,
+    /// Whether only the first pattern to match (by index) matters, as for a caller
+    /// trying the patterns in order: see [`ordered_closure`].
+    first_match: bool,
+}
+
+impl Patterns {
+    fn of(&self, state: u32) -> Option<usize> {
+        self.starts.partition_point(|start| *start <= state).checked_sub(1)
+    }
 }
 
 /// Priority-ordered epsilon-closure of an (ordered) seed of NFA states: every
@@ -35,36 +93,64 @@ pub struct Dfa {
 /// cut. This is the heart of first-match semantics: once a higher-priority thread
 /// has matched, lower-priority continuations can never be preferred, so dropping
 /// them is both correct and what keeps the ordered-subset state space finite.
-fn ordered_closure(nfa: &Nfa, seed: &[u32], final_state: u32) -> Closure {
+///
+/// With several patterns the cut is per pattern: reaching one pattern's accept
+/// drops only that pattern's lower-priority threads, so every pattern keeps its
+/// own leftmost-first match. With `first_match` set, reaching it also drops every
+/// later pattern's threads: that pattern's match beats theirs, so the walk only
+/// goes on while an earlier pattern can still extend.
+///
+/// With `all` set nothing is cut, and since priority no longer decides anything
+/// the closure is returned sorted, so states differing only in order merge (see
+/// [`Dfa::build_all`]).
+fn ordered_closure(nfa: &Nfa, seed: &[u32], patterns: &Patterns, all: bool, first_match: bool) -> Closure {
     let mut out: Closure = vec![];
     let mut seen: HashSet<u32> = HashSet::new();
+    let mut cut = vec![false; patterns.finals.len()];
+    let mut remaining = patterns.finals.len();
     // Pre-order DFS with an explicit stack (avoids recursion blowups on large
     // unrolled `{n}` machines). Successors are pushed in reverse so they pop in
     // stored order; the whole seed is pushed reversed for the same reason.
     let mut stack: Vec<u32> = seed.iter().rev().copied().collect();
     while let Some(state) = stack.pop() {
-        if !seen.insert(state) {
+        let pattern = patterns.of(state);
+        if pattern.is_some_and(|pattern| cut[pattern]) || !seen.insert(state) {
             continue;
         }
         out.push(state);
-        if state == final_state {
-            // Cut lower-priority threads still on the stack.
-            break;
+        if let Some(pattern) = pattern.filter(|pattern| !all && patterns.finals[*pattern] == state) {
+            // Cut the pattern's lower-priority threads still on the stack, and with
+            // `first_match` every later pattern's too. The seed lists the patterns
+            // in order, so none of those has been visited yet.
+            let last = if first_match { cut.len() } else { pattern + 1 };
+            for flag in &mut cut[pattern..last] {
+                if !*flag {
+                    *flag = true;
+                    remaining -= 1;
+                }
+            }
+            if remaining == 0 {
+                break;
+            }
+            continue;
         }
         if let Some(transitions) = nfa.transitions.get(&state) {
             for (event, target) in transitions.iter().rev() {
-                if matches!(event, TransitionEvent::Epsilon) {
+                if matches!(event, TransitionEvent::Epsilon | TransitionEvent::Save(_)) {
                     stack.push(*target);
                 }
             }
         }
     }
+    if all {
+        out.sort_unstable();
+    }
     out
 }
 
 /// Sorts and merges a list of inclusive codepoint ranges into disjoint, ordered
 /// pieces (adjacent ranges are coalesced).
-fn normalize(ranges: &mut Ranges) {
+pub(crate) fn normalize(ranges: &mut Ranges) {
     ranges.retain(|(lo, hi)| lo <= hi);
     ranges.sort_unstable();
     let mut merged: Vec<(u32, u32)> = vec![];
@@ -78,7 +164,7 @@ fn normalize(ranges: &mut Ranges) {
 }
 
 /// The complement of a normalized range list over the whole codepoint space.
-fn complement(ranges: &[(u32, u32)]) -> Ranges {
+pub(crate) fn complement(ranges: &[(u32, u32)]) -> Ranges {
     let mut out = vec![];
     let mut next = 0u32;
     for (lo, hi) in ranges {
@@ -94,8 +180,26 @@ fn complement(ranges: &[(u32, u32)]) -> Ranges {
     out
 }
 
+/// The codepoints in both normalized range lists.
+pub(crate) fn intersect(a: &[(u32, u32)], b: &[(u32, u32)]) -> Ranges {
+    let mut out = vec![];
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let (lo, hi) = (a[i].0.max(b[j].0), a[i].1.min(b[j].1));
+        if lo <= hi {
+            out.push((lo, hi));
+        }
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    out
+}
+
 /// The set of codepoints a consuming transition accepts, as normalized ranges.
-fn event_ranges(event: &TransitionEvent) -> Ranges {
+pub(crate) fn event_ranges(event: &TransitionEvent) -> Ranges {
     match event {
         TransitionEvent::Char(c) => vec![(*c as u32, *c as u32)],
         TransitionEvent::Chars(inverted, entries) => {
@@ -245,25 +349,147 @@ impl Dfa {
     /// consume" rule is exactly leftmost-first (greedy keeps going; a lazy or
     /// preferred-empty path has no surviving consuming edge and accepts immediately).
     pub fn build(nfa: &Nfa) -> Self {
-        let final_singleton: Closure = vec![nfa.final_state];
+        Self::build_patterns(
+            nfa,
+            &Patterns {
+                starts: vec![0],
+                finals: vec![nfa.final_state],
+                first_match: false,
+            },
+            false,
+            usize::MAX,
+        )
+        .expect("an unbounded build always completes")
+        .minimize()
+    }
+
+    /// [`Dfa::build`] without the [`Dfa::minimize`] pass: one state per priority-ordered
+    /// closure the subset construction reached.
+    pub fn build_unminimized(nfa: &Nfa) -> Self {
+        Self::build_patterns(
+            nfa,
+            &Patterns {
+                starts: vec![0],
+                finals: vec![nfa.final_state],
+                first_match: false,
+            },
+            false,
+            usize::MAX,
+        )
+        .expect("an unbounded build always completes")
+    }
+
+    /// The every-match DFA of `nfa`: the same construction as [`Dfa::build`] but
+    /// without cutting at the accept, so a state accepts wherever *any* thread does
+    /// rather than only where the leftmost-first one would. Walking it from a start
+    /// position visits every end of a match there, which is what the earliest,
+    /// overlapping and longest searches need (the `regex` crate's `MatchKind::All`).
+    /// Priority no longer matters, so its states are plain sets of NFA states.
+    pub fn build_all(nfa: &Nfa) -> Self {
+        Self::build_patterns(
+            nfa,
+            &Patterns {
+                starts: vec![0],
+                finals: vec![nfa.final_state],
+                first_match: false,
+            },
+            true,
+            usize::MAX,
+        )
+        .expect("an unbounded build always completes")
+        .minimize()
+    }
+
+    /// [`Dfa::build`] (or with `all` set, [`Dfa::build_all`]) giving up once the DFA
+    /// grows past `max_states` states. The subset construction can blow up
+    /// exponentially (`(?s:.*?)a[ab]{20}` needs a state per combination of threads), so
+    /// a DFA that is only an optimization is built with a budget and skipped over it.
+    pub fn build_bounded(nfa: &Nfa, all: bool, max_states: usize) -> Option<Self> {
+        Self::build_patterns(
+            nfa,
+            &Patterns {
+                starts: vec![0],
+                finals: vec![nfa.final_state],
+                first_match: false,
+            },
+            all,
+            max_states,
+        )
+        .map(Self::minimize)
+    }
+
+    /// Builds one DFA matching every NFA at once, each as its own pattern (indexed
+    /// by position in `nfas`). The union is an NFA whose root splits into every
+    /// pattern in order; the closure cuts each pattern's threads independently, so
+    /// a single walk finds every pattern's leftmost-first match, and each accepting
+    /// state carries an `End` edge to the sink of every pattern it accepts (see
+    /// [`Dfa::final_states`]).
+    ///
+    /// With `first_match` set, a pattern's accept also drops every later pattern's
+    /// threads, for a caller that only reads pattern `i` when no earlier pattern
+    /// matched: the walk then stops once no pattern up to the last one matched can
+    /// extend, and a later pattern's accepts are only recorded where no earlier
+    /// pattern has a non-empty match.
+    ///
+    /// A walk takes a zero-width edge only when no consuming edge claims the
+    /// lookahead, which is one thread's view of the input: patterns with zero-width
+    /// assertions must not be combined, or a consuming pattern would starve them
+    /// (see [`crate::RegexSet`], which runs those standalone).
+    pub fn build_multi(nfas: &[Nfa], first_match: bool) -> Self {
+        let mut transitions: BTreeMap<u32, Vec<(TransitionEvent, u32)>> = BTreeMap::new();
+        let mut patterns = Patterns {
+            starts: vec![],
+            finals: vec![],
+            first_match,
+        };
+        let mut root = vec![];
+        let mut offset = 1u32;
+        for nfa in nfas {
+            patterns.starts.push(offset);
+            patterns.finals.push(nfa.final_state + offset);
+            root.push((TransitionEvent::Epsilon, offset));
+            for (state, edges) in &nfa.transitions {
+                transitions.insert(state + offset, edges.iter().map(|(event, target)| (event.clone(), target + offset)).collect());
+            }
+            offset += nfa.transitions.keys().next_back().map_or(0, |last| last + 1).max(nfa.final_state + 1);
+        }
+        transitions.insert(0, root);
+        let union = Nfa {
+            transitions,
+            final_state: patterns.finals.first().copied().unwrap_or(0),
+        };
+        Self::build_patterns(&union, &patterns, false, usize::MAX)
+            .expect("an unbounded build always completes")
+            .minimize()
+    }
+
+    fn build_patterns(nfa: &Nfa, patterns: &Patterns, all: bool, max_states: usize) -> Option<Self> {
         let mut interner = Interner {
             ids: HashMap::new(),
             next: 0,
         };
 
-        let start = ordered_closure(nfa, &[0], nfa.final_state);
+        // An empty match doesn't settle a first-match walk: the derive lets an empty
+        // skipped token fall through to the later variants.
+        let start = ordered_closure(nfa, &[0], patterns, all, false);
         interner.intern(&start); // id 0
-        let final_state = interner.intern(&final_singleton);
+        // Each pattern's accepting closure is a transition-less sink.
+        let final_singletons: Vec<Closure> = patterns.finals.iter().map(|state| vec![*state]).collect();
+        let final_states: Vec<u32> = final_singletons.iter().map(|singleton| interner.intern(singleton)).collect();
 
         let mut transitions: BTreeMap<u32, Vec<(TransitionEvent, u32)>> = BTreeMap::new();
+        let mut lock_step = false;
         let mut processed: HashSet<u32> = HashSet::new();
         let mut worklist = vec![start];
         while let Some(set) = worklist.pop() {
             let id = interner.intern(&set);
-            // The accepting closure is a transition-less sink; the rest are built once.
-            if set == final_singleton || !processed.insert(id) {
+            // The accepting closures are transition-less sinks; the rest are built once.
+            if final_singletons.contains(&set) || !processed.insert(id) {
                 continue;
             }
+            if interner.next as usize > max_states {
+                return None;
+            }
 
             // Gather the non-epsilon edges leaving the closure, in priority order.
             let mut consuming: Vec<(Ranges, u32)> = vec![];
@@ -283,11 +509,19 @@ impl Dfa {
                     targets.push(target);
                 }
             };
+            // Whether an assertion outranks some consuming edge, which the walk would take first.
+            let mut seen_assertion = false;
+            let mut assertion_outranks = false;
             for state in &set {
                 let Some(state_transitions) = nfa.transitions.get(state) else { continue };
                 for (event, target) in state_transitions {
+                    if is_assertion(event) {
+                        seen_assertion = true;
+                    } else if matches!(event, TransitionEvent::Char(_) | TransitionEvent::Chars(..)) {
+                        assertion_outranks |= seen_assertion;
+                    }
                     match event {
-                        TransitionEvent::Epsilon => {}
+                        TransitionEvent::Epsilon | TransitionEvent::Save(_) => {}
                         TransitionEvent::Char(_) | TransitionEvent::Chars(..) => consuming.push((event_ranges(event), *target)),
                         TransitionEvent::EndOfInput => push_unique(&mut end_of_input, *target),
                         TransitionEvent::StartOfText => push_unique(&mut start_of_text, *target),
@@ -309,7 +543,7 @@ impl Dfa {
 
             let mut out: Vec<(TransitionEvent, u32)> = vec![];
             let wire = |targets: Closure, interner: &mut Interner, worklist: &mut Vec<Closure>| -> u32 {
-                let closed = ordered_closure(nfa, &targets, nfa.final_state);
+                let closed = ordered_closure(nfa, &targets, patterns, all, patterns.first_match);
                 let target_id = interner.intern(&closed);
                 worklist.push(closed);
                 target_id
@@ -371,18 +605,256 @@ impl Dfa {
             // guarantees any consuming edge above outranks this accept, so trying
             // them first is leftmost-first (greedy); a lazy/empty-preferred state has
             // no surviving consuming edge and accepts here immediately.
-            if set.contains(&nfa.final_state) {
-                out.push((TransitionEvent::End, final_state));
+            for (pattern, final_state) in patterns.finals.iter().enumerate() {
+                if set.contains(final_state) {
+                    out.push((TransitionEvent::End, final_states[pattern]));
+                }
             }
 
+            let moves = out.iter().filter(|(event, _)| !matches!(event, TransitionEvent::End)).count();
+            lock_step |= moves > 1 && (assertion_outranks || out.iter().any(|(event, target)| is_assertion(event) && !final_states.contains(target)));
+
             transitions.insert(id, out);
         }
 
+        let states = interner.next as usize;
+        Some(Self {
+            transitions,
+            final_state: final_states.first().copied().unwrap_or(0),
+            final_states,
+            lock_step,
+            minimization: Minimization {
+                before: states,
+                after: states,
+            },
+        })
+    }
+
+    /// Merges the states no walk can tell apart (Moore's partition refinement), keeping
+    /// state 0 the start.
+    ///
+    /// Two states are merged when they offer the same consuming classes into merged
+    /// states, and the same zero-width and `End` edges, in the same order, into merged
+    /// states. The order of the zero-width edges is the priority the walk tries them
+    /// in, so it has to match, not just the language; the consuming classes are
+    /// disjoint, so only which chars lead where counts, and a class split across two
+    /// edges into states that merge becomes one edge. Each pattern's accepting sink
+    /// stays its own state. Merged states step alike on every input, so the walk, the
+    /// generated matcher and the JIT all match exactly as before, including
+    /// [`Dfa::lock_step`].
+    pub fn minimize(self) -> Self {
+        let mut states: Vec<u32> = self.transitions.keys().copied().chain(self.final_states.iter().copied()).collect();
+        states.sort_unstable();
+        states.dedup();
+        // Start with each sink alone and every other state together.
+        let mut class: HashMap<u32, u32> = states
+            .iter()
+            .map(|state| {
+                let sink = self.final_pattern(*state).unwrap_or(self.final_states.len());
+                (*state, sink as u32)
+            })
+            .collect();
+        let mut classes = states.iter().map(|state| class[state]).collect::<HashSet<_>>().len();
+        loop {
+            let mut ids: BTreeMap<(u32, ClassEdges), u32> = BTreeMap::new();
+            let mut refined = HashMap::new();
+            for state in &states {
+                let next = ids.len() as u32;
+                refined.insert(*state, *ids.entry((class[state], self.edges_by_class(*state, &class))).or_insert(next));
+            }
+            class = refined;
+            // Refining never merges, so an unchanged count is a fixpoint.
+            if ids.len() == classes {
+                break;
+            }
+            classes = ids.len();
+        }
+
+        let mut transitions = BTreeMap::new();
+        for state in &states {
+            if transitions.contains_key(&class[state]) || self.final_pattern(*state).is_some() {
+                continue;
+            }
+            let (consuming, other) = self.edges_by_class(*state, &class);
+            let out = consuming
+                .into_iter()
+                .map(|(ranges, target)| (ranges_to_event(&ranges), target))
+                .chain(other)
+                .collect();
+            transitions.insert(class[state], out);
+        }
         Self {
             transitions,
-            final_state,
+            final_state: self.final_states.first().map_or(0, |state| class[state]),
+            final_states: self.final_states.iter().map(|state| class[state]).collect(),
+            lock_step: self.lock_step,
+            minimization: Minimization {
+                before: self.minimization.before,
+                after: classes,
+            },
+        }
+    }
+
+    /// The edges leaving `state` with their targets replaced by the targets' classes:
+    /// the consuming ones as the chars leading into each class, ordered by first char,
+    /// and the rest in stored order. Equal for two states exactly when the walk can't
+    /// tell them apart one step on.
+    fn edges_by_class(&self, state: u32, class: &HashMap<u32, u32>) -> ClassEdges {
+        let mut consuming: BTreeMap<u32, Ranges> = BTreeMap::new();
+        let mut other = vec![];
+        for (event, target) in self.transitions.get(&state).into_iter().flatten() {
+            if matches!(event, TransitionEvent::Char(_) | TransitionEvent::Chars(..)) {
+                consuming.entry(class[target]).or_default().extend(event_ranges(event));
+            } else {
+                other.push((event.clone(), class[target]));
+            }
+        }
+        let mut consuming: Vec<(Ranges, u32)> = consuming
+            .into_iter()
+            .map(|(target, mut ranges)| {
+                normalize(&mut ranges);
+                (ranges, target)
+            })
+            .collect();
+        consuming.sort_unstable();
+        (consuming, other)
+    }
+
+    /// The consuming edges leaving `state`, as the chars each one steps on. The classes
+    /// are disjoint, so a char leads to at most one target.
+    pub(crate) fn char_edges(&self, state: u32) -> Vec<(Ranges, u32)> {
+        self.transitions
+            .get(&state)
+            .into_iter()
+            .flatten()
+            .filter(|(event, _)| matches!(event, TransitionEvent::Char(_) | TransitionEvent::Chars(..)))
+            .map(|(event, target)| (event_ranges(event), *target))
+            .collect()
+    }
+
+    /// Whether any state carries a zero-width assertion edge.
+    pub fn has_zero_width(&self) -> bool {
+        self.transitions.values().flatten().any(|(event, _)| is_assertion(event))
+    }
+
+    /// The pattern whose accepting sink is `state`, if it is one.
+    pub fn final_pattern(&self, state: u32) -> Option<usize> {
+        self.final_states.iter().position(|final_state| *final_state == state)
+    }
+
+    /// The every-match DFA of every string, for [`Dfa::complement`] to subtract from.
+    fn universal() -> Self {
+        Self {
+            transitions: BTreeMap::from([(0, vec![(TransitionEvent::Chars(true, vec![]), 0), (TransitionEvent::End, 1)])]),
+            final_state: 1,
+            final_states: vec![1],
+            lock_step: false,
+            minimization: Minimization {
+                before: 2,
+                after: 2,
+            },
         }
     }
+
+    /// The DFA of the strings this one doesn't match: `~(...)`. Like [`Dfa::product`],
+    /// it takes an every-match DFA without zero-width assertions.
+    pub(crate) fn complement(&self) -> Self {
+        Self::universal().product(self, SetOperation::Difference)
+    }
+
+    /// Walks this DFA and `other` in step, accepting the strings `operation` combines
+    /// their matches into. Both must be every-match DFAs ([`Dfa::build_all`]) without
+    /// zero-width assertions, so that a state accepts exactly the strings leading to
+    /// it; the product is one too. An empty product is a start state with no edges.
+    pub(crate) fn product(&self, other: &Dfa, operation: SetOperation) -> Self {
+        // A product state is a pair of states, `None` once `other` has died on the
+        // input. A walk this DFA dies on can't accept under either operation, so it is
+        // dropped, as is one `other` dies on when intersecting. State 1 is the sink.
+        let mut ids: HashMap<(u32, Option<u32>), u32> = HashMap::from([((0, Some(0)), 0)]);
+        let mut transitions = BTreeMap::new();
+        let mut worklist = vec![(0, Some(0))];
+        while let Some((state, other_state)) = worklist.pop() {
+            // Tag each target with the DFA it's in, so `partition` splits the chars by
+            // where each DFA steps on them.
+            let mut edges: Vec<(Ranges, u32)> = self.char_edges(state).into_iter().map(|(ranges, target)| (ranges, target * 2)).collect();
+            edges.extend(
+                other_state
+                    .into_iter()
+                    .flat_map(|state| other.char_edges(state))
+                    .map(|(ranges, target)| (ranges, target * 2 + 1)),
+            );
+            let mut out = vec![];
+            for (targets, ranges) in partition(&edges) {
+                let Some(next) = targets.iter().find(|target| *target % 2 == 0).map(|target| target / 2) else {
+                    continue;
+                };
+                let other_next = targets.iter().find(|target| *target % 2 == 1).map(|target| target / 2);
+                if operation == SetOperation::Intersection && other_next.is_none() {
+                    continue;
+                }
+                let next_id = ids.len() as u32 + 1;
+                let target = *ids.entry((next, other_next)).or_insert_with(|| {
+                    worklist.push((next, other_next));
+                    next_id
+                });
+                out.push((ranges_to_event(&ranges), target));
+            }
+            let other_accepts = other_state.is_some_and(|state| other.accepts(state));
+            let accepts = self.accepts(state)
+                && match operation {
+                    SetOperation::Intersection => other_accepts,
+                    SetOperation::Difference => !other_accepts,
+                };
+            if accepts {
+                out.push((TransitionEvent::End, 1));
+            }
+            transitions.insert(ids[&(state, other_state)], out);
+        }
+        let states = ids.len() + 1;
+        Self {
+            transitions,
+            final_state: 1,
+            final_states: vec![1],
+            lock_step: false,
+            minimization: Minimization {
+                before: states,
+                after: states,
+            },
+        }
+        .minimize()
+    }
+
+    /// The every-match DFA of the strings this one matches, reversed. Like
+    /// [`Dfa::product`], it takes a DFA without zero-width assertions.
+    pub(crate) fn reversed(&self) -> Self {
+        // An NFA of the edges turned around, with state `n + 1` standing for state `n`:
+        // its start steps into every accepting state, and the old start accepts.
+        let last = self.transitions.keys().chain(&self.final_states).copied().max().unwrap_or(0);
+        let final_state = last + 2;
+        let mut transitions: BTreeMap<u32, Vec<(TransitionEvent, u32)>> = (0..=final_state).map(|state| (state, vec![])).collect();
+        for (state, edges) in &self.transitions {
+            for (event, target) in edges {
+                let (from, event) = match event {
+                    TransitionEvent::End => (0, TransitionEvent::Epsilon),
+                    event => (target + 1, event.clone()),
+                };
+                transitions.entry(from).or_default().push((event, state + 1));
+            }
+        }
+        transitions.entry(1).or_default().push((TransitionEvent::Epsilon, final_state));
+        Self::build_all(&Nfa {
+            transitions,
+            final_state,
+        })
+    }
+}
+
+/// Whether `event` is a zero-width assertion: neither consuming, nor an epsilon, nor an accept.
+pub(crate) fn is_assertion(event: &TransitionEvent) -> bool {
+    !matches!(
+        event,
+        TransitionEvent::Epsilon | TransitionEvent::Save(_) | TransitionEvent::Char(_) | TransitionEvent::Chars(..) | TransitionEvent::End
+    )
 }
 
 #[cfg(test)]
@@ -395,6 +867,18 @@ mod tests {
         Dfa::build(&Nfa::build(&SimpleRegexAst::parse(pattern).expect("valid pattern")))
     }
 
+    #[test]
+    fn lock_step_is_needed_where_an_assertion_competes() {
+        // An assertion on its own, or gating an accept behind every consuming edge.
+        for pattern in ["abc", r"\bfoo\b", r"[a-z]+\b", ".+$", "(?m)^a+$"] {
+            assert!(!build(pattern).lock_step, "{pattern}");
+        }
+        // An assertion outranking a consuming edge, or leading on to one beside it.
+        for pattern in ["(?:^|a+)", "(?m)(?:^|a)*", r".*\bx", r"(?:\b|%)+"] {
+            assert!(build(pattern).lock_step, "{pattern}");
+        }
+    }
+
     /// Every state a transition can reach must either be the final (sink) state or
     /// itself have an entry in the transition table; otherwise the generated
     /// matcher would land on a state with no arms.
@@ -502,6 +986,52 @@ mod tests {
         assert_eq!(consuming_edges(state), 0, "the `b` of the second branch was cut");
     }
 
+    #[test]
+    fn minimize_merges_states_that_step_alike() {
+        // After `a` and after `c` the closures differ, but both only wait for `b`; after
+        // `ab` and after `cb`, both only accept.
+        let dfa = build("ab|cb");
+        assert_eq!(
+            dfa.minimization,
+            Minimization {
+                before: 6,
+                after: 4,
+            }
+        );
+        assert_eq!(dfa.transitions.len(), 3);
+        assert_total(&dfa);
+        assert_disjoint_consuming(&dfa);
+    }
+
+    #[test]
+    fn minimize_keeps_priority_and_accepts() {
+        let inputs = ["", "a", "ab", "abab", "aab", "ba", "foo bar", "x1_y", "é\u{1F600}"];
+        for pattern in [
+            "a|ab",
+            "(?:a|ab)+",
+            "a*?b",
+            "[a-z]*x|[a-z]+",
+            r"\bfoo\b|foo",
+            r"(?m)^a+$",
+            "(?:ab)*(?:ab)?",
+            r"\w+\b",
+        ] {
+            let ast = SimpleRegexAst::parse(pattern).expect("valid pattern");
+            let nfa = Nfa::build(&ast);
+            let (unminimized, minimized) = (Dfa::build_unminimized(&nfa), Dfa::build(&nfa));
+            assert!(minimized.minimization.after <= minimized.minimization.before, "{pattern}");
+            assert_eq!(minimized.minimization.before, unminimized.transitions.len() + 1, "{pattern}");
+            assert_eq!(minimized.lock_step, unminimized.lock_step, "{pattern}");
+            assert_total(&minimized);
+            assert_disjoint_consuming(&minimized);
+            for input in inputs {
+                for prev in [None, Some('a'), Some(' ')] {
+                    assert_eq!(minimized.prefix_ends(input, prev), unminimized.prefix_ends(input, prev), "{pattern} on {input:?}");
+                }
+            }
+        }
+    }
+
     #[test]
     fn shared_prefix_alternation_is_total_and_disjoint() {
         // `a|ab` and `(a|ab)z` are exactly the cases a non-merging construction got
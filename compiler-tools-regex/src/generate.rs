@@ -1,3 +1,5 @@
+use quote::format_ident;
+
 use super::*;
 
 impl Regex {
@@ -8,14 +10,143 @@ impl Regex {
     /// that inspects the lookahead char `c`, mutates `state`/`counter`/`c` in place, and
     /// either falls through (re-entering the loop) or `break`s. There is no per-state
     /// function and no intermediate `MatchResult` enum — folding the old two-level
-    /// dispatch into one `match` lets the optimizer build a single jump table.
+    /// dispatch into one `match` lets the optimizer build a single jump table. The DFA
+    /// is minimized ([`Dfa::minimize`]), so states that step alike share one arm;
+    /// [`Dfa::minimization`] has the counts.
     ///
     /// Accepting states record `last = counter` at the top of their own arm (instead of
     /// a per-iteration `is_accepting` check), and the zero-width bookkeeping — `prev`
     /// (for `^` under `(?m)` and `\b`/`\B`) and the `zero_width` cycle guard — is only
     /// emitted when the DFA actually contains those edges, so the common no-assertion
     /// loop stays tight.
+    ///
+    /// A pattern the DFA walk can't match exactly gets the Pike-VM matcher of
+    /// [`PikeVm::generate_parser`] instead. A bytes-mode pattern ([`Regex::parse_bytes`])
+    /// gets a `fn(&[u8], Option<u8>) -> Option<(&[u8], &[u8])>` with the same body,
+    /// stepping one byte at a time.
     pub fn generate_parser(&self, fn_name: Ident) -> TokenStream {
+        if self.dfa.lock_step {
+            return self.pike_vm().generate_parser(fn_name);
+        }
+        self.dfa.generate_walk(
+            Units {
+                bytes: self.bytes,
+            },
+            false,
+            fn_name,
+        )
+    }
+
+    /// Emit a `fn(&str, usize) -> Option<(usize, usize)>` that runs [`Regex::search_at`]:
+    /// the leftmost-first match starting at or after the given byte offset, as its start
+    /// and end offsets. The [`Prefilter`] scan, and the forward and reverse walks where
+    /// the pattern has them, become nested fns; otherwise the search tries the
+    /// [`Regex::generate_parser`] matcher at each candidate. A bytes-mode pattern gets a
+    /// `fn(&[u8], usize) -> Option<(usize, usize)>`.
+    pub fn generate_searcher(&self, fn_name: Ident) -> TokenStream {
+        let units = Units {
+            bytes: self.bytes,
+        };
+        let input = units.input();
+        let searcher = self.searcher();
+        let (raw, prev, start, next) = if self.bytes {
+            (
+                quote! { haystack },
+                quote! { at.checked_sub(1).map(|i| haystack[i]) },
+                quote! { (start <= haystack.len()).then_some(start)? },
+                quote! { (at < haystack.len()).then_some(1)? },
+            )
+        } else {
+            (
+                quote! { haystack.as_bytes() },
+                quote! { haystack[..at].chars().next_back() },
+                quote! { (start..=haystack.len()).find(|&at| haystack.is_char_boundary(at))? },
+                quote! { haystack[at..].chars().next()?.len_utf8() },
+            )
+        };
+        let (prefilter, skip) = match &searcher.prefilter {
+            Some(prefilter) => (prefilter.generate(), quote! { at = prefilter(#raw, at)?; }),
+            None => (quote! {}, quote! {}),
+        };
+        let body = match &searcher.dfas {
+            Some(dfas) => {
+                let forward = dfas.forward.generate_walk(units, false, format_ident!("forward"));
+                let reverse = dfas.reverse.generate_walk(units, true, format_ident!("reverse"));
+                quote! {
+                    #forward
+                    #reverse
+                    let mut at = #start;
+                    #skip
+                    // Neither walk has assertions, so neither needs the unit beyond its input.
+                    let (matched, _) = forward(&haystack[at..], None)?;
+                    let end = at + matched.len();
+                    // Bounded at `at`: a match can't start before the search does.
+                    let (_, matched) = reverse(&haystack[at..end], None).expect("a forward match matches in reverse");
+                    Some((end - matched.len(), end))
+                }
+            }
+            None => {
+                let anchored = self.generate_parser(format_ident!("anchored"));
+                quote! {
+                    #anchored
+                    let mut at = #start;
+                    loop {
+                        #skip
+                        if let Some((matched, _)) = anchored(&haystack[at..], #prev) {
+                            return Some((at, at + matched.len()));
+                        }
+                        at += #next;
+                    }
+                }
+            }
+        };
+        quote! {
+            #[allow(unused_mut)]
+            fn #fn_name(haystack: &#input, start: usize) -> Option<(usize, usize)> {
+                #prefilter
+                #body
+            }
+        }
+    }
+}
+
+impl Prefilter {
+    /// The scan of [`Prefilter::find`] as a nested `fn prefilter(&[u8], usize) -> Option<usize>`.
+    fn generate(&self) -> TokenStream {
+        let scan = match self {
+            Prefilter::Literal(literal) => {
+                let first = literal[0];
+                let tail = &literal[1..];
+                quote! {
+                    let mut at = 0;
+                    loop {
+                        at += rest[at..].iter().position(|&byte| byte == #first)?;
+                        if rest[at + 1..].starts_with(&[#(#tail),*]) {
+                            return Some(from + at);
+                        }
+                        at += 1;
+                    }
+                }
+            }
+            Prefilter::Bytes(set) => quote! {
+                rest.iter().position(|byte| matches!(byte, #(#set)|*)).map(|at| from + at)
+            },
+        };
+        quote! {
+            fn prefilter(haystack: &[u8], from: usize) -> Option<usize> {
+                let rest = haystack.get(from..)?;
+                #scan
+            }
+        }
+    }
+}
+
+impl Dfa {
+    /// The matcher body behind [`Regex::generate_parser`], walking this DFA over the
+    /// input and returning `(matched, rest)` at its last accept. With `reverse` set it
+    /// reads the input backwards from its end instead, `prev` is the unit *after* the
+    /// input, and it returns `(rest, matched)` with the match a suffix of the input.
+    fn generate_walk(&self, units: Units, reverse: bool, fn_name: Ident) -> TokenStream {
         // Which zero-width machinery is actually reachable in this DFA? Omitting the
         // unused parts keeps the hot loop small for the overwhelmingly common case of a
         // pattern with no `$`/`^`/`\b`.
@@ -23,7 +154,7 @@ impl Regex {
         let mut has_zero_width = false; // any zero-width edge → keep the cycle guard + counter
         let mut needs_word_ascii = false; // any ASCII `\b`/`\B` → emit `is_word_ascii`
         let mut needs_word_unicode = false; // any Unicode `\b`/`\B` → emit `is_word_unicode` + table
-        for transitions in self.dfa.transitions.values() {
+        for transitions in self.transitions.values() {
             for (transition, _) in transitions {
                 match transition {
                     nfa::TransitionEvent::WordBoundary {
@@ -67,44 +198,11 @@ impl Regex {
         // The word-ness helpers are emitted once at the top of the generated fn (not
         // per boundary arm): the Unicode table is large, so inlining it per arm would
         // bloat the output. Each takes `Option<char>` (an input edge is non-word).
-        let is_word_fns = {
-            let ascii = needs_word_ascii.then(|| {
-                quote! {
-                    fn is_word_ascii(ch: Option<char>) -> bool {
-                        matches!(ch, Some('0'..='9' | 'a'..='z' | 'A'..='Z' | '_'))
-                    }
-                }
-            });
-            let unicode = needs_word_unicode.then(|| {
-                let ranges = crate::unicode::word_ranges().into_iter().map(|(lo, hi)| quote! { (#lo, #hi) });
-                let ranges = flatten(ranges.map(|r| quote! { #r, }));
-                quote! {
-                    fn is_word_unicode(ch: Option<char>) -> bool {
-                        // Sorted, disjoint `\w` codepoint ranges; membership by binary search.
-                        const RANGES: &[(char, char)] = &[ #ranges ];
-                        match ch {
-                            Some(c) => RANGES
-                                .binary_search_by(|&(lo, hi)| {
-                                    if c < lo {
-                                        ::core::cmp::Ordering::Greater
-                                    } else if c > hi {
-                                        ::core::cmp::Ordering::Less
-                                    } else {
-                                        ::core::cmp::Ordering::Equal
-                                    }
-                                })
-                                .is_ok(),
-                            None => false,
-                        }
-                    }
-                }
-            });
-            quote! { #ascii #unicode }
-        };
+        let is_word_fns = word_fns(needs_word_ascii, needs_word_unicode);
 
         // A zero-width move keeps the lookahead char and byte position, so a cycle of
         // them (e.g. `\b*`) can never extend the match; bound it by the state count.
-        let zero_width_limit = self.dfa.transitions.len() + 1;
+        let zero_width_limit = self.transitions.len() + 1;
 
         // Shared fragments injected into every consuming arm. `prev`/`zero_width` are
         // only maintained when some state needs them.
@@ -122,7 +220,7 @@ impl Regex {
         };
 
         let mut state_arms = vec![];
-        for (state, transitions) in &self.dfa.transitions {
+        for (state, transitions) in &self.transitions {
             // Consuming edges become `Some(..)` arms that advance the cursor. `End` only
             // marks the state accepting; the zero-width moves (`$`/`\z`, the `(?m)` line
             // anchors, and word boundaries) are collected in stored (priority) order and
@@ -132,7 +230,7 @@ impl Regex {
             let mut is_accepting = false;
             for (transition, target) in transitions {
                 match transition {
-                    nfa::TransitionEvent::Epsilon => unreachable!(),
+                    nfa::TransitionEvent::Epsilon | nfa::TransitionEvent::Save(_) => unreachable!(),
                     nfa::TransitionEvent::End => is_accepting = true,
                     nfa::TransitionEvent::WordBoundary {
                         ..
@@ -145,63 +243,14 @@ impl Regex {
                     | nfa::TransitionEvent::StartOfLine {
                         ..
                     } => zero_width.push((transition, *target)),
-                    nfa::TransitionEvent::Char(c) => {
-                        let prev_set = set_prev(quote! { Some(#c) });
-                        consuming_arms.push(quote! {
-                            Some(#c) => {
-                                state = #target;
-                                counter += #c.len_utf8();
+                    nfa::TransitionEvent::Char(_) | nfa::TransitionEvent::Chars(..) => {
+                        consuming_arms.extend(consuming_arm(units, transition, *target, |ch| {
+                            let prev_set = set_prev(quote! { Some(#ch) });
+                            quote! {
                                 #prev_set
                                 #reset_zw
-                                c = chars.next();
                             }
-                        });
-                    }
-                    nfa::TransitionEvent::Chars(inverted, group) => {
-                        let mut matching = vec![];
-                        for entry in group {
-                            match entry {
-                                GroupEntry::Char(c) => {
-                                    if !matching.is_empty() {
-                                        matching.push(quote! { | })
-                                    }
-                                    matching.push(quote! { #c });
-                                }
-                                GroupEntry::Range(start, end) => {
-                                    if !matching.is_empty() {
-                                        matching.push(quote! { | })
-                                    }
-                                    matching.push(quote! { #start ..= #end });
-                                }
-                            }
-                        }
-                        let matching_empty = matching.is_empty();
-                        let matching = flatten(matching);
-                        let prev_set = set_prev(quote! { Some(ch) });
-                        let advance = quote! {
-                            state = #target;
-                            counter += ch.len_utf8();
-                            #prev_set
-                            #reset_zw
-                            c = chars.next();
-                        };
-                        if *inverted && matching_empty {
-                            // An inverted empty class (`.` under `(?s)`) matches any char,
-                            // but only a real one — at end of input there is nothing to
-                            // consume, matching the runtime interpreter (`eval_state`),
-                            // which guards every consuming edge with `if let Some(ch) = c`.
-                            consuming_arms.push(quote! { Some(ch) => { #advance } });
-                        } else if matching_empty {
-                            // A non-inverted empty class matches no character, so emit no
-                            // arm at all (an empty `matches!(ch, )` would be invalid Rust).
-                        } else {
-                            let guard = if *inverted {
-                                quote! { !matches!(ch, #matching) }
-                            } else {
-                                quote! { matches!(ch, #matching) }
-                            };
-                            consuming_arms.push(quote! { Some(ch) if #guard => { #advance } });
-                        }
+                        }));
                     }
                 }
             }
@@ -282,7 +331,7 @@ impl Regex {
         // still transition *into* it (e.g. after the last char of `a`), so it gets an
         // explicit arm: it is always accepting and has no edges, so record the accept and
         // stop.
-        let final_state = self.dfa.final_state;
+        let final_state = self.final_state;
 
         // Optional state, declared only when some arm references it.
         let prev_param = if needs_prev {
@@ -291,7 +340,8 @@ impl Regex {
             quote! { _prev_in }
         };
         let prev_decl = if needs_prev {
-            quote! { let mut prev: Option<char> = prev_in; }
+            let prev_in = units.widen(quote! { prev_in });
+            quote! { let mut prev: Option<char> = #prev_in; }
         } else {
             quote! {}
         };
@@ -300,6 +350,13 @@ impl Regex {
         } else {
             quote! {}
         };
+        let input = units.input();
+        let prev_ty = units.prev();
+        let (chars, split) = if reverse {
+            (units.chars_rev(), quote! { Some(from.split_at(from.len() - last)) })
+        } else {
+            (units.chars(), quote! { Some((&from[..last], &from[last..])) })
+        };
 
         quote! {
             // `prev_in` is the char immediately before `from` in the larger input (the
@@ -309,7 +366,7 @@ impl Regex {
             // A trivial pattern (e.g. the empty regex or a bare anchor) compiles to a
             // `loop` whose every arm `break`s; that is correct, so silence `never_loop`.
             #[allow(clippy::never_loop)]
-            fn #fn_name(from: &str, #prev_param: Option<char>) -> Option<(&str, &str)> {
+            fn #fn_name(from: &#input, #prev_param: Option<#prev_ty>) -> Option<(&#input, &#input)> {
                 // Word-ness helpers, emitted once for the whole matcher (only when a
                 // `\b`/`\B` of the matching mode is reachable).
                 #is_word_fns
@@ -319,7 +376,7 @@ impl Regex {
                 let mut last = usize::MAX;
                 let mut counter = 0usize;
                 #prev_decl
-                let mut chars = from.chars();
+                let mut chars = #chars;
                 let mut c = chars.next();
                 #zero_width_decl
                 // Leftmost-first: priority is baked into the DFA, so following consuming
@@ -340,23 +397,12 @@ impl Regex {
                 if last == usize::MAX {
                     None
                 } else {
-                    Some((&from[..last], &from[last..]))
+                    #split
                 }
             }
         }
     }
 
-    /// Whether `state` is accepting: the transition-less sink, or any state carrying
-    /// an `End` edge. Mirrors `matching::Regex::accepts`.
-    fn state_accepts(&self, state: u32) -> bool {
-        state == self.dfa.final_state
-            || self
-                .dfa
-                .transitions
-                .get(&state)
-                .is_some_and(|transitions| transitions.iter().any(|(t, _)| matches!(t, nfa::TransitionEvent::End)))
-    }
-
     /// One condition per simple path of zero-width assertion edges from `state` to an
     /// accepting state — the `&&` of the edges' conditions (lookahead `c`); the caller
     /// `||`s them. This is the build-time counterpart of the interpreter's
@@ -376,13 +422,13 @@ impl Regex {
         if !on_path.insert(state) {
             return;
         }
-        if let Some(transitions) = self.dfa.transitions.get(&state) {
+        if let Some(transitions) = self.transitions.get(&state) {
             for (transition, target) in transitions {
                 if matches!(transition, nfa::TransitionEvent::Char(_) | nfa::TransitionEvent::Chars(..) | nfa::TransitionEvent::End) {
                     continue;
                 }
                 acc.push(zw_cond(transition, &quote! { c }));
-                if self.state_accepts(*target) {
+                if self.accepts(*target) {
                     out.push(flatten(acc.iter().enumerate().map(|(i, c)| {
                         if i == 0 {
                             quote! { #c }
@@ -399,6 +445,516 @@ impl Regex {
     }
 }
 
+impl RegexSet {
+    /// Emit a self-contained `fn(&str, Option<char>) -> [Option<usize>; N]` matcher
+    /// for the whole set: entry `i` is the byte length of pattern `i`'s anchored
+    /// leftmost-first match at the start of `from` (what [`Regex::generate_parser`]
+    /// would return for it alone), or `None`.
+    ///
+    /// The combined DFA is walked once, in the same `loop { match state { .. } }`
+    /// shape as the single-pattern matcher; an accepting state records the position
+    /// for every pattern it accepts, and the walk runs until no pattern can extend.
+    /// Patterns with zero-width assertions are emitted as nested single-pattern
+    /// matchers and run after the walk.
+    pub fn generate_parser(&self, fn_name: Ident) -> TokenStream {
+        self.generate_walk(self.dfa.as_ref(), fn_name)
+    }
+
+    /// [`RegexSet::generate_parser`] walking [`RegexSet::first_match_dfa`], for a caller
+    /// that reads entry `i` only when no earlier pattern matched: entries are exact up
+    /// to the first pattern with a non-empty match, and a later one may be `None` where
+    /// that pattern would have matched.
+    pub fn generate_first_match_parser(&self, fn_name: Ident) -> TokenStream {
+        self.generate_walk(self.first_match_dfa().as_ref(), fn_name)
+    }
+
+    fn generate_walk(&self, dfa: Option<&Dfa>, fn_name: Ident) -> TokenStream {
+        let len = self.regexes.len();
+        let units = Units {
+            bytes: self.bytes,
+        };
+
+        let combined = dfa.map(|dfa| {
+            let mut state_arms = vec![];
+            for (state, transitions) in &dfa.transitions {
+                let mut accepts = vec![];
+                let mut consuming_arms = vec![];
+                for (transition, target) in transitions {
+                    if matches!(transition, nfa::TransitionEvent::End) {
+                        let pattern = self.combined[dfa.final_pattern(*target).expect("End edges point at a pattern sink")];
+                        accepts.push(quote! { last[#pattern] = Some(counter); });
+                    } else {
+                        consuming_arms.extend(consuming_arm(units, transition, *target, |_| quote! {}));
+                    }
+                }
+                let accepts = flatten(accepts);
+                let consuming_arms = flatten(consuming_arms);
+                state_arms.push(quote! {
+                    #state => {
+                        #accepts
+                        match c {
+                            #consuming_arms
+                            _ => break,
+                        }
+                    }
+                });
+            }
+            // Each pattern's sink is entered once only that pattern's accept is left.
+            for (index, final_state) in dfa.final_states.iter().enumerate() {
+                let pattern = self.combined[index];
+                state_arms.push(quote! {
+                    #final_state => {
+                        last[#pattern] = Some(counter);
+                        break;
+                    }
+                });
+            }
+            let state_arms = flatten(state_arms);
+            let chars = units.chars();
+            quote! {
+                {
+                    let mut state = 0u32;
+                    let mut counter = 0usize;
+                    let mut chars = #chars;
+                    let mut c = chars.next();
+                    loop {
+                        match state {
+                            #state_arms
+                            _ => break,
+                        }
+                    }
+                }
+            }
+        });
+
+        let standalone = flatten((0..len).filter(|i| !self.combined.contains(i)).map(|pattern| {
+            let ident = format_ident!("pattern_{}", pattern);
+            let parser = self.regexes[pattern].generate_parser(ident.clone());
+            quote! {
+                {
+                    #parser
+                    last[#pattern] = #ident(from, prev).map(|(matched, _)| matched.len());
+                }
+            }
+        }));
+        let prev_param = if self.combined.len() < len {
+            quote! { prev }
+        } else {
+            quote! { _prev }
+        };
+        let input = units.input();
+        let prev_ty = units.prev();
+
+        quote! {
+            // `prev` is the char before `from`, as for the single-pattern matcher; only the
+            // standalone (assertion-bearing) patterns read it.
+            #[allow(clippy::never_loop)]
+            fn #fn_name(from: &#input, #prev_param: Option<#prev_ty>) -> [Option<usize>; #len] {
+                let mut last = [None; #len];
+                #combined
+                #standalone
+                last
+            }
+        }
+    }
+}
+
+impl PikeVm {
+    /// Emit a self-contained
+    /// `fn(&str, usize, Option<char>) -> Option<[Option<(usize, usize)>; N]>` capture
+    /// pass: given the input, the byte length of a match starting at its beginning (as
+    /// found by the matcher from [`Regex::generate_parser`]) and the char before the
+    /// input, it returns the byte span of each capture group in that match, or `None`
+    /// if no path through the pattern spans it. This is the interpreted pass unrolled
+    /// over the capture NFA: each state's epsilon, `Save` and assertion edges become an
+    /// arm of `add`, and its consuming edges an arm of the step loop.
+    pub fn generate_captures(&self, fn_name: Ident) -> TokenStream {
+        let groups = self.captures_len();
+        let slot_count = groups * 2;
+        let states = self.nfa.transitions.len();
+        let (threads, step_arms) = self.generate_threads(slot_count);
+        let final_state = self.nfa.final_state;
+        let units = Units {
+            bytes: self.bytes,
+        };
+        let (input, prev_ty, prev) = (units.input(), units.prev(), units.widen(quote! { prev }));
+        let (first, width, next) = (units.at(quote! { 0 }), units.width(quote! { ch }), units.at(quote! { pos }));
+
+        quote! {
+            #[allow(unused_variables, clippy::match_single_binding, clippy::too_many_arguments)]
+            fn #fn_name(from: &#input, end: usize, prev: Option<#prev_ty>) -> Option<[Option<(usize, usize)>; #groups]> {
+                #threads
+
+                let prev: Option<char> = #prev;
+                let mut seen = [false; #states];
+                let mut slots: Slots = [None; #slot_count];
+                slots[0] = Some(0);
+                let mut c = #first;
+                let (mut threads, mut next, mut stack) = (Vec::with_capacity(#states), Vec::with_capacity(#states), Vec::with_capacity(#states));
+                add(&mut threads, &mut stack, &mut seen, 0, slots, 0, prev, c);
+                let mut pos = 0;
+                while pos < end {
+                    let ch = c?;
+                    pos += #width;
+                    c = #next;
+                    seen = [false; #states];
+                    for &(state, slots) in &threads {
+                        match state {
+                            #step_arms
+                            _ => {}
+                        }
+                    }
+                    if next.is_empty() {
+                        return None;
+                    }
+                    ::core::mem::swap(&mut threads, &mut next);
+                    next.clear();
+                }
+
+                let (_, mut slots) = threads.into_iter().find(|(state, _)| *state == #final_state)?;
+                slots[1] = Some(end);
+                let mut groups = [None; #groups];
+                for (group, span) in groups.iter_mut().enumerate() {
+                    if let (Some(start), Some(end)) = (slots[group * 2], slots[group * 2 + 1]) {
+                        *span = Some((start, end));
+                    }
+                }
+                Some(groups)
+            }
+        }
+    }
+
+    /// Emit a `fn(&str, Option<char>) -> Option<(&str, &str)>` matcher with the same
+    /// contract as [`Regex::generate_parser`], running [`PikeVm::find_prefix`]'s
+    /// lock-step simulation instead of walking the DFA. `generate_parser` emits this
+    /// for the patterns the DFA walk can't match exactly.
+    pub fn generate_parser(&self, fn_name: Ident) -> TokenStream {
+        let states = self.nfa.transitions.len();
+        let (threads, step_arms) = self.generate_threads(0);
+        let final_state = self.nfa.final_state;
+        let units = Units {
+            bytes: self.bytes,
+        };
+        let (input, prev_ty, prev) = (units.input(), units.prev(), units.widen(quote! { prev }));
+        let (first, width, next) = (units.at(quote! { 0 }), units.width(quote! { ch }), units.at(quote! { pos }));
+
+        quote! {
+            #[allow(unused_variables, clippy::match_single_binding, clippy::too_many_arguments)]
+            fn #fn_name(from: &#input, prev: Option<#prev_ty>) -> Option<(&#input, &#input)> {
+                #threads
+
+                let prev: Option<char> = #prev;
+                let mut seen = [false; #states];
+                let mut c = #first;
+                let (mut threads, mut next, mut stack) = (Vec::with_capacity(#states), Vec::with_capacity(#states), Vec::with_capacity(#states));
+                add(&mut threads, &mut stack, &mut seen, 0, [], 0, prev, c);
+                let mut last = None;
+                let mut pos = 0;
+                loop {
+                    // Threads are in priority order: the first to accept cuts every
+                    // thread after it.
+                    if let Some(accepting) = threads.iter().position(|(state, _)| *state == #final_state) {
+                        last = Some(pos);
+                        threads.truncate(accepting);
+                    }
+                    let Some(ch) = c.filter(|_| !threads.is_empty()) else {
+                        break;
+                    };
+                    pos += #width;
+                    c = #next;
+                    seen = [false; #states];
+                    for &(state, slots) in &threads {
+                        match state {
+                            #step_arms
+                            _ => {}
+                        }
+                    }
+                    ::core::mem::swap(&mut threads, &mut next);
+                    next.clear();
+                }
+                last.map(|last| (&from[..last], &from[last..]))
+            }
+        }
+    }
+
+    /// The thread machinery both generated passes share: the word-ness helpers, the
+    /// `Slots` a thread carries (`slot_count` of them, none when only matching) and
+    /// the `add` fn following a state's epsilon, `Save` and assertion edges. Also
+    /// returns the arms of the step loop's `match state`, which advance each thread
+    /// over the consumed `ch` into `next`. The passes allocate `threads`, `next` and
+    /// `add`'s `stack` once, with room for a thread per state, and swap `threads` and
+    /// `next` between steps, so the loop itself never allocates.
+    fn generate_threads(&self, slot_count: usize) -> (TokenStream, TokenStream) {
+        let states = self.nfa.transitions.len();
+        let mut needs_word_ascii = false;
+        let mut needs_word_unicode = false;
+        let mut add_arms = vec![];
+        let mut step_arms = vec![];
+        for (state, transitions) in &self.nfa.transitions {
+            // Successors are pushed in reverse so they pop in priority order.
+            let mut pushes = vec![];
+            let mut steps = vec![];
+            for (transition, target) in transitions {
+                match transition {
+                    nfa::TransitionEvent::Epsilon => pushes.push(quote! { stack.push((#target, slots)); }),
+                    // Slots are only carried when capturing.
+                    nfa::TransitionEvent::Save(_) if slot_count == 0 => pushes.push(quote! { stack.push((#target, slots)); }),
+                    nfa::TransitionEvent::Save(slot) => {
+                        let slot = *slot as usize;
+                        pushes.push(quote! {
+                            let mut saved = slots;
+                            saved[#slot] = Some(pos);
+                            stack.push((#target, saved));
+                        });
+                    }
+                    nfa::TransitionEvent::End => {}
+                    nfa::TransitionEvent::Char(c) => steps.push(quote! {
+                        if ch == #c {
+                            add(&mut next, &mut stack, &mut seen, #target, slots, pos, Some(ch), c);
+                        }
+                    }),
+                    nfa::TransitionEvent::Chars(inverted, group) => {
+                        let advance = quote! { add(&mut next, &mut stack, &mut seen, #target, slots, pos, Some(ch), c); };
+                        steps.extend(match (inverted, class_pattern(group)) {
+                            (true, None) => Some(advance),
+                            (false, None) => None,
+                            (true, Some(matching)) => Some(quote! { if !matches!(ch, #matching) { #advance } }),
+                            (false, Some(matching)) => Some(quote! { if matches!(ch, #matching) { #advance } }),
+                        });
+                    }
+                    assertion => {
+                        if let nfa::TransitionEvent::WordBoundary {
+                            unicode,
+                            ..
+                        } = assertion
+                        {
+                            needs_word_unicode |= *unicode;
+                            needs_word_ascii |= !*unicode;
+                        }
+                        let cond = zw_cond(assertion, &quote! { c });
+                        pushes.push(quote! {
+                            if #cond {
+                                stack.push((#target, slots));
+                            }
+                        });
+                    }
+                }
+            }
+            if !pushes.is_empty() {
+                let pushes = flatten(pushes.into_iter().rev());
+                add_arms.push(quote! { #state => { #pushes } });
+            }
+            if !steps.is_empty() {
+                let steps = flatten(steps);
+                step_arms.push(quote! { #state => { #steps } });
+            }
+        }
+        let add_arms = flatten(add_arms);
+        let step_arms = flatten(step_arms);
+        let is_word_fns = word_fns(needs_word_ascii, needs_word_unicode);
+
+        let threads = quote! {
+            #is_word_fns
+            // Slot `2i` is where group `i` starts and `2i + 1` where it ends.
+            type Slots = [Option<usize>; #slot_count];
+            // Adds `state` and everything reachable from it without consuming a char to
+            // `threads`, in priority order; a state a higher-priority thread holds is skipped.
+            // `stack` is scratch space the caller allocates once, and is left empty.
+            fn add(threads: &mut Vec<(u32, Slots)>, stack: &mut Vec<(u32, Slots)>, seen: &mut [bool; #states], state: u32, slots: Slots, pos: usize, prev: Option<char>, c: Option<char>) {
+                stack.push((state, slots));
+                while let Some((state, slots)) = stack.pop() {
+                    if ::core::mem::replace(&mut seen[state as usize], true) {
+                        continue;
+                    }
+                    match state {
+                        #add_arms
+                        _ => {}
+                    }
+                    threads.push((state, slots));
+                }
+            }
+        };
+        (threads, step_arms)
+    }
+}
+
+/// How a generated matcher reads its input: a `&str` one char at a time, or for a
+/// bytes-mode pattern a `&[u8]` one byte at a time, each byte widened to the char of
+/// the same value so the state arms compare chars either way.
+#[derive(Clone, Copy)]
+struct Units {
+    bytes: bool,
+}
+
+impl Units {
+    /// The input type behind the `&`.
+    fn input(self) -> TokenStream {
+        if self.bytes {
+            quote! { [u8] }
+        } else {
+            quote! { str }
+        }
+    }
+
+    /// The type of the unit preceding the input.
+    fn prev(self) -> TokenStream {
+        if self.bytes {
+            quote! { u8 }
+        } else {
+            quote! { char }
+        }
+    }
+
+    /// `prev`, an `Option` of the preceding unit, as an `Option<char>`.
+    fn widen(self, prev: TokenStream) -> TokenStream {
+        if self.bytes {
+            quote! { #prev.map(char::from) }
+        } else {
+            prev
+        }
+    }
+
+    /// An iterator over the units of `from`, as chars.
+    fn chars(self) -> TokenStream {
+        if self.bytes {
+            quote! { from.iter().map(|&b| char::from(b)) }
+        } else {
+            quote! { from.chars() }
+        }
+    }
+
+    /// [`Units::chars`] from the end of `from` back.
+    fn chars_rev(self) -> TokenStream {
+        if self.bytes {
+            quote! { from.iter().rev().map(|&b| char::from(b)) }
+        } else {
+            quote! { from.chars().rev() }
+        }
+    }
+
+    /// The unit at byte offset `pos` of `from`, as an `Option<char>`.
+    fn at(self, pos: TokenStream) -> TokenStream {
+        if self.bytes {
+            quote! { from.get(#pos).map(|&b| char::from(b)) }
+        } else {
+            quote! { from[#pos..].chars().next() }
+        }
+    }
+
+    /// The byte width of the unit `ch`.
+    fn width(self, ch: TokenStream) -> TokenStream {
+        if self.bytes {
+            quote! { 1 }
+        } else {
+            quote! { #ch.len_utf8() }
+        }
+    }
+}
+
+/// The `match c` arm that consumes the lookahead along a `Char`/`Chars` edge into
+/// `target`. `bookkeeping` receives the consumed char's expression and returns any
+/// extra statements the caller's loop maintains (`prev`, the zero-width guard). A
+/// non-inverted empty class matches nothing and gets no arm.
+fn consuming_arm(units: Units, transition: &nfa::TransitionEvent, target: u32, bookkeeping: impl Fn(&TokenStream) -> TokenStream) -> Option<TokenStream> {
+    match transition {
+        nfa::TransitionEvent::Char(c) => {
+            let extra = bookkeeping(&quote! { #c });
+            let width = units.width(quote! { #c });
+            Some(quote! {
+                Some(#c) => {
+                    state = #target;
+                    counter += #width;
+                    #extra
+                    c = chars.next();
+                }
+            })
+        }
+        nfa::TransitionEvent::Chars(inverted, group) => {
+            let matching = class_pattern(group);
+            let extra = bookkeeping(&quote! { ch });
+            let width = units.width(quote! { ch });
+            // A byte's width doesn't read `ch`, so without bookkeeping nothing may.
+            let binding = if units.bytes && extra.is_empty() {
+                quote! { _ }
+            } else {
+                quote! { ch }
+            };
+            let advance = quote! {
+                state = #target;
+                counter += #width;
+                #extra
+                c = chars.next();
+            };
+            match (inverted, matching) {
+                // An inverted empty class (`.` under `(?s)`) matches any char,
+                // but only a real one — at end of input there is nothing to
+                // consume, matching the runtime interpreter (`eval_state`),
+                // which guards every consuming edge with `if let Some(ch) = c`.
+                (true, None) => Some(quote! { Some(#binding) => { #advance } }),
+                // A non-inverted empty class matches no character, so emit no
+                // arm at all (an empty `matches!(ch, )` would be invalid Rust).
+                (false, None) => None,
+                (true, Some(matching)) => Some(quote! { Some(ch) if !matches!(ch, #matching) => { #advance } }),
+                (false, Some(matching)) => Some(quote! { Some(ch) if matches!(ch, #matching) => { #advance } }),
+            }
+        }
+        _ => unreachable!("not a consuming edge"),
+    }
+}
+
+/// The `|`-separated pattern matching the chars of a class's entries, or `None` for
+/// an empty class.
+fn class_pattern(group: &[GroupEntry]) -> Option<TokenStream> {
+    let mut matching = vec![];
+    for entry in group {
+        if !matching.is_empty() {
+            matching.push(quote! { | })
+        }
+        match entry {
+            GroupEntry::Char(c) => matching.push(quote! { #c }),
+            GroupEntry::Range(start, end) => matching.push(quote! { #start ..= #end }),
+        }
+    }
+    (!matching.is_empty()).then(|| flatten(matching))
+}
+
+/// The `is_word_ascii`/`is_word_unicode` helpers a generated matcher's `\b`/`\B`
+/// conditions call, as requested. Each takes `Option<char>` (an input edge is non-word).
+fn word_fns(ascii: bool, unicode: bool) -> TokenStream {
+    let ascii = ascii.then(|| {
+        quote! {
+            fn is_word_ascii(ch: Option<char>) -> bool {
+                matches!(ch, Some('0'..='9' | 'a'..='z' | 'A'..='Z' | '_'))
+            }
+        }
+    });
+    let unicode = unicode.then(|| {
+        let ranges = crate::unicode::word_ranges().into_iter().map(|(lo, hi)| quote! { (#lo, #hi) });
+        let ranges = flatten(ranges.map(|r| quote! { #r, }));
+        quote! {
+            fn is_word_unicode(ch: Option<char>) -> bool {
+                // Sorted, disjoint `\w` codepoint ranges; membership by binary search.
+                const RANGES: &[(char, char)] = &[ #ranges ];
+                match ch {
+                    Some(c) => RANGES
+                        .binary_search_by(|&(lo, hi)| {
+                            if c < lo {
+                                ::core::cmp::Ordering::Greater
+                            } else if c > hi {
+                                ::core::cmp::Ordering::Less
+                            } else {
+                                ::core::cmp::Ordering::Equal
+                            }
+                        })
+                        .is_ok(),
+                    None => false,
+                }
+            }
+        }
+    });
+    quote! { #ascii #unicode }
+}
+
 /// The boolean condition under which a zero-width assertion edge holds, evaluated
 /// against `prev` and the given lookahead token (`c` at the top of a state arm, the
 /// `other` binding in the consuming-`match`'s fallback). Compound conditions are
@@ -446,7 +1002,11 @@ fn zw_cond(event: &nfa::TransitionEvent, look: &TokenStream) -> TokenStream {
                 WordBoundaryKind::EndHalf => quote! { (!#n) },
             }
         }
-        nfa::TransitionEvent::Epsilon | nfa::TransitionEvent::Char(_) | nfa::TransitionEvent::Chars(..) | nfa::TransitionEvent::End => {
+        nfa::TransitionEvent::Epsilon
+        | nfa::TransitionEvent::Save(_)
+        | nfa::TransitionEvent::Char(_)
+        | nfa::TransitionEvent::Chars(..)
+        | nfa::TransitionEvent::End => {
             unreachable!("not a zero-width assertion")
         }
     }
@@ -0,0 +1,351 @@
+//! The NFA and DFA of a pattern laid out for a person to read, to see what a
+//! misbehaving `#[token(regex = ...)]` was compiled to.
+//!
+//! [`Nfa::graph`], [`Dfa::graph`] and [`RegexSet::graph`] give a [`Graph`]: every
+//! state with its edges in the order the matcher tries them, each labelled in regex
+//! syntax, and the patterns each state accepts. [`Graph::to_dot`] renders it for
+//! Graphviz, and with the `serde` feature it serializes, e.g. to JSON.
+
+use std::fmt::Write;
+
+use super::*;
+
+/// An automaton's states and edges, from [`Nfa::graph`], [`Dfa::graph`] or
+/// [`RegexSet::graph`]. The walk starts at state 0.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct Graph {
+    /// Every state, by id.
+    pub states: Vec<GraphState>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GraphState {
+    pub id: u32,
+    /// The patterns the walk has matched on reaching the state, by pattern index: of a
+    /// [`RegexSet`], its set index, otherwise 0. A DFA state accepting by an `end` edge
+    /// is listed too.
+    pub accepts: Vec<usize>,
+    /// The edges leaving the state, in the order they are tried. For an NFA this is
+    /// thread priority: the greedy or earlier branch comes first. A DFA's consuming
+    /// edges never overlap, and its zero-width edges are taken in this order once none
+    /// of them claims the next char.
+    pub edges: Vec<GraphEdge>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct GraphEdge {
+    pub target: u32,
+    /// The edge's [`TransitionEvent`] in regex syntax: a char or class like `a` or
+    /// `[0-9_]`, an assertion like `\b` or `(?m:$)`, `ε`, `save(n)` for a capture slot,
+    /// or `end` for the edge into an accepting sink.
+    pub label: String,
+    /// Whether the edge steps over a char, rather than being zero-width.
+    pub consumes: bool,
+}
+
+impl Nfa {
+    /// The NFA's states and edges, for [`Graph::to_dot`] or serializing.
+    pub fn graph(&self) -> Graph {
+        let states = self
+            .transitions
+            .iter()
+            .map(|(state, edges)| GraphState {
+                id: *state,
+                accepts: if *state == self.final_state { vec![0] } else { vec![] },
+                edges: edges.iter().map(|(event, target)| GraphEdge::new(event, *target)).collect(),
+            })
+            .collect();
+        Graph {
+            states,
+        }
+    }
+
+    /// The NFA in Graphviz DOT; see [`Graph::to_dot`].
+    pub fn to_dot(&self) -> String {
+        self.graph().to_dot()
+    }
+}
+
+impl Dfa {
+    /// The DFA's states and edges, for [`Graph::to_dot`] or serializing. A pattern's
+    /// accepting sink has no transitions, but is listed all the same.
+    pub fn graph(&self) -> Graph {
+        self.graph_of_patterns(&(0..self.final_states.len()).collect::<Vec<_>>())
+    }
+
+    /// The DFA in Graphviz DOT; see [`Graph::to_dot`].
+    pub fn to_dot(&self) -> String {
+        self.graph().to_dot()
+    }
+
+    /// [`Dfa::graph`] numbering the DFA's patterns by `indices`.
+    fn graph_of_patterns(&self, indices: &[usize]) -> Graph {
+        let pattern = |sink: u32| self.final_states.iter().position(|x| *x == sink).map(|pattern| indices[pattern]);
+        let mut ids: Vec<u32> = self.transitions.keys().chain(&self.final_states).copied().collect();
+        ids.sort_unstable();
+        ids.dedup();
+        let states = ids
+            .into_iter()
+            .map(|state| {
+                let edges = self.transitions.get(&state).map_or(&[][..], |edges| edges);
+                let mut accepts: Vec<usize> = pattern(state)
+                    .into_iter()
+                    .chain(
+                        edges
+                            .iter()
+                            .filter(|(event, _)| *event == TransitionEvent::End)
+                            .filter_map(|(_, target)| pattern(*target)),
+                    )
+                    .collect();
+                accepts.sort_unstable();
+                accepts.dedup();
+                GraphState {
+                    id: state,
+                    accepts,
+                    edges: edges.iter().map(|(event, target)| GraphEdge::new(event, *target)).collect(),
+                }
+            })
+            .collect();
+        Graph {
+            states,
+        }
+    }
+}
+
+impl RegexSet {
+    /// The combined DFA every pattern without zero-width assertions is matched by in
+    /// one scan. Its states accept patterns by their index in the set, and it has none
+    /// if every pattern is left out. A pattern with assertions is matched by its own
+    /// DFA, so it isn't in this one; see its [`Dfa::graph`].
+    pub fn graph(&self) -> Graph {
+        self.graph_of(self.dfa.as_ref())
+    }
+
+    /// [`RegexSet::graph`] of [`RegexSet::first_match_dfa`]. Outside `longest_match`,
+    /// this is the DFA a derived tokenizer mode scans with: its generated `dfa_dot`
+    /// renders it.
+    pub fn first_match_graph(&self) -> Graph {
+        self.graph_of(self.first_match_dfa().as_ref())
+    }
+
+    fn graph_of(&self, dfa: Option<&Dfa>) -> Graph {
+        dfa.map_or_else(
+            || Graph {
+                states: vec![],
+            },
+            |dfa| dfa.graph_of_patterns(&self.combined),
+        )
+    }
+
+    /// The combined DFA in Graphviz DOT; see [`RegexSet::graph`] and [`Graph::to_dot`].
+    pub fn to_dot(&self) -> String {
+        self.graph().to_dot()
+    }
+}
+
+impl Graph {
+    /// The automaton as a Graphviz DOT `digraph`, read left to right from an arrow
+    /// into state 0. Accepting states are double circles labelled with the patterns
+    /// they accept, zero-width edges are dashed, and where a state has several edges
+    /// that could both be taken (an NFA's epsilons, a DFA's assertions) their labels
+    /// are numbered in the order they are tried.
+    pub fn to_dot(&self) -> String {
+        let mut dot = String::from("digraph {\n    rankdir=LR;\n");
+        // An empty graph has no state 0 to start at.
+        if !self.states.is_empty() {
+            dot.push_str("    node [shape=circle];\n    start [shape=point];\n    start -> 0;\n");
+        }
+        for state in &self.states {
+            if !state.accepts.is_empty() {
+                let accepts: Vec<String> = state.accepts.iter().map(|pattern| format!("#{pattern}")).collect();
+                writeln!(dot, "    {} [shape=doublecircle, label=\"{}\\n{}\"];", state.id, state.id, accepts.join(" ")).unwrap();
+            }
+        }
+        for state in &self.states {
+            // A DFA state accepts every pattern it has an `end` edge for, so those aren't tried in turn.
+            let tried = |edge: &&GraphEdge| !edge.consumes && edge.label != "end";
+            let ordered = state.edges.iter().filter(tried).count() > 1;
+            for (priority, edge) in state.edges.iter().filter(tried).enumerate() {
+                let label = if ordered {
+                    format!("{}. {}", priority + 1, edge.label)
+                } else {
+                    edge.label.clone()
+                };
+                writeln!(dot, "    {} -> {} [label=\"{}\", style=dashed];", state.id, edge.target, escape(&label)).unwrap();
+            }
+            for edge in state.edges.iter().filter(|edge| !edge.consumes && !tried(edge)) {
+                writeln!(dot, "    {} -> {} [label=\"end\", style=dashed];", state.id, edge.target).unwrap();
+            }
+            for edge in state.edges.iter().filter(|edge| edge.consumes) {
+                writeln!(dot, "    {} -> {} [label=\"{}\"];", state.id, edge.target, escape(&edge.label)).unwrap();
+            }
+        }
+        dot.push_str("}\n");
+        dot
+    }
+}
+
+impl GraphEdge {
+    fn new(event: &TransitionEvent, target: u32) -> GraphEdge {
+        let label = match event {
+            TransitionEvent::Epsilon => "ε".to_string(),
+            TransitionEvent::Char(c) => char_label(*c, false),
+            TransitionEvent::Chars(inverted, entries) => {
+                let entries: String = entries
+                    .iter()
+                    .map(|entry| match entry {
+                        GroupEntry::Char(c) => char_label(*c, true),
+                        GroupEntry::Range(start, end) => format!("{}-{}", char_label(*start, true), char_label(*end, true)),
+                    })
+                    .collect();
+                format!("[{}{}]", if *inverted { "^" } else { "" }, entries)
+            }
+            TransitionEvent::EndOfInput => "$".to_string(),
+            TransitionEvent::StartOfText => "^".to_string(),
+            TransitionEvent::WordBoundary {
+                kind,
+                unicode,
+            } => {
+                let assertion = match kind {
+                    WordBoundaryKind::Both => r"\b",
+                    WordBoundaryKind::BothNegate => r"\B",
+                    WordBoundaryKind::Start => r"\b{start}",
+                    WordBoundaryKind::End => r"\b{end}",
+                    WordBoundaryKind::StartHalf => r"\b{start-half}",
+                    WordBoundaryKind::EndHalf => r"\b{end-half}",
+                };
+                if *unicode { format!("(?u:{assertion})") } else { assertion.to_string() }
+            }
+            TransitionEvent::StartOfLine {
+                crlf,
+            } => format!("(?{}m:^)", if *crlf { "R" } else { "" }),
+            TransitionEvent::EndOfLine {
+                crlf,
+            } => format!("(?{}m:$)", if *crlf { "R" } else { "" }),
+            TransitionEvent::Save(slot) => format!("save({slot})"),
+            TransitionEvent::End => "end".to_string(),
+        };
+        GraphEdge {
+            target,
+            label,
+            consumes: !matches!(event, TransitionEvent::Epsilon | TransitionEvent::Save(_) | TransitionEvent::End) && !dfa::is_assertion(event),
+        }
+    }
+}
+
+/// `c` as a regex would spell it, in a class if `in_class`: printable ASCII as is,
+/// unless the syntax needs it escaped, other chars up to `U+00FF` as `\xNN` (which
+/// reads as the byte of a bytes-mode pattern too), and the rest as is if printable.
+fn char_label(c: char, in_class: bool) -> String {
+    let special = if in_class { r"\[]^-" } else { r"\.+*?()|[]{}^$" };
+    match c {
+        '\n' => r"\n".to_string(),
+        '\r' => r"\r".to_string(),
+        '\t' => r"\t".to_string(),
+        c if special.contains(c) => format!("\\{c}"),
+        ' '..='~' => c.to_string(),
+        c if (c as u32) <= 0xFF => format!("\\x{:02X}", c as u32),
+        c if c.is_alphanumeric() => c.to_string(),
+        c => format!("\\u{{{:X}}}", c as u32),
+    }
+}
+
+/// `label` as the inside of a DOT string.
+fn escape(label: &str) -> String {
+    label.replace('\\', r"\\").replace('"', "\\\"")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn edge_labels(graph: &Graph) -> Vec<String> {
+        graph
+            .states
+            .iter()
+            .flat_map(|state| state.edges.iter().map(|edge| edge.label.clone()))
+            .collect()
+    }
+
+    #[test]
+    fn edges_are_labelled_in_regex_syntax() {
+        let regex = Regex::parse(r"\bfo[^a-c\n]+$").unwrap();
+        let labels = edge_labels(&regex.dfa.graph());
+        for label in [r"\b", "f", "o", r"[\x00-\t\x0B-`d-\u{D7FF}\u{E000}-\u{10FFFF}]", "$"] {
+            assert!(labels.iter().any(|x| x == label), "no {label} in {labels:?}");
+        }
+        let nfa = Nfa::build(&SimpleRegexAst::parse(r"a*?").unwrap());
+        let labels = edge_labels(&nfa.graph());
+        assert!(labels.iter().any(|x| x == "ε"));
+        assert!(labels.iter().any(|x| x == "a"));
+    }
+
+    #[test]
+    fn accepting_states_name_their_patterns() {
+        let regex = Regex::parse("ab?").unwrap();
+        let accepts: Vec<(u32, Vec<usize>)> = regex
+            .dfa
+            .graph()
+            .states
+            .into_iter()
+            .filter(|state| !state.accepts.is_empty())
+            .map(|state| (state.id, state.accepts))
+            .collect();
+        // The sink, and the states after `a` and `ab`, which have `end` edges to it.
+        assert_eq!(accepts.len(), 3);
+        assert!(accepts.iter().all(|(_, accepts)| *accepts == [0]));
+        assert!(regex.dfa.graph().states[0].accepts.is_empty());
+
+        let set = RegexSet::new(["[a-z]+", r"\bx", "[0-9]+|let"]).unwrap();
+        let graph = set.graph();
+        let mut accepted: Vec<usize> = graph.states.iter().flat_map(|state| state.accepts.iter().copied()).collect();
+        accepted.sort_unstable();
+        accepted.dedup();
+        // `\bx` has an assertion, so it's matched on its own.
+        assert_eq!(accepted, [0, 2]);
+        // `let` matches both the first pattern and the third.
+        let both = graph.states.iter().find(|state| state.accepts == [0, 2]).unwrap();
+        assert_eq!(both.edges.iter().filter(|edge| edge.label == "end").count(), 2);
+        // Walked first-match, `[a-z]+` settles `let` before the third pattern accepts it.
+        assert!(
+            set.first_match_graph()
+                .states
+                .iter()
+                .all(|state| !state.accepts.contains(&2) || !state.accepts.contains(&0))
+        );
+    }
+
+    #[test]
+    fn dot_draws_accepts_and_orders_zero_width_edges() {
+        let dot = Regex::parse("ab?").unwrap().dfa.to_dot();
+        assert_eq!(
+            dot,
+            "digraph {\n    rankdir=LR;\n    node [shape=circle];\n    start [shape=point];\n    start -> 0;\n    1 [shape=doublecircle, label=\"1\\n#0\"];\n    \
+             2 [shape=doublecircle, label=\"2\\n#0\"];\n    3 [shape=doublecircle, label=\"3\\n#0\"];\n    0 -> 2 [label=\"a\"];\n    \
+             2 -> 1 [label=\"end\", style=dashed];\n    2 -> 3 [label=\"b\"];\n    3 -> 1 [label=\"end\", style=dashed];\n}\n"
+        );
+        let dot = RegexSet::new(["[a-z]+", "let"]).unwrap().to_dot();
+        assert!(dot.contains(r#"\n#0 #1"]"#), "{dot}");
+        assert!(!dot.contains("1. end"), "{dot}");
+        let dot = Nfa::build(&SimpleRegexAst::parse(r#"a|"\b"#).unwrap()).to_dot();
+        assert!(dot.contains(r#"[label="1. ε", style=dashed]"#), "{dot}");
+        assert!(dot.contains(r#"[label="2. ε", style=dashed]"#), "{dot}");
+        assert!(dot.contains(r#"[label="\""]"#) && dot.contains(r#"[label="\\b", style=dashed]"#), "{dot}");
+        // An empty set, or one of assertion patterns only, draws no states.
+        assert_eq!(RegexSet::new([]).unwrap().to_dot(), "digraph {\n    rankdir=LR;\n}\n");
+        assert_eq!(RegexSet::new([r"\bx"]).unwrap().to_dot(), "digraph {\n    rankdir=LR;\n}\n");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn graph_serializes_to_json() {
+        let json = serde_json::to_string(&Regex::parse("a").unwrap().dfa.graph()).unwrap();
+        assert_eq!(
+            json,
+            r#"{"states":[{"id":0,"accepts":[],"edges":[{"target":1,"label":"a","consumes":true}]},{"id":1,"accepts":[0],"edges":[]}]}"#
+        );
+    }
+}
@@ -43,6 +43,15 @@
 //! and threads the `(cp, width)` SSA values through that block, so there is no per-character
 //! helper call. Unicode `\w` word-ness still uses an imported helper ([`jit_is_word_unicode`]),
 //! since the table is large and `\b` is comparatively rare; inlining it is a possible follow-up.
+//!
+//! A bytes-mode regex ([`Regex::parse_bytes`]) compiles to the same ABI over a `&[u8]`: the
+//! inline decode is a single byte load of width 1 (see [`Lower::byte_at`]), and `prev` is the
+//! preceding byte. [`JitRegex::find_prefix_bytes`] is its entry point.
+//!
+//! Where the regex has the forward DFA of an unanchored search (see
+//! [`Regex::search_at`]), that is compiled too, as a second function with the same ABI:
+//! it is the walk over the whole haystack. The reverse walk only covers the match and
+//! stays interpreted.
 
 use cranelift_codegen::ir::condcodes::IntCC;
 use cranelift_codegen::ir::{AbiParam, Block, FuncRef, InstBuilder, MemFlags, Value, types};
@@ -54,7 +63,8 @@ use std::collections::HashMap;
 
 use crate::dfa::Dfa;
 use crate::nfa::TransitionEvent;
-use crate::{GroupEntry, Regex, WordBoundaryKind};
+use crate::unanchored::Searcher;
+use crate::{GroupEntry, PikeVm, Regex, WordBoundaryKind};
 
 /// Whether codepoint `cp` is a Unicode `\w` word char, as `0`/`1`. The JIT's
 /// `is_word_unicode`: the table is large, so rather than emit a binary search in IR we
@@ -83,6 +93,21 @@ pub struct JitRegex {
     /// construction, but must outlive every call.
     _module: JITModule,
     func: MatchFn,
+    /// The capture-group program of the source regex; captures run the interpreter's
+    /// submatch pass over the spans the native matcher finds.
+    pike_vm: PikeVm,
+    /// Whether the DFA walk can miss a match ([`Dfa::lock_step`](crate::dfa::Dfa::lock_step)), so
+    /// matching runs on `pike_vm` rather than the native code.
+    lock_step: bool,
+    /// The every-match DFA of the source regex. Only the leftmost-first DFA is compiled;
+    /// the earliest and all-ends matches interpret this one, as the interpreter does.
+    all_matches_dfa: Dfa,
+    /// Whether the native code reads bytes rather than decoding UTF-8 ([`Regex::parse_bytes`]).
+    bytes: bool,
+    /// The unanchored search of the source regex ([`Regex::search_at`]).
+    searcher: Searcher,
+    /// The native walk of the searcher's forward DFA, if it has one.
+    search: Option<MatchFn>,
 }
 
 /// A failure while building the native matcher (Cranelift setup, codegen, or linking).
@@ -142,23 +167,37 @@ impl JitRegex {
         ctx.func.signature.params.push(AbiParam::new(types::I32)); // prev: u32
         ctx.func.signature.returns.push(AbiParam::new(ptr_type)); // last: usize
 
+        let signature = ctx.func.signature.clone();
         let mut func_ctx = FunctionBuilderContext::new();
-        emit_body(&mut module, &mut ctx.func, &mut func_ctx, is_word_id, regex);
-
-        let id = module.declare_function("regex_match", Linkage::Export, &ctx.func.signature).map_err(err)?;
-        module.define_function(id, &mut ctx).map_err(err)?;
-        module.clear_context(&mut ctx);
+        let mut define = |name: &str, dfa: &Dfa| {
+            ctx.func.signature = signature.clone();
+            emit_body(&mut module, &mut ctx.func, &mut func_ctx, is_word_id, dfa, regex.is_bytes());
+            let id = module.declare_function(name, Linkage::Export, &ctx.func.signature).map_err(err)?;
+            module.define_function(id, &mut ctx).map_err(err)?;
+            module.clear_context(&mut ctx);
+            Ok::<_, JitError>(id)
+        };
+        let match_id = define("regex_match", &regex.dfa)?;
+        let searcher = regex.searcher().clone();
+        let search_id = searcher.dfas.as_ref().map(|dfas| define("regex_search", &dfas.forward)).transpose()?;
         module.finalize_definitions().map_err(err)?;
 
-        let code = module.get_finalized_function(id);
-        // SAFETY: `code` points at the finalized body of a function compiled with exactly
-        // the `MatchFn` signature and call convention declared above, and `module` (kept in
-        // the returned struct) owns that memory for the lifetime of the pointer.
-        let func: MatchFn = unsafe { std::mem::transmute::<*const u8, MatchFn>(code) };
+        // SAFETY: each id names the finalized body of a function compiled with exactly the
+        // `MatchFn` signature and call convention declared above, and `module` (kept in the
+        // returned struct) owns that memory for the lifetime of the pointers.
+        let finalized = |id| unsafe { std::mem::transmute::<*const u8, MatchFn>(module.get_finalized_function(id)) };
+        let func = finalized(match_id);
+        let search = search_id.map(finalized);
 
         Ok(JitRegex {
             _module: module,
             func,
+            pike_vm: regex.pike_vm().clone(),
+            lock_step: regex.dfa.lock_step,
+            all_matches_dfa: regex.all_matches_dfa().clone(),
+            bytes: regex.is_bytes(),
+            searcher,
+            search,
         })
     }
 
@@ -166,6 +205,9 @@ impl JitRegex {
     /// `prev` is the char immediately before `from` in the larger input (`None` at the
     /// start of text), seeding the zero-width assertions.
     pub fn find_prefix<'a>(&self, from: &'a str, prev: Option<char>) -> Option<(&'a str, &'a str)> {
+        if self.lock_step {
+            return self.pike_vm.find_prefix(from, prev);
+        }
         let prev_enc = prev.map_or(u32::MAX, u32::from);
         let last = (self.func)(from.as_ptr(), from.len(), prev_enc);
         if last == NO_MATCH {
@@ -174,6 +216,91 @@ impl JitRegex {
             Some((&from[..last], &from[last..]))
         }
     }
+
+    /// Every byte length at which the regex matches a prefix of `from`, mirroring
+    /// [`Regex::find_prefix_ends`](crate::Regex::find_prefix_ends).
+    pub fn find_prefix_ends(&self, from: &str, prev: Option<char>) -> Vec<usize> {
+        if self.all_matches_dfa.lock_step {
+            return self.pike_vm.find_prefix_ends(from, prev);
+        }
+        self.all_matches_dfa.prefix_ends(from, prev)
+    }
+
+    /// The shortest prefix of `from` the regex matches, mirroring
+    /// [`Regex::find_prefix_earliest`](crate::Regex::find_prefix_earliest).
+    pub fn find_prefix_earliest<'a>(&self, from: &'a str, prev: Option<char>) -> Option<(&'a str, &'a str)> {
+        if self.all_matches_dfa.lock_step {
+            return self.pike_vm.find_prefix_earliest(from, prev);
+        }
+        self.all_matches_dfa.prefix_earliest(from, prev).map(|n| (&from[..n], &from[n..]))
+    }
+
+    /// Match a prefix of bytes, mirroring [`Regex::find_prefix_bytes`](crate::Regex::find_prefix_bytes).
+    ///
+    /// Panics unless the regex was parsed with [`Regex::parse_bytes`]: the native code of
+    /// a `str` regex decodes UTF-8 without validating it, so it must never see raw bytes.
+    pub fn find_prefix_bytes<'a>(&self, from: &'a [u8], prev: Option<u8>) -> Option<(&'a [u8], &'a [u8])> {
+        assert!(self.bytes, "find_prefix_bytes needs a regex from Regex::parse_bytes");
+        if self.lock_step {
+            return self.pike_vm.find_prefix_bytes(from, prev);
+        }
+        let prev_enc = prev.map_or(u32::MAX, u32::from);
+        let last = (self.func)(from.as_ptr(), from.len(), prev_enc);
+        if last == NO_MATCH { None } else { Some(from.split_at(last)) }
+    }
+
+    /// Every byte length at which the regex matches a prefix of bytes, mirroring
+    /// [`Regex::find_prefix_ends_bytes`](crate::Regex::find_prefix_ends_bytes).
+    pub fn find_prefix_ends_bytes(&self, from: &[u8], prev: Option<u8>) -> Vec<usize> {
+        if self.all_matches_dfa.lock_step {
+            return self.pike_vm.find_prefix_ends_bytes(from, prev);
+        }
+        self.all_matches_dfa.prefix_ends(from, prev.map(char::from))
+    }
+
+    /// The shortest prefix of bytes the regex matches, mirroring
+    /// [`Regex::find_prefix_earliest_bytes`](crate::Regex::find_prefix_earliest_bytes).
+    pub fn find_prefix_earliest_bytes<'a>(&self, from: &'a [u8], prev: Option<u8>) -> Option<(&'a [u8], &'a [u8])> {
+        if self.all_matches_dfa.lock_step {
+            return self.pike_vm.find_prefix_earliest_bytes(from, prev);
+        }
+        self.all_matches_dfa.prefix_earliest(from, prev.map(char::from)).map(|n| from.split_at(n))
+    }
+
+    /// The leftmost-first match in `haystack` starting at or after byte offset `start`,
+    /// mirroring [`Regex::search_at`]: the walk over the haystack runs natively.
+    pub fn search_at(&self, haystack: &str, start: usize) -> Option<(usize, usize)> {
+        self.searcher.find(
+            haystack,
+            start,
+            |from, prev| self.run_search(from.as_bytes(), prev),
+            |from, prev| self.find_prefix(from, prev).map(|(matched, _)| matched.len()),
+        )
+    }
+
+    /// [`JitRegex::search_at`] over bytes, mirroring [`Regex::search_at_bytes`]. Panics
+    /// unless the regex was parsed with [`Regex::parse_bytes`], as
+    /// [`JitRegex::find_prefix_bytes`] does.
+    pub fn search_at_bytes(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        assert!(self.bytes, "search_at_bytes needs a regex from Regex::parse_bytes");
+        self.searcher.find(
+            haystack,
+            start,
+            |from, prev| self.run_search(from, prev),
+            |from, prev| self.find_prefix_bytes(from, prev.map(|prev| prev as u8)).map(|(matched, _)| matched.len()),
+        )
+    }
+
+    /// The last accept of the native forward search walk over `from`.
+    fn run_search(&self, from: &[u8], prev: Option<char>) -> Option<usize> {
+        let last = (self.search?)(from.as_ptr(), from.len(), prev.map_or(u32::MAX, u32::from));
+        (last != NO_MATCH).then_some(last)
+    }
+
+    /// The capture-group program [`RegexSearch::captures`](crate::RegexSearch::captures) runs over each match.
+    pub fn pike_vm(&self) -> &PikeVm {
+        &self.pike_vm
+    }
 }
 
 /// Funnel any Cranelift error into [`JitError`] via its `Display`.
@@ -206,6 +333,8 @@ struct Lower<'a> {
     /// per state means a zero-width cycle), so it never truncates a real match.
     zw_limit: i64,
     dfa: &'a Dfa,
+    /// Read one byte per unit instead of decoding UTF-8 (a bytes-mode regex).
+    bytes: bool,
 }
 
 /// Build the matcher body: one basic block per DFA state, consuming edges branching
@@ -215,7 +344,8 @@ fn emit_body(
     func: &mut cranelift_codegen::ir::Function,
     func_ctx: &mut FunctionBuilderContext,
     is_word_id: cranelift_module::FuncId,
-    regex: &Regex,
+    dfa: &Dfa,
+    bytes: bool,
 ) {
     // Reference the imported helper before the builder takes `func`.
     let iw_ref = module.declare_func_in_func(is_word_id, func);
@@ -232,8 +362,6 @@ fn emit_body(
     bcx.declare_var(vars.prev, types::I32);
     bcx.declare_var(vars.zero_width, types::I64);
 
-    let dfa = &regex.dfa;
-
     // One block per state id referenced anywhere (keys, edge targets, the start state 0,
     // and the accepting sink), plus a shared return block. A BTreeSet keeps creation order
     // deterministic (handy when reading disassembly).
@@ -273,6 +401,7 @@ fn emit_body(
         state_blocks: &state_blocks,
         zw_limit: dfa.transitions.len() as i64 + 1,
         dfa,
+        bytes,
     };
 
     bcx.ins().jump(state_blocks[&0], &[]);
@@ -313,7 +442,7 @@ impl Lower<'_> {
         // values defined here; this block dominates every block the rest of the state emits,
         // so they're usable throughout (advance, the edge tests, the assertion chain).
         let pos = bcx.use_var(self.vars.counter);
-        let (cp, cw) = self.decode_at(bcx, pos);
+        let (cp, cw) = if self.bytes { self.byte_at(bcx, pos) } else { self.decode_at(bcx, pos) };
 
         if accepting {
             let cnt = bcx.use_var(self.vars.counter);
@@ -342,7 +471,7 @@ impl Lower<'_> {
                 TransitionEvent::Char(_) | TransitionEvent::Chars(..) => {
                     leaves.extend(consuming_ranges(event).into_iter().map(|(lo, hi)| (lo, hi, *target)));
                 }
-                TransitionEvent::End | TransitionEvent::Epsilon => {} // accept marker / never in a DFA
+                TransitionEvent::End | TransitionEvent::Epsilon | TransitionEvent::Save(_) => {} // accept marker / never in a DFA
                 _ => zw_edges.push((event, *target)),
             }
         }
@@ -544,6 +673,33 @@ impl Lower<'_> {
         (cp_out, w_out)
     }
 
+    /// The bytes-mode [`Lower::decode_at`]: the byte at offset `pos` as `(unit: I32, width: I64)`,
+    /// width 1, or `(0, 0)` at end of input. The builder is left positioned in the merge block.
+    fn byte_at(&self, bcx: &mut FunctionBuilder, pos: Value) -> (Value, Value) {
+        let merge = bcx.create_block();
+        let unit_out = bcx.append_block_param(merge, types::I32);
+        let w_out = bcx.append_block_param(merge, types::I64);
+
+        let load_block = bcx.create_block();
+        let eoi_block = bcx.create_block();
+        let in_bounds = bcx.ins().icmp(IntCC::UnsignedLessThan, pos, self.len);
+        bcx.ins().brif(in_bounds, load_block, &[], eoi_block, &[]);
+
+        bcx.switch_to_block(eoi_block);
+        let z32 = bcx.ins().iconst(types::I32, 0);
+        let z64 = bcx.ins().iconst(types::I64, 0);
+        bcx.ins().jump(merge, &[z32.into(), z64.into()]);
+
+        bcx.switch_to_block(load_block);
+        let base = bcx.ins().iadd(self.ptr, pos);
+        let b0 = self.load_byte(bcx, MemFlags::trusted(), base, 0);
+        let w1 = bcx.ins().iconst(types::I64, 1);
+        bcx.ins().jump(merge, &[b0.into(), w1.into()]);
+
+        bcx.switch_to_block(merge);
+        (unit_out, w_out)
+    }
+
     /// Load the byte at `base + offset` and zero-extend it to `I32`.
     fn load_byte(&self, bcx: &mut FunctionBuilder, flags: MemFlags, base: Value, offset: i32) -> Value {
         let byte = bcx.ins().load(types::I8, flags, base, offset);
@@ -646,7 +802,7 @@ impl Lower<'_> {
                     WordBoundaryKind::EndHalf => bcx.ins().bxor_imm(nw, 1),
                 }
             }
-            TransitionEvent::Epsilon | TransitionEvent::Char(_) | TransitionEvent::Chars(..) | TransitionEvent::End => {
+            TransitionEvent::Epsilon | TransitionEvent::Save(_) | TransitionEvent::Char(_) | TransitionEvent::Chars(..) | TransitionEvent::End => {
                 unreachable!("not a zero-width assertion")
             }
         }
@@ -896,6 +1052,26 @@ mod tests {
         }
     }
 
+    #[test]
+    fn captures_match_the_interpreter() {
+        use crate::RegexSearch;
+        let re = Regex::parse("(?P<key>[a-z]+)=([0-9]+)?").unwrap();
+        let jit = re.compile_jit().expect("JIT build");
+        for haystack in ["x a=1 b= c=23", "none"] {
+            let spans = |m: &dyn RegexSearch| {
+                let mut all = vec![];
+                let mut at = 0;
+                while let Some(caps) = m.captures_at(haystack, at) {
+                    at = caps.get_match().end().max(at + 1);
+                    all.push(caps.iter().map(|m| m.map(|m| m.range())).collect::<Vec<_>>());
+                }
+                all
+            };
+            assert_eq!(spans(&jit), spans(&re), "{haystack:?}");
+        }
+        assert_eq!(jit.replace_all("a=1 b=2", "$2=$key"), "1=a 2=b");
+    }
+
     // A rough per-match timing comparison on a long input, where the inline UTF-8 decode (vs
     // the old per-char helper call) actually shows. Not a gate — run with
     // `cargo test -p compiler-tools-regex --features jit --release -- --ignored --nocapture jit_timing`.
@@ -922,4 +1098,25 @@ mod tests {
         let jitted = t.elapsed() / iters;
         println!("interpreter: {interp:.3?}/match   jit: {jitted:.3?}/match   ({:.2}x)", interp.as_secs_f64() / jitted.as_secs_f64());
     }
+
+    #[test]
+    fn bytes_mode_loads_single_bytes() {
+        for (pattern, input) in [(r"a.[\x80-\xFF]+", &b"a\xFF\x80\x81z"[..]), ("(?u).+", "é!".as_bytes()), ("(?u).+", b"ab\xFFc"), (r"\x00+$", b"\x00\x00")] {
+            let re = Regex::parse_bytes(pattern).unwrap();
+            let jit = re.compile_jit().expect("JIT build");
+            assert_eq!(jit.find_prefix_bytes(input, None), re.find_prefix_bytes(input, None), "pattern {pattern:?} on input {input:?}");
+        }
+    }
+
+    #[test]
+    fn search_runs_the_forward_walk_natively() {
+        for (pattern, input) in [("[0-9]+x", "ab12x 3x"), ("é+|b", "aébé"), (r"\bfoo\b", "foox foo"), ("q", "abc")] {
+            let re = Regex::parse(pattern).unwrap();
+            let jit = re.compile_jit().expect("JIT build");
+            for start in 0..=input.len() {
+                assert_eq!(jit.search_at(input, start), re.search_at(input, start), "pattern {pattern:?} on input {input:?} from {start}");
+            }
+        }
+        assert!(Regex::parse("[0-9]+x").unwrap().compile_jit().unwrap().search.is_some());
+    }
 }
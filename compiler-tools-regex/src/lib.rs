@@ -4,26 +4,57 @@
 //! interpret it at runtime ([`Regex::find_prefix`]) and emit a self-contained
 //! Rust matcher from it ([`Regex::generate_parser`]). The proc-macro crate
 //! (`compiler-tools-derive`) consumes the latter; the conformance test crate
-//! exercises both against the upstream `regex` test corpus.
+//! exercises both against the upstream `regex` test corpus. A [`RegexSet`] does the
+//! same for many patterns at once from a single combined DFA, and a [`PikeVm`]
+//! recovers the capture groups of a match, interpreted or as emitted Rust
+//! ([`PikeVm::generate_captures`]). [`Regex::search_at`] finds a match anywhere in a
+//! haystack in one pass, narrowed down by a [`Prefilter`], and
+//! [`Regex::generate_searcher`] emits that search as Rust. [`Regex::shadows`] tells
+//! whether one pattern leaves another nothing to match, for the derive to report
+//! unreachable token variants, and [`Regex::samples`] makes up strings a pattern
+//! matches, for tests. [`Dfa::to_dot`] and [`Nfa::to_dot`] draw what a pattern
+//! compiled to, and [`RegexSet::to_dot`] and [`RegexSet::first_match_graph`] the
+//! DFA a whole tokenizer mode scans with, which a derived tokenizer hands out as
+//! `dfa_dot`.
+
+use std::sync::OnceLock;
 
 use proc_macro2::{Ident, TokenStream};
 use quote::{ToTokens, TokenStreamExt, quote};
 
-use self::{dfa::Dfa, nfa::Nfa};
+use self::unanchored::Searcher;
 
 mod dfa;
 mod generate;
+mod graph;
 #[cfg(feature = "jit")]
 mod jit;
 mod matching;
 mod nfa;
 mod parse;
+mod pikevm;
+mod prefilter;
+mod sample;
 mod search;
+mod serialize;
+mod set;
+mod shadow;
+mod unanchored;
 mod unicode;
 
 #[cfg(feature = "jit")]
 pub use jit::{JitError, JitRegex};
-pub use search::{Match, Matches, RegexSearch, Split, SplitN};
+pub use dfa::{Dfa, Minimization};
+pub use graph::{Graph, GraphEdge, GraphState};
+pub use nfa::{Nfa, TransitionEvent};
+pub use parse::{ParseOptions, RegexError, RegexErrorKind};
+pub use pikevm::PikeVm;
+pub use prefilter::Prefilter;
+pub use sample::seeded_rng;
+pub use serialize::{FORMAT_VERSION, LoadError, LoadErrorKind};
+pub use search::{CaptureMatches, Captures, Match, Matches, OverlappingMatches, RegexSearch, Split, SplitN};
+pub use set::{RegexSet, SetMatches, SetMatchesIter};
+pub use shadow::Priority;
 
 /// Collect an iterator of token-producing values into one [`TokenStream`].
 ///
@@ -132,13 +163,27 @@ pub enum Atom {
     EndOfLine {
         crlf: bool,
     },
-    /// A parenthesised sub-expression with alternation: `(a|bc|d)`. Each inner
-    /// `Vec<AtomRepeat>` is one `|`-separated branch (a sequence of atoms); a plain
-    /// group `(...)` is just an alternation with a single branch. Capturing,
-    /// non-capturing (`(?:...)`) and named (`(?P<n>...)`/`(?<n>...)`) groups are all
-    /// represented identically — this engine does not extract capture spans, so the
-    /// distinction is irrelevant once parsed.
+    /// A sub-pattern given by the strings it matches rather than by its syntax: what
+    /// the intersection `(?&...)`, difference `(?-...)` and complement `~(...)`
+    /// operators build under [`ParseOptions::set_operators`], as an every-match DFA
+    /// without assertions (see `parse.rs`). It matches the longest string of the
+    /// language it can, like a greedy repeat.
+    Language(Box<Dfa>),
+    /// A parenthesised sub-expression with alternation: `(?:a|bc|d)`. Each inner
+    /// `Vec<AtomRepeat>` is one `|`-separated branch (a sequence of atoms); a group
+    /// without `|` is just an alternation with a single branch. This is the
+    /// non-capturing form; capturing groups are [`Atom::Capture`].
     Alternation(Vec<Vec<AtomRepeat>>),
+    /// A capturing group, `(...)` or named `(?P<name>...)`/`(?<name>...)`: an
+    /// alternation whose span is recorded as capture group `index`. Groups are
+    /// numbered from 1 in the order their `(` appears (group 0 is the whole match).
+    /// The DFA treats it exactly like [`Atom::Alternation`]; only the submatch pass
+    /// (see [`PikeVm`]) reads the index.
+    Capture {
+        index: u32,
+        name: Option<String>,
+        branches: Vec<Vec<AtomRepeat>>,
+    },
 }
 
 #[derive(Debug, Clone)]
@@ -157,17 +202,125 @@ pub struct SimpleRegexAst {
 }
 
 pub struct Regex {
-    pub ast: SimpleRegexAst,
     pub dfa: Dfa,
+    /// What the rest of the regex is built from, or was loaded with.
+    source: Source,
+    /// Whether the pattern matches bytes ([`Regex::parse_bytes`]).
+    bytes: bool,
+}
+
+/// Where a [`Regex`]'s capture program and search DFAs come from. A loaded regex has
+/// no AST to build them from, so it holds them already built.
+enum Source {
+    Parsed {
+        ast: SimpleRegexAst,
+        /// Built from `ast` the first time captures are asked for.
+        pike_vm: OnceLock<PikeVm>,
+        /// The every-match DFA ([`Dfa::build_all`]), built from `ast` the first time a
+        /// search other than leftmost-first asks for it.
+        all_matches_dfa: OnceLock<Dfa>,
+        /// The unanchored search ([`Regex::search_at`]), built from `ast` the first time the
+        /// regex is searched.
+        searcher: OnceLock<Searcher>,
+    },
+    /// Loaded with [`Regex::from_bytes`].
+    Loaded { pike_vm: PikeVm, all_matches_dfa: Dfa, searcher: Searcher },
 }
 
 impl Regex {
-    pub fn parse(from: &str) -> Option<Regex> {
-        let parsed = SimpleRegexAst::parse(from)?;
-        let nfa = Nfa::build(&parsed);
-        Some(Regex {
-            ast: parsed,
+    pub fn parse(from: &str) -> Result<Regex, RegexError> {
+        Ok(Self::from_ast(SimpleRegexAst::parse(from)?))
+    }
+
+    /// Parses a pattern for matching `[u8]` input, where the DFA steps one byte at a
+    /// time (see [`SimpleRegexAst::parse_bytes`]). Match it with the `_bytes` methods
+    /// ([`Regex::find_prefix_bytes`]); [`Regex::generate_parser`] emits a
+    /// `fn(&[u8], Option<u8>) -> Option<(&[u8], &[u8])>` matcher for it.
+    pub fn parse_bytes(from: &str) -> Result<Regex, RegexError> {
+        Ok(Self::from_ast_bytes(SimpleRegexAst::parse_bytes(from)?))
+    }
+
+    /// Parses a pattern with `options` set, e.g. to read the set operators (see
+    /// [`SimpleRegexAst::parse_with`]).
+    pub fn parse_with(from: &str, options: ParseOptions) -> Result<Regex, RegexError> {
+        let ast = SimpleRegexAst::parse_with(from, options)?;
+        Ok(if options.bytes { Self::from_ast_bytes(ast) } else { Self::from_ast(ast) })
+    }
+
+    /// Compiles an already-built AST, e.g. one assembled from literals without going
+    /// through the pattern syntax.
+    pub fn from_ast(ast: SimpleRegexAst) -> Regex {
+        let nfa = Nfa::build(&ast);
+        Regex {
             dfa: Dfa::build(&nfa),
-        })
+            source: Source::Parsed {
+                ast,
+                pike_vm: OnceLock::new(),
+                all_matches_dfa: OnceLock::new(),
+                searcher: OnceLock::new(),
+            },
+            bytes: false,
+        }
+    }
+
+    /// [`Regex::from_ast`] for a bytes-mode AST, whose chars are all bytes
+    /// (`U+0000..=U+00FF`).
+    pub fn from_ast_bytes(ast: SimpleRegexAst) -> Regex {
+        Regex {
+            bytes: true,
+            ..Self::from_ast(ast)
+        }
+    }
+
+    /// Whether the pattern matches bytes rather than chars ([`Regex::parse_bytes`]).
+    pub fn is_bytes(&self) -> bool {
+        self.bytes
+    }
+
+    /// The parsed pattern, or `None` for a regex loaded with [`Regex::from_bytes`].
+    pub fn ast(&self) -> Option<&SimpleRegexAst> {
+        match &self.source {
+            Source::Parsed { ast, .. } => Some(ast),
+            Source::Loaded { .. } => None,
+        }
+    }
+
+    /// The capture-group program [`RegexSearch::captures`] runs over each match.
+    pub fn pike_vm(&self) -> &PikeVm {
+        match &self.source {
+            Source::Parsed { ast, pike_vm, .. } => pike_vm.get_or_init(|| if self.bytes { PikeVm::new_bytes(ast) } else { PikeVm::new(ast) }),
+            Source::Loaded { pike_vm, .. } => pike_vm,
+        }
+    }
+
+    /// The DFA the earliest, overlapping and longest searches walk, which accepts
+    /// wherever any thread does.
+    pub fn all_matches_dfa(&self) -> &Dfa {
+        match &self.source {
+            Source::Parsed { ast, all_matches_dfa, .. } => all_matches_dfa.get_or_init(|| Dfa::build_all(&Nfa::build(ast))),
+            Source::Loaded { all_matches_dfa, .. } => all_matches_dfa,
+        }
+    }
+
+    /// The byte scan the unanchored search starts with, if the pattern pins down how a
+    /// match begins.
+    pub fn prefilter(&self) -> Option<&Prefilter> {
+        self.searcher().prefilter.as_ref()
+    }
+
+    pub(crate) fn searcher(&self) -> &Searcher {
+        match &self.source {
+            Source::Parsed { ast, searcher, .. } => searcher.get_or_init(|| Searcher::new(ast, self.bytes)),
+            Source::Loaded { searcher, .. } => searcher,
+        }
+    }
+
+    /// The NFA the DFAs are built from. A loaded regex has no AST, but its capture
+    /// NFA is the same NFA with capture slots saved on the way.
+    pub(crate) fn nfa(&self) -> Nfa {
+        match &self.source {
+            Source::Parsed { ast, .. } => Nfa::build(ast),
+            Source::Loaded { pike_vm, .. } => pike_vm.nfa.without_saves(),
+        }
     }
 }
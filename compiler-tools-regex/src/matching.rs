@@ -27,13 +27,74 @@ fn atoms_could_capture_newline(atoms: &[AtomRepeat]) -> bool {
         | Atom::EndOfLine {
             ..
         } => false,
-        Atom::Alternation(branches) => branches.iter().any(|branch| atoms_could_capture_newline(branch)),
+        Atom::Alternation(branches)
+        | Atom::Capture {
+            branches,
+            ..
+        } => branches.iter().any(|branch| atoms_could_capture_newline(branch)),
+        Atom::Language(dfa) => dfa
+            .transitions
+            .values()
+            .flatten()
+            .any(|(event, _)| !matches!(event, super::nfa::TransitionEvent::End) && event.matches('\n')),
     })
 }
 
+/// The input a matcher reads: a `str` one char at a time, or a `[u8]` one byte at a
+/// time (bytes mode, [`Regex::parse_bytes`]) with each byte read as the char of the
+/// same value, so the DFA and NFA edges, which are all chars, apply to both. Positions
+/// are byte offsets either way.
+pub(crate) trait Haystack {
+    /// The unit starting at byte offset `pos`, or `None` at the end.
+    fn unit_at(&self, pos: usize) -> Option<char>;
+
+    /// The unit ending at byte offset `pos`, or `None` at the start.
+    fn unit_before(&self, pos: usize) -> Option<char>;
+
+    /// How many bytes `unit` takes up.
+    fn unit_len(unit: char) -> usize;
+}
+
+impl Haystack for str {
+    fn unit_at(&self, pos: usize) -> Option<char> {
+        self[pos..].chars().next()
+    }
+
+    fn unit_before(&self, pos: usize) -> Option<char> {
+        self[..pos].chars().next_back()
+    }
+
+    fn unit_len(unit: char) -> usize {
+        unit.len_utf8()
+    }
+}
+
+impl Haystack for [u8] {
+    fn unit_at(&self, pos: usize) -> Option<char> {
+        self.get(pos).copied().map(char::from)
+    }
+
+    fn unit_before(&self, pos: usize) -> Option<char> {
+        pos.checked_sub(1).map(|before| char::from(self[before]))
+    }
+
+    fn unit_len(_: char) -> usize {
+        1
+    }
+}
+
 impl Regex {
     pub fn could_capture_newline(&self) -> bool {
-        atoms_could_capture_newline(&self.ast.atoms)
+        match self.ast() {
+            Some(ast) => atoms_could_capture_newline(&ast.atoms),
+            // Without the pattern, any edge the DFA could step over a `\n` on.
+            None => self
+                .dfa
+                .transitions
+                .values()
+                .flatten()
+                .any(|(event, _)| *event != TransitionEvent::End && event.matches('\n')),
+        }
     }
 
     /// Whether the regex matches some prefix of `from`. Used at macro-expansion
@@ -49,10 +110,16 @@ impl Regex {
     pub fn matches(&self, from: &str) -> bool {
         use super::nfa::TransitionEvent;
         let mut state = 0u32;
-        if self.accepts(state) {
+        if self.dfa.accepts(state) {
             return true;
         }
-        for ch in from.chars() {
+        // A bytes-mode DFA reads each byte of the UTF-8 encoding as a char of its own.
+        let units: Box<dyn Iterator<Item = char>> = if self.bytes {
+            Box::new(from.bytes().map(char::from))
+        } else {
+            Box::new(from.chars())
+        };
+        for ch in units {
             let Some(transitions) = self.dfa.transitions.get(&state) else {
                 return false;
             };
@@ -63,63 +130,13 @@ impl Regex {
                 Some((_, target)) => state = *target,
                 None => return false,
             }
-            if self.accepts(state) {
-                return true;
-            }
-        }
-        false
-    }
-
-    /// Whether `state` accepts here, or can reach an accepting state purely through
-    /// zero-width assertion edges that hold at this position (`prev`/`c`). This is
-    /// what lets a greedy consuming thread back off to an assertion-gated accept it
-    /// would otherwise consume past: `.+\b` records the `\b` accept while `.+` keeps
-    /// eating, then backs off to it (and `\B(?:fo|foo)\B` accepts `fo` once the
-    /// longer `foo` fails its trailing `\B`). A zero-width move never advances the
-    /// position, so every state on such a path is evaluated at the same `prev`/`c`.
-    fn accepts_via_assertions(&self, start: u32, prev: Option<char>, c: Option<char>) -> bool {
-        use super::nfa::TransitionEvent;
-        if self.accepts(start) {
-            return true;
-        }
-        let is_zero_width = |t: &TransitionEvent| !matches!(t, TransitionEvent::Char(_) | TransitionEvent::Chars(..) | TransitionEvent::End);
-        // Fast path: a state with no zero-width edge can't reach an assertion-gated
-        // accept, so the overwhelmingly common no-assertion state allocates nothing.
-        let Some(transitions) = self.dfa.transitions.get(&start) else { return false };
-        if !transitions.iter().any(|(t, _)| is_zero_width(t)) {
-            return false;
-        }
-        let mut stack: Vec<u32> = vec![start];
-        let mut seen = std::collections::HashSet::new();
-        while let Some(state) = stack.pop() {
-            if !seen.insert(state) {
-                continue;
-            }
-            if self.accepts(state) {
+            if self.dfa.accepts(state) {
                 return true;
             }
-            let Some(transitions) = self.dfa.transitions.get(&state) else { continue };
-            for (transition, target) in transitions {
-                if is_zero_width(transition) && zero_width_holds(transition, prev, c) {
-                    stack.push(*target);
-                }
-            }
         }
         false
     }
 
-    /// Whether `state` is accepting: the transition-less sink, or any state with an
-    /// `End` edge to it.
-    fn accepts(&self, state: u32) -> bool {
-        use super::nfa::TransitionEvent;
-        state == self.dfa.final_state
-            || self
-                .dfa
-                .transitions
-                .get(&state)
-                .is_some_and(|transitions| transitions.iter().any(|(transition, _)| matches!(transition, TransitionEvent::End)))
-    }
-
     /// Run the DFA as a runtime interpreter, returning the matched prefix and the
     /// remaining input — the same `(matched, remaining)` contract the generated
     /// (`generate_parser`) matcher produces.
@@ -142,31 +159,124 @@ impl Regex {
     /// `prev` is the char immediately before `from` in the larger input (`None` for
     /// start of text); it seeds the zero-width assertions (`^` under `(?m)`, `\b`) so
     /// a slice taken mid-input still sees the correct preceding context.
+    ///
+    /// A pattern the DFA walk can't match exactly ([`Dfa::lock_step`](crate::dfa::Dfa::lock_step)) runs on its
+    /// [`PikeVm`](crate::PikeVm) instead.
     #[allow(dead_code)] // exercised by the conformance unit tests, not the macro itself
     pub fn find_prefix<'a>(&self, from: &'a str, prev: Option<char>) -> Option<(&'a str, &'a str)> {
+        if self.dfa.lock_step {
+            return self.pike_vm().find_prefix(from, prev);
+        }
+        self.dfa.prefix_end(from, prev).map(|n| (&from[..n], &from[n..]))
+    }
+
+    /// Every byte length at which the regex matches a prefix of `from`, in increasing
+    /// order, whatever the match priority — a leftmost-first match is one of them,
+    /// and e.g. `a+` on `"aaa"` gives `[1, 2, 3]`. Walks the every-match DFA
+    /// ([`Dfa::build_all`](crate::dfa::Dfa::build_all)), or the [`PikeVm`] where that walk can't be exact.
+    pub fn find_prefix_ends(&self, from: &str, prev: Option<char>) -> Vec<usize> {
+        let dfa = self.all_matches_dfa();
+        if dfa.lock_step {
+            return self.pike_vm().find_prefix_ends(from, prev);
+        }
+        dfa.prefix_ends(from, prev)
+    }
+
+    /// The shortest prefix of `from` the regex matches: the walk stops at the first
+    /// accepting position, so `a+` matches one `a` and `abc|a` matches `a`.
+    pub fn find_prefix_earliest<'a>(&self, from: &'a str, prev: Option<char>) -> Option<(&'a str, &'a str)> {
+        let dfa = self.all_matches_dfa();
+        if dfa.lock_step {
+            return self.pike_vm().find_prefix_earliest(from, prev);
+        }
+        dfa.prefix_earliest(from, prev).map(|n| (&from[..n], &from[n..]))
+    }
+
+    /// [`Regex::find_prefix`] over bytes, for a pattern from [`Regex::parse_bytes`]:
+    /// `prev` is the byte before `from`, and the split is at a byte offset that need not
+    /// be a char boundary.
+    pub fn find_prefix_bytes<'a>(&self, from: &'a [u8], prev: Option<u8>) -> Option<(&'a [u8], &'a [u8])> {
+        if self.dfa.lock_step {
+            return self.pike_vm().find_prefix_bytes(from, prev);
+        }
+        self.dfa.prefix_end(from, prev.map(char::from)).map(|n| from.split_at(n))
+    }
+
+    /// [`Regex::find_prefix_ends`] over bytes; see [`Regex::find_prefix_bytes`].
+    pub fn find_prefix_ends_bytes(&self, from: &[u8], prev: Option<u8>) -> Vec<usize> {
+        let dfa = self.all_matches_dfa();
+        if dfa.lock_step {
+            return self.pike_vm().find_prefix_ends_bytes(from, prev);
+        }
+        dfa.prefix_ends(from, prev.map(char::from))
+    }
+
+    /// [`Regex::find_prefix_earliest`] over bytes; see [`Regex::find_prefix_bytes`].
+    pub fn find_prefix_earliest_bytes<'a>(&self, from: &'a [u8], prev: Option<u8>) -> Option<(&'a [u8], &'a [u8])> {
+        let dfa = self.all_matches_dfa();
+        if dfa.lock_step {
+            return self.pike_vm().find_prefix_earliest_bytes(from, prev);
+        }
+        dfa.prefix_earliest(from, prev.map(char::from)).map(|n| from.split_at(n))
+    }
+
+    /// The leftmost-first match in `haystack` starting at or after byte offset `start`,
+    /// as its start and end offsets: what trying [`Regex::find_prefix`] at each char
+    /// boundary from `start` on finds first, found in one pass over the haystack where
+    /// the pattern allows (see [`RegexSearch::find_at`](crate::RegexSearch::find_at)).
+    /// A `start` inside a char is moved up to the next boundary.
+    pub fn search_at(&self, haystack: &str, start: usize) -> Option<(usize, usize)> {
+        let searcher = self.searcher();
+        searcher.find(
+            haystack,
+            start,
+            |from, prev| searcher.dfas.as_ref()?.forward.prefix_end(from, prev),
+            |from, prev| self.find_prefix(from, prev).map(|(matched, _)| matched.len()),
+        )
+    }
+
+    /// [`Regex::search_at`] over bytes; see [`Regex::find_prefix_bytes`].
+    pub fn search_at_bytes(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        let searcher = self.searcher();
+        searcher.find(
+            haystack,
+            start,
+            |from, prev| searcher.dfas.as_ref()?.forward.prefix_end(from, prev),
+            |from, prev| self.find_prefix_bytes(from, prev.map(|prev| prev as u8)).map(|(matched, _)| matched.len()),
+        )
+    }
+}
+
+impl Dfa {
+    /// The interpreter's walk from state 0 over `from`, calling `on_accept` with the
+    /// byte offset of every accepting position reached (an offset may repeat across
+    /// zero-width moves) until it returns `true` or the walk dead-ends.
+    ///
+    /// Consuming edges win over zero-width ones; `$`/`\z` only fires at end of
+    /// input; word boundaries are tested against `prev`/`c` without consuming; and
+    /// an `End` edge marks a state accepting (it never moves).
+    pub(crate) fn walk<H: Haystack + ?Sized>(&self, from: &H, prev: Option<char>, mut on_accept: impl FnMut(usize) -> bool) {
         let mut counter = 0usize;
         let mut state = 0u32;
-        let mut last: Option<usize> = None;
         let mut prev: Option<char> = prev;
-        let mut chars = from.chars();
-        let mut c = chars.next();
+        let mut c = from.unit_at(0);
         // A bound on consecutive zero-width moves; more than one per state would
         // mean a zero-width cycle (e.g. `\b*`), so this can never truncate a real match.
-        let zero_width_limit = self.dfa.transitions.len() + 1;
+        let zero_width_limit = self.transitions.len() + 1;
         let mut zero_width = 0usize;
         loop {
-            if self.accepts_via_assertions(state, prev, c) {
-                last = Some(counter);
+            if self.accepts_via_assertions(state, prev, c) && on_accept(counter) {
+                break;
             }
-            let Some(transitions) = self.dfa.transitions.get(&state) else { break };
+            let Some(transitions) = self.transitions.get(&state) else { break };
             match eval_state(transitions, prev, c) {
                 Step::Matched(next) => {
                     state = next;
                     if let Some(ch) = c {
-                        counter += ch.len_utf8();
+                        counter += H::unit_len(ch);
                     }
                     prev = c;
-                    c = chars.next();
+                    c = from.unit_at(counter);
                     zero_width = 0;
                 }
                 Step::MatchedEmpty(next) => {
@@ -180,7 +290,170 @@ impl Regex {
                 Step::NoMatch => break,
             }
         }
-        last.map(|n| (&from[..n], &from[n..]))
+    }
+
+    /// [`Dfa::walk`] to its dead end, keeping the last accepting offset: the
+    /// leftmost-first match, or on the every-match DFA the longest.
+    pub(crate) fn prefix_end<H: Haystack + ?Sized>(&self, from: &H, prev: Option<char>) -> Option<usize> {
+        let mut last = None;
+        self.walk(from, prev, |end| {
+            last = Some(end);
+            false
+        });
+        last
+    }
+
+    /// [`Dfa::walk`] collecting each accepting offset once — on the every-match DFA,
+    /// the end of every match starting at `from`.
+    pub(crate) fn prefix_ends<H: Haystack + ?Sized>(&self, from: &H, prev: Option<char>) -> Vec<usize> {
+        let mut ends = vec![];
+        self.walk(from, prev, |end| {
+            if ends.last() != Some(&end) {
+                ends.push(end);
+            }
+            false
+        });
+        ends
+    }
+
+    /// [`Dfa::walk`] stopped at the first accepting offset.
+    pub(crate) fn prefix_earliest<H: Haystack + ?Sized>(&self, from: &H, prev: Option<char>) -> Option<usize> {
+        let mut earliest = None;
+        self.walk(from, prev, |end| {
+            earliest = Some(end);
+            true
+        });
+        earliest
+    }
+
+    /// The state the interpreter's walk reaches from `state` on `c` after `prev`,
+    /// taking whatever zero-width edges hold on the way, or `None` where it dead-ends.
+    pub(crate) fn step(&self, mut state: u32, prev: Option<char>, c: char) -> Option<u32> {
+        // As in `walk`, more zero-width moves than states would be a cycle.
+        for _ in 0..=self.transitions.len() {
+            match eval_state(self.transitions.get(&state)?, prev, Some(c)) {
+                Step::Matched(next) => return Some(next),
+                Step::MatchedEmpty(next) => state = next,
+                Step::NoMatch => return None,
+            }
+        }
+        None
+    }
+
+    /// Whether `state` accepts here, or can reach an accepting state purely through
+    /// zero-width assertion edges that hold at this position (`prev`/`c`). This is
+    /// what lets a greedy consuming thread back off to an assertion-gated accept it
+    /// would otherwise consume past: `.+\b` records the `\b` accept while `.+` keeps
+    /// eating, then backs off to it (and `\B(?:fo|foo)\B` accepts `fo` once the
+    /// longer `foo` fails its trailing `\B`). A zero-width move never advances the
+    /// position, so every state on such a path is evaluated at the same `prev`/`c`.
+    pub(crate) fn accepts_via_assertions(&self, start: u32, prev: Option<char>, c: Option<char>) -> bool {
+        use super::nfa::TransitionEvent;
+        if self.accepts(start) {
+            return true;
+        }
+        let is_zero_width = |t: &TransitionEvent| !matches!(t, TransitionEvent::Char(_) | TransitionEvent::Chars(..) | TransitionEvent::End);
+        // Fast path: a state with no zero-width edge can't reach an assertion-gated
+        // accept, so the overwhelmingly common no-assertion state allocates nothing.
+        let Some(transitions) = self.transitions.get(&start) else { return false };
+        if !transitions.iter().any(|(t, _)| is_zero_width(t)) {
+            return false;
+        }
+        let mut stack: Vec<u32> = vec![start];
+        let mut seen = std::collections::HashSet::new();
+        while let Some(state) = stack.pop() {
+            if !seen.insert(state) {
+                continue;
+            }
+            if self.accepts(state) {
+                return true;
+            }
+            let Some(transitions) = self.transitions.get(&state) else { continue };
+            for (transition, target) in transitions {
+                if is_zero_width(transition) && zero_width_holds(transition, prev, c) {
+                    stack.push(*target);
+                }
+            }
+        }
+        false
+    }
+
+    /// Whether `state` is accepting: the transition-less sink, or any state with an
+    /// `End` edge to it.
+    pub(crate) fn accepts(&self, state: u32) -> bool {
+        use super::nfa::TransitionEvent;
+        state == self.final_state
+            || self
+                .transitions
+                .get(&state)
+                .is_some_and(|transitions| transitions.iter().any(|(transition, _)| matches!(transition, TransitionEvent::End)))
+    }
+}
+
+impl RegexSet {
+    /// Run every pattern anchored at the start of `from` in one pass, returning for
+    /// each pattern (by set index) the byte length of its leftmost-first match, or
+    /// `None` — entry `i` is what [`Regex::find_prefix`] would match for pattern `i`
+    /// alone. The same contract as the generated matcher ([`RegexSet::generate_parser`]).
+    ///
+    /// The combined DFA is walked once: an `End` edge records the position for the
+    /// pattern it accepts, entering a pattern's sink records it and stops, and the
+    /// walk runs until no consuming edge claims the lookahead. The patterns kept
+    /// standalone (those with zero-width assertions) then run on their own, seeded
+    /// with `prev`.
+    pub fn find_prefix_all(&self, from: &str, prev: Option<char>) -> Vec<Option<usize>> {
+        let mut last = self.walk_combined(from);
+        for (index, regex) in self.regexes.iter().enumerate() {
+            if !self.combined.contains(&index) {
+                last[index] = regex.find_prefix(from, prev).map(|(matched, _)| matched.len());
+            }
+        }
+        last
+    }
+
+    /// [`RegexSet::find_prefix_all`] over bytes, for a set of [`Regex::parse_bytes`]
+    /// patterns; `prev` is the byte before `from`.
+    pub fn find_prefix_all_bytes(&self, from: &[u8], prev: Option<u8>) -> Vec<Option<usize>> {
+        let mut last = self.walk_combined(from);
+        for (index, regex) in self.regexes.iter().enumerate() {
+            if !self.combined.contains(&index) {
+                last[index] = regex.find_prefix_bytes(from, prev).map(|(matched, _)| matched.len());
+            }
+        }
+        last
+    }
+
+    /// The combined DFA's walk over `from`: the match length of each combined pattern,
+    /// by set index, with `None` for the standalone ones.
+    fn walk_combined<H: Haystack + ?Sized>(&self, from: &H) -> Vec<Option<usize>> {
+        use super::nfa::TransitionEvent;
+        let mut last = vec![None; self.regexes.len()];
+        if let Some(dfa) = &self.dfa {
+            let mut counter = 0usize;
+            let mut state = 0u32;
+            let mut c = from.unit_at(0);
+            loop {
+                if let Some(pattern) = dfa.final_pattern(state) {
+                    last[self.combined[pattern]] = Some(counter);
+                    break;
+                }
+                let Some(transitions) = dfa.transitions.get(&state) else { break };
+                let mut next = None;
+                for (transition, target) in transitions {
+                    if matches!(transition, TransitionEvent::End) {
+                        let pattern = dfa.final_pattern(*target).expect("End edges point at a pattern sink");
+                        last[self.combined[pattern]] = Some(counter);
+                    } else if next.is_none() && c.is_some_and(|c| transition.matches(c)) {
+                        next = Some(*target);
+                    }
+                }
+                let (Some(next), Some(ch)) = (next, c) else { break };
+                state = next;
+                counter += H::unit_len(ch);
+                c = from.unit_at(counter);
+            }
+        }
+        last
     }
 }
 
@@ -211,7 +484,7 @@ fn is_word(ch: Option<char>, unicode: bool) -> bool {
 /// progression) and `accepts_via_assertions` (recording an assertion-gated accept).
 /// Returns `false` for any non-zero-width event.
 #[allow(dead_code)]
-fn zero_width_holds(transition: &super::nfa::TransitionEvent, prev: Option<char>, c: Option<char>) -> bool {
+pub(crate) fn zero_width_holds(transition: &super::nfa::TransitionEvent, prev: Option<char>, c: Option<char>) -> bool {
     use super::nfa::TransitionEvent;
     match transition {
         TransitionEvent::EndOfInput => c.is_none(),
@@ -252,11 +525,7 @@ fn eval_state(transitions: &[(super::nfa::TransitionEvent, u32)], prev: Option<c
     use super::nfa::TransitionEvent;
     for (transition, target) in transitions {
         match transition {
-            TransitionEvent::Char(ch) => {
-                if c == Some(*ch) {
-                    return Step::Matched(*target);
-                }
-            }
+            TransitionEvent::Char(ch) if c == Some(*ch) => return Step::Matched(*target),
             TransitionEvent::Chars(inverted, group) => {
                 if let Some(ch) = c {
                     let in_group = group.iter().any(|entry| match entry {
@@ -316,7 +585,7 @@ mod tests {
 
     #[test]
     fn parse_rejects_unclosed_group() {
-        assert!(Regex::parse("[abc").is_none());
+        assert!(Regex::parse("[abc").is_err());
     }
 
     #[test]
@@ -507,7 +776,7 @@ mod tests {
     #[test]
     fn non_capturing_and_named_groups_behave_like_groups() {
         for pattern in ["(?:foo|bar)!", "(?P<word>foo|bar)!", "(?<word>foo|bar)!"] {
-            let re = Regex::parse(pattern).unwrap_or_else(|| panic!("parse {pattern}"));
+            let re = Regex::parse(pattern).unwrap_or_else(|err| panic!("parse {pattern}: {err}"));
             assert!(re.matches("foo!"), "{pattern} should match foo!");
             assert!(re.matches("bar!"), "{pattern} should match bar!");
             assert!(!re.matches("baz!"), "{pattern} should not match baz!");
@@ -563,10 +832,10 @@ mod tests {
     #[test]
     fn unsupported_or_malformed_group_syntax_is_rejected() {
         // Unclosed group, stray close, lookaround and unmodellable flags are rejected.
-        assert!(Regex::parse("(abc").is_none());
-        assert!(Regex::parse("abc)").is_none());
-        assert!(Regex::parse("(?=foo)").is_none());
-        assert!(Regex::parse("(?<=foo)bar").is_none());
+        assert!(Regex::parse("(abc").is_err());
+        assert!(Regex::parse("abc)").is_err());
+        assert!(Regex::parse("(?=foo)").is_err());
+        assert!(Regex::parse("(?<=foo)bar").is_err());
     }
 
     #[test]
@@ -757,4 +1026,57 @@ mod tests {
         assert!(re.matches("(a|b)"));
         assert!(!re.matches("a"));
     }
+
+    #[test]
+    fn bytes_mode_matches_raw_and_encoded_bytes() {
+        // `(?-u)` semantics: `.` and classes take any single byte, valid UTF-8 or not.
+        let re = Regex::parse_bytes(r"a.[\x80-\xFF]+").unwrap();
+        assert_eq!(re.find_prefix_bytes(b"a\xFF\x80\x81z", None), Some((&b"a\xFF\x80\x81"[..], &b"z"[..])));
+        assert_eq!(re.find_prefix_bytes(b"a\nz", None), None);
+        // `(?u)` classes match whole encoded chars, so `.` spans both bytes of `é` and
+        // stops at an invalid byte.
+        let re = Regex::parse_bytes("(?u).+").unwrap();
+        assert_eq!(re.find_prefix_bytes("é!\u{FF}".as_bytes(), None).map(|(m, _)| m.len()), Some(5));
+        assert_eq!(re.find_prefix_bytes(b"ab\xFFc", None).map(|(m, _)| m.len()), Some(2));
+        // Assertions see the byte before the slice.
+        let re = Regex::parse_bytes(r"\bx").unwrap();
+        assert_eq!(re.find_prefix_bytes(b"x", Some(b'a')), None);
+        assert_eq!(re.find_prefix_bytes(b"x", Some(0xFF)).map(|(m, _)| m), Some(&b"x"[..]));
+        assert_eq!(Regex::parse_bytes("a+").unwrap().find_prefix_ends_bytes(b"aa\xFF", None), [1, 2]);
+    }
+
+    #[test]
+    fn set_operators_match_by_language() {
+        let parse = |pattern: &str| {
+            let options = ParseOptions {
+                set_operators: true,
+                ..ParseOptions::default()
+            };
+            Regex::parse_with(pattern, options).unwrap()
+        };
+        let matched = |pattern: &str, input: &'static str| parse(pattern).find_prefix(input, None).map(|(m, _)| m);
+        // An identifier that isn't a keyword: the longest identifier, unless it is one.
+        let ident = r"[a-z]+(?-if|else)";
+        assert_eq!(matched(ident, "iffy+"), Some("iffy"));
+        // `if` is a keyword, but its prefix `i` is an identifier all the same.
+        assert_eq!(matched(ident, "if+"), Some("i"));
+        assert_eq!(matched(&format!("{ident} "), "else x"), None);
+        // A `/* */` comment whose body doesn't contain `*/`.
+        let comment = parse(r"/\*~((?s:.*)\*/(?s:.*))\*/");
+        assert_eq!(comment.find_prefix("/* a */ b */", None), Some(("/* a */", " b */")));
+        assert_eq!(comment.find_prefix("/* a", None), None);
+        assert_eq!(matched(r"\w+(?&.*\d.*)", "ab1c d"), Some("ab1c"));
+        assert_eq!(matched(r"\w+(?&.*\d.*)", "abc d"), None);
+        // `(?-i)` still clears a flag, and a `~` before anything but a group is a literal.
+        assert_eq!(matched("(?i)a(?-i)b", "AB"), None);
+        assert_eq!(matched("~a", "~a"), Some("~a"));
+        // The unanchored search finds them as well, with word boundaries around the
+        // operand to keep `if` from matching as `i`.
+        let find = |pattern: &str, haystack: &'static str| parse(pattern).find(haystack).map(|m| m.range());
+        assert_eq!(find(&format!(r"\b(?:{ident})\b"), "if else elsewhere"), Some(8..17));
+        assert_eq!(find("[0-9]+(?-0.*)", "0 07 70"), Some(3..4));
+        // Without the option, `~(a)` is a `~` then a group, and `(?-i:a)` a group clearing a flag.
+        assert_eq!(Regex::parse("~(a)").unwrap().find_prefix("~ab", None), Some(("~a", "b")));
+        assert_eq!(Regex::parse("(?i)(?-i:a)").unwrap().find_prefix("A", None), None);
+    }
 }
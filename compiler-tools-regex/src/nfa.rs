@@ -1,4 +1,4 @@
-use std::collections::BTreeMap;
+use std::collections::{BTreeMap, HashMap};
 
 use super::{Atom, AtomRepeat, GroupEntry, Repeat, SimpleRegexAst, WordBoundaryKind};
 
@@ -31,13 +31,17 @@ pub enum TransitionEvent {
     EndOfLine {
         crlf: bool,
     },
+    /// An epsilon edge that records the current position into capture slot `n`
+    /// (slot `2i` opens group `i`, `2i + 1` closes it). Only emitted by
+    /// [`Nfa::build_captures`], for the submatch pass; the DFA treats it as epsilon.
+    Save(u32),
     End,
 }
 
 impl TransitionEvent {
     pub fn matches(&self, target: char) -> bool {
         match self {
-            TransitionEvent::Epsilon => false,
+            TransitionEvent::Epsilon | TransitionEvent::Save(_) => false,
             TransitionEvent::Char(c) => *c == target,
             TransitionEvent::Chars(inverted, group) => {
                 for entry in group {
@@ -73,7 +77,7 @@ impl TransitionEvent {
     }
 }
 
-#[derive(Debug)]
+#[derive(Debug, Clone)]
 pub struct Nfa {
     // state => [(event, state)]
     pub transitions: BTreeMap<u32, Vec<(TransitionEvent, u32)>>,
@@ -99,6 +103,8 @@ pub struct Nfa {
 struct Builder {
     transitions: BTreeMap<u32, Vec<(TransitionEvent, u32)>>,
     next: u32,
+    /// Whether capture groups get [`TransitionEvent::Save`] edges around them.
+    captures: bool,
 }
 
 impl Builder {
@@ -147,6 +153,17 @@ impl Builder {
                 self.edge(body_end, TransitionEvent::Epsilon, end);
                 end
             }
+            Repeat::ZeroOrMore if self.captures => {
+                // Built as `(?:e+)?`, as the `regex` crate does, so a body that
+                // matches empty still runs once and records its captures. Looping
+                // back to the split would drop that thread there as already visited.
+                let end = self.new_state();
+                let body_start = self.new_state();
+                self.split(start, body_start, end, lazy);
+                let body_end = self.build_atom(&atom.atom, body_start);
+                self.split(body_end, body_start, end, lazy);
+                end
+            }
             Repeat::ZeroOrMore => {
                 // `start` is the loop split: body-or-exit. The body loops back to the
                 // split, so re-entry re-reads the same priority order.
@@ -235,29 +252,79 @@ impl Builder {
                 );
                 end
             }
-            Atom::Alternation(branches) => {
-                // `start` is a pure-epsilon split: one epsilon edge per branch, in
-                // declaration order (so earlier branches have higher priority for the
-                // leftmost-first matcher). Each branch is built from its own dedicated
-                // start state and merges into one `end`.
+            Atom::Language(dfa) => {
+                // One split per DFA state, stepping on each of its classes from a state of
+                // its own and, if the state accepts, exiting after them, so the longest
+                // string of the language wins.
+                let states: HashMap<u32, u32> = dfa.transitions.keys().map(|state| (*state, self.new_state())).collect();
                 let end = self.new_state();
-                for branch in branches {
-                    let branch_start = self.new_state();
-                    self.edge(start, TransitionEvent::Epsilon, branch_start);
-                    let branch_end = self.build_sequence(branch, branch_start);
-                    self.edge(branch_end, TransitionEvent::Epsilon, end);
+                self.edge(start, TransitionEvent::Epsilon, states[&0]);
+                for (state, edges) in &dfa.transitions {
+                    for (event, target) in edges {
+                        if let TransitionEvent::End = event {
+                            self.edge(states[state], TransitionEvent::Epsilon, end);
+                        } else {
+                            let step = self.new_state();
+                            self.edge(states[state], TransitionEvent::Epsilon, step);
+                            self.edge(step, event.clone(), states[target]);
+                        }
+                    }
                 }
                 end
             }
+            Atom::Alternation(branches) => self.build_alternation(branches, start),
+            Atom::Capture {
+                index,
+                branches,
+                ..
+            } if self.captures => {
+                // Bracket the group with the saves of its two slots.
+                let open = self.new_state();
+                self.edge(start, TransitionEvent::Save(index * 2), open);
+                let body_end = self.build_alternation(branches, open);
+                let end = self.new_state();
+                self.edge(body_end, TransitionEvent::Save(index * 2 + 1), end);
+                end
+            }
+            Atom::Capture {
+                branches,
+                ..
+            } => self.build_alternation(branches, start),
         }
     }
+
+    fn build_alternation(&mut self, branches: &[Vec<AtomRepeat>], start: u32) -> u32 {
+        // `start` is a pure-epsilon split: one epsilon edge per branch, in
+        // declaration order (so earlier branches have higher priority for the
+        // leftmost-first matcher). Each branch is built from its own dedicated
+        // start state and merges into one `end`.
+        let end = self.new_state();
+        for branch in branches {
+            let branch_start = self.new_state();
+            self.edge(start, TransitionEvent::Epsilon, branch_start);
+            let branch_end = self.build_sequence(branch, branch_start);
+            self.edge(branch_end, TransitionEvent::Epsilon, end);
+        }
+        end
+    }
 }
 
 impl Nfa {
     pub fn build(from: &SimpleRegexAst) -> Self {
+        Self::build_with(from, false)
+    }
+
+    /// Like [`build`](Self::build), but with [`TransitionEvent::Save`] edges
+    /// recording where each capture group starts and ends.
+    pub fn build_captures(from: &SimpleRegexAst) -> Self {
+        Self::build_with(from, true)
+    }
+
+    fn build_with(from: &SimpleRegexAst, captures: bool) -> Self {
         let mut builder = Builder {
             transitions: Default::default(),
             next: 0,
+            captures,
         };
         // State 0 is the start; the whole pattern is one sequence fragment, whose
         // end state becomes the single accepting state.
@@ -268,6 +335,30 @@ impl Nfa {
             final_state,
         }
     }
+
+    /// The NFA with its [`TransitionEvent::Save`] edges made plain epsilons: what
+    /// [`build`](Self::build) gives for the AST [`build_captures`](Self::build_captures)
+    /// was given.
+    pub(crate) fn without_saves(&self) -> Self {
+        let transitions = self
+            .transitions
+            .iter()
+            .map(|(state, edges)| {
+                let edges = edges
+                    .iter()
+                    .map(|(event, target)| match event {
+                        TransitionEvent::Save(_) => (TransitionEvent::Epsilon, *target),
+                        event => (event.clone(), *target),
+                    })
+                    .collect();
+                (*state, edges)
+            })
+            .collect();
+        Self {
+            transitions,
+            final_state: self.final_state,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -1,10 +1,86 @@
 use super::*;
+use crate::dfa::SetOperation;
 
 /// An upper bound on how far a `{n,m}` counted repetition is unrolled. Patterns
 /// that ask for more are treated as a literal `{...}` so a stray brace cannot
 /// blow up macro-expansion time / generated code size.
 const MAX_REPEAT: usize = 1024;
 
+/// Why a pattern failed to parse, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    pub kind: RegexErrorKind,
+    /// The byte offset in the pattern of the construct at fault: the `(` of an unclosed
+    /// group, the `\` of a bad escape, the letter of an unknown flag.
+    pub offset: usize,
+    /// What is wrong, quoting the offending text where that helps.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegexErrorKind {
+    /// A `(` with no matching `)`.
+    UnclosedGroup,
+    /// A `)` with no open group.
+    UnopenedGroup,
+    /// A `[` with no matching `]`.
+    UnclosedClass,
+    /// A class range with a class as one of its bounds (`[a-\d]`).
+    InvalidClassRange,
+    /// A malformed `\x`/`\u`/`\U` escape, or one naming no char (or, in a byte class, no byte).
+    InvalidEscape,
+    /// A `\p`/`\P` class naming no Unicode property the tables know.
+    UnknownProperty,
+    /// A lookahead or lookbehind group, which a DFA can't express.
+    UnsupportedLookaround,
+    /// A letter in a `(?flags)` group that isn't a flag.
+    UnknownFlag,
+    /// A capture group name that isn't a letter or `_` followed by word chars.
+    InvalidGroupName,
+    /// A second capture group with a name already taken.
+    DuplicateGroupName,
+    /// Something a bytes-mode pattern can't match a byte at a time: a `\p` class or
+    /// non-ASCII char in a byte class, or a Unicode `\b`.
+    UnsupportedInBytes,
+    /// An operand of `(?&...)`, `(?-...)` or `~(...)` that isn't a plain set of
+    /// strings: missing, or holding a capture group or a zero-width assertion.
+    InvalidOperand,
+}
+
+impl RegexError {
+    fn new(kind: RegexErrorKind, offset: usize, message: impl Into<String>) -> RegexError {
+        RegexError {
+            kind,
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for RegexError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "regex parse error at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for RegexError {}
+
+/// How to read a pattern, for what its own syntax can't say: see [`SimpleRegexAst::parse_with`].
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct ParseOptions {
+    /// Parse in bytes mode, as [`SimpleRegexAst::parse_bytes`] does.
+    pub bytes: bool,
+    /// Read `A(?&B)` as an intersection, `A(?-B)` as a difference and `~(A)` as a
+    /// complement. Off by default, since the `regex` crate reads `~(` as a literal `~`
+    /// before a group and every `(?-` as clearing flags.
+    pub set_operators: bool,
+}
+
+/// The byte offset in `pattern` that `iter`, a cursor over it, has reached.
+fn offset(pattern: &str, iter: &std::str::Chars) -> usize {
+    pattern.len() - iter.as_str().len()
+}
+
 /// The inline mode flags (`(?imsxU)`) currently in effect while parsing. Flags
 /// are scoped: a bare `(?flags)` directive mutates the flags for the rest of the
 /// enclosing group, while a `(?flags:...)` group applies them only to its body.
@@ -39,6 +115,15 @@ struct Flags {
     /// so under `(?m)` the `^`/`$` anchors treat them as one boundary (no split
     /// inside `\r\n`) and `.` excludes `\r` as well as `\n`. Defaults off (`\n`-only).
     crlf: bool,
+    /// Bytes mode ([`SimpleRegexAst::parse_bytes`]): the pattern matches a `[u8]`, one
+    /// byte per char of the same value (`U+0000..=U+00FF`). Not a flag letter; it is
+    /// fixed for the whole pattern. Literals match their UTF-8 encoding; with `u` off a
+    /// class, `.` or shorthand matches single bytes (and `\xNN` is a raw byte), while
+    /// with `u` on it matches the UTF-8 encoding of a codepoint in the set.
+    bytes: bool,
+    /// Whether the set operators are read ([`ParseOptions::set_operators`]). Like
+    /// `bytes`, fixed for the whole pattern.
+    set_operators: bool,
 }
 
 /// Applies one flag letter to `flags`, setting it when `negate` is false and
@@ -256,7 +341,12 @@ fn extract_bindable(atoms: &mut Vec<AtomRepeat>) -> Option<Atom> {
             }
             Some(Atom::Literal(c.to_string()))
         }
-        Atom::Group(..) | Atom::Alternation(..) => Some(atoms.pop().unwrap().atom),
+        Atom::Group(..)
+        | Atom::Alternation(..)
+        | Atom::Capture {
+            ..
+        }
+        | Atom::Language(_) => Some(atoms.pop().unwrap().atom),
         Atom::EndOfInput
         | Atom::StartOfText
         | Atom::WordBoundary {
@@ -342,18 +432,23 @@ fn parse_boundary_directive(iter: &mut std::str::Chars) -> Option<WordBoundaryKi
     Some(kind)
 }
 
-fn parse_group(iter: &mut impl Iterator<Item = char>, flags: Flags) -> Option<Atom> {
+/// Parses a `[...]` class body; `iter` is just past the `[`, at byte offset `open + 1`
+/// of `pattern`.
+fn parse_group(pattern: &str, iter: &mut std::str::Chars, open: usize, flags: Flags) -> Result<Atom, RegexError> {
     let mut group_entries = vec![];
     let mut escaped = false;
+    let mut escape_at = open;
     let mut in_range = false;
     let mut inverted = false;
     let mut first = true;
     loop {
+        let at = offset(pattern, iter);
         match iter.next() {
-            None => return None,
+            None => return Err(RegexError::new(RegexErrorKind::UnclosedClass, open, "unclosed character class")),
             Some(']') if !escaped => break,
             Some('\\') if !escaped => {
                 escaped = !escaped;
+                escape_at = at;
             }
             Some('-') if !escaped && matches!(group_entries.last(), Some(GroupEntry::Char(_))) => {
                 group_entries.push(GroupEntry::Char('-'));
@@ -370,9 +465,12 @@ fn parse_group(iter: &mut impl Iterator<Item = char>, flags: Flags) -> Option<At
                         // A property class can't be a range bound; otherwise union its
                         // (always-positive) ranges straight into the class.
                         if in_range {
-                            return None;
+                            return Err(RegexError::new(RegexErrorKind::InvalidClassRange, escape_at, "a class can't be a range bound"));
+                        }
+                        if byte_classes(flags) {
+                            return Err(RegexError::new(RegexErrorKind::UnsupportedInBytes, escape_at, "Unicode property classes need `(?u)` in bytes mode"));
                         }
-                        group_entries.extend(parse_property(c, iter)?);
+                        group_entries.extend(parse_property(c, iter).ok_or_else(|| unknown_property(pattern, iter, escape_at))?);
                         first = false;
                         continue;
                     }
@@ -380,7 +478,7 @@ fn parse_group(iter: &mut impl Iterator<Item = char>, flags: Flags) -> Option<At
                         // A shorthand can't be a range bound; a negated shorthand
                         // (`[\D]`, `[a\W]`) is unioned in as its materialised complement.
                         if in_range {
-                            return None;
+                            return Err(RegexError::new(RegexErrorKind::InvalidClassRange, escape_at, "a class can't be a range bound"));
                         }
                         if inverted {
                             group_entries.extend(crate::unicode::negate(&entries));
@@ -391,14 +489,24 @@ fn parse_group(iter: &mut impl Iterator<Item = char>, flags: Flags) -> Option<At
                         continue;
                     }
                     match c {
-                        'x' | 'u' | 'U' => parse_hex_escape(c, iter)?,
+                        'x' | 'u' | 'U' => {
+                            let escape = parse_hex_escape(c, iter).ok_or_else(|| invalid_escape(pattern, iter, escape_at))?;
+                            if byte_classes(flags) && u32::from(escape) > 0xFF {
+                                let text = &pattern[escape_at..offset(pattern, iter)];
+                                return Err(RegexError::new(RegexErrorKind::InvalidEscape, escape_at, format!("`{text}` is not a byte")));
+                            }
+                            escape
+                        }
                         _ => escape_char(c),
                     }
+                } else if byte_classes(flags) && !c.is_ascii() {
+                    // A byte class can't hold a multi-byte char; spell a byte as `\xNN`.
+                    return Err(RegexError::new(RegexErrorKind::UnsupportedInBytes, at, format!("`{c}` is not a byte; spell a byte as `\\xNN`")));
                 } else {
                     c
                 };
                 if in_range {
-                    let start = pop_range_start(&mut group_entries)?;
+                    let start = pop_range_start(&mut group_entries).ok_or_else(|| invalid_range(at))?;
                     in_range = false;
                     group_entries.push(GroupEntry::Range(start, effective))
                 } else {
@@ -410,7 +518,7 @@ fn parse_group(iter: &mut impl Iterator<Item = char>, flags: Flags) -> Option<At
     }
     if escaped {
         if in_range {
-            let start = pop_range_start(&mut group_entries)?;
+            let start = pop_range_start(&mut group_entries).ok_or_else(|| invalid_range(escape_at))?;
             group_entries.push(GroupEntry::Range(start, '\\'))
         } else {
             group_entries.push(GroupEntry::Char('\\'));
@@ -433,7 +541,64 @@ fn parse_group(iter: &mut impl Iterator<Item = char>, flags: Flags) -> Option<At
         };
     }
 
-    Some(Atom::Group(inverted, group_entries))
+    Ok(class_atom(inverted, group_entries, flags))
+}
+
+/// The error for a `\x`/`\u`/`\U` escape at `escape_at` that [`parse_hex_escape`]
+/// rejected, quoting it up to where `iter` stopped.
+fn invalid_escape(pattern: &str, iter: &std::str::Chars, escape_at: usize) -> RegexError {
+    let text = &pattern[escape_at..offset(pattern, iter)];
+    RegexError::new(RegexErrorKind::InvalidEscape, escape_at, format!("invalid escape `{text}`"))
+}
+
+/// The error for a `\p`/`\P` class at `escape_at` that [`parse_property`] rejected.
+fn unknown_property(pattern: &str, iter: &std::str::Chars, escape_at: usize) -> RegexError {
+    let text = &pattern[escape_at..offset(pattern, iter)];
+    RegexError::new(RegexErrorKind::UnknownProperty, escape_at, format!("unknown Unicode property class `{text}`"))
+}
+
+fn invalid_range(at: usize) -> RegexError {
+    RegexError::new(RegexErrorKind::InvalidClassRange, at, "invalid class range")
+}
+
+/// Whether classes (`[...]`, `.`, the shorthands) match single bytes: bytes mode
+/// with the `u` flag off. Such a class can only name bytes, so a non-ASCII member
+/// must be a `\xNN` escape and `\p{...}` is rejected.
+fn byte_classes(flags: Flags) -> bool {
+    flags.bytes && !flags.unicode
+}
+
+/// The atom matching one char of the class `(inverted, entries)`. Outside bytes mode,
+/// and for a byte class, that is the class itself. A Unicode class in bytes mode
+/// matches the UTF-8 encoding of one of its codepoints instead: an alternation of
+/// byte-range sequences (the complement is taken over codepoints first, so `[^a]`
+/// matches a whole encoded char, never a stray byte). An all-ASCII class encodes
+/// as itself.
+fn class_atom(inverted: bool, entries: Vec<GroupEntry>, flags: Flags) -> Atom {
+    if !flags.bytes || byte_classes(flags) {
+        return Atom::Group(inverted, entries);
+    }
+    let entries = if inverted { crate::unicode::negate(&entries) } else { entries };
+    let ascii = entries.iter().all(|entry| match entry {
+        GroupEntry::Char(c) | GroupEntry::Range(_, c) => c.is_ascii(),
+    });
+    if ascii {
+        return Atom::Group(false, entries);
+    }
+    let branches = crate::unicode::utf8_sequences(&entries)
+        .into_iter()
+        .map(|sequence| {
+            sequence
+                .into_iter()
+                .map(|(lo, hi)| AtomRepeat {
+                    atom: Atom::Group(false, vec![GroupEntry::Range(char::from(lo), char::from(hi))]),
+                    repeat: Repeat::Once,
+                    lazy: false,
+                })
+                .collect()
+        })
+        .collect();
+    Atom::Alternation(branches)
 }
 
 /// Appends `c` as a literal char, coalescing it onto a trailing unquantified
@@ -473,132 +638,253 @@ fn push_char(atoms: &mut Vec<AtomRepeat>, c: char, flags: Flags) {
         // char stays a coalescible literal.
         if entries.len() > 1 {
             atoms.push(AtomRepeat {
-                atom: Atom::Group(false, entries),
+                atom: class_atom(false, entries, flags),
                 repeat: Repeat::Once,
                 lazy: false,
             });
             return;
         }
     }
+    if flags.bytes && !c.is_ascii() {
+        // In bytes mode a non-ASCII char matches its UTF-8 encoding. The bytes go in as
+        // one unit (a single-branch alternation, not a literal run), so a trailing
+        // quantifier repeats the whole char rather than its last byte.
+        let mut buf = [0; 4];
+        let encoded = c.encode_utf8(&mut buf).bytes().map(char::from).collect();
+        atoms.push(AtomRepeat {
+            atom: Atom::Alternation(vec![vec![AtomRepeat {
+                atom: Atom::Literal(encoded),
+                repeat: Repeat::Once,
+                lazy: false,
+            }]]),
+            repeat: Repeat::Once,
+            lazy: false,
+        });
+        return;
+    }
     push_lit(atoms, c);
 }
 
 /// What a `(...)` prefix turned out to be once its leading `(?...)` modifier (if
 /// any) was consumed.
 enum GroupPrefix {
-    /// A real sub-expression to recurse into, with these flags active in its body.
+    /// A non-capturing sub-expression to recurse into, with these flags active in its body.
     Group(Flags),
+    /// A capturing group, with its name if it has one and the flags active in its body.
+    Capture(Flags, Option<String>),
     /// A bare `(?flags)` directive — no sub-expression; the returned flags become
     /// the current flags for the rest of the enclosing group.
     SetFlags(Flags),
+    /// The right operand of an intersection `(?&...)` or difference `(?-...)`, whose
+    /// left operand is what precedes it in the branch.
+    Operation(Flags, SetOperation),
 }
 
 /// Consumes the group-modifier prefix immediately after a `(`, if any, leaving
-/// `iter` positioned at the start of the group body. `current` is the flag set in
-/// effect at the `(`. Returns `None` for an unsupported `(?...)` construct
-/// (lookaround, an unsupported flag) so the whole pattern is rejected rather than
-/// silently mis-parsed.
+/// `iter` positioned at the start of the group body. `open` is the `(`'s byte offset
+/// in `pattern` and `current` the flag set in effect there. Returns an error for an
+/// unsupported `(?...)` construct (lookaround, an unsupported flag) so the whole
+/// pattern is rejected rather than silently mis-parsed.
 ///
-/// Capturing `(...)`, non-capturing `(?:...)` and named `(?P<name>...)` /
-/// `(?<name>...)` groups are all treated identically — this engine never extracts
-/// capture spans, so the name and capturing-ness are simply skipped. Inline flags
+/// Capturing `(...)` and named `(?P<name>...)` / `(?<name>...)` groups become
+/// [`GroupPrefix::Capture`]; non-capturing `(?:...)` is a plain group. Inline flags
 /// come in two shapes: a bare `(?flags)` directive and a scoped `(?flags:...)`
-/// group; both update the flags relative to `current`. See [`Flags`].
-fn consume_group_prefix(iter: &mut std::str::Chars, current: Flags) -> Option<GroupPrefix> {
+/// group; both update the flags relative to `current`. See [`Flags`]. With the set
+/// operators on, `(?&` and a `(?-` not followed by flags open the right operand of a
+/// [`GroupPrefix::Operation`].
+fn consume_group_prefix(pattern: &str, iter: &mut std::str::Chars, open: usize, current: Flags) -> Result<GroupPrefix, RegexError> {
+    let unclosed = || RegexError::new(RegexErrorKind::UnclosedGroup, open, "unclosed group");
+    let lookaround = || RegexError::new(RegexErrorKind::UnsupportedLookaround, open, "lookaround is not supported");
     if iter.clone().next() != Some('?') {
         // A plain capturing group inherits the surrounding flags.
-        return Some(GroupPrefix::Group(current));
+        return Ok(GroupPrefix::Capture(current, None));
     }
     iter.next(); // the '?'
-    match iter.clone().next()? {
+    match iter.clone().next().ok_or_else(unclosed)? {
         // Non-capturing group: nothing more to skip.
         ':' => {
             iter.next();
-            Some(GroupPrefix::Group(current))
+            Ok(GroupPrefix::Group(current))
         }
         // Python-style named group `(?P<name>...)`.
         'P' => {
+            let at = offset(pattern, iter);
             iter.next();
-            if iter.next()? != '<' {
-                return None;
+            if iter.next().ok_or_else(unclosed)? != '<' {
+                return Err(RegexError::new(RegexErrorKind::UnknownFlag, at, "unknown flag `P`"));
             }
-            skip_until_gt(iter)?;
-            Some(GroupPrefix::Group(current))
+            Ok(GroupPrefix::Capture(current, Some(parse_group_name(pattern, iter, open)?)))
         }
         // `(?<name>...)`; reject lookbehind `(?<=...)` / `(?<!...)` (unsupported).
         '<' => {
             iter.next();
             match iter.clone().next() {
-                Some('=') | Some('!') => None,
-                _ => {
-                    skip_until_gt(iter)?;
-                    Some(GroupPrefix::Group(current))
-                }
+                Some('=') | Some('!') => Err(lookaround()),
+                _ => Ok(GroupPrefix::Capture(current, Some(parse_group_name(pattern, iter, open)?))),
             }
         }
         // Lookahead `(?=...)` / `(?!...)` is unsupported.
-        '=' | '!' => None,
+        '=' | '!' => Err(lookaround()),
+        '&' if current.set_operators => {
+            iter.next();
+            Ok(GroupPrefix::Operation(current, SetOperation::Intersection))
+        }
+        // `(?-` starts a difference unless what follows is only flags to clear, as in
+        // `(?-i)` or `(?-s:...)`.
+        '-' if current.set_operators && !is_flag_spec(iter.clone()) => {
+            iter.next();
+            Ok(GroupPrefix::Operation(current, SetOperation::Difference))
+        }
         // An inline-flag spec: `i m s x U u`, optionally with a `-` to start
         // clearing, terminated by `)` (a bare directive) or `:` (a scoped group).
         _ => {
             let mut flags = current;
             let mut negate = false;
             loop {
-                match iter.next()? {
-                    ')' => return Some(GroupPrefix::SetFlags(flags)),
-                    ':' => return Some(GroupPrefix::Group(flags)),
+                let at = offset(pattern, iter);
+                match iter.next().ok_or_else(unclosed)? {
+                    ')' => return Ok(GroupPrefix::SetFlags(flags)),
+                    ':' => return Ok(GroupPrefix::Group(flags)),
                     '-' => negate = true,
-                    c => apply_flag(&mut flags, c, negate)?,
+                    c => apply_flag(&mut flags, c, negate).ok_or_else(|| RegexError::new(RegexErrorKind::UnknownFlag, at, format!("unknown flag `{c}`")))?,
                 }
             }
         }
     }
 }
 
-/// Consumes characters up to and including the closing `>` of a group name.
-fn skip_until_gt(iter: &mut std::str::Chars) -> Option<()> {
+/// Whether `rest`, what follows a `(?`, is a run of flag letters and `-` ending the
+/// group (`)`) or starting its body (`:`), rather than an unclosed group.
+fn is_flag_spec(rest: std::str::Chars) -> bool {
+    let mut flags = Flags::default();
+    for c in rest {
+        match c {
+            ')' | ':' => return true,
+            '-' => {}
+            c => {
+                if apply_flag(&mut flags, c, true).is_none() {
+                    return false;
+                }
+            }
+        }
+    }
+    false
+}
+
+/// Consumes a group name up to and including its closing `>`. Like the `regex`
+/// crate, a name is a letter or `_` followed by letters, digits, `_`, `.`, `[` or `]`.
+fn parse_group_name(pattern: &str, iter: &mut std::str::Chars, open: usize) -> Result<String, RegexError> {
+    let start = offset(pattern, iter);
+    let mut name = String::new();
     loop {
-        if iter.next()? == '>' {
-            return Some(());
+        match iter.next() {
+            Some('>') if !name.is_empty() => return Ok(name),
+            Some(c) if c.is_alphabetic() || c == '_' => name.push(c),
+            Some(c) if !name.is_empty() && (c.is_alphanumeric() || matches!(c, '.' | '[' | ']')) => name.push(c),
+            Some(_) => return Err(RegexError::new(RegexErrorKind::InvalidGroupName, start, "invalid capture group name")),
+            None => return Err(RegexError::new(RegexErrorKind::UnclosedGroup, open, "unclosed group")),
         }
     }
 }
 
-/// Parses one alternation: a run of `|`-separated branches. When `in_group` it
-/// stops at (and consumes) the matching `)`; otherwise it runs to end of input.
-/// Returns `None` on a malformed pattern (unclosed `(`, stray `)`, bad escape).
-fn parse_branches(iter: &mut std::str::Chars, in_group: bool, mut flags: Flags) -> Option<Vec<Vec<AtomRepeat>>> {
+/// Parses one alternation: a run of `|`-separated branches. Inside a group (`open`
+/// is the byte offset of its `(` in `pattern`) it stops at (and consumes) the
+/// matching `)`; otherwise it runs to end of input. `groups` holds the names of the
+/// capture groups opened so far (group 0 first), and assigns the next index. Returns
+/// an error on a malformed pattern (unclosed `(`, stray `)`, bad escape, duplicate
+/// group name).
+fn parse_branches(
+    pattern: &str,
+    iter: &mut std::str::Chars,
+    open: Option<usize>,
+    mut flags: Flags,
+    groups: &mut Vec<Option<String>>,
+) -> Result<Vec<Vec<AtomRepeat>>, RegexError> {
     let mut branches: Vec<Vec<AtomRepeat>> = vec![];
     let mut atoms: Vec<AtomRepeat> = vec![];
     let mut escaped = false;
-    while let Some(next) = iter.next() {
+    let mut escape_at = 0;
+    loop {
+        let at = offset(pattern, iter);
+        let Some(next) = iter.next() else {
+            break;
+        };
         match next {
             '\\' if !escaped => {
                 escaped = true;
+                escape_at = at;
             }
-            '(' if !escaped => match consume_group_prefix(iter, flags)? {
+            '(' if !escaped => match consume_group_prefix(pattern, iter, at, flags)? {
                 // A bare `(?flags)` directive: apply to the rest of this group.
                 GroupPrefix::SetFlags(updated) => flags = updated,
                 GroupPrefix::Group(inner) => atoms.push(AtomRepeat {
-                    atom: Atom::Alternation(parse_branches(iter, true, inner)?),
+                    atom: Atom::Alternation(parse_branches(pattern, iter, Some(at), inner, groups)?),
                     repeat: Repeat::Once,
                     lazy: false,
                 }),
+                GroupPrefix::Capture(inner, name) => {
+                    if let Some(name) = name.as_ref().filter(|&name| groups.iter().flatten().any(|taken| taken == name)) {
+                        return Err(RegexError::new(RegexErrorKind::DuplicateGroupName, at, format!("duplicate capture group name `{name}`")));
+                    }
+                    let index = groups.len() as u32;
+                    groups.push(name.clone());
+                    atoms.push(AtomRepeat {
+                        atom: Atom::Capture {
+                            index,
+                            name,
+                            branches: parse_branches(pattern, iter, Some(at), inner, groups)?,
+                        },
+                        repeat: Repeat::Once,
+                        lazy: false,
+                    });
+                }
+                // The operator applies to the whole branch so far: `[a-z]+(?-if|else)`.
+                GroupPrefix::Operation(inner, operation) => {
+                    if atoms.is_empty() {
+                        let name = match operation {
+                            SetOperation::Intersection => "an intersection",
+                            SetOperation::Difference => "a difference",
+                        };
+                        return Err(RegexError::new(RegexErrorKind::InvalidOperand, at, format!("{name} needs a pattern before it")));
+                    }
+                    let left = language(&std::mem::take(&mut atoms), at)?;
+                    let right = parse_branches(pattern, iter, Some(at), inner, groups)?;
+                    atoms.push(AtomRepeat {
+                        atom: Atom::Language(Box::new(left.product(&language(&alternation(right), at)?, operation))),
+                        repeat: Repeat::Once,
+                        lazy: false,
+                    });
+                }
             },
+            // `~(...)`: the strings the group doesn't match. A `~` before anything else,
+            // or without the set operators, is a literal.
+            '~' if !escaped && flags.set_operators && iter.clone().next() == Some('(') => {
+                let open = offset(pattern, iter);
+                iter.next();
+                let (GroupPrefix::Group(inner) | GroupPrefix::Capture(inner, None)) = consume_group_prefix(pattern, iter, open, flags)? else {
+                    return Err(RegexError::new(RegexErrorKind::InvalidOperand, at, "`~` takes a plain or non-capturing group"));
+                };
+                let operand = parse_branches(pattern, iter, Some(open), inner, groups)?;
+                atoms.push(AtomRepeat {
+                    atom: Atom::Language(Box::new(language(&alternation(operand), at)?.complement())),
+                    repeat: Repeat::Once,
+                    lazy: false,
+                });
+            }
             ')' if !escaped => {
-                if !in_group {
+                if open.is_none() {
                     // A `)` with no open group is malformed (matching the `regex` crate).
-                    return None;
+                    return Err(RegexError::new(RegexErrorKind::UnopenedGroup, at, "unopened group"));
                 }
                 branches.push(atoms);
-                return Some(branches);
+                return Ok(branches);
             }
             '|' if !escaped => {
                 branches.push(std::mem::take(&mut atoms));
             }
             '[' if !escaped => {
                 atoms.push(AtomRepeat {
-                    atom: parse_group(iter, flags)?,
+                    atom: parse_group(pattern, iter, at, flags)?,
                     repeat: Repeat::Once,
                     lazy: false,
                 });
@@ -705,11 +991,11 @@ fn parse_branches(iter: &mut std::str::Chars, in_group: bool, mut flags: Flags)
                 // crate; under `(?s)` (dot-all) an inverted *empty* class matches
                 // everything, and under `(?R)` (CRLF) the excluded set adds `\r`.
                 atom: if flags.dot_matches_newline {
-                    Atom::Group(true, vec![])
+                    class_atom(true, vec![], flags)
                 } else if flags.crlf {
-                    Atom::Group(true, vec![GroupEntry::Char('\n'), GroupEntry::Char('\r')])
+                    class_atom(true, vec![GroupEntry::Char('\n'), GroupEntry::Char('\r')], flags)
                 } else {
-                    Atom::Group(true, vec![GroupEntry::Char('\n')])
+                    class_atom(true, vec![GroupEntry::Char('\n')], flags)
                 },
                 repeat: Repeat::Once,
                 lazy: false,
@@ -729,7 +1015,10 @@ fn parse_branches(iter: &mut std::str::Chars, in_group: bool, mut flags: Flags)
                 if escaped {
                     escaped = false;
                     if c == 'p' || c == 'P' {
-                        let mut entries = parse_property(c, iter)?;
+                        if byte_classes(flags) {
+                            return Err(RegexError::new(RegexErrorKind::UnsupportedInBytes, escape_at, "Unicode property classes need `(?u)` in bytes mode"));
+                        }
+                        let mut entries = parse_property(c, iter).ok_or_else(|| unknown_property(pattern, iter, escape_at))?;
                         if flags.case_insensitive && flags.unicode {
                             entries = crate::unicode::case_fold(&entries);
                         }
@@ -737,16 +1026,20 @@ fn parse_branches(iter: &mut std::str::Chars, in_group: bool, mut flags: Flags)
                             // Property classes resolve to a positive range set (the
                             // complement is materialised for `\P`), so the group is
                             // never inverted.
-                            atom: Atom::Group(false, entries),
+                            atom: class_atom(false, entries, flags),
                             repeat: Repeat::Once,
                             lazy: false,
                         });
                     } else if let Some((inverted, entries)) = shorthand_class(c, flags.unicode) {
                         atoms.push(AtomRepeat {
-                            atom: Atom::Group(inverted, entries),
+                            atom: class_atom(inverted, entries, flags),
                             repeat: Repeat::Once,
                             lazy: false,
                         });
+                    } else if flags.bytes && flags.unicode && matches!(c, 'b' | 'B') {
+                        // A Unicode word boundary would have to decode the chars either
+                        // side of it, and the matcher only sees single bytes.
+                        return Err(RegexError::new(RegexErrorKind::UnsupportedInBytes, escape_at, "a Unicode word boundary can't be matched in bytes mode"));
                     } else {
                         match c {
                             // `\A` (start-of-text) / `\z` (end-of-text) are the explicit,
@@ -782,7 +1075,19 @@ fn parse_branches(iter: &mut std::str::Chars, in_group: bool, mut flags: Flags)
                                 repeat: Repeat::Once,
                                 lazy: false,
                             }),
-                            'x' | 'u' | 'U' => push_char(&mut atoms, parse_hex_escape(c, iter)?, flags),
+                            'x' | 'u' | 'U' => {
+                                let c = parse_hex_escape(c, iter).ok_or_else(|| invalid_escape(pattern, iter, escape_at))?;
+                                if byte_classes(flags) && !c.is_ascii() {
+                                    // With `u` off in bytes mode, `\xNN` is a raw byte.
+                                    if u32::from(c) > 0xFF {
+                                        let text = &pattern[escape_at..offset(pattern, iter)];
+                                        return Err(RegexError::new(RegexErrorKind::InvalidEscape, escape_at, format!("`{text}` is not a byte")));
+                                    }
+                                    push_lit(&mut atoms, c);
+                                } else {
+                                    push_char(&mut atoms, c, flags);
+                                }
+                            }
                             _ => push_char(&mut atoms, escape_char(c), flags),
                         }
                     }
@@ -792,21 +1097,90 @@ fn parse_branches(iter: &mut std::str::Chars, in_group: bool, mut flags: Flags)
             }
         }
     }
-    if in_group {
+    if let Some(open) = open {
         // Reached end of input without a closing `)`.
-        return None;
+        return Err(RegexError::new(RegexErrorKind::UnclosedGroup, open, "unclosed group"));
     }
     if escaped {
         push_lit(&mut atoms, '\\');
     }
     branches.push(atoms);
-    Some(branches)
+    Ok(branches)
+}
+
+/// The atoms of a group of `branches`.
+fn alternation(branches: Vec<Vec<AtomRepeat>>) -> Vec<AtomRepeat> {
+    vec![AtomRepeat {
+        atom: Atom::Alternation(branches),
+        repeat: Repeat::Once,
+        lazy: false,
+    }]
+}
+
+/// The every-match DFA of `atoms`, an operand of the set operator at byte `at`. The
+/// operators work on sets of strings, so an operand can't capture, nor assert
+/// anything about the text around it.
+fn language(atoms: &[AtomRepeat], at: usize) -> Result<Dfa, RegexError> {
+    fn captures(atoms: &[AtomRepeat]) -> bool {
+        atoms.iter().any(|atom| match &atom.atom {
+            Atom::Capture {
+                ..
+            } => true,
+            Atom::Alternation(branches) => branches.iter().any(|branch| captures(branch)),
+            _ => false,
+        })
+    }
+    if captures(atoms) {
+        return Err(RegexError::new(
+            RegexErrorKind::InvalidOperand,
+            at,
+            "an operand of a set operator can't hold a capture group; use `(?:...)`",
+        ));
+    }
+    let dfa = Dfa::build_all(&Nfa::build(&SimpleRegexAst {
+        atoms: atoms.to_vec(),
+    }));
+    if dfa.has_zero_width() {
+        return Err(RegexError::new(RegexErrorKind::InvalidOperand, at, "an operand of a set operator can't hold an assertion"));
+    }
+    Ok(dfa)
 }
 
 impl SimpleRegexAst {
-    pub fn parse(from: &str) -> Option<SimpleRegexAst> {
+    pub fn parse(from: &str) -> Result<SimpleRegexAst, RegexError> {
+        Self::parse_flags(from, Flags::default())
+    }
+
+    /// Parses `from` with `options` set. [`SimpleRegexAst::parse`] and
+    /// [`SimpleRegexAst::parse_bytes`] are this with every option off but `bytes`.
+    pub fn parse_with(from: &str, options: ParseOptions) -> Result<SimpleRegexAst, RegexError> {
+        Self::parse_flags(
+            from,
+            Flags {
+                bytes: options.bytes,
+                set_operators: options.set_operators,
+                ..Flags::default()
+            },
+        )
+    }
+
+    /// Parses `from` in bytes mode, for matching a `[u8]` one byte at a time (see
+    /// [`Regex::parse_bytes`]). Under the default ASCII mode every class, `.` and
+    /// shorthand matches single bytes, as under the `regex` crate's `(?-u)`; `(?u)`
+    /// makes them match UTF-8 encoded codepoints. A Unicode `\b`/`\B` is rejected.
+    pub fn parse_bytes(from: &str) -> Result<SimpleRegexAst, RegexError> {
+        Self::parse_flags(
+            from,
+            Flags {
+                bytes: true,
+                ..Flags::default()
+            },
+        )
+    }
+
+    fn parse_flags(from: &str, flags: Flags) -> Result<SimpleRegexAst, RegexError> {
         let mut iter = from.chars();
-        let branches = parse_branches(&mut iter, false, Flags::default())?;
+        let branches = parse_branches(from, &mut iter, None, flags, &mut vec![None])?;
         // A single branch stays a flat atom sequence (no wrapper); multiple
         // top-level branches become one alternation atom.
         let atoms = if branches.len() == 1 {
@@ -818,7 +1192,7 @@ impl SimpleRegexAst {
                 lazy: false,
             }]
         };
-        Some(SimpleRegexAst {
+        Ok(SimpleRegexAst {
             atoms,
         })
     }
@@ -1170,10 +1544,10 @@ mod tests {
 
     #[test]
     fn malformed_hex_escape_is_rejected() {
-        assert!(SimpleRegexAst::parse("\\x4").is_none()); // too few digits
-        assert!(SimpleRegexAst::parse("\\xZZ").is_none()); // non-hex
-        assert!(SimpleRegexAst::parse("\\x{}").is_none()); // empty braces
-        assert!(SimpleRegexAst::parse("\\u{110000}").is_none()); // not a scalar value
+        assert!(SimpleRegexAst::parse("\\x4").is_err()); // too few digits
+        assert!(SimpleRegexAst::parse("\\xZZ").is_err()); // non-hex
+        assert!(SimpleRegexAst::parse("\\x{}").is_err()); // empty braces
+        assert!(SimpleRegexAst::parse("\\u{110000}").is_err()); // not a scalar value
     }
 
     #[test]
@@ -1281,19 +1655,36 @@ mod tests {
 
     #[test]
     fn unclosed_group_is_rejected() {
-        assert!(SimpleRegexAst::parse("[abc").is_none());
-        assert!(SimpleRegexAst::parse("[a-").is_none());
+        assert!(SimpleRegexAst::parse("[abc").is_err());
+        assert!(SimpleRegexAst::parse("[a-").is_err());
     }
 
-    /// Helper: assert an atom is an alternation and return its branches.
+    /// Helper: assert an atom is an alternation or capture group and return its branches.
     #[track_caller]
     fn branches(atom: &AtomRepeat) -> &[Vec<AtomRepeat>] {
         match &atom.atom {
-            Atom::Alternation(branches) => branches,
+            Atom::Alternation(branches)
+            | Atom::Capture {
+                branches,
+                ..
+            } => branches,
             other => panic!("expected alternation, got {other:?}"),
         }
     }
 
+    /// Helper: assert an atom is a capture group and return its index and name.
+    #[track_caller]
+    fn capture(atom: &AtomRepeat) -> (u32, Option<&str>) {
+        match &atom.atom {
+            Atom::Capture {
+                index,
+                name,
+                ..
+            } => (*index, name.as_deref()),
+            other => panic!("expected capture group, got {other:?}"),
+        }
+    }
+
     #[test]
     fn top_level_alternation_wraps_in_single_atom() {
         // `a|bc` -> one alternation atom with branches ["a"], ["bc"].
@@ -1315,7 +1706,7 @@ mod tests {
 
     #[test]
     fn group_is_alternation_atom() {
-        // `(ab)` -> a single-branch alternation atom holding "ab".
+        // `(ab)` -> a single-branch capture group holding "ab".
         let a = atoms("(ab)");
         assert_eq!(a.len(), 1);
         let b = branches(&a[0]);
@@ -1357,8 +1748,7 @@ mod tests {
     }
 
     #[test]
-    fn group_modifiers_are_skipped() {
-        // Non-capturing and named groups parse to a plain alternation.
+    fn group_modifiers_parse_the_same_body() {
         for pattern in ["(?:a|b)", "(?P<n>a|b)", "(?<n>a|b)"] {
             let a = atoms(pattern);
             let b = branches(&a[0]);
@@ -1366,17 +1756,90 @@ mod tests {
             assert_lit(&b[0][0], "a");
             assert_lit(&b[1][0], "b");
         }
+        assert!(matches!(atoms("(?:a)")[0].atom, Atom::Alternation(_)));
+    }
+
+    #[test]
+    fn capture_groups_are_numbered_by_open_paren() {
+        // `(a(?P<inner>b)(?:c)(d))`: the outer group is 1, `inner` 2, the last 3.
+        let a = atoms("(a(?P<inner>b)(?:c)(d))");
+        assert_eq!(capture(&a[0]), (1, None));
+        let outer = &branches(&a[0])[0];
+        assert_eq!(capture(&outer[1]), (2, Some("inner")));
+        assert_eq!(capture(&outer[3]), (3, None));
+    }
+
+    #[test]
+    fn bad_group_names_are_rejected() {
+        assert!(SimpleRegexAst::parse("(?P<a_1.x[0]>a)").is_ok());
+        assert!(SimpleRegexAst::parse("(?P<>a)").is_err());
+        assert!(SimpleRegexAst::parse("(?P<1a>a)").is_err());
+        assert!(SimpleRegexAst::parse("(?<a-b>a)").is_err());
+        assert!(SimpleRegexAst::parse("(?P<a>x)(?<a>y)").is_err(), "duplicate name");
     }
 
     #[test]
     fn malformed_group_syntax_is_rejected() {
-        assert!(SimpleRegexAst::parse("(abc").is_none()); // unclosed
-        assert!(SimpleRegexAst::parse("abc)").is_none()); // stray close
-        assert!(SimpleRegexAst::parse("(?=x)").is_none()); // lookahead
-        assert!(SimpleRegexAst::parse("(?<=x)").is_none()); // lookbehind
-        assert!(SimpleRegexAst::parse("(?R)x").is_some()); // CRLF: now supported
-        assert!(SimpleRegexAst::parse("(?Q)x").is_none()); // unknown flag
-        assert!(SimpleRegexAst::parse("(?ix").is_none()); // unterminated flag spec
+        assert!(SimpleRegexAst::parse("(abc").is_err()); // unclosed
+        assert!(SimpleRegexAst::parse("abc)").is_err()); // stray close
+        assert!(SimpleRegexAst::parse("(?=x)").is_err()); // lookahead
+        assert!(SimpleRegexAst::parse("(?<=x)").is_err()); // lookbehind
+        assert!(SimpleRegexAst::parse("(?R)x").is_ok()); // CRLF: now supported
+        assert!(SimpleRegexAst::parse("(?Q)x").is_err()); // unknown flag
+        assert!(SimpleRegexAst::parse("(?ix").is_err()); // unterminated flag spec
+    }
+
+    #[test]
+    fn errors_point_at_the_construct_at_fault() {
+        let error = |pattern: &str| {
+            let error = SimpleRegexAst::parse(pattern).err().expect("invalid pattern");
+            (error.kind, error.offset)
+        };
+        assert_eq!(error("a(b(c)"), (RegexErrorKind::UnclosedGroup, 1));
+        assert_eq!(error("ab)"), (RegexErrorKind::UnopenedGroup, 2));
+        assert_eq!(error("x[ab"), (RegexErrorKind::UnclosedClass, 1));
+        assert_eq!(error(r"[a-\d]"), (RegexErrorKind::InvalidClassRange, 3));
+        assert_eq!(error(r"é\xZZ"), (RegexErrorKind::InvalidEscape, 2));
+        assert_eq!(error(r"a\p{Nope}"), (RegexErrorKind::UnknownProperty, 1));
+        assert_eq!(error("a(?=b)"), (RegexErrorKind::UnsupportedLookaround, 1));
+        assert_eq!(error("(?im-Q)"), (RegexErrorKind::UnknownFlag, 5));
+        assert_eq!(error("(?P<1a>a)"), (RegexErrorKind::InvalidGroupName, 4));
+        assert_eq!(error("(?P<a>x)(?<a>y)"), (RegexErrorKind::DuplicateGroupName, 8));
+        let set_error = |pattern: &str| {
+            let options = ParseOptions {
+                set_operators: true,
+                ..ParseOptions::default()
+            };
+            let error = SimpleRegexAst::parse_with(pattern, options).err().expect("invalid pattern");
+            (error.kind, error.offset)
+        };
+        assert_eq!(set_error("(?&a)"), (RegexErrorKind::InvalidOperand, 0));
+        assert_eq!(set_error("(a)(?-b)"), (RegexErrorKind::InvalidOperand, 3));
+        assert_eq!(set_error(r"x~(a\b)"), (RegexErrorKind::InvalidOperand, 1));
+        let bytes = SimpleRegexAst::parse_bytes("ab[é]").err().expect("invalid pattern");
+        assert_eq!((bytes.kind, bytes.offset), (RegexErrorKind::UnsupportedInBytes, 3));
+
+        let unknown = SimpleRegexAst::parse(r"\p{Nope}").err().unwrap();
+        assert_eq!(unknown.message, r"unknown Unicode property class `\p{Nope}`");
+        assert_eq!(unknown.to_string(), r"regex parse error at offset 0: unknown Unicode property class `\p{Nope}`");
+    }
+
+    #[test]
+    fn set_operators_are_opt_in() {
+        // Off, `~(a)` is a literal `~` then a group, `(?-i:a)` clears a flag and `(?&` is no flag.
+        assert_lit(&atoms("~(a)")[0], "~");
+        assert!(matches!(atoms("~(a)")[1].atom, Atom::Capture { .. }));
+        assert!(matches!(atoms("(?-i:a)")[0].atom, Atom::Alternation(_)));
+        assert_eq!(SimpleRegexAst::parse("a(?&b)").err().map(|x| x.kind), Some(RegexErrorKind::UnknownFlag));
+        // On, they build languages, though `(?-i:a)` still clears a flag.
+        let options = ParseOptions {
+            set_operators: true,
+            ..ParseOptions::default()
+        };
+        let set_atoms = |pattern: &str| SimpleRegexAst::parse_with(pattern, options).unwrap().atoms;
+        assert!(matches!(set_atoms("~(a)")[0].atom, Atom::Language(_)));
+        assert!(matches!(set_atoms("[a-z]+(?-if)")[0].atom, Atom::Language(_)));
+        assert!(matches!(set_atoms("(?-i:a)")[0].atom, Atom::Alternation(_)));
     }
 
     #[test]
@@ -1463,8 +1926,8 @@ mod tests {
 
     #[test]
     fn unknown_property_is_rejected() {
-        assert!(SimpleRegexAst::parse(r"\p{NotAProperty}").is_none());
-        assert!(SimpleRegexAst::parse(r"[\p{Nope}]").is_none());
+        assert!(SimpleRegexAst::parse(r"\p{NotAProperty}").is_err());
+        assert!(SimpleRegexAst::parse(r"[\p{Nope}]").is_err());
     }
 
     #[test]
@@ -1492,4 +1955,25 @@ mod tests {
         assert_eq!(a.len(), 1);
         assert_lit(&a[0], "(a|b)");
     }
+
+    #[test]
+    fn bytes_mode_classes_match_bytes_or_encoded_chars() {
+        let bytes_atoms = |pattern: &str| SimpleRegexAst::parse_bytes(pattern).expect("valid bytes pattern").atoms;
+        // With `u` off a hex escape is a raw byte, and classes hold bytes.
+        assert_lit(&bytes_atoms(r"a\xFF")[0], "a\u{ff}");
+        assert_group(&bytes_atoms(r"[\x80-\xFF]")[0], false, &[GroupEntry::Range('\u{80}', '\u{ff}')]);
+        assert!(SimpleRegexAst::parse_bytes("[é]").is_err());
+        assert!(SimpleRegexAst::parse_bytes(r"\x{100}").is_err());
+        assert!(SimpleRegexAst::parse_bytes(r"\pL").is_err());
+        // A literal char is its UTF-8 encoding, quantified as a whole.
+        let e = bytes_atoms("é+");
+        assert!(matches!(e[0].repeat, Repeat::OnceOrMore));
+        assert_lit(&branches(&e[0])[0][0], "\u{c3}\u{a9}");
+        // With `u` on a class matches encoded codepoints: one branch per sequence.
+        assert_eq!(branches(&bytes_atoms("(?u)[aé]")[0]).len(), 2);
+        assert_group(&bytes_atoms("(?u)[a-c]")[0], false, &[GroupEntry::Range('a', 'c')]);
+        // A Unicode word boundary can't be decided a byte at a time.
+        assert!(SimpleRegexAst::parse_bytes(r"(?u)\b").is_err());
+        assert!(SimpleRegexAst::parse_bytes(r"\b").is_ok());
+    }
 }
@@ -0,0 +1,396 @@
+//! The submatch pass behind [`RegexSearch::captures`](crate::RegexSearch::captures).
+//!
+//! A DFA state is a set of NFA states, so once the DFA has found where a match
+//! starts and ends, it no longer knows which path through the pattern produced
+//! it — and that path is what says where each capture group matched. The
+//! [`PikeVm`] recovers it by re-running the match span over an NFA built with
+//! [`TransitionEvent::Save`] edges around every capture group
+//! ([`Nfa::build_captures`]): every thread advances in lock-step one char at a
+//! time, carrying its own copy of the capture slots, and a thread reaching an NFA
+//! state a higher-priority thread already holds is dropped.
+//!
+//! The VM only accepts at the end the DFA found. The leftmost-first match is the
+//! highest-priority accepting path through the pattern, so among the threads
+//! accepting there, the highest-priority one is that path and its slots are the
+//! captures.
+//!
+//! The same simulation also matches on its own ([`PikeVm::find_prefix`]). Running
+//! every thread decides each zero-width assertion on the thread that reaches it, so
+//! it handles the patterns a single-state DFA walk can't: an assertion competing
+//! with a consuming branch (`(?m)(?:^|a)*`, `.*\bx`). `Regex::find_prefix` and
+//! `Regex::generate_parser` switch to it for those patterns.
+
+use std::sync::Arc;
+
+use crate::{
+    Atom, AtomRepeat, SimpleRegexAst,
+    matching::{Haystack, zero_width_holds},
+    nfa::{Nfa, TransitionEvent},
+};
+
+/// For each slot, the byte offset it was saved at; slot `2i` is where group `i`
+/// starts and `2i + 1` where it ends. `None` for a group that did not participate.
+pub(crate) type Slots = Vec<Option<usize>>;
+
+/// A compiled capture-group program: the capture-annotated NFA of one pattern and
+/// its group names.
+#[derive(Debug, Clone)]
+pub struct PikeVm {
+    pub(crate) nfa: Nfa,
+    pub(crate) names: Arc<[Option<String>]>,
+    /// Whether the pattern matches bytes, which only changes the generated passes.
+    pub(crate) bytes: bool,
+}
+
+/// A thread: an NFA state and the slots saved on the way to it.
+type Thread = (u32, Slots);
+
+/// The threads at one position, in priority order, with which states they hold and
+/// the stack [`PikeVm::add`] follows edges with. A run allocates two, each with room
+/// for a thread per state, and steps from one into the other.
+struct Threads {
+    list: Vec<Thread>,
+    seen: Vec<bool>,
+    stack: Vec<Thread>,
+}
+
+impl Threads {
+    fn new(states: usize) -> Self {
+        Self {
+            list: Vec::with_capacity(states),
+            seen: vec![false; states],
+            stack: Vec::with_capacity(states),
+        }
+    }
+
+    fn clear(&mut self) {
+        self.list.clear();
+        self.seen.fill(false);
+    }
+}
+
+impl PikeVm {
+    pub fn new(ast: &SimpleRegexAst) -> Self {
+        let mut names = vec![None];
+        collect_names(&ast.atoms, &mut names);
+        Self {
+            nfa: Nfa::build_captures(ast),
+            names: names.into(),
+            bytes: false,
+        }
+    }
+
+    /// [`PikeVm::new`] for a bytes-mode AST ([`SimpleRegexAst::parse_bytes`]), whose
+    /// generated passes read `[u8]` input.
+    pub fn new_bytes(ast: &SimpleRegexAst) -> Self {
+        Self {
+            bytes: true,
+            ..Self::new(ast)
+        }
+    }
+
+    /// The number of capture groups, including group 0 (the whole match).
+    pub fn captures_len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// The name of each capture group, by index; `None` for group 0 and unnamed groups.
+    pub fn capture_names(&self) -> impl Iterator<Item = Option<&str>> {
+        self.names.iter().map(|name| name.as_deref())
+    }
+
+    pub(crate) fn names(&self) -> &Arc<[Option<String>]> {
+        &self.names
+    }
+
+    /// Matches the pattern against the start of `from`, like
+    /// [`Regex::find_prefix`](crate::Regex::find_prefix), but running every thread
+    /// in lock-step rather than walking the DFA. A zero-width assertion is decided
+    /// separately on each thread, at the position that thread reaches it, so an
+    /// assertion under a repetition or alongside a consuming branch (`(?m)(?:^|a)*`,
+    /// `.*\bx`) matches as in the `regex` crate.
+    ///
+    /// `prev` is the char before `from` in the larger input, `None` at the start of text.
+    pub fn find_prefix<'a>(&self, from: &'a str, prev: Option<char>) -> Option<(&'a str, &'a str)> {
+        self.prefix(from, prev).map(|n| (&from[..n], &from[n..]))
+    }
+
+    /// [`PikeVm::find_prefix`] over bytes, like [`Regex::find_prefix_bytes`](crate::Regex::find_prefix_bytes).
+    pub fn find_prefix_bytes<'a>(&self, from: &'a [u8], prev: Option<u8>) -> Option<(&'a [u8], &'a [u8])> {
+        self.prefix(from, prev.map(char::from)).map(|n| from.split_at(n))
+    }
+
+    /// The byte length of the leftmost-first match at the start of `from`.
+    fn prefix<H: Haystack + ?Sized>(&self, from: &H, prev: Option<char>) -> Option<usize> {
+        let mut c = from.unit_at(0);
+        let states = self.nfa.transitions.len();
+        let (mut threads, mut next) = (Threads::new(states), Threads::new(states));
+        self.add(&mut threads, (0, vec![]), 0, prev, c);
+
+        let mut last = None;
+        let mut pos = 0;
+        loop {
+            // Threads are in priority order, so the first to accept is the
+            // leftmost-first match so far, and every thread after it can only
+            // produce a lower-priority one.
+            if let Some(accepting) = threads.list.iter().position(|(state, _)| *state == self.nfa.final_state) {
+                last = Some(pos);
+                threads.list.truncate(accepting);
+            }
+            let Some(ch) = c.filter(|_| !threads.list.is_empty()) else {
+                break;
+            };
+            pos += H::unit_len(ch);
+            c = from.unit_at(pos);
+            self.step(&mut threads, &mut next, ch, pos, c);
+        }
+        last
+    }
+
+    /// Every byte length at which the pattern matches a prefix of `from`, in
+    /// increasing order, like [`Regex::find_prefix_ends`](crate::Regex::find_prefix_ends).
+    pub fn find_prefix_ends(&self, from: &str, prev: Option<char>) -> Vec<usize> {
+        let mut ends = vec![];
+        self.run_all(from, prev, |end| {
+            ends.push(end);
+            false
+        });
+        ends
+    }
+
+    /// The shortest prefix of `from` the pattern matches, like
+    /// [`Regex::find_prefix_earliest`](crate::Regex::find_prefix_earliest).
+    pub fn find_prefix_earliest<'a>(&self, from: &'a str, prev: Option<char>) -> Option<(&'a str, &'a str)> {
+        let mut earliest = None;
+        self.run_all(from, prev, |end| {
+            earliest = Some(end);
+            true
+        });
+        earliest.map(|n| (&from[..n], &from[n..]))
+    }
+
+    /// [`PikeVm::find_prefix_ends`] over bytes.
+    pub fn find_prefix_ends_bytes(&self, from: &[u8], prev: Option<u8>) -> Vec<usize> {
+        let mut ends = vec![];
+        self.run_all(from, prev.map(char::from), |end| {
+            ends.push(end);
+            false
+        });
+        ends
+    }
+
+    /// [`PikeVm::find_prefix_earliest`] over bytes.
+    pub fn find_prefix_earliest_bytes<'a>(&self, from: &'a [u8], prev: Option<u8>) -> Option<(&'a [u8], &'a [u8])> {
+        let mut earliest = None;
+        self.run_all(from, prev.map(char::from), |end| {
+            earliest = Some(end);
+            true
+        });
+        earliest.map(|n| from.split_at(n))
+    }
+
+    /// Runs every thread over `from` without the leftmost-first truncation, calling
+    /// `on_accept` once for each position some thread accepts at, until it returns
+    /// `true` or every thread has died.
+    fn run_all<H: Haystack + ?Sized>(&self, from: &H, prev: Option<char>, mut on_accept: impl FnMut(usize) -> bool) {
+        let mut c = from.unit_at(0);
+        let states = self.nfa.transitions.len();
+        let (mut threads, mut next) = (Threads::new(states), Threads::new(states));
+        self.add(&mut threads, (0, vec![]), 0, prev, c);
+
+        let mut pos = 0;
+        loop {
+            if threads.list.iter().any(|(state, _)| *state == self.nfa.final_state) && on_accept(pos) {
+                return;
+            }
+            let Some(ch) = c.filter(|_| !threads.list.is_empty()) else {
+                return;
+            };
+            pos += H::unit_len(ch);
+            c = from.unit_at(pos);
+            self.step(&mut threads, &mut next, ch, pos, c);
+        }
+    }
+
+    /// The capture slots of the leftmost-first match spanning exactly
+    /// `haystack[start..end]`, or `None` if no path through the pattern accepts
+    /// that span. The text around it is only read as context for zero-width
+    /// assertions.
+    pub(crate) fn slots<H: Haystack + ?Sized>(&self, haystack: &H, start: usize, end: usize) -> Option<Slots> {
+        let mut slots = vec![None; self.names.len() * 2];
+        slots[0] = Some(start);
+        let mut c = haystack.unit_at(start);
+        let states = self.nfa.transitions.len();
+        let (mut threads, mut next) = (Threads::new(states), Threads::new(states));
+        self.add(&mut threads, (0, slots), start, haystack.unit_before(start), c);
+
+        let mut pos = start;
+        while pos < end {
+            let ch = c?;
+            pos += H::unit_len(ch);
+            c = haystack.unit_at(pos);
+            self.step(&mut threads, &mut next, ch, pos, c);
+            if threads.list.is_empty() {
+                return None;
+            }
+        }
+
+        let (_, mut slots) = threads.list.into_iter().find(|(state, _)| *state == self.nfa.final_state)?;
+        slots[1] = Some(end);
+        Some(slots)
+    }
+
+    /// Advances every thread over `ch`, ending at byte offset `pos` with `c` the
+    /// char after it, and swaps the surviving threads into `threads`, leaving the
+    /// old list in `next` to be reused by the next step.
+    fn step(&self, threads: &mut Threads, next: &mut Threads, ch: char, pos: usize, c: Option<char>) {
+        next.clear();
+        for (state, slots) in threads.list.drain(..) {
+            for (event, target) in &self.nfa.transitions[&state] {
+                if matches!(event, TransitionEvent::Char(_) | TransitionEvent::Chars(..)) && event.matches(ch) {
+                    self.add(next, (*target, slots.clone()), pos, Some(ch), c);
+                }
+            }
+        }
+        std::mem::swap(threads, next);
+    }
+
+    /// Adds `thread` and everything reachable from it without consuming a char to
+    /// `threads`, in priority order. `pos` is the byte offset the threads are at,
+    /// with `prev` and `c` the chars either side of it for the zero-width assertions.
+    fn add(&self, threads: &mut Threads, thread: Thread, pos: usize, prev: Option<char>, c: Option<char>) {
+        // Pre-order DFS, as in `dfa::ordered_closure`: successors are pushed in
+        // reverse so they pop in priority order.
+        let Threads {
+            list,
+            seen,
+            stack,
+        } = threads;
+        stack.push(thread);
+        while let Some((state, slots)) = stack.pop() {
+            if std::mem::replace(&mut seen[state as usize], true) {
+                continue;
+            }
+            for (event, target) in self.nfa.transitions[&state].iter().rev() {
+                match event {
+                    TransitionEvent::Epsilon => stack.push((*target, slots.clone())),
+                    TransitionEvent::Save(slot) => {
+                        let mut slots = slots.clone();
+                        // `find_prefix` threads carry no slots.
+                        if let Some(saved) = slots.get_mut(*slot as usize) {
+                            *saved = Some(pos);
+                        }
+                        stack.push((*target, slots));
+                    }
+                    TransitionEvent::Char(_) | TransitionEvent::Chars(..) | TransitionEvent::End => {}
+                    assertion => {
+                        if zero_width_holds(assertion, prev, c) {
+                            stack.push((*target, slots.clone()));
+                        }
+                    }
+                }
+            }
+            list.push((state, slots));
+        }
+    }
+}
+
+/// Records the name of every capture group in `atoms` at its index.
+fn collect_names(atoms: &[AtomRepeat], names: &mut Vec<Option<String>>) {
+    for atom in atoms {
+        match &atom.atom {
+            Atom::Capture {
+                index,
+                name,
+                branches,
+            } => {
+                let index = *index as usize;
+                if names.len() <= index {
+                    names.resize(index + 1, None);
+                }
+                names[index] = name.clone();
+                branches.iter().for_each(|branch| collect_names(branch, names));
+            }
+            Atom::Alternation(branches) => branches.iter().for_each(|branch| collect_names(branch, names)),
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slots(pattern: &str, haystack: &str, start: usize, end: usize) -> Option<Slots> {
+        PikeVm::new(&SimpleRegexAst::parse(pattern).unwrap()).slots(haystack, start, end)
+    }
+
+    #[test]
+    fn records_group_spans() {
+        assert_eq!(slots("([a-z]+)-([0-9]+)", "ab-12", 0, 5), Some(vec![Some(0), Some(5), Some(0), Some(2), Some(3), Some(5)]));
+        // A group that didn't participate stays unset.
+        assert_eq!(slots("(a)|(b)", "b", 0, 1), Some(vec![Some(0), Some(1), None, None, Some(0), Some(1)]));
+    }
+
+    #[test]
+    fn follows_the_leftmost_first_path() {
+        // `a|ab` then `c|bcd` over `abcd`: the only path ending at 4 takes `a`, then `bcd`.
+        assert_eq!(slots("(a|ab)(c|bcd)", "abcd", 0, 4), Some(vec![Some(0), Some(4), Some(0), Some(1), Some(1), Some(4)]));
+        // A repeated group keeps its last iteration.
+        assert_eq!(slots("(?:(a)|b)+", "xaab", 1, 4), Some(vec![Some(1), Some(4), Some(2), Some(3)]));
+        // Lazy repeats give the earlier group as little as possible.
+        assert_eq!(slots("(a*?)(a*)", "aaa", 0, 3), Some(vec![Some(0), Some(3), Some(0), Some(0), Some(0), Some(3)]));
+    }
+
+    #[test]
+    fn assertions_see_the_surrounding_text() {
+        assert_eq!(slots(r"\b(a)", "ba a", 3, 4), Some(vec![Some(3), Some(4), Some(3), Some(4)]));
+        assert_eq!(slots(r"\b(a)", "ba a", 1, 2), None);
+    }
+
+    fn find_prefix<'a>(pattern: &str, from: &'a str, prev: Option<char>) -> Option<&'a str> {
+        PikeVm::new(&SimpleRegexAst::parse(pattern).unwrap())
+            .find_prefix(from, prev)
+            .map(|(matched, _)| matched)
+    }
+
+    #[test]
+    fn assertions_run_in_lock_step_with_consuming_threads() {
+        // `.*` could take the `x`, but only leaving it to `\bx` matches.
+        assert_eq!(find_prefix(r".*\bx", "x", None), Some("x"));
+        assert_eq!(find_prefix(r".*\bx", "a x", None), Some("a x"));
+        // The higher-priority `^` accepts before `a+` gets to consume.
+        assert_eq!(find_prefix("(?:^|a+)", "aa", None), Some(""));
+        assert_eq!(find_prefix("(?:^|a+)", "aa", Some('b')), Some("aa"));
+        assert_eq!(find_prefix("(?m)(?:^|a)+", "aa", Some('\n')), Some(""));
+        assert_eq!(find_prefix(r"(?:\b|%)+", "%%a", None), Some("%%"));
+        assert_eq!(find_prefix(r"(?:\b|%)+", "%%a", Some('a')), Some(""));
+        assert_eq!(find_prefix("a*(^a)", "a", None), Some("a"));
+        assert_eq!(find_prefix("a*(^a)", "aa", None), Some("a"));
+    }
+
+    #[test]
+    fn every_end_ignores_priority() {
+        let vm = PikeVm::new(&SimpleRegexAst::parse("a|ab|a+").unwrap());
+        assert_eq!(vm.find_prefix_ends("aab", None), [1, 2]);
+        assert_eq!(vm.find_prefix_earliest("aab", None).map(|(matched, _)| matched), Some("a"));
+        // The `^` alternative accepts empty only at the start of the text.
+        let vm = PikeVm::new(&SimpleRegexAst::parse("(?:^|a)+").unwrap());
+        assert_eq!(vm.find_prefix_ends("aa", None), [0, 1, 2]);
+        assert_eq!(vm.find_prefix_ends("aa", Some('b')), [1, 2]);
+    }
+
+    #[test]
+    fn names_are_indexed_by_group() {
+        let vm = PikeVm::new(&SimpleRegexAst::parse("(?P<y>[0-9]+)-(?:x)([0-9]+)-(?<d>[0-9]+)").unwrap());
+        assert_eq!(vm.captures_len(), 4);
+        assert_eq!(vm.capture_names().collect::<Vec<_>>(), [None, Some("y"), None, Some("d")]);
+    }
+
+    #[test]
+    fn bytes_mode_steps_a_byte_at_a_time() {
+        let vm = PikeVm::new_bytes(&SimpleRegexAst::parse_bytes(r".*\bx").unwrap());
+        assert_eq!(vm.find_prefix_bytes(b"\xFF x", None).map(|(matched, _)| matched), Some(&b"\xFF x"[..]));
+        assert_eq!(vm.slots(&b"\xFF x"[..], 1, 3), Some(vec![Some(1), Some(3)]));
+    }
+}
@@ -0,0 +1,213 @@
+//! Literal prefilters for the unanchored search.
+//!
+//! Most searched-for patterns pin down how a match begins: `foo\d+` can only start at
+//! an `f` followed by `oo`, and `(?:cat|dog)s?` only at a `c` or a `d`. A [`Prefilter`]
+//! is that requirement pulled out of the [`SimpleRegexAst`], as something a plain byte
+//! scan can look for. The search skips straight to the first place it holds before
+//! running any automaton, which is what makes looking for a rare pattern in a long
+//! haystack cheap. The scans are simple `position` loops over the bytes, which the
+//! compiler vectorizes well enough that no `memchr` dependency is needed.
+
+use std::collections::BTreeSet;
+
+use super::*;
+
+/// How many distinct first bytes a [`Prefilter::Bytes`] scan checks for, at most. Past
+/// that, most bytes of a typical haystack are candidates and the scan doesn't pay off.
+const MAX_LEADING_BYTES: usize = 3;
+
+/// How many chars of a class are expanded while looking for its first bytes; a bigger
+/// class is treated as matching anything.
+const MAX_CLASS_CHARS: u32 = 256;
+
+/// Where a match of a pattern can start, as a byte scan over the haystack.
+///
+/// Every match of the pattern starts at a position the scan reports, so the search
+/// never needs to look anywhere else. The converse doesn't hold: a candidate is only a
+/// place a match *may* start.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Prefilter {
+    /// Every match starts with these bytes (`foo\d+` gives `foo`).
+    Literal(Vec<u8>),
+    /// Every match starts with one of these bytes (`(?:cat|dog)s?` gives `c` and `d`).
+    Bytes(Vec<u8>),
+}
+
+impl Prefilter {
+    /// The prefilter of `ast`, or `None` if a match can start with almost anything (or
+    /// be empty). `bytes` says the AST is from [`SimpleRegexAst::parse_bytes`], whose
+    /// chars are bytes rather than scalar values to encode as UTF-8.
+    pub fn new(ast: &SimpleRegexAst, bytes: bool) -> Option<Prefilter> {
+        let encode = |c: char, out: &mut Vec<u8>| {
+            if bytes {
+                out.push(c as u8);
+            } else {
+                out.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+            }
+        };
+        let mut prefix = String::new();
+        literal_prefix(&ast.atoms, &mut prefix);
+        if !prefix.is_empty() {
+            let mut literal = vec![];
+            for c in prefix.chars() {
+                encode(c, &mut literal);
+            }
+            return Some(Prefilter::Literal(literal));
+        }
+        let mut first_bytes = BTreeSet::new();
+        for c in leading_chars(&ast.atoms)? {
+            let mut encoded = vec![];
+            encode(c, &mut encoded);
+            first_bytes.insert(encoded[0]);
+        }
+        (first_bytes.len() <= MAX_LEADING_BYTES).then(|| Prefilter::Bytes(first_bytes.into_iter().collect()))
+    }
+
+    /// The first candidate position in `haystack` at or after byte offset `from`, or
+    /// `None` if no match can start there or later. A candidate in a `str` haystack is
+    /// always a char boundary, since every match starts with a whole char.
+    pub fn find(&self, haystack: &[u8], from: usize) -> Option<usize> {
+        let rest = haystack.get(from..)?;
+        match self {
+            Prefilter::Literal(literal) => {
+                let (first, tail) = literal.split_first()?;
+                let mut at = 0;
+                loop {
+                    at += rest[at..].iter().position(|byte| byte == first)?;
+                    if rest[at + 1..].starts_with(tail) {
+                        return Some(from + at);
+                    }
+                    at += 1;
+                }
+            }
+            Prefilter::Bytes(set) => rest.iter().position(|byte| set.contains(byte)).map(|at| from + at),
+        }
+    }
+}
+
+/// Whether `atom` consumes nothing, so it can be stepped over while looking for how a
+/// match starts.
+fn is_zero_width(atom: &Atom) -> bool {
+    matches!(
+        atom,
+        Atom::EndOfInput | Atom::StartOfText | Atom::WordBoundary { .. } | Atom::StartOfLine { .. } | Atom::EndOfLine { .. }
+    )
+}
+
+/// Appends to `prefix` the literal every match of `atoms` starts with, returning whether
+/// all of `atoms` was that literal (so whatever follows extends it).
+fn literal_prefix(atoms: &[AtomRepeat], prefix: &mut String) -> bool {
+    for atom in atoms {
+        match (&atom.atom, atom.repeat) {
+            (atom, _) if is_zero_width(atom) => {}
+            (Atom::Literal(literal), Repeat::Once) => prefix.push_str(literal),
+            // The first repetition is required; what follows it may be another.
+            (Atom::Literal(literal), Repeat::OnceOrMore) => {
+                prefix.push_str(literal);
+                return false;
+            }
+            (
+                Atom::Alternation(branches)
+                | Atom::Capture {
+                    branches,
+                    ..
+                },
+                Repeat::Once | Repeat::OnceOrMore,
+            ) if branches.len() == 1 => {
+                if !literal_prefix(&branches[0], prefix) || matches!(atom.repeat, Repeat::OnceOrMore) {
+                    return false;
+                }
+            }
+            _ => return false,
+        }
+    }
+    true
+}
+
+/// The chars a match of `atoms` can start with, or `None` if it may start with any of
+/// too many, or be empty.
+fn leading_chars(atoms: &[AtomRepeat]) -> Option<BTreeSet<char>> {
+    for atom in atoms {
+        if is_zero_width(&atom.atom) {
+            continue;
+        }
+        if matches!(atom.repeat, Repeat::ZeroOrOnce | Repeat::ZeroOrMore) {
+            return None;
+        }
+        return match &atom.atom {
+            Atom::Literal(literal) => match literal.chars().next() {
+                Some(c) => Some(BTreeSet::from([c])),
+                None => continue,
+            },
+            Atom::Group(false, entries) => {
+                let mut chars = BTreeSet::new();
+                let mut budget = MAX_CLASS_CHARS;
+                for entry in entries {
+                    let (start, end) = match entry {
+                        GroupEntry::Char(c) => (*c, *c),
+                        GroupEntry::Range(start, end) => (*start, *end),
+                    };
+                    let count = (end as u32).checked_sub(start as u32)? + 1;
+                    budget = budget.checked_sub(count)?;
+                    chars.extend(start..=end);
+                }
+                Some(chars)
+            }
+            Atom::Alternation(branches)
+            | Atom::Capture {
+                branches,
+                ..
+            } => {
+                let mut chars = BTreeSet::new();
+                for branch in branches {
+                    chars.extend(leading_chars(branch)?);
+                }
+                Some(chars)
+            }
+            _ => None,
+        };
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn prefilter(pattern: &str) -> Option<Prefilter> {
+        Prefilter::new(&SimpleRegexAst::parse(pattern).expect("valid pattern"), false)
+    }
+
+    #[test]
+    fn extracts_the_required_literal() {
+        assert_eq!(prefilter(r"foo\d+"), Some(Prefilter::Literal(b"foo".to_vec())));
+        assert_eq!(prefilter(r"\bab(?:cd)e*"), Some(Prefilter::Literal(b"abcd".to_vec())));
+        assert_eq!(prefilter("(?:ab)+c"), Some(Prefilter::Literal(b"ab".to_vec())));
+        assert_eq!(prefilter("é+"), Some(Prefilter::Literal("é".as_bytes().to_vec())));
+    }
+
+    #[test]
+    fn falls_back_to_leading_bytes() {
+        assert_eq!(prefilter("(?:cat|dog)s?"), Some(Prefilter::Bytes(b"cd".to_vec())));
+        assert_eq!(prefilter("[xy]z"), Some(Prefilter::Bytes(b"xy".to_vec())));
+        // Every char of a two-byte range shares one lead byte.
+        assert_eq!(prefilter("[à-ï]"), Some(Prefilter::Bytes(vec![0xC3])));
+    }
+
+    #[test]
+    fn none_where_a_match_can_start_anywhere() {
+        for pattern in ["a*b", r"\w+", "[a-z]x", "(?:a|b*)c", ""] {
+            assert_eq!(prefilter(pattern), None, "{pattern}");
+        }
+    }
+
+    #[test]
+    fn find_reports_candidates_in_order() {
+        let literal = Prefilter::Literal(b"ab".to_vec());
+        assert_eq!(literal.find(b"aaxab", 0), Some(3));
+        assert_eq!(literal.find(b"abab", 1), Some(2));
+        assert_eq!(literal.find(b"aba", 1), None);
+        assert_eq!(literal.find(b"ab", 3), None);
+        assert_eq!(Prefilter::Bytes(b"cd".to_vec()).find(b"xxdc", 0), Some(2));
+    }
+}
@@ -0,0 +1,319 @@
+//! Strings a regex matches, and strings just short of matching, for testing a
+//! tokenizer or fuzzing what reads its tokens.
+//!
+//! Both walk the DFA the way the interpreter does, stepping on a char the state, or a
+//! state its zero-width edges lead to, has an edge for. A string is only handed out
+//! once [`Regex::find_prefix`] agrees on it, so a walk that goes wrong on a
+//! [lock-step](Dfa::lock_step) pattern is dropped rather than misreported.
+//!
+//! A bytes-mode regex ([`Regex::parse_bytes`]) gives strings of chars up to
+//! `U+00FF`, each standing for the byte of the same value, as its DFA reads them.
+
+use std::collections::HashMap;
+
+use super::*;
+use crate::dfa::{Ranges, complement, intersect, is_assertion, normalize};
+use crate::nfa::TransitionEvent;
+
+/// How many walks [`Regex::samples`] and [`Regex::near_misses`] try for one string
+/// before giving up on the pattern.
+const MAX_ATTEMPTS: usize = 64;
+
+/// What the walks need to know of each DFA state.
+struct Graph {
+    /// The chars the state steps on, by its own edges or those of a state its
+    /// zero-width edges lead to.
+    chars: HashMap<u32, Ranges>,
+    /// The fewest chars from the state to an accept, taking every zero-width edge to
+    /// hold. Missing for a state that never accepts.
+    distance: HashMap<u32, usize>,
+}
+
+impl Graph {
+    fn new(regex: &Regex) -> Graph {
+        let dfa = &regex.dfa;
+        let domain = regex.units();
+        let mut chars = HashMap::new();
+        for state in dfa.transitions.keys() {
+            let mut reached = vec![*state];
+            let mut ranges = vec![];
+            while let Some(at) = reached.pop() {
+                ranges.extend(dfa.char_edges(at).into_iter().flat_map(|(ranges, _)| ranges));
+                for (event, target) in dfa.transitions.get(&at).into_iter().flatten() {
+                    if is_assertion(event) && target != state && !reached.contains(target) {
+                        reached.push(*target);
+                    }
+                }
+            }
+            normalize(&mut ranges);
+            chars.insert(*state, intersect(&ranges, &domain));
+        }
+
+        let mut distance: HashMap<u32, usize> = dfa.final_states.iter().map(|state| (*state, 0)).collect();
+        loop {
+            let mut changed = false;
+            for (state, edges) in &dfa.transitions {
+                let best = edges
+                    .iter()
+                    .filter_map(|(event, target)| match event {
+                        TransitionEvent::End => Some(0),
+                        event if is_assertion(event) => distance.get(target).copied(),
+                        _ => distance.get(target).map(|distance| distance + 1),
+                    })
+                    .min();
+                if let Some(best) = best.filter(|best| distance.get(state).is_none_or(|known| best < known)) {
+                    distance.insert(*state, best);
+                    changed = true;
+                }
+            }
+            if !changed {
+                return Graph {
+                    chars,
+                    distance,
+                };
+            }
+        }
+    }
+
+    /// The chars `state` steps on.
+    fn chars(&self, state: u32) -> &[(u32, u32)] {
+        self.chars.get(&state).map_or(&[], |ranges| ranges)
+    }
+
+    /// Whether an accept is at most `budget` chars on from `state`.
+    fn reaches_accept(&self, state: u32, budget: usize) -> bool {
+        self.distance.get(&state).is_some_and(|distance| *distance <= budget)
+    }
+}
+
+impl Regex {
+    /// Random strings of at most `max_len` chars that the regex matches in full, so
+    /// that [`Regex::find_prefix`] on one matches all of it. `rng` supplies uniformly
+    /// random numbers, e.g. `|| rng.next_u64()` or a [`seeded_rng`]. Chars are
+    /// printable ASCII where the pattern allows them three times in four. The iterator
+    /// ends early if it can't find a string, as for a pattern whose matches are all
+    /// longer than `max_len`.
+    pub fn samples<'r>(&'r self, mut rng: impl FnMut() -> u64 + 'r, max_len: usize) -> impl Iterator<Item = String> + 'r {
+        let graph = Graph::new(self);
+        std::iter::from_fn(move || (0..MAX_ATTEMPTS).find_map(|_| self.sample(&graph, &mut rng, max_len)))
+    }
+
+    /// One random walk to an accept for [`Regex::samples`], if it gets there.
+    fn sample(&self, graph: &Graph, rng: &mut impl FnMut() -> u64, max_len: usize) -> Option<String> {
+        let mut state = 0;
+        let mut units: Vec<char> = vec![];
+        loop {
+            let budget = max_len - units.len();
+            if self.dfa.accepts_via_assertions(state, units.last().copied(), None) && (budget == 0 || rng() % 4 == 0) {
+                let sample: String = units.into_iter().collect();
+                return self.matches_exactly(&sample).then_some(sample);
+            }
+            if budget == 0 {
+                return None;
+            }
+            let c = pick_char(graph.chars(state), rng)?;
+            state = self.dfa.step(state, units.last().copied(), c).filter(|next| graph.reaches_accept(*next, budget - 1))?;
+            units.push(c);
+        }
+    }
+
+    /// Random strings of at most `max_len` chars that the regex doesn't match in full,
+    /// each a step away from a match: a walk along the pattern cut short, or ending in
+    /// a char the pattern can't take there. Like [`Regex::samples`], the iterator ends
+    /// early if it can't find one, as for `(?s).*`.
+    pub fn near_misses<'r>(&'r self, mut rng: impl FnMut() -> u64 + 'r, max_len: usize) -> impl Iterator<Item = String> + 'r {
+        let graph = Graph::new(self);
+        let domain = self.units();
+        std::iter::from_fn(move || {
+            (0..MAX_ATTEMPTS).find_map(|_| {
+                let length = (rng() % (max_len as u64 + 1)) as usize;
+                let mut state = 0;
+                let mut units: Vec<char> = vec![];
+                while units.len() < length {
+                    let Some((c, next)) = pick_char(graph.chars(state), &mut rng).and_then(|c| Some((c, self.dfa.step(state, units.last().copied(), c)?)))
+                    else {
+                        break;
+                    };
+                    units.push(c);
+                    state = next;
+                }
+                if units.len() < max_len && rng() % 2 == 0 {
+                    units.extend(pick_char(&intersect(&complement(graph.chars(state)), &domain), &mut rng));
+                }
+                let near_miss: String = units.into_iter().collect();
+                (!self.matches_exactly(&near_miss)).then_some(near_miss)
+            })
+        })
+    }
+
+    /// Every string of at most `max_len` chars the regex matches in full, in
+    /// lexicographic order of their chars. Lazy, so a pattern with too many to list,
+    /// like `.{4}`, can still be `take`n from.
+    pub fn enumerate(&self, max_len: usize) -> impl Iterator<Item = String> + '_ {
+        let graph = Graph::new(self);
+        let mut units: Vec<char> = vec![];
+        // The state after each prefix of `units`, with the chars left to try from it.
+        let mut stack = vec![Frame::new(0, &graph)];
+        let mut unchecked = true;
+        std::iter::from_fn(move || {
+            loop {
+                let frame = stack.last_mut()?;
+                if std::mem::take(&mut unchecked) && self.dfa.accepts_via_assertions(frame.state, units.last().copied(), None) {
+                    let string: String = units.iter().collect();
+                    if self.matches_exactly(&string) {
+                        return Some(string);
+                    }
+                }
+                let budget = max_len - units.len();
+                let Some(c) = frame.next_char().filter(|_| budget > 0) else {
+                    stack.pop();
+                    units.pop();
+                    continue;
+                };
+                let Some(next) = self.dfa.step(frame.state, units.last().copied(), c).filter(|next| graph.reaches_accept(*next, budget - 1)) else {
+                    continue;
+                };
+                units.push(c);
+                stack.push(Frame::new(next, &graph));
+                unchecked = true;
+            }
+        })
+    }
+
+    /// The chars a haystack can hold: every scalar value, or in bytes mode the bytes.
+    fn units(&self) -> Ranges {
+        if self.bytes { vec![(0, 0xFF)] } else { vec![(0, 0xD7FF), (0xE000, 0x10FFFF)] }
+    }
+
+    /// Whether [`Regex::find_prefix`] (or in bytes mode, [`Regex::find_prefix_bytes`])
+    /// matches all of `units`.
+    fn matches_exactly(&self, units: &str) -> bool {
+        if self.bytes {
+            let bytes: Vec<u8> = units.chars().map(|c| c as u8).collect();
+            self.find_prefix_bytes(&bytes, None).is_some_and(|(matched, _)| matched.len() == bytes.len())
+        } else {
+            self.find_prefix(units, None).is_some_and(|(matched, _)| matched.len() == units.len())
+        }
+    }
+}
+
+/// A state of [`Regex::enumerate`]'s walk and the chars it has yet to step on.
+struct Frame {
+    state: u32,
+    chars: Ranges,
+    /// The range of `chars` being stepped through, and the next char of it.
+    range: usize,
+    next: u32,
+}
+
+impl Frame {
+    fn new(state: u32, graph: &Graph) -> Frame {
+        Frame {
+            state,
+            chars: graph.chars(state).to_vec(),
+            range: 0,
+            next: 0,
+        }
+    }
+
+    fn next_char(&mut self) -> Option<char> {
+        while let Some(&(lo, hi)) = self.chars.get(self.range) {
+            let at = self.next.max(lo);
+            if at > hi {
+                self.range += 1;
+                continue;
+            }
+            self.next = at + 1;
+            return char::from_u32(at);
+        }
+        None
+    }
+}
+
+/// A random char of `ranges`, taken from its printable ASCII chars three times in four
+/// where it has some, so that most strings read well.
+fn pick_char(ranges: &[(u32, u32)], rng: &mut impl FnMut() -> u64) -> Option<char> {
+    let printable = intersect(ranges, &[(0x20, 0x7E)]);
+    let pool = if !printable.is_empty() && rng() % 4 != 0 { &printable[..] } else { ranges };
+    let total: u64 = pool.iter().map(|(lo, hi)| u64::from(hi - lo) + 1).sum();
+    if total == 0 {
+        return None;
+    }
+    let mut at = rng() % total;
+    for (lo, hi) in pool {
+        let len = u64::from(hi - lo) + 1;
+        if at < len {
+            return char::from_u32(lo + at as u32);
+        }
+        at -= len;
+    }
+    None
+}
+
+/// A xorshift generator seeded with `seed`, enough to drive [`Regex::samples`] and
+/// [`Regex::near_misses`] the same way on every run. A zero seed, which would only
+/// ever give 0, is taken as 1.
+pub fn seeded_rng(seed: u64) -> impl FnMut() -> u64 {
+    let mut state = seed.max(1);
+    move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn samples_are_whole_matches() {
+        let options = ParseOptions {
+            set_operators: true,
+            ..ParseOptions::default()
+        };
+        for pattern in ["[a-z]+[0-9]?", "a|ab", "(?:foo|ba[rz])+", r"\bx+\b", "[0-9]+(?-0.*)", "(?s).", r"\w+$"] {
+            let regex = Regex::parse_with(pattern, options).unwrap();
+            let samples: Vec<String> = regex.samples(seeded_rng(7), 8).take(50).collect();
+            assert_eq!(samples.len(), 50, "{pattern}");
+            for sample in samples {
+                assert!(sample.chars().count() <= 8, "{pattern}: {sample:?}");
+                assert_eq!(regex.find_prefix(&sample, None), Some((&sample[..], "")), "{pattern}");
+            }
+        }
+        // `a|ab` stops at `a`, so `ab` is never a whole match.
+        assert!(Regex::parse("a|ab").unwrap().samples(seeded_rng(1), 8).take(20).all(|sample| sample == "a"));
+        // Every match is longer than the limit.
+        assert_eq!(Regex::parse("[a-z]{5}").unwrap().samples(seeded_rng(1), 4).next(), None);
+    }
+
+    #[test]
+    fn near_misses_are_not_whole_matches() {
+        for pattern in ["[a-z]+[0-9]", "let", r"\d+\.\d+", "a*"] {
+            let regex = Regex::parse(pattern).unwrap();
+            for near_miss in regex.near_misses(seeded_rng(3), 6).take(50) {
+                assert!(near_miss.chars().count() <= 6, "{pattern}: {near_miss:?}");
+                assert_ne!(regex.find_prefix(&near_miss, None).map(|(matched, _)| matched.len()), Some(near_miss.len()), "{pattern}");
+            }
+        }
+        assert_eq!(Regex::parse("(?s).*").unwrap().near_misses(seeded_rng(3), 6).next(), None);
+    }
+
+    #[test]
+    fn enumerate_lists_every_whole_match_in_order() {
+        let options = ParseOptions {
+            set_operators: true,
+            ..ParseOptions::default()
+        };
+        let all = |pattern: &str, max_len: usize| Regex::parse_with(pattern, options).unwrap().enumerate(max_len).collect::<Vec<_>>();
+        assert_eq!(all("[ab]{1,2}", 4), ["a", "aa", "ab", "b", "ba", "bb"]);
+        assert_eq!(all("x*", 2), ["", "x", "xx"]);
+        assert_eq!(all("a|ab", 4), ["a"]);
+        assert_eq!(all(r"[a-c]+(?-b.*)\b", 2), ["a", "aa", "ab", "ac", "c", "ca", "cb", "cc"]);
+        assert_eq!(Regex::parse(".").unwrap().enumerate(1).count(), 0x10FFFF + 1 - 0x800 - 1);
+        // Bytes mode lists bytes, as chars of the same value.
+        let bytes = Regex::parse_bytes(r"[\x80-\xFF]").unwrap();
+        assert_eq!(bytes.enumerate(2).count(), 128);
+    }
+}
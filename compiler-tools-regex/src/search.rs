@@ -10,30 +10,42 @@
 //! successive start positions, iterating non-overlapping matches, replacing, and
 //! splitting.
 //!
-//! [`RegexSearch`] is that orchestration, written once as default methods over a
-//! single required [`find_prefix`](RegexSearch::find_prefix) and implemented for
-//! every engine, so `Regex` (the interpreter) and `JitRegex` (the Cranelift
-//! JIT) share one API. Bring the trait into scope to call [`is_match`],
-//! [`find`], [`find_iter`], [`replace`]/[`replace_all`], [`split`], etc.
+//! [`RegexSearch`] is that orchestration, written once as default methods over
+//! [`find_prefix`](RegexSearch::find_prefix) and implemented for every engine, so
+//! `Regex` (the interpreter) and `JitRegex` (the Cranelift JIT) share one API.
+//! Bring the trait into scope to call [`is_match`], [`find`], [`find_iter`],
+//! [`captures`], [`replace`]/[`replace_all`], [`split`], etc.
+//!
+//! Beyond leftmost-first, [`find_earliest_iter`] and [`shortest_match`] report a
+//! match as soon as it is known, and [`find_overlapping_iter`] reports every match,
+//! overlapping or not. They build on two more anchored primitives,
+//! [`find_prefix_earliest`](RegexSearch::find_prefix_earliest) and
+//! [`find_prefix_ends`](RegexSearch::find_prefix_ends), which the engines answer
+//! from the every-match DFA.
+//!
+//! Trying every start position makes a search for a rare pattern in a long haystack
+//! cost the haystack length times the pattern's, so `Regex` and `JitRegex` answer
+//! [`find_at`](RegexSearch::find_at), and every leftmost-first search built on it, with
+//! their one-pass unanchored search instead ([`Regex::search_at`](crate::Regex::search_at)).
+//!
+//! Capture groups come from a second pass: the engine finds the match bounds,
+//! then the [`PikeVm`] of the pattern works out where each group matched within
+//! them. Matches that aren't asked for their captures never pay for it.
 //!
 //! [`is_match`]: RegexSearch::is_match
 //! [`find`]: RegexSearch::find
 //! [`find_iter`]: RegexSearch::find_iter
+//! [`captures`]: RegexSearch::captures
 //! [`replace`]: RegexSearch::replace
 //! [`replace_all`]: RegexSearch::replace_all
 //! [`split`]: RegexSearch::split
-//!
-//! # What's missing (needs engine work)
-//!
-//! Capture groups — [`Regex::captures`](https://docs.rs/regex)/`captures_iter`, a
-//! `Captures` type, and `$1`/`$name` expansion in replacements — are *not* here.
-//! This engine never tracks capture-span positions (every `(...)`, `(?:...)` and
-//! `(?P<n>...)` lowers to the same [`Atom::Alternation`](crate::Atom::Alternation)
-//! and the spans are discarded), so the replacement helpers take a literal string
-//! rather than a `Replacer`. See `REGEX_PARITY.md` for the engine work that gap
-//! needs.
+//! [`find_earliest_iter`]: RegexSearch::find_earliest_iter
+//! [`shortest_match`]: RegexSearch::shortest_match
+//! [`find_overlapping_iter`]: RegexSearch::find_overlapping_iter
 
-use std::borrow::Cow;
+use std::{borrow::Cow, cmp::Reverse, ops::Index, sync::Arc};
+
+use crate::PikeVm;
 
 /// A single non-overlapping match within a haystack: a byte range plus the
 /// haystack it indexes, mirroring `regex::Match`.
@@ -88,11 +100,119 @@ impl From<Match<'_>> for std::ops::Range<usize> {
     }
 }
 
+/// The capture groups of one match, mirroring `regex::Captures`. Group 0 is the
+/// whole match; the rest are numbered by the position of their `(` in the pattern.
+#[derive(Debug, Clone)]
+pub struct Captures<'h> {
+    haystack: &'h str,
+    slots: Vec<Option<usize>>,
+    names: Arc<[Option<String>]>,
+}
+
+impl<'h> Captures<'h> {
+    /// The match of group `i`, or `None` if there is no such group or it did not
+    /// participate in the match (e.g. the untaken side of `(a)|(b)`).
+    pub fn get(&self, i: usize) -> Option<Match<'h>> {
+        let start = (*self.slots.get(i * 2)?)?;
+        let end = self.slots[i * 2 + 1]?;
+        Some(Match {
+            haystack: self.haystack,
+            start,
+            end,
+        })
+    }
+
+    /// The match of the group named `name`, as [`get`](Self::get).
+    pub fn name(&self, name: &str) -> Option<Match<'h>> {
+        self.get(self.names.iter().position(|group| group.as_deref() == Some(name))?)
+    }
+
+    /// The whole match, group 0.
+    pub fn get_match(&self) -> Match<'h> {
+        self.get(0).expect("group 0 always participates")
+    }
+
+    /// The number of groups in the pattern, including group 0; the same for every
+    /// match of a regex.
+    #[allow(clippy::len_without_is_empty)] // group 0 is always there
+    pub fn len(&self) -> usize {
+        self.names.len()
+    }
+
+    /// Every group's match, by index.
+    pub fn iter(&self) -> impl Iterator<Item = Option<Match<'h>>> + '_ {
+        (0..self.len()).map(|i| self.get(i))
+    }
+
+    /// Appends `replacement` to `dst`, with group references replaced by the text
+    /// of those groups, as in `regex::Captures::expand`.
+    ///
+    /// `$1` or `$name` refers to a group by index or name, taking the longest run
+    /// of `[0-9A-Za-z_]` after the `$`; `${name}` delimits it explicitly (so
+    /// `${1}a` is group 1 then `a`, where `$1a` is the group named `1a`). `$$` is a
+    /// literal `$`, as is a `$` not followed by a reference. A reference to a group
+    /// that doesn't exist or didn't participate expands to nothing.
+    pub fn expand(&self, mut replacement: &str, dst: &mut String) {
+        while let Some(dollar) = replacement.find('$') {
+            dst.push_str(&replacement[..dollar]);
+            replacement = &replacement[dollar + 1..];
+            if let Some(rest) = replacement.strip_prefix('$') {
+                dst.push('$');
+                replacement = rest;
+                continue;
+            }
+            let Some((group, rest)) = split_group_ref(replacement) else {
+                dst.push('$');
+                continue;
+            };
+            let m = match group.parse::<usize>() {
+                Ok(i) => self.get(i),
+                Err(_) => self.name(group),
+            };
+            if let Some(m) = m {
+                dst.push_str(m.as_str());
+            }
+            replacement = rest;
+        }
+        dst.push_str(replacement);
+    }
+}
+
+impl Index<usize> for Captures<'_> {
+    type Output = str;
+
+    /// The text of group `i`. Panics if there is no such group or it did not participate.
+    fn index(&self, i: usize) -> &str {
+        self.get(i).unwrap_or_else(|| panic!("no group at index '{i}'")).as_str()
+    }
+}
+
+impl Index<&str> for Captures<'_> {
+    type Output = str;
+
+    /// The text of the group named `name`. Panics if there is no such group or it did not participate.
+    fn index(&self, name: &str) -> &str {
+        self.name(name).unwrap_or_else(|| panic!("no group named '{name}'")).as_str()
+    }
+}
+
+/// Splits the group reference after a `$` off `text`: `{name}`, or the longest
+/// run of `[0-9A-Za-z_]`. `None` if there isn't one.
+fn split_group_ref(text: &str) -> Option<(&str, &str)> {
+    if let Some(braced) = text.strip_prefix('{') {
+        let close = braced.find('}')?;
+        return (close > 0).then(|| (&braced[..close], &braced[close + 1..]));
+    }
+    let len = text.find(|c: char| !(c.is_ascii_alphanumeric() || c == '_')).unwrap_or(text.len());
+    (len > 0).then(|| text.split_at(len))
+}
+
 /// The `regex`-crate-shaped search interface over an anchored prefix matcher.
 ///
-/// Implementors supply one method — [`find_prefix`](Self::find_prefix), the
-/// anchored leftmost-first prefix match the engines already expose — and get the
-/// search/replace/split family for free. The search is leftmost-first and
+/// Implementors supply [`find_prefix`](Self::find_prefix), the anchored
+/// leftmost-first prefix match the engines already expose, and the pattern's
+/// [`pike_vm`](Self::pike_vm) for capture groups, and get the
+/// search/captures/replace/split family for free. The search is leftmost-first and
 /// non-overlapping, matching the `regex` crate's default `find`/`find_iter`
 /// semantics (including the empty-match adjacency rule in [`find_iter`]).
 ///
@@ -108,6 +228,30 @@ pub trait RegexSearch {
     /// implement it by forwarding to their inherent `find_prefix`.
     fn find_prefix<'h>(&self, from: &'h str, prev: Option<char>) -> Option<(&'h str, &'h str)>;
 
+    /// The capture-group program of the pattern, which [`captures`](Self::captures)
+    /// runs over the bounds of each match.
+    fn pike_vm(&self) -> &PikeVm;
+
+    /// Anchored shortest match: the shortest prefix of `from` the regex matches,
+    /// whatever the match priority (`a+` matches one `a`, `abc|a` matches `a`). The
+    /// primitive behind the earliest searches; by default it runs the [`PikeVm`].
+    fn find_prefix_earliest<'h>(&self, from: &'h str, prev: Option<char>) -> Option<(&'h str, &'h str)> {
+        self.pike_vm().find_prefix_earliest(from, prev)
+    }
+
+    /// Every byte length at which the regex matches a prefix of `from`, in increasing
+    /// order, whatever the match priority (the `regex` crate's `MatchKind::All`). The
+    /// primitive behind [`find_overlapping_iter`](Self::find_overlapping_iter); by
+    /// default it runs the [`PikeVm`].
+    fn find_prefix_ends(&self, from: &str, prev: Option<char>) -> Vec<usize> {
+        self.pike_vm().find_prefix_ends(from, prev)
+    }
+
+    /// The number of capture groups in the pattern, including group 0 (the whole match).
+    fn captures_len(&self) -> usize {
+        self.pike_vm().captures_len()
+    }
+
     /// Whether the regex matches anywhere in `haystack`.
     fn is_match(&self, haystack: &str) -> bool {
         self.find(haystack).is_some()
@@ -131,27 +275,87 @@ pub trait RegexSearch {
     /// Walks successive start positions, evaluating the anchored
     /// [`find_prefix`](Self::find_prefix) at each (with the correct preceding char
     /// so `^`/`\b`/`(?m)` behave) and returning the first that matches — the same
-    /// leftmost search the `regex` crate performs.
+    /// leftmost search the `regex` crate performs. `Regex` and `JitRegex` find the
+    /// same match in one pass instead ([`Regex::search_at`](crate::Regex::search_at)).
     fn find_at<'h>(&self, haystack: &'h str, start: usize) -> Option<Match<'h>> {
+        leftmost(haystack, start, |from, prev| self.find_prefix(from, prev))
+    }
+
+    /// The leftmost match in `haystack`, ending at the first position it is known to
+    /// match rather than extending it: `a+` on `"aaa"` finds the first `a`. Equivalent
+    /// to [`find_earliest_at`](Self::find_earliest_at) with `start == 0`.
+    fn find_earliest<'h>(&self, haystack: &'h str) -> Option<Match<'h>> {
+        self.find_earliest_at(haystack, 0)
+    }
+
+    /// The leftmost match in `haystack` starting at or after byte offset `start`, cut
+    /// at its earliest end (see [`find_prefix_earliest`](Self::find_prefix_earliest)).
+    fn find_earliest_at<'h>(&self, haystack: &'h str, start: usize) -> Option<Match<'h>> {
+        leftmost(haystack, start, |from, prev| self.find_prefix_earliest(from, prev))
+    }
+
+    /// An iterator over the non-overlapping [`find_earliest`](Self::find_earliest)
+    /// matches in `haystack`, with the same empty-match rule as [`find_iter`](Self::find_iter).
+    fn find_earliest_iter<'r, 'h>(&'r self, haystack: &'h str) -> Matches<'r, 'h, Self>
+    where
+        Self: Sized,
+    {
+        Matches {
+            re: self,
+            haystack,
+            last_end: 0,
+            last_match: None,
+            earliest: true,
+        }
+    }
+
+    /// The end offset of the match that ends first in `haystack`, like
+    /// `regex::Regex::shortest_match`. That may be neither the leftmost match nor
+    /// where the leftmost-first match ends: `a+|b` on `"aab"` gives `1`.
+    fn shortest_match(&self, haystack: &str) -> Option<usize> {
+        self.shortest_match_at(haystack, 0)
+    }
+
+    /// [`shortest_match`](Self::shortest_match) for matches starting at or after byte
+    /// offset `start`.
+    fn shortest_match_at(&self, haystack: &str, start: usize) -> Option<usize> {
+        let mut shortest: Option<usize> = None;
         let mut pos = start;
-        while pos <= haystack.len() {
-            if !haystack.is_char_boundary(pos) {
-                pos += 1;
-                continue;
-            }
-            // The char immediately before `pos`, so the matcher's zero-width
-            // assertions see the right context rather than start-of-text everywhere.
-            let prev = haystack[..pos].chars().next_back();
-            if let Some((matched, _)) = self.find_prefix(&haystack[pos..], prev) {
-                return Some(Match {
-                    haystack,
-                    start: pos,
-                    end: pos + matched.len(),
-                });
+        // A match starting at or after the shortest end found can't end before it.
+        while pos <= haystack.len() && shortest.is_none_or(|end| pos < end) {
+            if haystack.is_char_boundary(pos) {
+                let prev = haystack[..pos].chars().next_back();
+                if let Some((matched, _)) = self.find_prefix_earliest(&haystack[pos..], prev) {
+                    let end = pos + matched.len();
+                    shortest = Some(shortest.map_or(end, |shortest| shortest.min(end)));
+                }
             }
             pos += 1;
         }
-        None
+        shortest
+    }
+
+    /// Every match in `haystack`, overlapping or not, from every start position to
+    /// every end the regex accepts there (the `regex` crate's overlapping search with
+    /// `MatchKind::All`). Matches come in order of where they end, and of those ending
+    /// together the shortest first: `a+` on `"aa"` gives `0..1`, `1..2`, `0..2`.
+    ///
+    /// The whole haystack is searched before the first match is returned, since a
+    /// match ending early can start late.
+    fn find_overlapping_iter<'h>(&self, haystack: &'h str) -> OverlappingMatches<'h> {
+        let mut matches = vec![];
+        for start in (0..=haystack.len()).filter(|pos| haystack.is_char_boundary(*pos)) {
+            let prev = haystack[..start].chars().next_back();
+            matches.extend(self.find_prefix_ends(&haystack[start..], prev).into_iter().map(|length| Match {
+                haystack,
+                start,
+                end: start + length,
+            }));
+        }
+        matches.sort_by_key(|m| (m.end, Reverse(m.start)));
+        OverlappingMatches {
+            inner: matches.into_iter(),
+        }
     }
 
     /// An iterator over all non-overlapping leftmost matches in `haystack`.
@@ -169,15 +373,42 @@ pub trait RegexSearch {
             haystack,
             last_end: 0,
             last_match: None,
+            earliest: false,
+        }
+    }
+
+    /// The capture groups of the leftmost match in `haystack`, or `None` if there is
+    /// no match.
+    ///
+    /// The groups are found by running the [`PikeVm`] over the match the engine
+    /// found. Where the two disagree on whether that span matches at all — the
+    /// zero-width assertion shapes `REGEX_PARITY.md` lists, which the DFA engines
+    /// don't evaluate in lock-step — only group 0 is set.
+    fn captures<'h>(&self, haystack: &'h str) -> Option<Captures<'h>> {
+        self.captures_at(haystack, 0)
+    }
+
+    /// The capture groups of the leftmost match in `haystack` starting at or after
+    /// byte offset `start`. See [`captures`](Self::captures).
+    fn captures_at<'h>(&self, haystack: &'h str, start: usize) -> Option<Captures<'h>> {
+        Some(captures_of(self.pike_vm(), self.find_at(haystack, start)?))
+    }
+
+    /// An iterator over the capture groups of every match [`find_iter`](Self::find_iter) yields.
+    fn captures_iter<'r, 'h>(&'r self, haystack: &'h str) -> CaptureMatches<'r, 'h, Self>
+    where
+        Self: Sized,
+    {
+        CaptureMatches {
+            matches: self.find_iter(haystack),
         }
     }
 
     /// Replace the leftmost match of the regex in `haystack` with `rep`, returning
     /// a [`Cow`] that borrows `haystack` untouched when there is no match.
     ///
-    /// `rep` is inserted literally — unlike the `regex` crate, `$1`/`$name`
-    /// references are *not* expanded, because this engine does not track capture
-    /// groups (see the module docs).
+    /// `$1`, `$name` and `${name}` in `rep` are replaced with the text of that
+    /// capture group, as in [`Captures::expand`]; write `$$` for a literal `$`.
     fn replace<'h>(&self, haystack: &'h str, rep: &str) -> Cow<'h, str>
     where
         Self: Sized,
@@ -186,7 +417,7 @@ pub trait RegexSearch {
     }
 
     /// Replace all non-overlapping matches in `haystack` with `rep`. See
-    /// [`replace`](Self::replace) for the literal-replacement caveat.
+    /// [`replace`](Self::replace) for the group references `rep` can contain.
     fn replace_all<'h>(&self, haystack: &'h str, rep: &str) -> Cow<'h, str>
     where
         Self: Sized,
@@ -196,20 +427,26 @@ pub trait RegexSearch {
 
     /// Replace the first `limit` non-overlapping matches in `haystack` with `rep`
     /// (a `limit` of `0` means replace all), mirroring `regex::Regex::replacen`.
-    /// See [`replace`](Self::replace) for the literal-replacement caveat.
+    /// See [`replace`](Self::replace) for the group references `rep` can contain.
     fn replacen<'h>(&self, haystack: &'h str, limit: usize, rep: &str) -> Cow<'h, str>
     where
         Self: Sized,
     {
         let mut out: Option<String> = None;
         let mut last = 0;
+        // Only run the submatch pass when there's a reference to expand.
+        let expand = rep.contains('$');
         for (i, m) in self.find_iter(haystack).enumerate() {
             if limit != 0 && i >= limit {
                 break;
             }
             let buf = out.get_or_insert_with(|| String::with_capacity(haystack.len()));
             buf.push_str(&haystack[last..m.start()]);
-            buf.push_str(rep);
+            if expand {
+                captures_of(self.pike_vm(), m).expand(rep, buf);
+            } else {
+                buf.push_str(rep);
+            }
             last = m.end();
         }
         match out {
@@ -248,12 +485,15 @@ pub trait RegexSearch {
     }
 }
 
-/// Iterator over non-overlapping matches, returned by [`RegexSearch::find_iter`].
+/// Iterator over non-overlapping matches, returned by [`RegexSearch::find_iter`]
+/// and [`RegexSearch::find_earliest_iter`].
 pub struct Matches<'r, 'h, M: ?Sized> {
     re: &'r M,
     haystack: &'h str,
     last_end: usize,
     last_match: Option<usize>,
+    /// Whether each match is cut at its earliest end.
+    earliest: bool,
 }
 
 impl<'h, M: RegexSearch> Iterator for Matches<'_, 'h, M> {
@@ -264,7 +504,11 @@ impl<'h, M: RegexSearch> Iterator for Matches<'_, 'h, M> {
             if self.last_end > self.haystack.len() {
                 return None;
             }
-            let m = self.re.find_at(self.haystack, self.last_end)?;
+            let m = if self.earliest {
+                self.re.find_earliest_at(self.haystack, self.last_end)?
+            } else {
+                self.re.find_at(self.haystack, self.last_end)?
+            };
             if m.is_empty() {
                 // Empty match: advance past it so the search makes progress, and
                 // drop it if it sits exactly where the previous match ended (the
@@ -282,6 +526,49 @@ impl<'h, M: RegexSearch> Iterator for Matches<'_, 'h, M> {
     }
 }
 
+/// Iterator over every match, returned by [`RegexSearch::find_overlapping_iter`].
+pub struct OverlappingMatches<'h> {
+    inner: std::vec::IntoIter<Match<'h>>,
+}
+
+impl<'h> Iterator for OverlappingMatches<'h> {
+    type Item = Match<'h>;
+
+    fn next(&mut self) -> Option<Match<'h>> {
+        self.inner.next()
+    }
+}
+
+/// Iterator over the capture groups of non-overlapping matches, returned by
+/// [`RegexSearch::captures_iter`].
+pub struct CaptureMatches<'r, 'h, M: ?Sized> {
+    matches: Matches<'r, 'h, M>,
+}
+
+impl<'h, M: RegexSearch> Iterator for CaptureMatches<'_, 'h, M> {
+    type Item = Captures<'h>;
+
+    fn next(&mut self) -> Option<Captures<'h>> {
+        let m = self.matches.next()?;
+        Some(captures_of(self.matches.re.pike_vm(), m))
+    }
+}
+
+/// The capture groups of `m`, found by running `vm` over its bounds.
+fn captures_of<'h>(vm: &PikeVm, m: Match<'h>) -> Captures<'h> {
+    let slots = vm.slots(m.haystack, m.start, m.end).unwrap_or_else(|| {
+        let mut slots = vec![None; vm.captures_len() * 2];
+        slots[0] = Some(m.start);
+        slots[1] = Some(m.end);
+        slots
+    });
+    Captures {
+        haystack: m.haystack,
+        slots,
+        names: vm.names().clone(),
+    }
+}
+
 /// Iterator over the substrings between matches, returned by [`RegexSearch::split`].
 pub struct Split<'r, 'h, M: ?Sized> {
     finder: Matches<'r, 'h, M>,
@@ -336,6 +623,30 @@ impl<'h, M: RegexSearch> Iterator for SplitN<'_, 'h, M> {
     }
 }
 
+/// The first match of the anchored `prefix` matcher trying each start position in
+/// `haystack` from `start` on, for the leftmost searches.
+fn leftmost<'h>(haystack: &'h str, start: usize, prefix: impl Fn(&'h str, Option<char>) -> Option<(&'h str, &'h str)>) -> Option<Match<'h>> {
+    let mut pos = start;
+    while pos <= haystack.len() {
+        if !haystack.is_char_boundary(pos) {
+            pos += 1;
+            continue;
+        }
+        // The char immediately before `pos`, so the matcher's zero-width
+        // assertions see the right context rather than start-of-text everywhere.
+        let prev = haystack[..pos].chars().next_back();
+        if let Some((matched, _)) = prefix(&haystack[pos..], prev) {
+            return Some(Match {
+                haystack,
+                start: pos,
+                end: pos + matched.len(),
+            });
+        }
+        pos += 1;
+    }
+    None
+}
+
 /// The next char boundary strictly after `pos` (used to advance past an empty match).
 fn next_char_boundary(haystack: &str, mut pos: usize) -> usize {
     pos += 1;
@@ -350,6 +661,37 @@ impl RegexSearch for crate::Regex {
         // Forward to the inherent interpreter; fully-qualified so this never recurses.
         crate::Regex::find_prefix(self, from, prev)
     }
+
+    fn pike_vm(&self) -> &PikeVm {
+        crate::Regex::pike_vm(self)
+    }
+
+    fn find_prefix_earliest<'h>(&self, from: &'h str, prev: Option<char>) -> Option<(&'h str, &'h str)> {
+        crate::Regex::find_prefix_earliest(self, from, prev)
+    }
+
+    fn find_prefix_ends(&self, from: &str, prev: Option<char>) -> Vec<usize> {
+        crate::Regex::find_prefix_ends(self, from, prev)
+    }
+
+    fn find_at<'h>(&self, haystack: &'h str, start: usize) -> Option<Match<'h>> {
+        let (start, end) = self.search_at(haystack, start)?;
+        Some(Match {
+            haystack,
+            start,
+            end,
+        })
+    }
+}
+
+impl RegexSearch for PikeVm {
+    fn find_prefix<'h>(&self, from: &'h str, prev: Option<char>) -> Option<(&'h str, &'h str)> {
+        PikeVm::find_prefix(self, from, prev)
+    }
+
+    fn pike_vm(&self) -> &PikeVm {
+        self
+    }
 }
 
 #[cfg(feature = "jit")]
@@ -357,6 +699,27 @@ impl RegexSearch for crate::JitRegex {
     fn find_prefix<'h>(&self, from: &'h str, prev: Option<char>) -> Option<(&'h str, &'h str)> {
         crate::JitRegex::find_prefix(self, from, prev)
     }
+
+    fn pike_vm(&self) -> &PikeVm {
+        crate::JitRegex::pike_vm(self)
+    }
+
+    fn find_prefix_earliest<'h>(&self, from: &'h str, prev: Option<char>) -> Option<(&'h str, &'h str)> {
+        crate::JitRegex::find_prefix_earliest(self, from, prev)
+    }
+
+    fn find_prefix_ends(&self, from: &str, prev: Option<char>) -> Vec<usize> {
+        crate::JitRegex::find_prefix_ends(self, from, prev)
+    }
+
+    fn find_at<'h>(&self, haystack: &'h str, start: usize) -> Option<Match<'h>> {
+        let (start, end) = self.search_at(haystack, start)?;
+        Some(Match {
+            haystack,
+            start,
+            end,
+        })
+    }
 }
 
 #[cfg(test)]
@@ -413,6 +776,32 @@ mod tests {
         assert_eq!(empties, [(0, 0), (1, 2)]);
     }
 
+    #[test]
+    fn find_earliest_iter_stops_at_first_accept() {
+        let spans: Vec<_> = re("a+").find_earliest_iter("aaa").map(|m| m.range()).collect();
+        assert_eq!(spans, [0..1, 1..2, 2..3]);
+        // Leftmost-first would prefer `abc`; the earliest search ends at `a`.
+        assert_eq!(re("abc|a").find_earliest("abc").unwrap().range(), 0..1);
+    }
+
+    #[test]
+    fn shortest_match_may_not_be_leftmost() {
+        assert_eq!(re("a+|b").shortest_match("aab"), Some(1));
+        assert_eq!(re("xa+|b").shortest_match("xaab"), Some(2));
+        assert_eq!(re("abc|b").shortest_match("abc"), Some(2));
+        assert_eq!(re("[0-9]").shortest_match("abc"), None);
+    }
+
+    #[test]
+    fn find_overlapping_iter_reports_every_match() {
+        let spans: Vec<_> = re("a+").find_overlapping_iter("aaa").map(|m| m.range()).collect();
+        assert_eq!(spans, [0..1, 1..2, 0..2, 2..3, 1..3, 0..3]);
+
+        // Every alternative counts, not only the preferred one.
+        let spans: Vec<_> = re("a|ab").find_overlapping_iter("ab").map(|m| m.range()).collect();
+        assert_eq!(spans, [0..1, 0..2]);
+    }
+
     #[test]
     fn replace_first_only() {
         let re = re("[0-9]+");
@@ -439,6 +828,54 @@ mod tests {
         assert!(matches!(re.replace_all("abc", "#"), Cow::Borrowed("abc")));
     }
 
+    #[test]
+    fn captures_by_index_and_name() {
+        let re = re("(?P<key>[a-z]+)=(?:([0-9]+)|(?P<word>[a-z]+))");
+        let caps = re.captures("-- size=12 --").unwrap();
+        assert_eq!(caps.len(), 4);
+        assert_eq!(re.captures_len(), 4);
+        assert_eq!(caps.get_match().range(), 3..10);
+        assert_eq!(&caps[1], "size");
+        assert_eq!(&caps["key"], "size");
+        assert_eq!(caps.get(2).map(|m| m.range()), Some(8..10));
+        assert!(caps.name("word").is_none(), "the other branch didn't participate");
+        assert!(caps.get(4).is_none());
+        assert!(re.captures("size").is_none());
+    }
+
+    #[test]
+    fn captures_iter_follows_find_iter() {
+        let re = re("([a-z])([0-9])?");
+        let groups: Vec<_> = re
+            .captures_iter("a1 b c3")
+            .map(|caps| (caps[1].to_string(), caps.get(2).map(|m| m.as_str())))
+            .collect();
+        assert_eq!(groups, [("a".to_string(), Some("1")), ("b".to_string(), None), ("c".to_string(), Some("3"))]);
+    }
+
+    #[test]
+    fn expand_group_references() {
+        let re = re("(?P<first>[a-z]+) (?P<last>[a-z]+)");
+        let caps = re.captures("ada lovelace").unwrap();
+        let expand = |replacement: &str| {
+            let mut out = String::new();
+            caps.expand(replacement, &mut out);
+            out
+        };
+        assert_eq!(expand("$last, $first"), "lovelace, ada");
+        assert_eq!(expand("$2_$1"), "ada", "`$2_` is a name, not group 2");
+        assert_eq!(expand("${2}_${1}"), "lovelace_ada");
+        assert_eq!(expand("$$1 costs $"), "$1 costs $");
+        assert_eq!(expand("$9${missing}$ {x}"), "$ {x}");
+    }
+
+    #[test]
+    fn replace_expands_group_references() {
+        let re = re("([0-9]+)-([0-9]+)");
+        assert_eq!(re.replace_all("1-2, 30-40", "$2-$1"), "2-1, 40-30");
+        assert_eq!(re.replace("1-2", "$$"), "$");
+    }
+
     #[test]
     fn split_on_matches() {
         let re = re("[, ]+");
@@ -0,0 +1,709 @@
+//! A compact binary format for compiled DFAs and regexes, so a program that builds
+//! its patterns at runtime can compile them once and load them on later starts
+//! instead of parsing and running the subset construction again.
+//!
+//! [`Dfa::to_bytes`] writes one DFA, and [`Regex::to_bytes`] a whole regex: its DFA,
+//! the every-match DFA, the capture NFA and the unanchored search, which is all the
+//! interpreter and the JIT run from. [`Regex::from_bytes`] gives back a regex with no
+//! [`SimpleRegexAst`], since nothing needs it. Loading checks the input holds together
+//! (every edge leads to a state, a state's classes don't overlap, and so on) rather
+//! than trusting it, as it may come from a file.
+//!
+//! Both start with a five-byte magic number, `ctdfa` or `ctrgx`, and the format
+//! version, [`FORMAT_VERSION`]. Numbers are unsigned LEB128 varints, and chars their
+//! scalar values. A DFA is its flags (bit 0: [`Dfa::lock_step`]), the
+//! [`Minimization`] counts, its accepting sinks, and then each state's id and edges.
+//! An edge is its [`TransitionEvent`], a tag byte and any payload, then its target.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use super::*;
+use crate::dfa::event_ranges;
+use crate::prefilter::Prefilter;
+use crate::unanchored::SearchDfas;
+
+/// The version of the format [`Dfa::to_bytes`] and [`Regex::to_bytes`] write, and
+/// the only one this build loads. It changes whenever the layout does.
+pub const FORMAT_VERSION: u8 = 1;
+
+const DFA_MAGIC: &[u8; 5] = b"ctdfa";
+const REGEX_MAGIC: &[u8; 5] = b"ctrgx";
+
+/// Why bytes given to [`Dfa::from_bytes`] or [`Regex::from_bytes`] didn't load, and where.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LoadError {
+    pub kind: LoadErrorKind,
+    /// The byte offset in the input where reading failed, or for an automaton that
+    /// doesn't hold together, where it starts.
+    pub offset: usize,
+    /// What is wrong.
+    pub message: String,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LoadErrorKind {
+    /// The input doesn't start with the magic number, so it isn't a serialized DFA
+    /// (or regex) at all.
+    NotSerialized,
+    /// The input is from a version of the format this build can't read.
+    UnsupportedVersion,
+    /// The input ends part way through, or goes on past the end.
+    Truncated,
+    /// A value out of range, or an automaton that doesn't hold together.
+    Invalid,
+}
+
+impl LoadError {
+    fn new(kind: LoadErrorKind, offset: usize, message: impl Into<String>) -> LoadError {
+        LoadError {
+            kind,
+            offset,
+            message: message.into(),
+        }
+    }
+}
+
+impl std::fmt::Display for LoadError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "invalid serialized automaton at offset {}: {}", self.offset, self.message)
+    }
+}
+
+impl std::error::Error for LoadError {}
+
+impl Dfa {
+    /// The DFA in the binary format, to load with [`Dfa::from_bytes`].
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Writer::header(DFA_MAGIC);
+        out.dfa(self);
+        out.bytes
+    }
+
+    /// Loads a DFA written by [`Dfa::to_bytes`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Dfa, LoadError> {
+        let mut input = Reader::header(bytes, DFA_MAGIC)?;
+        let dfa = input.dfa()?;
+        input.end()?;
+        Ok(dfa)
+    }
+}
+
+impl Regex {
+    /// The regex in the binary format, to load with [`Regex::from_bytes`]. Builds the
+    /// parts of the regex that are otherwise built the first time they're used, so the
+    /// loaded regex never needs to.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut out = Writer::header(REGEX_MAGIC);
+        out.u8(u8::from(self.bytes));
+        out.dfa(&self.dfa);
+        out.dfa(self.all_matches_dfa());
+        let pike_vm = self.pike_vm();
+        out.nfa(&pike_vm.nfa);
+        out.varint(pike_vm.names().len() as u64);
+        for name in pike_vm.names().iter() {
+            match name {
+                Some(name) => {
+                    out.u8(1);
+                    out.varint(name.len() as u64);
+                    out.bytes.extend_from_slice(name.as_bytes());
+                }
+                None => out.u8(0),
+            }
+        }
+        let searcher = self.searcher();
+        let (tag, prefix) = match &searcher.prefilter {
+            None => (0, &[][..]),
+            Some(Prefilter::Literal(literal)) => (1, &literal[..]),
+            Some(Prefilter::Bytes(bytes)) => (2, &bytes[..]),
+        };
+        out.u8(tag);
+        out.varint(prefix.len() as u64);
+        out.bytes.extend_from_slice(prefix);
+        match &searcher.dfas {
+            Some(dfas) => {
+                out.u8(1);
+                out.dfa(&dfas.forward);
+                out.dfa(&dfas.reverse);
+            }
+            None => out.u8(0),
+        }
+        out.bytes
+    }
+
+    /// Loads a regex written by [`Regex::to_bytes`]. It matches, searches, captures and
+    /// compiles to native code like the regex it was written from, but has no
+    /// [`Regex::ast`].
+    pub fn from_bytes(bytes: &[u8]) -> Result<Regex, LoadError> {
+        let mut input = Reader::header(bytes, REGEX_MAGIC)?;
+        let is_bytes = match input.u8()? {
+            0 => false,
+            1 => true,
+            flags => return Err(input.invalid(format!("unknown regex flags {flags:#04x}"))),
+        };
+        let dfa = input.single_dfa()?;
+        let all_matches_dfa = input.single_dfa()?;
+        let nfa = input.nfa()?;
+        let count = input.varint()?;
+        let mut names = vec![];
+        for _ in 0..count {
+            names.push(match input.u8()? {
+                0 => None,
+                1 => Some(input.string()?),
+                tag => return Err(input.invalid(format!("unknown capture name tag {tag}"))),
+            });
+        }
+        if names.is_empty() {
+            return Err(input.invalid("no capture group 0"));
+        }
+        let slots = names.len() * 2;
+        if nfa
+            .transitions
+            .values()
+            .flatten()
+            .any(|(event, _)| matches!(event, TransitionEvent::Save(slot) if *slot as usize >= slots))
+        {
+            return Err(input.invalid(format!("a capture slot past the {slots} of {} groups", names.len())));
+        }
+        let tag = input.u8()?;
+        let length = input.varint()?;
+        let prefix = input.take(length)?.to_vec();
+        let prefilter = match tag {
+            0 if prefix.is_empty() => None,
+            1 if !prefix.is_empty() => Some(Prefilter::Literal(prefix)),
+            2 if !prefix.is_empty() => Some(Prefilter::Bytes(prefix)),
+            _ => return Err(input.invalid(format!("a prefilter with tag {tag} and {length} bytes"))),
+        };
+        let dfas = match input.u8()? {
+            0 => None,
+            1 => Some(SearchDfas {
+                forward: input.single_dfa()?,
+                reverse: input.single_dfa()?,
+            }),
+            tag => return Err(input.invalid(format!("unknown search DFA tag {tag}"))),
+        };
+        input.end()?;
+        Ok(Regex {
+            dfa,
+            source: Source::Loaded {
+                pike_vm: PikeVm {
+                    nfa,
+                    names: Arc::from(names),
+                    bytes: is_bytes,
+                },
+                all_matches_dfa,
+                searcher: Searcher {
+                    dfas,
+                    prefilter,
+                },
+            },
+            bytes: is_bytes,
+        })
+    }
+}
+
+/// Writes the format into `bytes`.
+struct Writer {
+    bytes: Vec<u8>,
+}
+
+impl Writer {
+    fn header(magic: &[u8; 5]) -> Writer {
+        let mut bytes = magic.to_vec();
+        bytes.push(FORMAT_VERSION);
+        Writer {
+            bytes,
+        }
+    }
+
+    fn u8(&mut self, value: u8) {
+        self.bytes.push(value);
+    }
+
+    fn varint(&mut self, mut value: u64) {
+        while value >= 0x80 {
+            self.bytes.push(value as u8 | 0x80);
+            value >>= 7;
+        }
+        self.bytes.push(value as u8);
+    }
+
+    fn dfa(&mut self, dfa: &Dfa) {
+        self.u8(u8::from(dfa.lock_step));
+        self.varint(dfa.minimization.before as u64);
+        self.varint(dfa.minimization.after as u64);
+        self.varint(dfa.final_states.len() as u64);
+        for state in &dfa.final_states {
+            self.varint(u64::from(*state));
+        }
+        self.varint(u64::from(dfa.final_state));
+        self.transitions(&dfa.transitions);
+    }
+
+    fn nfa(&mut self, nfa: &Nfa) {
+        self.varint(u64::from(nfa.final_state));
+        self.transitions(&nfa.transitions);
+    }
+
+    fn transitions(&mut self, transitions: &BTreeMap<u32, Vec<(TransitionEvent, u32)>>) {
+        self.varint(transitions.len() as u64);
+        for (state, edges) in transitions {
+            self.varint(u64::from(*state));
+            self.varint(edges.len() as u64);
+            for (event, target) in edges {
+                self.event(event);
+                self.varint(u64::from(*target));
+            }
+        }
+    }
+
+    fn event(&mut self, event: &TransitionEvent) {
+        match event {
+            TransitionEvent::Epsilon => self.u8(0),
+            TransitionEvent::Char(c) => {
+                self.u8(1);
+                self.varint(u64::from(*c));
+            }
+            TransitionEvent::Chars(inverted, entries) => {
+                self.u8(2);
+                self.u8(u8::from(*inverted));
+                self.varint(entries.len() as u64);
+                for entry in entries {
+                    match entry {
+                        GroupEntry::Char(c) => {
+                            self.u8(0);
+                            self.varint(u64::from(*c));
+                        }
+                        GroupEntry::Range(start, end) => {
+                            self.u8(1);
+                            self.varint(u64::from(*start));
+                            self.varint(u64::from(*end));
+                        }
+                    }
+                }
+            }
+            TransitionEvent::EndOfInput => self.u8(3),
+            TransitionEvent::StartOfText => self.u8(4),
+            TransitionEvent::WordBoundary {
+                kind,
+                unicode,
+            } => {
+                self.u8(5);
+                self.u8(match kind {
+                    WordBoundaryKind::Both => 0,
+                    WordBoundaryKind::BothNegate => 1,
+                    WordBoundaryKind::Start => 2,
+                    WordBoundaryKind::End => 3,
+                    WordBoundaryKind::StartHalf => 4,
+                    WordBoundaryKind::EndHalf => 5,
+                });
+                self.u8(u8::from(*unicode));
+            }
+            TransitionEvent::StartOfLine {
+                crlf,
+            } => {
+                self.u8(6);
+                self.u8(u8::from(*crlf));
+            }
+            TransitionEvent::EndOfLine {
+                crlf,
+            } => {
+                self.u8(7);
+                self.u8(u8::from(*crlf));
+            }
+            TransitionEvent::Save(slot) => {
+                self.u8(8);
+                self.varint(u64::from(*slot));
+            }
+            TransitionEvent::End => self.u8(9),
+        }
+    }
+}
+
+/// Reads the format from `bytes`, `at` bytes in.
+struct Reader<'a> {
+    bytes: &'a [u8],
+    at: usize,
+}
+
+impl<'a> Reader<'a> {
+    fn header(bytes: &'a [u8], magic: &[u8; 5]) -> Result<Reader<'a>, LoadError> {
+        if !bytes.starts_with(magic) {
+            let what = if magic == DFA_MAGIC { "DFA" } else { "regex" };
+            return Err(LoadError::new(LoadErrorKind::NotSerialized, 0, format!("not a serialized {what}")));
+        }
+        let mut input = Reader {
+            bytes,
+            at: magic.len(),
+        };
+        match input.u8()? {
+            FORMAT_VERSION => Ok(input),
+            version => Err(LoadError::new(
+                LoadErrorKind::UnsupportedVersion,
+                magic.len(),
+                format!("format version {version}, where this build reads {FORMAT_VERSION}"),
+            )),
+        }
+    }
+
+    fn invalid(&self, message: impl Into<String>) -> LoadError {
+        LoadError::new(LoadErrorKind::Invalid, self.at, message)
+    }
+
+    fn take(&mut self, length: usize) -> Result<&'a [u8], LoadError> {
+        if self.bytes.len() - self.at < length {
+            return Err(LoadError::new(LoadErrorKind::Truncated, self.bytes.len(), "unexpected end of input"));
+        }
+        self.at += length;
+        Ok(&self.bytes[self.at - length..self.at])
+    }
+
+    fn end(&self) -> Result<(), LoadError> {
+        if self.at < self.bytes.len() {
+            return Err(LoadError::new(LoadErrorKind::Truncated, self.at, format!("{} bytes past the end", self.bytes.len() - self.at)));
+        }
+        Ok(())
+    }
+
+    fn u8(&mut self) -> Result<u8, LoadError> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn bool(&mut self) -> Result<bool, LoadError> {
+        match self.u8()? {
+            0 => Ok(false),
+            1 => Ok(true),
+            value => Err(self.invalid(format!("{value} for a flag"))),
+        }
+    }
+
+    fn varint(&mut self) -> Result<usize, LoadError> {
+        let mut value = 0u64;
+        for shift in (0..64).step_by(7) {
+            let byte = self.u8()?;
+            value |= u64::from(byte & 0x7F) << shift;
+            if byte & 0x80 == 0 {
+                return usize::try_from(value).map_err(|_| self.invalid(format!("{value} is too large")));
+            }
+        }
+        Err(self.invalid("an overlong number"))
+    }
+
+    fn u32(&mut self) -> Result<u32, LoadError> {
+        let value = self.varint()?;
+        u32::try_from(value).map_err(|_| self.invalid(format!("{value} is too large for a state or slot")))
+    }
+
+    fn char(&mut self) -> Result<char, LoadError> {
+        let value = self.u32()?;
+        char::from_u32(value).ok_or_else(|| self.invalid(format!("{value:#x} is not a char")))
+    }
+
+    fn string(&mut self) -> Result<String, LoadError> {
+        let length = self.varint()?;
+        let bytes = self.take(length)?;
+        String::from_utf8(bytes.to_vec()).map_err(|_| self.invalid("a name that isn't UTF-8"))
+    }
+
+    fn dfa(&mut self) -> Result<Dfa, LoadError> {
+        let start = self.at;
+        let lock_step = self.bool()?;
+        let minimization = Minimization {
+            before: self.varint()?,
+            after: self.varint()?,
+        };
+        let count = self.varint()?;
+        let mut final_states = vec![];
+        for _ in 0..count {
+            final_states.push(self.u32()?);
+        }
+        let final_state = self.u32()?;
+        let transitions = self.transitions()?;
+        let dfa = Dfa {
+            transitions,
+            final_state,
+            final_states,
+            lock_step,
+            minimization,
+        };
+        dfa.validate().map_err(|message| LoadError::new(LoadErrorKind::Invalid, start, message))?;
+        Ok(dfa)
+    }
+
+    /// A DFA of one pattern, as a regex has.
+    fn single_dfa(&mut self) -> Result<Dfa, LoadError> {
+        let start = self.at;
+        let dfa = self.dfa()?;
+        if dfa.final_states.len() != 1 {
+            return Err(LoadError::new(LoadErrorKind::Invalid, start, format!("a regex DFA with {} patterns", dfa.final_states.len())));
+        }
+        Ok(dfa)
+    }
+
+    fn nfa(&mut self) -> Result<Nfa, LoadError> {
+        let start = self.at;
+        let final_state = self.u32()?;
+        let transitions = self.transitions()?;
+        let invalid = |message: String| LoadError::new(LoadErrorKind::Invalid, start, message);
+        // The Pike VM sizes its per-state tables by the state count and indexes them by id.
+        if let Some((index, state)) = transitions.keys().enumerate().find(|(index, state)| *index as u32 != **state) {
+            return Err(invalid(format!("NFA state {state} where state {index} belongs")));
+        }
+        for state in [0, final_state] {
+            if !transitions.contains_key(&state) {
+                return Err(invalid(format!("no NFA state {state}")));
+            }
+        }
+        for (state, edges) in &transitions {
+            if let Some((_, target)) = edges.iter().find(|(_, target)| !transitions.contains_key(target)) {
+                return Err(invalid(format!("an edge from NFA state {state} to missing state {target}")));
+            }
+        }
+        Ok(Nfa {
+            transitions,
+            final_state,
+        })
+    }
+
+    fn transitions(&mut self) -> Result<BTreeMap<u32, Vec<(TransitionEvent, u32)>>, LoadError> {
+        let mut transitions = BTreeMap::new();
+        for _ in 0..self.varint()? {
+            let state = self.u32()?;
+            let mut edges = vec![];
+            for _ in 0..self.varint()? {
+                let event = self.event()?;
+                edges.push((event, self.u32()?));
+            }
+            if transitions.insert(state, edges).is_some() {
+                return Err(self.invalid(format!("state {state} listed twice")));
+            }
+        }
+        Ok(transitions)
+    }
+
+    fn event(&mut self) -> Result<TransitionEvent, LoadError> {
+        Ok(match self.u8()? {
+            0 => TransitionEvent::Epsilon,
+            1 => TransitionEvent::Char(self.char()?),
+            2 => {
+                let inverted = self.bool()?;
+                let mut entries = vec![];
+                for _ in 0..self.varint()? {
+                    entries.push(match self.u8()? {
+                        0 => GroupEntry::Char(self.char()?),
+                        1 => {
+                            let (start, end) = (self.char()?, self.char()?);
+                            if start > end {
+                                return Err(self.invalid(format!("a class range from {start:?} down to {end:?}")));
+                            }
+                            GroupEntry::Range(start, end)
+                        }
+                        tag => return Err(self.invalid(format!("unknown class entry tag {tag}"))),
+                    });
+                }
+                TransitionEvent::Chars(inverted, entries)
+            }
+            3 => TransitionEvent::EndOfInput,
+            4 => TransitionEvent::StartOfText,
+            5 => {
+                let kind = match self.u8()? {
+                    0 => WordBoundaryKind::Both,
+                    1 => WordBoundaryKind::BothNegate,
+                    2 => WordBoundaryKind::Start,
+                    3 => WordBoundaryKind::End,
+                    4 => WordBoundaryKind::StartHalf,
+                    5 => WordBoundaryKind::EndHalf,
+                    kind => return Err(self.invalid(format!("unknown word boundary kind {kind}"))),
+                };
+                TransitionEvent::WordBoundary {
+                    kind,
+                    unicode: self.bool()?,
+                }
+            }
+            6 => TransitionEvent::StartOfLine {
+                crlf: self.bool()?,
+            },
+            7 => TransitionEvent::EndOfLine {
+                crlf: self.bool()?,
+            },
+            8 => TransitionEvent::Save(self.u32()?),
+            9 => TransitionEvent::End,
+            tag => return Err(self.invalid(format!("unknown edge tag {tag}"))),
+        })
+    }
+}
+
+impl Dfa {
+    /// Whether the DFA is one the matchers can run: it starts at state 0, each
+    /// pattern's accepting sink has no edges, every edge leads to a state, only `End`
+    /// edges lead to a sink without stepping over a char or assertion, and a state's
+    /// chars each lead one way. Otherwise, what is wrong.
+    fn validate(&self) -> Result<(), String> {
+        let known = |state: &u32| self.transitions.contains_key(state) || self.final_states.contains(state);
+        if self.final_states.first() != Some(&self.final_state) {
+            return Err(format!("final state {} isn't the first pattern's sink", self.final_state));
+        }
+        if let Some(sink) = self.final_states.iter().find(|sink| self.transitions.contains_key(sink)) {
+            return Err(format!("accepting sink {sink} has edges"));
+        }
+        if !known(&0) {
+            return Err("no start state 0".to_string());
+        }
+        for (state, edges) in &self.transitions {
+            let mut ranges = vec![];
+            for (event, target) in edges {
+                if !known(target) {
+                    return Err(format!("an edge from state {state} to missing state {target}"));
+                }
+                match event {
+                    TransitionEvent::Epsilon | TransitionEvent::Save(_) => return Err(format!("an epsilon edge from state {state}")),
+                    TransitionEvent::End if !self.final_states.contains(target) => {
+                        return Err(format!("an end edge from state {state} to state {target}, which doesn't accept"));
+                    }
+                    _ => {}
+                }
+                ranges.extend(event_ranges(event));
+            }
+            ranges.sort_unstable();
+            if ranges.windows(2).any(|pair| pair[1].0 <= pair[0].1) {
+                return Err(format!("state {state} has two edges for the same char"));
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn dfas_round_trip() {
+        let options = ParseOptions {
+            set_operators: true,
+            ..ParseOptions::default()
+        };
+        for pattern in [r"[a-z_][a-z0-9_]*", r"(?m)^\s*#.*$", r"\bfoo\b|\B\d+", r"(?R)(?:^|a)*", "", r"[^\n]+(?-[0-9]+)"] {
+            let regex = Regex::parse_with(pattern, options).unwrap();
+            let loaded = Dfa::from_bytes(&regex.dfa.to_bytes()).unwrap();
+            assert_eq!(loaded.transitions, regex.dfa.transitions, "{pattern}");
+            assert_eq!((loaded.final_state, &loaded.final_states), (regex.dfa.final_state, &regex.dfa.final_states));
+            assert_eq!((loaded.lock_step, loaded.minimization), (regex.dfa.lock_step, regex.dfa.minimization));
+        }
+        let set = RegexSet::new(["[a-z]+", "let", "[0-9]+"]).unwrap();
+        let dfa = set.dfa.as_ref().unwrap();
+        assert_eq!(Dfa::from_bytes(&dfa.to_bytes()).unwrap().final_states, dfa.final_states);
+    }
+
+    #[test]
+    fn loaded_regexes_match_without_their_ast() {
+        let cases: [(&str, &str); 4] = [
+            (r"(?<key>[a-z]+)=(?<value>\d+)", "x; key=42 rest"),
+            (r"\bfo+\b", "a foo, fooo!"),
+            (r"(?m)^\w+$", "one\ntwo three\nfour"),
+            ("(?:a|ab)(?:c|bcd)", "zabcd"),
+        ];
+        for (pattern, haystack) in cases {
+            let regex = Regex::parse(pattern).unwrap();
+            let loaded = Regex::from_bytes(&regex.to_bytes()).unwrap();
+            assert!(loaded.ast().is_none());
+            let found = |regex: &Regex| regex.find_iter(haystack).map(|x| x.range()).collect::<Vec<_>>();
+            assert_eq!(found(&loaded), found(&regex), "{pattern}");
+            let captures = |regex: &Regex| {
+                regex
+                    .captures(haystack)
+                    .map(|x| (0..x.len()).map(|group| x.get(group).map(|x| x.range())).collect::<Vec<_>>())
+            };
+            assert_eq!(captures(&loaded), captures(&regex), "{pattern}");
+            assert_eq!(loaded.find_prefix_ends(haystack, None), regex.find_prefix_ends(haystack, None));
+            assert_eq!(loaded.could_capture_newline(), regex.could_capture_newline(), "{pattern}");
+            assert_eq!(loaded.pike_vm().capture_names().collect::<Vec<_>>(), regex.pike_vm().capture_names().collect::<Vec<_>>());
+        }
+        let regex = Regex::parse_bytes(r"[\x80-\xFF]+").unwrap();
+        let loaded = Regex::from_bytes(&regex.to_bytes()).unwrap();
+        assert!(loaded.is_bytes());
+        assert_eq!(loaded.find_prefix_bytes(b"\xFF\xFEa", None), Some((&b"\xFF\xFE"[..], &b"a"[..])));
+        // A set of loaded regexes combines their NFAs all the same.
+        let set = RegexSet::from_regexes(vec![Regex::from_bytes(&Regex::parse("[a-z]+").unwrap().to_bytes()).unwrap()]);
+        assert!(set.is_match("abc"));
+    }
+
+    #[test]
+    fn loading_rejects_bad_input() {
+        let dfa = Regex::parse("a[b-d]+").unwrap().dfa;
+        let bytes = dfa.to_bytes();
+        let kind = |bytes: &[u8]| Dfa::from_bytes(bytes).unwrap_err().kind;
+        assert_eq!(kind(b"not a dfa"), LoadErrorKind::NotSerialized);
+        assert_eq!(kind(&Regex::parse("a").unwrap().to_bytes()), LoadErrorKind::NotSerialized);
+        let mut newer = bytes.clone();
+        newer[5] = FORMAT_VERSION + 1;
+        assert_eq!(kind(&newer), LoadErrorKind::UnsupportedVersion);
+        assert_eq!(kind(&bytes[..bytes.len() - 1]), LoadErrorKind::Truncated);
+        assert_eq!(kind(&[&bytes[..], &[0]].concat()), LoadErrorKind::Truncated);
+
+        // Each edge pointed somewhere else, or given another char.
+        let mut broken = dfa.clone();
+        broken.transitions.get_mut(&0).unwrap()[0].1 = 99;
+        let error = Dfa::from_bytes(&broken.to_bytes()).unwrap_err();
+        assert_eq!(error.kind, LoadErrorKind::Invalid);
+        assert!(error.message.contains("missing state 99"), "{error}");
+        let mut broken = dfa.clone();
+        let (state, edges) = broken.transitions.iter_mut().find(|(_, edges)| edges.len() > 1).unwrap();
+        let state = *state;
+        let class = edges.iter().find(|(event, _)| matches!(event, TransitionEvent::Chars(..))).unwrap().clone();
+        edges.push(class);
+        let error = Dfa::from_bytes(&broken.to_bytes()).unwrap_err();
+        assert_eq!(error.message, format!("state {state} has two edges for the same char"));
+        let mut broken = dfa.clone();
+        broken.transitions.get_mut(&0).unwrap().push((TransitionEvent::End, 0));
+        assert!(Dfa::from_bytes(&broken.to_bytes()).unwrap_err().message.contains("doesn't accept"));
+
+        // A regex whose NFA skips a state id.
+        let regex = Regex::parse("ab").unwrap();
+        let mut nfa = regex.nfa();
+        let last = *nfa.transitions.keys().next_back().unwrap();
+        let edges = nfa.transitions.remove(&last).unwrap();
+        nfa.transitions.insert(0x70, edges);
+        for edges in nfa.transitions.values_mut() {
+            for (_, target) in edges.iter_mut().filter(|(_, target)| *target == last) {
+                *target = 0x70;
+            }
+        }
+        if nfa.final_state == last {
+            nfa.final_state = 0x70;
+        }
+        let mut writer = Writer::header(REGEX_MAGIC);
+        writer.nfa(&nfa);
+        let mut reader = Reader::header(&writer.bytes, REGEX_MAGIC).unwrap();
+        let error = reader.nfa().unwrap_err();
+        assert_eq!(error.message, format!("NFA state 112 where state {last} belongs"));
+
+        // Whatever a corrupted regex loads as, using it mustn't panic.
+        let mut rng = seeded_rng(0x9E37_79B9_7F4A_7C15);
+        for pattern in ["ab", r"(?<k>[a-z]+)=(?<v>\d+)", r"\bfo+\b", "x[0-9]*y|z"] {
+            let bytes = Regex::parse(pattern).unwrap().to_bytes();
+            for _ in 0..2000 {
+                let mut mutated = bytes.clone();
+                for _ in 0..1 + rng() % 3 {
+                    let at = 6 + (rng() as usize) % (mutated.len() - 6);
+                    mutated[at] = rng() as u8;
+                }
+                let Ok(loaded) = Regex::from_bytes(&mutated) else { continue };
+                for haystack in ["", "ab", "zz k=42 xy abab", "a foo x09y"] {
+                    let _ = loaded.find_iter(haystack).count();
+                    let _ = loaded.captures(haystack);
+                    let _ = loaded.find_prefix_ends(haystack, None);
+                }
+            }
+        }
+    }
+
+    #[cfg(feature = "jit")]
+    #[test]
+    fn loaded_regexes_compile_to_native_code() {
+        let regex = Regex::from_bytes(&Regex::parse(r"\b[a-z]+\d?\b").unwrap().to_bytes()).unwrap();
+        let jit = regex.compile_jit().unwrap();
+        assert_eq!(jit.find_prefix("ab1 cd", None), Some(("ab1", " cd")));
+        assert_eq!(jit.find_iter("ab1 cd2 ef").map(|x| x.as_str()).collect::<Vec<_>>(), ["ab1", "cd2", "ef"]);
+    }
+}
@@ -0,0 +1,290 @@
+//! Matching many simple regexes in one pass.
+//!
+//! A [`RegexSet`] compiles its patterns into a single multi-pattern DFA
+//! ([`Dfa::build_multi`]) whose accepting states are tagged with the patterns they
+//! accept, so one walk over the input reports every pattern's anchored
+//! leftmost-first match. The derive matches a mode's simple regexes and its
+//! literal alternation with one walk of it per token, built first-match
+//! ([`RegexSet::first_match_dfa`]) so the walk stops once the variant that wins is
+//! settled; the literal's variant is then picked by its text.
+//!
+//! [`RegexSet::find_prefix_all`] is that anchored walk at runtime, and
+//! [`RegexSet::matches`] searches a whole haystack with it, reporting which
+//! patterns match anywhere (the `regex` crate's `RegexSet::matches`).
+//!
+//! Zero-width assertions are the exception: the DFA walk takes an assertion edge
+//! only when no consuming edge claims the lookahead, which is only sound for one
+//! pattern at a time. Patterns containing them keep their own DFA and are run
+//! standalone alongside the combined walk; the derive gives each its own matcher.
+
+use super::*;
+
+pub struct RegexSet {
+    pub(crate) regexes: Vec<Regex>,
+    /// The combined DFA over every pattern without zero-width assertions, if any.
+    pub(crate) dfa: Option<Dfa>,
+    /// The set index of each pattern in `dfa`, by its pattern index there.
+    pub(crate) combined: Vec<usize>,
+    /// Whether the patterns match bytes ([`Regex::parse_bytes`]).
+    pub(crate) bytes: bool,
+}
+
+impl RegexSet {
+    /// Parses every pattern, failing with the error of the first invalid one.
+    pub fn new<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<RegexSet, RegexError> {
+        let regexes = patterns.into_iter().map(Regex::parse).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_regexes(regexes))
+    }
+
+    /// [`RegexSet::new`] for bytes-mode patterns, each parsed with [`Regex::parse_bytes`].
+    pub fn new_bytes<'a>(patterns: impl IntoIterator<Item = &'a str>) -> Result<RegexSet, RegexError> {
+        let regexes = patterns.into_iter().map(Regex::parse_bytes).collect::<Result<Vec<_>, _>>()?;
+        Ok(Self::from_regexes(regexes))
+    }
+
+    /// Combines compiled patterns into a set. They must all match the same input:
+    /// either every pattern is bytes-mode or none is.
+    pub fn from_regexes(regexes: Vec<Regex>) -> RegexSet {
+        let bytes = regexes.iter().any(Regex::is_bytes);
+        assert!(regexes.iter().all(|regex| regex.is_bytes() == bytes), "a regex set can't mix str and bytes patterns");
+        let combined: Vec<usize> = regexes.iter().enumerate().filter(|(_, regex)| Self::combines(regex)).map(|(i, _)| i).collect();
+        let dfa = Self::combined_dfa(&regexes, &combined, false);
+        RegexSet {
+            regexes,
+            dfa,
+            combined,
+            bytes,
+        }
+    }
+
+    fn combined_dfa(regexes: &[Regex], combined: &[usize], first_match: bool) -> Option<Dfa> {
+        if combined.is_empty() {
+            return None;
+        }
+        let nfas: Vec<Nfa> = combined.iter().map(|i| regexes[*i].nfa()).collect();
+        Some(Dfa::build_multi(&nfas, first_match))
+    }
+
+    /// The combined DFA for a caller that tries the patterns in order and takes the first that matches, as the derive
+    /// lexes a mode: [`Dfa::build_multi`] with `first_match`, whose walk stops once an earlier pattern has won rather
+    /// than running every pattern to its own end. `None` if every pattern is standalone.
+    pub fn first_match_dfa(&self) -> Option<Dfa> {
+        Self::combined_dfa(&self.regexes, &self.combined, true)
+    }
+
+    /// Whether [`RegexSet::from_regexes`] folds `regex` into the combined DFA. A pattern with zero-width assertions
+    /// isn't, and is matched by its own standalone scan instead.
+    pub fn combines(regex: &Regex) -> bool {
+        !regex.dfa.has_zero_width()
+    }
+
+    /// The number of patterns in the set.
+    pub fn len(&self) -> usize {
+        self.regexes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.regexes.is_empty()
+    }
+
+    /// The compiled patterns, by set index.
+    pub fn regexes(&self) -> &[Regex] {
+        &self.regexes
+    }
+
+    /// Which patterns match anywhere in `haystack`. Every start position is tried
+    /// with [`RegexSet::find_prefix_all`], stopping early once every pattern has matched.
+    pub fn matches(&self, haystack: &str) -> SetMatches {
+        let mut matched = vec![false; self.regexes.len()];
+        let mut unmatched = matched.len();
+        self.scan(haystack, |found| {
+            for (index, length) in found.into_iter().enumerate() {
+                if length.is_some() && !matched[index] {
+                    matched[index] = true;
+                    unmatched -= 1;
+                }
+            }
+            unmatched == 0
+        });
+        SetMatches {
+            matched,
+        }
+    }
+
+    /// Whether any pattern matches anywhere in `haystack`.
+    pub fn is_match(&self, haystack: &str) -> bool {
+        let mut any = false;
+        self.scan(haystack, |found| {
+            any = found.iter().any(Option::is_some);
+            any
+        });
+        any
+    }
+
+    /// Run [`RegexSet::find_prefix_all`] at each char boundary of `haystack` (the end
+    /// included, for empty matches) until `done` returns `true`.
+    fn scan(&self, haystack: &str, mut done: impl FnMut(Vec<Option<usize>>) -> bool) {
+        let mut prev = None;
+        let mut rest = haystack;
+        loop {
+            if done(self.find_prefix_all(rest, prev)) {
+                return;
+            }
+            let mut chars = rest.chars();
+            let Some(ch) = chars.next() else { return };
+            prev = Some(ch);
+            rest = chars.as_str();
+        }
+    }
+}
+
+/// Which patterns of a [`RegexSet`] matched a haystack, by set index; returned by
+/// [`RegexSet::matches`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetMatches {
+    matched: Vec<bool>,
+}
+
+impl SetMatches {
+    /// Whether pattern `index` matched. Panics if `index` is out of range for the set.
+    pub fn matched(&self, index: usize) -> bool {
+        self.matched[index]
+    }
+
+    /// Whether any pattern matched.
+    pub fn matched_any(&self) -> bool {
+        self.matched.contains(&true)
+    }
+
+    /// Whether every pattern matched (vacuously true for an empty set).
+    pub fn matched_all(&self) -> bool {
+        !self.matched.contains(&false)
+    }
+
+    /// The number of patterns in the set, matched or not.
+    pub fn len(&self) -> usize {
+        self.matched.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.matched.is_empty()
+    }
+
+    /// The set indices of the matched patterns, in increasing order.
+    pub fn iter(&self) -> SetMatchesIter<'_> {
+        SetMatchesIter {
+            inner: self.matched.iter().enumerate(),
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a SetMatches {
+    type Item = usize;
+    type IntoIter = SetMatchesIter<'a>;
+
+    fn into_iter(self) -> SetMatchesIter<'a> {
+        self.iter()
+    }
+}
+
+/// The matched set indices of a [`SetMatches`]; see [`SetMatches::iter`].
+pub struct SetMatchesIter<'a> {
+    inner: std::iter::Enumerate<std::slice::Iter<'a, bool>>,
+}
+
+impl Iterator for SetMatchesIter<'_> {
+    type Item = usize;
+
+    fn next(&mut self) -> Option<usize> {
+        self.inner.find_map(|(index, matched)| matched.then_some(index))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The patterns accepted by each state of the combined DFA, read off its `End` edges.
+    fn accepting(dfa: &Dfa) -> Vec<Vec<usize>> {
+        dfa.transitions
+            .values()
+            .map(|edges| {
+                edges
+                    .iter()
+                    .filter(|(event, _)| matches!(event, nfa::TransitionEvent::End))
+                    .map(|(_, target)| dfa.final_pattern(*target).expect("End edges point at a pattern sink"))
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn assertion_patterns_are_kept_standalone() {
+        let set = RegexSet::new(["[a-z]+", "\\bword\\b", "[0-9]+"]).unwrap();
+        assert_eq!(set.len(), 3);
+        assert_eq!(set.combined, vec![0, 2]);
+        assert_eq!(set.dfa.as_ref().unwrap().final_states.len(), 2);
+    }
+
+    #[test]
+    fn states_accept_every_overlapping_pattern() {
+        // After `ab`, both `[a-z]+` and `ab` accept; `abc` is still in progress.
+        let set = RegexSet::new(["[a-z]+", "ab", "abc"]).unwrap();
+        let dfa = set.dfa.as_ref().unwrap();
+        assert!(accepting(dfa).contains(&vec![0, 1]), "some state accepts both [a-z]+ and ab: {:?}", accepting(dfa));
+    }
+
+    #[test]
+    fn find_prefix_all_reports_each_pattern() {
+        let set = RegexSet::new(["[a-z]+", "ab", "abc", "\\bab\\b", "[0-9]+"]).unwrap();
+        assert_eq!(set.find_prefix_all("abd", None), [Some(3), Some(2), None, None, None]);
+        assert_eq!(set.find_prefix_all("ab cd", None), [Some(2), Some(2), None, Some(2), None]);
+        // `prev` reaches the standalone patterns: there's no boundary before `ab` after a letter.
+        assert_eq!(set.find_prefix_all("ab", Some('x')), [Some(2), Some(2), None, None, None]);
+        assert_eq!(set.find_prefix_all("", None), [None; 5]);
+    }
+
+    #[test]
+    fn matches_reports_patterns_matching_anywhere() {
+        let set = RegexSet::new(["foo", "bar", "[0-9]+", "^baz", "qux"]).unwrap();
+        let matches = set.matches("a bar and 42 baz");
+        assert_eq!(matches.iter().collect::<Vec<_>>(), [1, 2]);
+        assert!(matches.matched(2) && !matches.matched(3));
+        assert!(matches.matched_any() && !matches.matched_all());
+        assert_eq!(matches.len(), 5);
+        assert!(set.is_match("food"));
+        assert!(!set.is_match("ba z"));
+
+        let empty = RegexSet::new([]).unwrap();
+        assert!(!empty.matches("anything").matched_any());
+        assert!(empty.matches("anything").matched_all());
+    }
+
+    #[test]
+    fn truncation_is_scoped_per_pattern() {
+        // `a|ab` cuts its own `ab` branch once `a` matched, but must not cut `abc`.
+        let set = RegexSet::new(["a|ab", "abc"]).unwrap();
+        let dfa = set.dfa.as_ref().unwrap();
+        let (_, after_a) = dfa.transitions[&0].iter().find(|(event, _)| event.matches('a')).expect("start consumes a");
+        let after_a = &dfa.transitions[after_a];
+        assert!(after_a.iter().any(|(event, _)| matches!(event, nfa::TransitionEvent::End)));
+        assert!(after_a.iter().any(|(event, _)| event.matches('b')), "abc's thread survives a|ab's accept");
+    }
+
+    #[test]
+    fn first_match_walk_stops_once_an_earlier_pattern_won() {
+        // Per-pattern cuts keep `[a-z ]+!` walking to the `!` after `[a-z]+` matched, so lexing `abc abc ...` with
+        // them rescans the rest of the input at every identifier.
+        let mut set = RegexSet::new(["[a-z]+", "[ \t]+", "[a-z ]+!"]).unwrap();
+        assert_eq!(set.find_prefix_all("abc abc!", None), [Some(3), None, Some(8)]);
+        set.dfa = set.first_match_dfa();
+        assert_eq!(set.find_prefix_all("abc abc!", None), [Some(3), None, None]);
+        assert_eq!(set.find_prefix_all(" abc!", None), [None, Some(1), None]);
+        assert_eq!(set.find_prefix_all(" ab!", None), [None, Some(1), None]);
+
+        // An empty match doesn't settle the walk.
+        let mut set = RegexSet::new(["a*", "b+"]).unwrap();
+        set.dfa = set.first_match_dfa();
+        assert_eq!(set.find_prefix_all("bb", None), [Some(0), Some(2)]);
+    }
+}
@@ -0,0 +1,153 @@
+//! Whether one pattern leaves another nothing to match, which is how the derive spots a
+//! token variant that can never be lexed.
+//!
+//! A tokenizer tries its patterns at each position and picks one by [`Priority`]. A
+//! later pattern is unreachable when an earlier one wins on every input the later one
+//! matches. Both patterns are DFAs, so this is decided by walking their product, the
+//! pairs of states the two reach on the same input, looking for a pair where the later
+//! pattern would win.
+
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::*;
+use crate::dfa::{Ranges, complement};
+
+/// How a tokenizer picks between patterns that match at the same position.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Priority {
+    /// The first pattern that matches wins, however short its match.
+    First,
+    /// The longest match wins, and the earlier pattern breaks a tie.
+    Longest,
+}
+
+impl Regex {
+    /// Whether this pattern, tried before `later`, wins on every input `later` matches,
+    /// so that `later` never gets to match. If it does, returns the shortest string
+    /// `later` matches, on which this pattern wins. Patterns with zero-width assertions
+    /// are never reported, since whether they match depends on the text around the match.
+    pub fn shadows(&self, later: &Regex, priority: Priority) -> Option<String> {
+        if self.dfa.has_zero_width() || later.dfa.has_zero_width() {
+            return None;
+        }
+        // Look for an input `later` wins on: one it accepts where this pattern doesn't,
+        // and, if the first match wins, where this pattern hasn't accepted earlier on
+        // either. `None` is this pattern's walk having died.
+        let start = (Some(0), 0);
+        let mut seen = HashSet::from([start]);
+        let mut queue = VecDeque::from([start]);
+        while let Some((state, later_state)) = queue.pop_front() {
+            let accepts = state.is_some_and(|state| self.dfa.accepts(state));
+            if later.dfa.accepts(later_state) && !accepts {
+                return None;
+            }
+            if accepts && priority == Priority::First {
+                continue;
+            }
+            let edges = state.map(|state| self.dfa.char_edges(state)).unwrap_or_default();
+            let mut covered: Ranges = edges.iter().flat_map(|(ranges, _)| ranges.iter().copied()).collect();
+            covered.sort_unstable();
+            let dead = complement(&covered);
+            for (later_ranges, later_next) in later.dfa.char_edges(later_state) {
+                let steps = edges
+                    .iter()
+                    .filter(|(ranges, _)| overlap(ranges, &later_ranges).is_some())
+                    .map(|(_, next)| Some(*next))
+                    .chain(overlap(&dead, &later_ranges).map(|_| None));
+                for next in steps {
+                    if seen.insert((next, later_next)) {
+                        queue.push_back((next, later_next));
+                    }
+                }
+            }
+        }
+        shortest_match(&later.dfa)
+    }
+}
+
+/// The shortest string `dfa` accepts, if it accepts any.
+fn shortest_match(dfa: &Dfa) -> Option<String> {
+    // Each state reached, with the state and char it was first reached from.
+    let mut parents: HashMap<u32, Option<(u32, char)>> = HashMap::from([(0, None)]);
+    let mut queue = VecDeque::from([0]);
+    while let Some(state) = queue.pop_front() {
+        if dfa.accepts(state) {
+            let mut sample = vec![];
+            let mut at = state;
+            while let Some((parent, c)) = parents[&at] {
+                sample.push(c);
+                at = parent;
+            }
+            return Some(sample.into_iter().rev().collect());
+        }
+        for (ranges, next) in dfa.char_edges(state) {
+            if let (Some(c), false) = (sample_char(&ranges), parents.contains_key(&next)) {
+                parents.insert(next, Some((state, c)));
+                queue.push_back(next);
+            }
+        }
+    }
+    None
+}
+
+/// A char in both normalized range lists, if they overlap.
+fn overlap(a: &[(u32, u32)], b: &[(u32, u32)]) -> Option<(u32, u32)> {
+    let (mut i, mut j) = (0, 0);
+    while i < a.len() && j < b.len() {
+        let (lo, hi) = (a[i].0.max(b[j].0), a[i].1.min(b[j].1));
+        if lo <= hi {
+            return Some((lo, hi));
+        }
+        if a[i].1 < b[j].1 {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    None
+}
+
+/// A char of `ranges` to spell a sample with: a letter, digit or other printable ASCII
+/// char where the class has one, so the sample reads well in an error message.
+fn sample_char(ranges: &[(u32, u32)]) -> Option<char> {
+    let readable = [('a', 'z'), ('0', '9'), ('A', 'Z'), ('!', '~')];
+    readable
+        .iter()
+        .find_map(|&(lo, hi)| overlap(ranges, &[(lo as u32, hi as u32)]))
+        .into_iter()
+        .chain(ranges.iter().copied())
+        .find_map(|(lo, hi)| (lo..=hi).find_map(char::from_u32))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn shadows(earlier: &str, later: &str, priority: Priority) -> Option<String> {
+        Regex::parse(earlier).unwrap().shadows(&Regex::parse(later).unwrap(), priority)
+    }
+
+    #[test]
+    fn a_match_that_always_starts_earlier_shadows_under_first() {
+        assert_eq!(shadows("[a-z]+", "[a-z]+_x", Priority::First).as_deref(), Some("a_x"));
+        assert_eq!(shadows("[a-z]+", "let", Priority::First).as_deref(), Some("let"));
+        assert_eq!(shadows("a|ab", "ab", Priority::First).as_deref(), Some("ab"));
+        assert_eq!(shadows("[0-9]+", "[a-z]+", Priority::First), None);
+        assert_eq!(shadows("let", "[a-z]+", Priority::First), None);
+    }
+
+    #[test]
+    fn only_a_match_at_least_as_long_shadows_under_longest() {
+        assert_eq!(shadows("[a-z]+", "let", Priority::Longest).as_deref(), Some("let"));
+        assert_eq!(shadows("[a-z_]+", "[a-z]+_x", Priority::Longest).as_deref(), Some("a_x"));
+        assert_eq!(shadows("[a-z]+", "[a-z]+_x", Priority::Longest), None);
+        // `a|ab` stops at `a`, leaving `ab` the longer match.
+        assert_eq!(shadows("a|ab", "ab", Priority::Longest), None);
+    }
+
+    #[test]
+    fn assertions_are_never_reported() {
+        assert_eq!(shadows(r"[a-z]+\b", "let", Priority::First), None);
+        assert_eq!(shadows("[a-z]+", r"\blet", Priority::First), None);
+    }
+}
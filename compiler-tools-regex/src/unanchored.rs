@@ -0,0 +1,289 @@
+//! Unanchored search: finding the leftmost-first match anywhere in a haystack in one
+//! pass over it, rather than trying the anchored prefix match at every position.
+//!
+//! The search runs in three steps, each skipped where it can't help:
+//!
+//!   1. the [`Prefilter`] jumps to the first place a match can start;
+//!   2. the *forward* DFA, built from the pattern behind a lazy `(?s:.*?)`, walks on from
+//!      there. The lazy prefix lets it begin a match at every position it passes, giving
+//!      earlier starts priority, so its last accept is where the leftmost-first match ends;
+//!   3. the *reverse* DFA, the every-match DFA of the pattern read backwards, walks back
+//!      from that end. Its last accept is the leftmost position a match ending there can
+//!      start at, which is where the leftmost-first match starts.
+//!
+//! The reverse walk stops at the search start, where it can't see what comes before,
+//! so only patterns without assertions get the two DFAs; in the forward DFA the lazy
+//! prefix would compete with an assertion anyway ([`Dfa::lock_step`]). Those patterns,
+//! and ones that would need too big a DFA, search by running the anchored match at each
+//! prefilter candidate instead: `\bfoo\b` finds each `foo` with the prefilter and checks
+//! the boundaries there.
+
+use super::*;
+use crate::matching::Haystack;
+use crate::prefilter::Prefilter;
+
+/// The most states the forward or reverse DFA may have. Searching with a lazy `.*?` in
+/// front can blow up a pattern's DFA (see [`Dfa::build_bounded`]); such a pattern
+/// searches position by position instead.
+const MAX_SEARCH_STATES: usize = 4096;
+
+/// Everything the unanchored search of one pattern needs, built once per [`Regex`].
+#[derive(Debug, Clone)]
+pub(crate) struct Searcher {
+    /// The forward and reverse DFAs, or `None` where the search falls back to trying the
+    /// anchored match at each candidate.
+    pub(crate) dfas: Option<SearchDfas>,
+    pub(crate) prefilter: Option<Prefilter>,
+}
+
+#[derive(Debug, Clone)]
+pub(crate) struct SearchDfas {
+    /// The leftmost-first DFA of `(?s:.*?)` followed by the pattern.
+    pub(crate) forward: Dfa,
+    /// The every-match DFA of the reversed pattern.
+    pub(crate) reverse: Dfa,
+}
+
+/// An input the unanchored search runs over: a haystack ([`Haystack`]) that can also be
+/// split and scanned as bytes.
+pub(crate) trait Input: Haystack {
+    /// The length of the input, in bytes.
+    fn byte_len(&self) -> usize;
+
+    /// Whether a unit starts at byte offset `pos` (in bytes mode, every offset).
+    fn is_boundary(&self, pos: usize) -> bool;
+
+    /// The input from byte offset `pos` on.
+    fn tail(&self, pos: usize) -> &Self;
+
+    /// The input as bytes, for the prefilter.
+    fn raw(&self) -> &[u8];
+}
+
+impl Input for str {
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_boundary(&self, pos: usize) -> bool {
+        self.is_char_boundary(pos)
+    }
+
+    fn tail(&self, pos: usize) -> &str {
+        &self[pos..]
+    }
+
+    fn raw(&self) -> &[u8] {
+        self.as_bytes()
+    }
+}
+
+impl Input for [u8] {
+    fn byte_len(&self) -> usize {
+        self.len()
+    }
+
+    fn is_boundary(&self, _: usize) -> bool {
+        true
+    }
+
+    fn tail(&self, pos: usize) -> &[u8] {
+        &self[pos..]
+    }
+
+    fn raw(&self) -> &[u8] {
+        self
+    }
+}
+
+/// The bytes of a haystack from `start` to `end`, read backwards: offset `n` of the view
+/// is offset `end - n` of the haystack. The reverse DFA walks this.
+struct Reversed<'h, H: ?Sized> {
+    haystack: &'h H,
+    start: usize,
+    end: usize,
+}
+
+impl<H: Haystack + ?Sized> Haystack for Reversed<'_, H> {
+    fn unit_at(&self, pos: usize) -> Option<char> {
+        if self.end - pos == self.start {
+            None
+        } else {
+            self.haystack.unit_before(self.end - pos)
+        }
+    }
+
+    fn unit_before(&self, pos: usize) -> Option<char> {
+        if pos == 0 { None } else { self.haystack.unit_at(self.end - pos) }
+    }
+
+    fn unit_len(unit: char) -> usize {
+        H::unit_len(unit)
+    }
+}
+
+impl Searcher {
+    pub(crate) fn new(ast: &SimpleRegexAst, bytes: bool) -> Searcher {
+        Searcher {
+            dfas: SearchDfas::new(ast),
+            prefilter: Prefilter::new(ast, bytes),
+        }
+    }
+
+    /// The leftmost-first match in `haystack` starting at or after byte offset `start`,
+    /// as its start and end offsets. `forward` walks [`SearchDfas::forward`] over a tail of
+    /// the haystack given the unit before it, returning its last accept (the JIT runs
+    /// native code for it); `anchored` is the pattern's anchored prefix match, for the
+    /// position-by-position fallback.
+    pub(crate) fn find<H: Input + ?Sized>(
+        &self,
+        haystack: &H,
+        start: usize,
+        forward: impl Fn(&H, Option<char>) -> Option<usize>,
+        anchored: impl Fn(&H, Option<char>) -> Option<usize>,
+    ) -> Option<(usize, usize)> {
+        let mut at = start;
+        while at < haystack.byte_len() && !haystack.is_boundary(at) {
+            at += 1;
+        }
+        if at > haystack.byte_len() {
+            return None;
+        }
+        let walk = |mut at: usize| loop {
+            if let Some(prefilter) = &self.prefilter {
+                at = prefilter.find(haystack.raw(), at)?;
+            }
+            if let Some(length) = anchored(haystack.tail(at), haystack.unit_before(at)) {
+                return Some((at, at + length));
+            }
+            at += H::unit_len(haystack.unit_at(at)?);
+        };
+        let Some(dfas) = &self.dfas else {
+            return walk(at);
+        };
+        if let Some(prefilter) = &self.prefilter {
+            at = prefilter.find(haystack.raw(), at)?;
+        }
+        let end = at + forward(haystack.tail(at), haystack.unit_before(at))?;
+        let reversed = Reversed {
+            haystack,
+            start: at,
+            end,
+        };
+        // Built from one pattern, the two DFAs agree; loaded ones (`Regex::from_bytes`) needn't, and a forward match
+        // the reverse DFA can't trace back falls back to the anchored walk.
+        match dfas.reverse.prefix_end(&reversed, None) {
+            Some(length) if length <= end - at => Some((end - length, end)),
+            _ => walk(at),
+        }
+    }
+}
+
+impl SearchDfas {
+    /// The search DFAs of `ast`, or `None` if it has assertions or the DFAs would be
+    /// too big.
+    fn new(ast: &SimpleRegexAst) -> Option<SearchDfas> {
+        let mut atoms = vec![AtomRepeat {
+            atom: Atom::Group(true, vec![]),
+            repeat: Repeat::ZeroOrMore,
+            lazy: true,
+        }];
+        atoms.extend(ast.atoms.iter().cloned());
+        let forward = Dfa::build_bounded(
+            &Nfa::build(&SimpleRegexAst {
+                atoms,
+            }),
+            false,
+            MAX_SEARCH_STATES,
+        )?;
+        let reverse = Dfa::build_bounded(
+            &Nfa::build(&SimpleRegexAst {
+                atoms: reversed(&ast.atoms)?,
+            }),
+            true,
+            MAX_SEARCH_STATES,
+        )?;
+        Some(SearchDfas {
+            forward,
+            reverse,
+        })
+    }
+}
+
+/// `atoms` read backwards, matching the reversal of every string `atoms` matches, or
+/// `None` if they contain an assertion.
+fn reversed(atoms: &[AtomRepeat]) -> Option<Vec<AtomRepeat>> {
+    let branches = |branches: &[Vec<AtomRepeat>]| branches.iter().map(|branch| reversed(branch)).collect::<Option<Vec<_>>>();
+    atoms
+        .iter()
+        .rev()
+        .map(|atom| {
+            let mirrored = match &atom.atom {
+                Atom::Literal(literal) => Atom::Literal(literal.chars().rev().collect()),
+                Atom::Group(inverted, entries) => Atom::Group(*inverted, entries.clone()),
+                Atom::Alternation(alternation) => Atom::Alternation(branches(alternation)?),
+                Atom::Language(dfa) => Atom::Language(Box::new(dfa.reversed())),
+                Atom::Capture {
+                    index,
+                    name,
+                    branches: alternation,
+                } => Atom::Capture {
+                    index: *index,
+                    name: name.clone(),
+                    branches: branches(alternation)?,
+                },
+                Atom::EndOfInput
+                | Atom::StartOfText
+                | Atom::WordBoundary {
+                    ..
+                }
+                | Atom::StartOfLine {
+                    ..
+                }
+                | Atom::EndOfLine {
+                    ..
+                } => return None,
+            };
+            Some(AtomRepeat {
+                atom: mirrored,
+                repeat: atom.repeat,
+                lazy: atom.lazy,
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn searcher(pattern: &str) -> Searcher {
+        Searcher::new(&SimpleRegexAst::parse(pattern).expect("valid pattern"), false)
+    }
+
+    #[test]
+    fn reversed_pattern_matches_reversed_text() {
+        let ast = SimpleRegexAst::parse("(?:ab|c)+d").unwrap();
+        let reversed = Regex::from_ast(SimpleRegexAst {
+            atoms: reversed(&ast.atoms).unwrap(),
+        });
+        assert_eq!(reversed.find_prefix("dcba", None).map(|(m, _)| m), Some("dcba"));
+        assert_eq!(reversed.find_prefix("dcab", None).map(|(m, _)| m), Some("dc"));
+        assert_eq!(reversed.find_prefix("abcd", None), None);
+    }
+
+    #[test]
+    fn search_dfas_only_where_assertion_free_and_small() {
+        assert!(searcher("a[ab]{4}").dfas.is_some());
+        assert!(searcher("a[ab]{16}").dfas.is_none());
+        assert!(searcher(r"\bfoo").dfas.is_none());
+    }
+
+    #[test]
+    fn reverse_walk_stops_at_the_search_start() {
+        let regex = Regex::parse("aab|b").unwrap();
+        assert_eq!(regex.search_at("aab", 0), Some((0, 3)));
+        assert_eq!(regex.search_at("aab", 1), Some((2, 3)));
+        assert_eq!(Regex::parse("a*").unwrap().search_at("aba", 1), Some((1, 1)));
+    }
+}
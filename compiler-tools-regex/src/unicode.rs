@@ -7,7 +7,10 @@
 //! plain range checks as `[a-z]`, with no Unicode dependency in the generated
 //! matcher or the runtime crate.
 
-use regex_syntax::hir::{Class, ClassUnicode, ClassUnicodeRange, HirKind};
+use regex_syntax::{
+    hir::{Class, ClassUnicode, ClassUnicodeRange, HirKind},
+    utf8::Utf8Sequences,
+};
 
 use super::GroupEntry;
 
@@ -93,6 +96,18 @@ pub fn negate(entries: &[GroupEntry]) -> Vec<GroupEntry> {
     entries_of(&class)
 }
 
+/// The UTF-8 encodings of a class's codepoints, as sequences of inclusive byte
+/// ranges: a bytes-mode Unicode class matches one encoded char by matching every
+/// range of one sequence in turn. `regex-syntax` splits the ranges so that each
+/// sequence covers exactly the encodings of one codepoint run (surrogates excluded).
+pub fn utf8_sequences(entries: &[GroupEntry]) -> Vec<Vec<(u8, u8)>> {
+    class_from(entries)
+        .iter()
+        .flat_map(|range| Utf8Sequences::new(range.start(), range.end()))
+        .map(|sequence| sequence.as_slice().iter().map(|range| (range.start, range.end)).collect())
+        .collect()
+}
+
 /// Resolve a Perl shorthand class (`\d \w \s`) to its *positive* Unicode codepoint
 /// ranges via `regex-syntax`, used under Unicode mode in place of the ASCII sets.
 /// `escape` is the lowercase base letter; the caller handles the negated `\D \W \S`
@@ -193,4 +208,17 @@ mod tests {
         let greek = case_fold(&[GroupEntry::Range('α', 'ω')]);
         assert!(contains(&greek, 'Λ'));
     }
+    #[test]
+    fn utf8_sequences_cover_each_encoding_length() {
+        // `[a-z\u{e9}\u{20ac}]`: one ASCII range, one two-byte and one three-byte char.
+        let sequences = utf8_sequences(&[GroupEntry::Range('a', 'z'), GroupEntry::Char('é'), GroupEntry::Char('€')]);
+        assert_eq!(
+            sequences,
+            vec![
+                vec![(b'a', b'z')],
+                vec![(0xC3, 0xC3), (0xA9, 0xA9)],
+                vec![(0xE2, 0xE2), (0x82, 0x82), (0xAC, 0xAC)]
+            ]
+        );
+    }
 }
@@ -0,0 +1,535 @@
+//! Rendering diagnostics as annotated source snippets, in the style of rustc:
+//!
+//! ```text
+//! error: mismatched types
+//!  --> main.src:2:13
+//!   |
+//! 2 |     let x = "a" + 1;
+//!   |             ^^^ string
+//!   |                   - integer
+//!   |
+//!   = help: convert one side
+//! ```
+//!
+//! Snippets locate spans by their byte offsets, so they render the same whatever column unit the tokenizer counted
+//! in. Tabs are expanded to the tab stops of the [`LineIndex`] or [`SourceMap`] rendered against, or every 4 columns
+//! if it sets no tab width. Line and column numbers are printed 1-based.
+
+use std::{collections::BTreeSet, fmt::Write};
+
+use crate::{
+    line_index::{ColumnUnit, LineIndex},
+    source_map::{FileId, SourceMap},
+    span::Span,
+};
+
+/// Tab stop interval for sources whose index sets no tab width.
+const TAB_WIDTH: u64 = 4;
+
+/// Whether a rendered diagnostic is colored with ANSI escapes.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColorChoice {
+    #[default]
+    Never,
+    Always,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Severity {
+    Error,
+    Warning,
+    Note,
+    Help,
+}
+
+impl Severity {
+    fn name(self) -> &'static str {
+        match self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+            Severity::Note => "note",
+            Severity::Help => "help",
+        }
+    }
+
+    fn color(self) -> &'static str {
+        match self {
+            Severity::Error => "\x1b[1;31m",
+            Severity::Warning => "\x1b[1;33m",
+            Severity::Note => "\x1b[1;32m",
+            Severity::Help => "\x1b[1;36m",
+        }
+    }
+}
+
+/// A span to underline, with a message. Primary labels mark the cause of the diagnostic with `^`, secondary labels
+/// add context with `-`.
+#[derive(Clone, Debug)]
+pub struct Label {
+    pub span: Span,
+    pub message: String,
+    pub primary: bool,
+}
+
+impl Label {
+    pub fn primary(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            primary: true,
+        }
+    }
+
+    pub fn secondary(span: Span, message: impl Into<String>) -> Self {
+        Self {
+            span,
+            message: message.into(),
+            primary: false,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub message: String,
+    pub labels: Vec<Label>,
+    pub notes: Vec<String>,
+    pub help: Vec<String>,
+}
+
+impl Diagnostic {
+    pub fn new(severity: Severity, message: impl Into<String>) -> Self {
+        Self {
+            severity,
+            message: message.into(),
+            labels: vec![],
+            notes: vec![],
+            help: vec![],
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Self::new(Severity::Error, message)
+    }
+
+    pub fn warning(message: impl Into<String>) -> Self {
+        Self::new(Severity::Warning, message)
+    }
+
+    pub fn with_label(mut self, label: Label) -> Self {
+        self.labels.push(label);
+        self
+    }
+
+    pub fn with_note(mut self, note: impl Into<String>) -> Self {
+        self.notes.push(note.into());
+        self
+    }
+
+    pub fn with_help(mut self, help: impl Into<String>) -> Self {
+        self.help.push(help.into());
+        self
+    }
+
+    /// Renders the diagnostic against the single source of `index`, named `name`; every label's span is taken to be
+    /// in it.
+    pub fn render(&self, name: &str, index: &LineIndex, color: ColorChoice) -> String {
+        let labels: Vec<&Label> = self.labels.iter().collect();
+        self.render_files(&[(name, index.source(), index.tab_width(), labels)], color)
+    }
+
+    /// Renders the diagnostic against the files of `map`, with a snippet per file in order of first appearance.
+    /// Labels whose span carries no file are left out.
+    pub fn render_map(&self, map: &SourceMap, color: ColorChoice) -> String {
+        let mut files: Vec<(FileId, Vec<&Label>)> = vec![];
+        for label in &self.labels {
+            let Some(file) = label.span.file else {
+                continue;
+            };
+            match files.iter_mut().find(|(id, _)| *id == file) {
+                Some((_, labels)) => labels.push(label),
+                None => files.push((file, vec![label])),
+            }
+        }
+        let files: Vec<(&str, &str, Option<u64>, Vec<&Label>)> = files
+            .into_iter()
+            .map(|(file, labels)| (map.name(file), map.source(file), map.tab_width(), labels))
+            .collect();
+        self.render_files(&files, color)
+    }
+
+    /// Renders a snippet per `(name, source, tab width, labels)` file.
+    fn render_files(&self, files: &[(&str, &str, Option<u64>, Vec<&Label>)], color: ColorChoice) -> String {
+        let style = Style {
+            color: color == ColorChoice::Always,
+            severity: self.severity,
+        };
+        let files: Vec<FileSnippet> = files
+            .iter()
+            .map(|(name, source, tab_width, labels)| FileSnippet::new(name, source, tab_width.unwrap_or(TAB_WIDTH), labels))
+            .collect();
+        let gutter = files
+            .iter()
+            .filter_map(|file| file.lines.last())
+            .map(|line| (line + 1).to_string().len())
+            .max()
+            .unwrap_or(0);
+
+        let mut out = String::new();
+        writeln!(
+            out,
+            "{}{}",
+            style.paint(self.severity.color(), self.severity.name()),
+            style.paint("\x1b[1m", &format!(": {}", self.message))
+        )
+        .unwrap();
+        for file in &files {
+            file.render(&mut out, &style, gutter);
+        }
+        if !(self.notes.is_empty() && self.help.is_empty()) {
+            if !files.is_empty() {
+                writeln!(out, "{}", style.gutter(gutter, "")).unwrap();
+            }
+            for (kind, message) in self.notes.iter().map(|x| ("note", x)).chain(self.help.iter().map(|x| ("help", x))) {
+                writeln!(out, "{:gutter$} {} {}: {}", "", style.paint(GUTTER, "="), style.paint("\x1b[1m", kind), message).unwrap();
+            }
+        }
+        out
+    }
+}
+
+const GUTTER: &str = "\x1b[1;34m";
+const RESET: &str = "\x1b[0m";
+
+struct Style {
+    color: bool,
+    severity: Severity,
+}
+
+impl Style {
+    fn paint(&self, code: &str, text: &str) -> String {
+        if self.color && !text.is_empty() {
+            format!("{}{}{}", code, text, RESET)
+        } else {
+            text.to_string()
+        }
+    }
+
+    /// A snippet row without a line number: the gutter, then `rest`.
+    fn gutter(&self, width: usize, rest: &str) -> String {
+        format!("{:width$} {}{}", "", self.paint(GUTTER, "|"), rest).trim_end().to_string()
+    }
+
+    fn marker(&self, label: &ResolvedLabel, text: &str) -> String {
+        if label.primary {
+            self.paint(self.severity.color(), text)
+        } else {
+            self.paint(GUTTER, text)
+        }
+    }
+}
+
+/// A label located in its file's lines, with display columns.
+struct ResolvedLabel<'l> {
+    start_line: usize,
+    start_col: usize,
+    end_line: usize,
+    /// Display column just past the span; always past `start_col` for single-line labels.
+    end_col: usize,
+    primary: bool,
+    message: &'l str,
+}
+
+impl ResolvedLabel<'_> {
+    fn is_multiline(&self) -> bool {
+        self.start_line != self.end_line
+    }
+}
+
+struct FileSnippet<'a, 'l> {
+    name: &'a str,
+    /// The source's lines, with the tab stops it's rendered with.
+    index: LineIndex<'a>,
+    labels: Vec<ResolvedLabel<'l>>,
+    /// The lines to print, ascending.
+    lines: Vec<usize>,
+}
+
+impl<'a, 'l> FileSnippet<'a, 'l> {
+    fn new(name: &'a str, source: &'a str, tab_width: u64, labels: &[&'l Label]) -> Self {
+        let index = LineIndex::new(source).with_tab_width(tab_width);
+        let mut resolved = vec![];
+        let mut lines = BTreeSet::new();
+        // Spans from another source shouldn't panic the renderer, so offsets are clamped into this one.
+        let clamp = |offset: u64| {
+            let mut offset = (offset as usize).min(source.len());
+            while !source.is_char_boundary(offset) {
+                offset -= 1;
+            }
+            offset
+        };
+        let line_of = |offset: usize| index.line_col(offset, ColumnUnit::Bytes).unwrap().0 as usize;
+        // Display columns count chars, with tabs advancing to the next tab stop.
+        let display_col = |offset: usize| index.line_col(offset, ColumnUnit::Chars).unwrap().1 as usize;
+        for label in labels {
+            let start = clamp(label.span.byte_start);
+            let end = clamp(label.span.byte_end).max(start);
+            let start_line = line_of(start);
+            let start_col = display_col(start);
+            // The span is underlined through its last char; an empty span gets one marker at its position.
+            let (end_line, end_col) = match source[start..end].char_indices().next_back() {
+                Some((last, c)) => {
+                    let line = line_of(start + last);
+                    let col = display_col(start + last) as u64;
+                    let end_col = if c == '\t' { (col / tab_width + 1) * tab_width } else { col + 1 };
+                    (line, end_col as usize)
+                }
+                None => (start_line, start_col + 1),
+            };
+            lines.extend(start_line..=end_line);
+            resolved.push(ResolvedLabel {
+                start_line,
+                start_col,
+                end_line,
+                end_col,
+                primary: label.primary,
+                message: &label.message,
+            });
+        }
+        // Underline rows are printed left to right.
+        resolved.sort_by_key(|label| (label.start_line, label.start_col));
+        Self {
+            name,
+            index,
+            labels: resolved,
+            lines: lines.into_iter().collect(),
+        }
+    }
+
+    /// `line` with each tab replaced by spaces up to its tab stop.
+    fn expand_tabs(&self, line: &str) -> String {
+        let tab_width = self.index.tab_width().unwrap_or(TAB_WIDTH);
+        let mut out = String::new();
+        for c in line.chars() {
+            if c == '\t' {
+                let col = out.chars().count() as u64;
+                out.extend(std::iter::repeat_n(' ', ((col / tab_width + 1) * tab_width - col) as usize));
+            } else {
+                out.push(c);
+            }
+        }
+        out
+    }
+
+    fn render(&self, out: &mut String, style: &Style, gutter: usize) {
+        let arrow = style.paint(GUTTER, "-->");
+        match self.labels.iter().find(|label| label.primary).or(self.labels.first()) {
+            Some(label) => writeln!(out, "{:gutter$}{} {}:{}:{}", "", arrow, self.name, label.start_line + 1, label.start_col + 1).unwrap(),
+            None => writeln!(out, "{:gutter$}{} {}", "", arrow, self.name).unwrap(),
+        }
+        writeln!(out, "{}", style.gutter(gutter, "")).unwrap();
+
+        // With any multi-line label, source rows get a margin where a `|` joins its start and end.
+        let has_multiline = self.labels.iter().any(|label| label.is_multiline());
+        let margin = |line: usize| {
+            if !has_multiline {
+                return String::new();
+            }
+            match self
+                .labels
+                .iter()
+                .find(|label| label.is_multiline() && label.start_line < line && line <= label.end_line)
+            {
+                Some(label) => format!(" {} ", style.marker(label, "|")),
+                None => "   ".to_string(),
+            }
+        };
+        let message = |label: &ResolvedLabel| {
+            if label.message.is_empty() {
+                String::new()
+            } else {
+                format!(" {}", style.marker(label, label.message))
+            }
+        };
+
+        let mut previous = None;
+        for &line in &self.lines {
+            if previous.is_some_and(|previous| line > previous + 1) {
+                writeln!(out, "{}", style.paint(GUTTER, "...")).unwrap();
+            }
+            previous = Some(line);
+
+            let number = (line + 1).to_string();
+            let text = self.expand_tabs(self.index.line(line as u64).unwrap_or(""));
+            let row = format!(
+                "{}{} {}{} {}",
+                " ".repeat(gutter - number.len()),
+                style.paint(GUTTER, &number),
+                style.paint(GUTTER, "|"),
+                margin(line),
+                text
+            );
+            writeln!(out, "{}", row.trim_end()).unwrap();
+
+            for label in &self.labels {
+                let marker = if label.primary { "^" } else { "-" };
+                let row = if !label.is_multiline() && label.start_line == line {
+                    let underline = marker.repeat(label.end_col - label.start_col);
+                    format!("{} {}{}{}", margin(line), " ".repeat(label.start_col), style.marker(label, &underline), message(label))
+                } else if label.is_multiline() && label.start_line == line {
+                    format!("  {}", style.marker(label, &format!("{}{}", "_".repeat(label.start_col + 2), marker)))
+                } else if label.is_multiline() && label.end_line == line {
+                    let caret = label.end_col.saturating_sub(1);
+                    format!(" {}{}", style.marker(label, &format!("|{}{}", "_".repeat(caret + 2), marker)), message(label))
+                } else {
+                    continue;
+                };
+                writeln!(out, "{}", style.gutter(gutter, &row)).unwrap();
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The span of the `n`th occurrence of `needle` in `source`.
+    fn find(source: &str, needle: &str, n: usize) -> Span {
+        let start = source.match_indices(needle).nth(n).unwrap().0;
+        Span {
+            byte_start: start as u64,
+            byte_end: (start + needle.len()) as u64,
+            ..Span::default()
+        }
+    }
+
+    const SOURCE: &str = "fn main() {\n    let x = \"a\" + 1;\n}\n";
+
+    #[test]
+    fn single_line_labels() {
+        let diagnostic = Diagnostic::error("mismatched types")
+            .with_label(Label::primary(find(SOURCE, "\"a\"", 0), "string"))
+            .with_label(Label::secondary(find(SOURCE, "1", 0), "integer"))
+            .with_help("convert one side");
+        let rendered = diagnostic.render("main.src", &LineIndex::new(SOURCE), ColorChoice::Never);
+        assert_eq!(
+            rendered,
+            "error: mismatched types
+ --> main.src:2:13
+  |
+2 |     let x = \"a\" + 1;
+  |             ^^^ string
+  |                   - integer
+  |
+  = help: convert one side
+"
+        );
+    }
+
+    #[test]
+    fn multi_line_labels() {
+        let body = Span {
+            byte_start: SOURCE.find('{').unwrap() as u64,
+            byte_end: SOURCE.rfind('}').unwrap() as u64 + 1,
+            ..Span::default()
+        };
+        let diagnostic = Diagnostic::warning("unused function")
+            .with_label(Label::primary(body, "body"))
+            .with_note("`main` is never called");
+        let rendered = diagnostic.render("main.src", &LineIndex::new(SOURCE), ColorChoice::Never);
+        assert_eq!(
+            rendered,
+            "warning: unused function
+ --> main.src:1:11
+  |
+1 |    fn main() {
+  |  ____________^
+2 | |      let x = \"a\" + 1;
+3 | |  }
+  | |__^ body
+  |
+  = note: `main` is never called
+"
+        );
+    }
+
+    #[test]
+    fn source_map_files_and_elided_lines() {
+        let mut map = SourceMap::new();
+        let a = map.add("a.src", "one\ntwo\nthree\nfour\nfive\nsix\nseven\neight\nnine\nten\n");
+        let b = map.add("b.src", "use ten;\n");
+        let in_file = |file, span: Span| Span {
+            file: Some(file),
+            ..span
+        };
+        let source = map.source(a);
+        let diagnostic = Diagnostic::error("duplicate definition")
+            .with_label(Label::primary(in_file(a, find(source, "ten", 0)), "redefined here"))
+            .with_label(Label::secondary(in_file(a, find(source, "two", 0)), "first defined here"))
+            .with_label(Label::secondary(in_file(b, find(map.source(b), "ten", 0)), "imported here"))
+            .with_label(Label::secondary(Span::default(), "no file"));
+        assert_eq!(
+            diagnostic.render_map(&map, ColorChoice::Never),
+            "error: duplicate definition
+  --> a.src:10:1
+   |
+ 2 | two
+   | --- first defined here
+...
+10 | ten
+   | ^^^ redefined here
+  --> b.src:1:5
+   |
+ 1 | use ten;
+   |     --- imported here
+"
+        );
+    }
+
+    #[test]
+    fn tabs_expand_to_the_sources_tab_stops() {
+        let source = "ab\tx\t= 1;\n";
+        let diagnostic = Diagnostic::error("unused").with_label(Label::primary(find(source, "\t=", 0), "tab"));
+        assert_eq!(
+            diagnostic.render("tabs.src", &LineIndex::new(source).with_tab_width(8), ColorChoice::Never),
+            "error: unused
+ --> tabs.src:1:10
+  |
+1 | ab      x       = 1;
+  |          ^^^^^^^^ tab
+"
+        );
+        let mut map = SourceMap::new().with_tab_width(8);
+        let file = map.add("tabs.src", source);
+        let in_file = Span {
+            file: Some(file),
+            ..find(source, "\t=", 0)
+        };
+        let diagnostic = Diagnostic::error("unused").with_label(Label::primary(in_file, "tab"));
+        assert!(diagnostic.render_map(&map, ColorChoice::Never).contains("\n  |          ^^^^^^^^ tab\n"));
+        // Without a tab width, tabs stop every 4 columns.
+        assert!(
+            Diagnostic::error("unused")
+                .with_label(Label::primary(find(source, "x", 0), ""))
+                .render("tabs.src", &LineIndex::new(source), ColorChoice::Never)
+                .contains("1 | ab  x   = 1;\n  |     ^\n")
+        );
+    }
+
+    #[test]
+    fn colored_output() {
+        let diagnostic = Diagnostic::error("bad").with_label(Label::primary(find(SOURCE, "x", 0), "here"));
+        let rendered = diagnostic.render("main.src", &LineIndex::new(SOURCE), ColorChoice::Always);
+        assert!(rendered.starts_with("\x1b[1;31merror\x1b[0m\x1b[1m: bad\x1b[0m\n"));
+        assert!(rendered.contains("\x1b[1;31m^\x1b[0m \x1b[1;31mhere\x1b[0m"));
+        // stripping the escapes gives the plain rendering
+        let mut plain = rendered.clone();
+        while let Some(start) = plain.find('\x1b') {
+            let end = start + plain[start..].find('m').unwrap() + 1;
+            plain.replace_range(start..end, "");
+        }
+        assert_eq!(plain, diagnostic.render("main.src", &LineIndex::new(SOURCE), ColorChoice::Never));
+    }
+}
@@ -0,0 +1,81 @@
+use std::fmt;
+
+use crate::span::Span;
+
+/// Why a tokenizer could not produce its next token.
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct LexError {
+    /// For [`LexErrorKind::NoMatch`], the empty span at the position the tokenizer is stuck at.
+    /// For [`LexErrorKind::ParseFailed`], the span of the matched text.
+    pub span: Span,
+    pub kind: LexErrorKind,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub enum LexErrorKind {
+    /// No token matches the remaining input. The tokenizer does not advance, so retrying fails the same way.
+    NoMatch,
+    /// A variant matched, but its payload failed to `parse()` from the matched text.
+    /// The tokenizer has already advanced past that text.
+    ParseFailed {
+        /// The name of the variant whose payload failed to parse.
+        variant: String,
+        /// The `Display` text of the payload's `FromStr::Err`.
+        message: String,
+    },
+}
+
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.kind {
+            LexErrorKind::NoMatch => write!(f, "no token matches input at {}:{}", self.span.line_start, self.span.col_start),
+            LexErrorKind::ParseFailed {
+                variant,
+                message,
+            } => write!(f, "failed to parse '{}' token at {}: {}", variant, self.span, message),
+        }
+    }
+}
+
+impl std::error::Error for LexError {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lex_error_display() {
+        let no_match = LexError {
+            span: Span {
+                line_start: 2,
+                line_stop: 2,
+                col_start: 4,
+                col_stop: 4,
+                byte_start: 12,
+                byte_end: 12,
+                file: None,
+            },
+            kind: LexErrorKind::NoMatch,
+        };
+        assert_eq!(no_match.to_string(), "no token matches input at 2:4");
+
+        let parse_failed = LexError {
+            span: Span {
+                line_start: 0,
+                line_stop: 0,
+                col_start: 1,
+                col_stop: 4,
+                byte_start: 1,
+                byte_end: 4,
+                file: None,
+            },
+            kind: LexErrorKind::ParseFailed {
+                variant: "Int".to_string(),
+                message: "number too large to fit in target type".to_string(),
+            },
+        };
+        assert_eq!(parse_failed.to_string(), "failed to parse 'Int' token at 0:1-4: number too large to fit in target type");
+    }
+}
@@ -4,6 +4,21 @@ pub use tokenizer::*;
 pub mod span;
 pub use span::*;
 
+pub mod source_map;
+pub use source_map::*;
+
+pub mod line_index;
+pub use line_index::*;
+
+pub mod diagnostics;
+pub use diagnostics::*;
+
+pub mod trivia;
+pub use trivia::*;
+
+pub mod error;
+pub use error::*;
+
 pub mod misc;
 pub use misc::*;
 
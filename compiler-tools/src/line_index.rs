@@ -0,0 +1,206 @@
+use std::ops::Range;
+
+use crate::span::Span;
+
+/// What a column counts, for [`LineIndex`] conversions.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ColumnUnit {
+    #[default]
+    Bytes,
+    Chars,
+    /// UTF-16 code units, as used by LSP.
+    Utf16,
+}
+
+impl ColumnUnit {
+    fn width(self, c: char) -> usize {
+        match self {
+            ColumnUnit::Bytes => c.len_utf8(),
+            ColumnUnit::Chars => 1,
+            ColumnUnit::Utf16 => c.len_utf16(),
+        }
+    }
+}
+
+/// The line starts of a source text, for converting between byte offsets and lines and columns.
+/// Lines are split on `\n`; lines and columns count from 0, as in [`Span`].
+pub struct LineIndex<'a> {
+    source: &'a str,
+    /// Byte offset of the start of each line.
+    line_starts: Vec<usize>,
+    /// Tab stop interval, if tabs advance the column to the next tab stop rather than by one unit.
+    tab_width: Option<u64>,
+}
+
+impl<'a> LineIndex<'a> {
+    pub fn new(source: &'a str) -> Self {
+        let line_starts = std::iter::once(0).chain(source.match_indices('\n').map(|(i, _)| i + 1)).collect();
+        Self {
+            source,
+            line_starts,
+            tab_width: None,
+        }
+    }
+
+    /// Advances the column over a tab to the next multiple of `tab_width`, as a tokenizer derived with
+    /// `#[token_parse(tab_width = ...)]` does, so their columns agree. Panics if `tab_width` is 0.
+    pub fn with_tab_width(mut self, tab_width: u64) -> Self {
+        assert!(tab_width > 0, "a tab width must be positive");
+        self.tab_width = Some(tab_width);
+        self
+    }
+
+    /// The tab stop interval set with [`LineIndex::with_tab_width`].
+    pub fn tab_width(&self) -> Option<u64> {
+        self.tab_width
+    }
+
+    /// The column after `c`, starting at `col`.
+    fn advance(&self, col: u64, c: char, unit: ColumnUnit) -> u64 {
+        match self.tab_width {
+            Some(tab_width) if c == '\t' => (col / tab_width + 1) * tab_width,
+            _ => col + unit.width(c) as u64,
+        }
+    }
+
+    /// The column at the end of `text`, which holds no newline.
+    fn measure(&self, text: &str, unit: ColumnUnit) -> u64 {
+        match (unit, self.tab_width) {
+            (ColumnUnit::Bytes, None) => text.len() as u64,
+            _ => text.chars().fold(0, |col, c| self.advance(col, c, unit)),
+        }
+    }
+
+    pub fn source(&self) -> &'a str {
+        self.source
+    }
+
+    /// The number of lines; a trailing newline starts an empty last line.
+    pub fn line_count(&self) -> usize {
+        self.line_starts.len()
+    }
+
+    /// The line and column of `offset`, or `None` if it is past the end of the source or not on a char boundary.
+    pub fn line_col(&self, offset: usize, unit: ColumnUnit) -> Option<(u64, u64)> {
+        if !self.source.is_char_boundary(offset) {
+            return None;
+        }
+        let line = self.line_starts.partition_point(|start| *start <= offset) - 1;
+        let col = self.measure(&self.source[self.line_starts[line]..offset], unit);
+        Some((line as u64, col))
+    }
+
+    /// The byte offset of `col` on `line`, or `None` if the position isn't in the source or falls inside a char
+    /// (or a tab, with a tab width). The column just past the end of a line is its newline.
+    pub fn offset(&self, line: u64, col: u64, unit: ColumnUnit) -> Option<usize> {
+        let start = *self.line_starts.get(line as usize)?;
+        let text = self.line_with_newline(line as usize);
+        let mut seen = 0;
+        for (i, c) in text.char_indices() {
+            if seen == col {
+                return Some(start + i);
+            }
+            if c == '\n' || seen > col {
+                return None;
+            }
+            seen = self.advance(seen, c, unit);
+        }
+        (seen == col).then_some(start + text.len())
+    }
+
+    /// The byte range of the source `span` covers.
+    pub fn range(&self, span: &Span) -> Range<usize> {
+        span.byte_start as usize..span.byte_end as usize
+    }
+
+    /// The text of `line`, without its line ending.
+    pub fn line(&self, line: u64) -> Option<&'a str> {
+        if line as usize >= self.line_starts.len() {
+            return None;
+        }
+        let text = self.line_with_newline(line as usize);
+        let text = text.strip_suffix('\n').unwrap_or(text);
+        Some(text.strip_suffix('\r').unwrap_or(text))
+    }
+
+    fn line_with_newline(&self, line: usize) -> &'a str {
+        let end = self.line_starts.get(line + 1).copied().unwrap_or(self.source.len());
+        &self.source[self.line_starts[line]..end]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "let é = 1;\r\n\t😀x\n";
+
+    #[test]
+    fn lines() {
+        let index = LineIndex::new(SOURCE);
+        assert_eq!(index.line_count(), 3);
+        assert_eq!(index.line(0), Some("let é = 1;"));
+        assert_eq!(index.line(1), Some("\t😀x"));
+        assert_eq!(index.line(2), Some(""));
+        assert_eq!(index.line(3), None);
+    }
+
+    #[test]
+    fn offset_to_line_col() {
+        let index = LineIndex::new(SOURCE);
+        // `=` follows the two-byte `é`
+        assert_eq!(index.line_col(7, ColumnUnit::Bytes), Some((0, 7)));
+        assert_eq!(index.line_col(7, ColumnUnit::Chars), Some((0, 6)));
+        // `x` follows a tab and a four-byte, two-code-unit emoji
+        let x = SOURCE.find('x').unwrap();
+        assert_eq!(index.line_col(x, ColumnUnit::Bytes), Some((1, 5)));
+        assert_eq!(index.line_col(x, ColumnUnit::Chars), Some((1, 2)));
+        assert_eq!(index.line_col(x, ColumnUnit::Utf16), Some((1, 3)));
+        assert_eq!(index.line_col(SOURCE.len(), ColumnUnit::Chars), Some((2, 0)));
+        assert_eq!(index.line_col(5, ColumnUnit::Bytes), None, "inside `é`");
+        assert_eq!(index.line_col(SOURCE.len() + 1, ColumnUnit::Bytes), None);
+    }
+
+    #[test]
+    fn line_col_to_offset() {
+        let index = LineIndex::new(SOURCE);
+        let x = SOURCE.find('x').unwrap();
+        for unit in [ColumnUnit::Bytes, ColumnUnit::Chars, ColumnUnit::Utf16] {
+            let (line, col) = index.line_col(x, unit).unwrap();
+            assert_eq!(index.offset(line, col, unit), Some(x));
+        }
+        // the end of a line is its line ending
+        assert_eq!(index.offset(1, 3, ColumnUnit::Chars), Some(SOURCE.len() - 1));
+        assert_eq!(index.offset(1, 4, ColumnUnit::Chars), None);
+        assert_eq!(index.offset(1, 2, ColumnUnit::Utf16), None, "inside the emoji's surrogate pair");
+        assert_eq!(index.offset(2, 0, ColumnUnit::Bytes), Some(SOURCE.len()));
+        assert_eq!(index.offset(3, 0, ColumnUnit::Bytes), None);
+    }
+
+    #[test]
+    fn tabs_advance_to_tab_stops() {
+        let index = LineIndex::new(SOURCE).with_tab_width(4);
+        let x = SOURCE.find('x').unwrap();
+        assert_eq!(index.line_col(x, ColumnUnit::Bytes), Some((1, 8)));
+        assert_eq!(index.line_col(x, ColumnUnit::Chars), Some((1, 5)));
+        assert_eq!(index.line_col(x, ColumnUnit::Utf16), Some((1, 6)));
+        for unit in [ColumnUnit::Bytes, ColumnUnit::Chars, ColumnUnit::Utf16] {
+            let (line, col) = index.line_col(x, unit).unwrap();
+            assert_eq!(index.offset(line, col, unit), Some(x));
+        }
+        assert_eq!(index.offset(1, 4, ColumnUnit::Chars), Some(SOURCE.find('😀').unwrap()));
+        assert_eq!(index.offset(1, 2, ColumnUnit::Chars), None, "inside the tab");
+    }
+
+    #[test]
+    fn span_range() {
+        let index = LineIndex::new(SOURCE);
+        let span = Span {
+            byte_start: 4,
+            byte_end: 6,
+            ..Span::default()
+        };
+        assert_eq!(index.range(&span), 4..6);
+        assert_eq!(&SOURCE[index.range(&span)], "é");
+    }
+}
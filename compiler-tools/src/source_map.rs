@@ -0,0 +1,129 @@
+use crate::{
+    line_index::{ColumnUnit, LineIndex},
+    span::Span,
+};
+
+/// Identifies a file registered in a [`SourceMap`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileId(pub u32);
+
+struct SourceFile {
+    name: String,
+    source: String,
+}
+
+/// The sources of a multi-file compilation. Tokenizers created with `XxxTokenizer::with_file` tag every span with
+/// the file it came from, so it can be resolved back to its name and text here.
+#[derive(Default)]
+pub struct SourceMap {
+    files: Vec<SourceFile>,
+    /// What the tokenizers lexing the files count a column as.
+    columns: ColumnUnit,
+    /// Tab stop interval, if the tokenizers advance the column over a tab to the next tab stop.
+    tab_width: Option<u64>,
+}
+
+impl SourceMap {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Counts columns in `columns`, as a tokenizer derived with `#[token_parse(columns = ...)]` does.
+    pub fn with_columns(mut self, columns: ColumnUnit) -> Self {
+        self.columns = columns;
+        self
+    }
+
+    /// Advances the column over a tab to the next multiple of `tab_width`, as a tokenizer derived with
+    /// `#[token_parse(tab_width = ...)]` does; see [`LineIndex::with_tab_width`]. Panics if `tab_width` is 0.
+    pub fn with_tab_width(mut self, tab_width: u64) -> Self {
+        assert!(tab_width > 0, "a tab width must be positive");
+        self.tab_width = Some(tab_width);
+        self
+    }
+
+    /// The unit to convert the map's positions with, in [`LineIndex::line_col`] and [`LineIndex::offset`].
+    pub fn columns(&self) -> ColumnUnit {
+        self.columns
+    }
+
+    /// The tab stop interval set with [`SourceMap::with_tab_width`].
+    pub fn tab_width(&self) -> Option<u64> {
+        self.tab_width
+    }
+
+    /// Registers a source, returning the id to lex it with.
+    pub fn add(&mut self, name: impl Into<String>, source: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files.push(SourceFile {
+            name: name.into(),
+            source: source.into(),
+        });
+        id
+    }
+
+    /// Panics if `file` is not from this map.
+    pub fn name(&self, file: FileId) -> &str {
+        &self.files[file.0 as usize].name
+    }
+
+    /// Panics if `file` is not from this map.
+    pub fn source(&self, file: FileId) -> &str {
+        &self.files[file.0 as usize].source
+    }
+
+    /// Indexes the lines of `file`, with the map's tab width. This scans the whole source, so keep the index around
+    /// for repeated lookups.
+    pub fn line_index(&self, file: FileId) -> LineIndex<'_> {
+        let index = LineIndex::new(self.source(file));
+        match self.tab_width {
+            Some(tab_width) => index.with_tab_width(tab_width),
+            None => index,
+        }
+    }
+
+    /// Every registered file, in registration order.
+    pub fn files(&self) -> impl Iterator<Item = FileId> {
+        (0..self.files.len() as u32).map(FileId)
+    }
+
+    /// The text `span` covers, if it carries a file.
+    pub fn span_text(&self, span: &Span) -> Option<&str> {
+        Some(span.slice(self.source(span.file?)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn registers_and_resolves_files() {
+        let mut map = SourceMap::new();
+        let a = map.add("a.txt", "let a = 1;");
+        let b = map.add("b.txt", String::from("b\nc"));
+        assert_ne!(a, b);
+        assert_eq!(map.files().collect::<Vec<_>>(), vec![a, b]);
+        assert_eq!((map.name(b), map.source(b)), ("b.txt", "b\nc"));
+        assert_eq!(map.line_index(b).line(1), Some("c"));
+
+        let span = Span {
+            byte_start: 4,
+            byte_end: 5,
+            file: Some(a),
+            ..Span::default()
+        };
+        assert_eq!(map.span_text(&span), Some("a"));
+        assert_eq!(map.span_text(&Span::default()), None);
+    }
+
+    #[test]
+    fn line_indexes_count_columns_like_the_map() {
+        let mut map = SourceMap::new().with_columns(ColumnUnit::Chars).with_tab_width(4);
+        let file = map.add("a.txt", "\tlet é\tx");
+        let index = map.line_index(file);
+        assert_eq!(index.line_col(map.source(file).find('x').unwrap(), map.columns()), Some((0, 12)));
+        assert_eq!(SourceMap::new().columns(), ColumnUnit::Bytes);
+    }
+}
@@ -3,6 +3,8 @@ use std::{
     ops::{Deref, DerefMut},
 };
 
+use crate::source_map::FileId;
+
 #[derive(Clone, Debug, Copy, Default)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub struct Span {
@@ -10,6 +12,19 @@ pub struct Span {
     pub line_stop: u64,
     pub col_start: u64,
     pub col_stop: u64,
+    /// Byte offset of the start of the span in the source
+    pub byte_start: u64,
+    /// Byte offset just past the end of the span in the source
+    pub byte_end: u64,
+    /// The file of the source, for tokenizers created with `with_file`
+    pub file: Option<FileId>,
+}
+
+impl Span {
+    /// The text of the source the span covers.
+    pub fn slice<'a>(&self, source: &'a str) -> &'a str {
+        &source[self.byte_start as usize..self.byte_end as usize]
+    }
 }
 
 impl PartialEq for Span {
@@ -35,7 +50,14 @@ impl fmt::Display for Span {
 impl std::ops::Add for Span {
     type Output = Self;
 
+    /// Panics in debug builds when the spans are from different files.
     fn add(self, other: Self) -> Self {
+        debug_assert!(
+            self.file.is_none() || other.file.is_none() || self.file == other.file,
+            "merging spans from different files ({:?} and {:?})",
+            self.file,
+            other.file
+        );
         // Combine into the smallest span that contains both. Positions are
         // compared as (line, col) pairs so the earliest start and latest stop win,
         // which is correct regardless of operand order or how many lines are spanned.
@@ -46,6 +68,9 @@ impl std::ops::Add for Span {
             line_stop,
             col_start,
             col_stop,
+            byte_start: self.byte_start.min(other.byte_start),
+            byte_end: self.byte_end.max(other.byte_end),
+            file: self.file.or(other.file),
         }
     }
 }
@@ -99,6 +124,15 @@ mod tests {
             line_stop,
             col_start,
             col_stop,
+            ..Span::default()
+        }
+    }
+
+    fn bytes(byte_start: u64, byte_end: u64) -> Span {
+        Span {
+            byte_start,
+            byte_end,
+            ..Span::default()
         }
     }
 
@@ -172,6 +206,44 @@ mod tests {
         assert_eq!(acc.col_stop, expected.col_stop);
     }
 
+    #[test]
+    fn span_add_merges_byte_ranges() {
+        let combined = bytes(7, 9) + bytes(2, 4);
+        assert_eq!((combined.byte_start, combined.byte_end), (2, 9));
+    }
+
+    #[test]
+    fn span_add_keeps_the_file() {
+        let file = Span {
+            file: Some(FileId(3)),
+            ..Span::default()
+        };
+        assert_eq!((file + Span::default()).file, Some(FileId(3)));
+        assert_eq!((Span::default() + file).file, Some(FileId(3)));
+    }
+
+    #[test]
+    #[cfg(debug_assertions)]
+    #[should_panic(expected = "merging spans from different files")]
+    fn span_add_rejects_different_files() {
+        let a = Span {
+            file: Some(FileId(0)),
+            ..Span::default()
+        };
+        let b = Span {
+            file: Some(FileId(1)),
+            ..Span::default()
+        };
+        let _ = a + b;
+    }
+
+    #[test]
+    fn span_slice() {
+        let source = "let x = 1;";
+        assert_eq!(bytes(4, 5).slice(source), "x");
+        assert_eq!((bytes(0, 3) + bytes(8, 9)).slice(source), "let x = 1");
+    }
+
     #[test]
     fn spanned_deref_and_display() {
         let spanned = Spanned {
@@ -1,6 +1,6 @@
 use std::marker::PhantomData;
 
-use crate::span::Spanned;
+use crate::{error::LexError, line_index::ColumnUnit, span::Spanned};
 
 pub trait TokenExt: Clone + Copy + PartialEq {
     fn matches_class(&self, other: &Self) -> bool;
@@ -9,7 +9,18 @@ pub trait TokenExt: Clone + Copy + PartialEq {
 pub trait TokenParse<'a> {
     type Token: TokenExt + 'a;
 
+    /// What a column of the tokenizer's spans counts.
+    const COLUMNS: ColumnUnit = ColumnUnit::Bytes;
+    /// Tab stop interval, if tabs advance the tokenizer's column to the next tab stop rather than by one unit.
+    const TAB_WIDTH: Option<u64> = None;
+
     fn next(&mut self) -> Option<Spanned<Self::Token>>;
+
+    /// Like `next`, but reports why lexing stopped: `Ok(None)` only at the end of input.
+    /// Tokenizers that can't fail mid-stream can rely on the default, which never errors.
+    fn try_next(&mut self) -> Result<Option<Spanned<Self::Token>>, LexError> {
+        Ok(self.next())
+    }
 }
 
 pub struct TokenizerWrap<'a, T: TokenParse<'a>> {
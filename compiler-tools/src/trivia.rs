@@ -0,0 +1,352 @@
+use std::{collections::VecDeque, marker::PhantomData, ops::Deref};
+
+use crate::{
+    line_index::LineIndex,
+    span::{Span, Spanned},
+    tokenizer::{TokenExt, TokenParse},
+};
+
+/// A run of source text that is not a significant token.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Trivia<'a, T> {
+    /// The ignored token lexed from `text`, or `None` for text the tokenizer never returned a token for: matches of
+    /// `#[token(skip)]` variants, and any input left when it stopped.
+    pub token: Option<T>,
+    pub span: Span,
+    pub text: &'a str,
+}
+
+impl<T: TokenExt> Trivia<'_, T> {
+    /// Whether this trivia is a token of the same class as `class`.
+    pub fn is(&self, class: &T) -> bool {
+        self.token.is_some_and(|token| token.matches_class(class))
+    }
+}
+
+/// A significant token with its source text and the trivia around it.
+///
+/// Trailing trivia is everything after the token that starts on the line the token ends on; the rest belongs to the
+/// leading trivia of the next token. Trivia after the last token of the input is all trailing.
+#[derive(Clone, Debug)]
+pub struct TriviaSpanned<'a, T: Clone + Copy> {
+    pub token: Spanned<T>,
+    pub text: &'a str,
+    pub leading: Vec<Trivia<'a, T>>,
+    pub trailing: Vec<Trivia<'a, T>>,
+}
+
+impl<'a, T: TokenExt> TriviaSpanned<'a, T> {
+    /// The leading trivia of class `class`, e.g. the doc comments before a declaration.
+    pub fn leading_of<'b>(&'b self, class: &'b T) -> impl Iterator<Item = &'b Trivia<'a, T>> + 'b {
+        self.leading.iter().filter(move |trivia| trivia.is(class))
+    }
+
+    /// The trailing trivia of class `class`.
+    pub fn trailing_of<'b>(&'b self, class: &'b T) -> impl Iterator<Item = &'b Trivia<'a, T>> + 'b {
+        self.trailing.iter().filter(move |trivia| trivia.is(class))
+    }
+
+    /// Appends the source of the token and its trivia, exactly as it appeared in the input.
+    pub fn write_source(&self, out: &mut String) {
+        for trivia in &self.leading {
+            out.push_str(trivia.text);
+        }
+        out.push_str(self.text);
+        for trivia in &self.trailing {
+            out.push_str(trivia.text);
+        }
+    }
+}
+
+impl<T: Clone + Copy> Deref for TriviaSpanned<'_, T> {
+    type Target = Spanned<T>;
+
+    fn deref(&self) -> &Self::Target {
+        &self.token
+    }
+}
+
+/// A lossless alternative to [`TokenizerWrap`](crate::TokenizerWrap): rather than dropping ignored tokens, attaches
+/// them to the significant tokens around them, so concatenating every token's [`TriviaSpanned::write_source`]
+/// reproduces the input.
+///
+/// `source` must be the input `inner` lexes: token text is sliced out of it by span.
+pub struct TriviaWrap<'a, T: TokenParse<'a>> {
+    inner: T,
+    source: &'a str,
+    trivia_tokens: Vec<T::Token>,
+    /// The empty span at the end of the last token lexed from `inner`.
+    position: Span,
+    /// Lexed but not yet returned: trivia, then possibly the significant token following it.
+    pending: VecDeque<Trivia<'a, T::Token>>,
+    peeked: Option<(Spanned<T::Token>, &'a str)>,
+    eof_trivia: Vec<Trivia<'a, T::Token>>,
+    _lifetime: PhantomData<&'a ()>,
+}
+
+impl<'a, T: TokenParse<'a>> TriviaWrap<'a, T> {
+    pub fn new(inner: T, source: &'a str, trivia_tokens: impl IntoIterator<Item = T::Token>) -> Self {
+        Self {
+            inner,
+            source,
+            trivia_tokens: trivia_tokens.into_iter().collect(),
+            position: Span::default(),
+            pending: VecDeque::new(),
+            peeked: None,
+            eof_trivia: vec![],
+            _lifetime: PhantomData,
+        }
+    }
+
+    /// The trivia of an input without any significant token, once `next` has returned `None`.
+    pub fn eof_trivia(&self) -> &[Trivia<'a, T::Token>] {
+        &self.eof_trivia
+    }
+
+    /// Text between the end of the last token and the start of `until`, which `inner` consumed without returning it.
+    fn gap(&mut self, until: Span) -> Option<Trivia<'a, T::Token>> {
+        let from = self.position;
+        self.position = end_of(until);
+        if from.byte_start == until.byte_start {
+            return None;
+        }
+        let span = Span {
+            line_stop: until.line_start,
+            col_stop: until.col_start,
+            byte_end: until.byte_start,
+            file: until.file,
+            ..from
+        };
+        Some(Trivia {
+            token: None,
+            span,
+            text: span.slice(self.source),
+        })
+    }
+
+    /// Lexes one token, preceded by the gap before it, if any. Returns whether it's significant.
+    fn lex(&mut self) -> Option<bool> {
+        let Some(next) = self.inner.next() else {
+            // Whatever `inner` left unlexed, up to the end of the source, ending where the tokenizer's own line and
+            // column would.
+            let mut index = LineIndex::new(self.source);
+            if let Some(tab_width) = T::TAB_WIDTH {
+                index = index.with_tab_width(tab_width);
+            }
+            let mut end = self.position;
+            end.byte_end = self.source.len() as u64;
+            (end.line_stop, end.col_stop) = index
+                .line_col(self.source.len(), T::COLUMNS)
+                .expect("the end of the source is a position in it");
+            if let Some(gap) = self.gap(end_of(end)) {
+                self.pending.push_back(gap);
+            }
+            return None;
+        };
+        if let Some(gap) = self.gap(next.span) {
+            self.pending.push_back(gap);
+        }
+        let text = next.span.slice(self.source);
+        if self.trivia_tokens.iter().any(|x| x.matches_class(&next)) {
+            self.pending.push_back(Trivia {
+                token: Some(next.token),
+                span: next.span,
+                text,
+            });
+            Some(false)
+        } else {
+            self.peeked = Some((next, text));
+            Some(true)
+        }
+    }
+
+    #[allow(clippy::should_implement_trait)]
+    pub fn next(&mut self) -> Option<TriviaSpanned<'a, T::Token>> {
+        while self.peeked.is_none() {
+            if self.lex().is_none() {
+                self.eof_trivia.extend(self.pending.drain(..));
+                return None;
+            }
+        }
+        let (token, text) = self.peeked.take().unwrap();
+        let leading = self.pending.drain(..).collect();
+
+        // Read ahead to the next significant token to split the trivia in between.
+        let mut at_eof = false;
+        while self.peeked.is_none() {
+            if self.lex().is_none() {
+                at_eof = true;
+                break;
+            }
+        }
+        let trailing = if at_eof {
+            self.pending.drain(..).collect()
+        } else {
+            let same_line = self.pending.iter().take_while(|trivia| trivia.span.line_start == token.span.line_stop).count();
+            self.pending.drain(..same_line).collect()
+        };
+        Some(TriviaSpanned {
+            token,
+            text,
+            leading,
+            trailing,
+        })
+    }
+}
+
+/// The empty span at the end of `span`.
+fn end_of(span: Span) -> Span {
+    Span {
+        line_start: span.line_stop,
+        line_stop: span.line_stop,
+        col_start: span.col_stop,
+        col_stop: span.col_stop,
+        byte_start: span.byte_end,
+        byte_end: span.byte_end,
+        file: span.file,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::line_index::ColumnUnit;
+
+    #[derive(Clone, Copy, PartialEq, Debug)]
+    enum Tok {
+        Word,
+        Ws,
+        Comment,
+    }
+
+    impl TokenExt for Tok {
+        fn matches_class(&self, other: &Self) -> bool {
+            self == other
+        }
+    }
+
+    /// Lexes `[a-z]+` words, `[ \n]+` whitespace and `#` line comments. `-` is consumed without a token, like a
+    /// `#[token(skip)]` variant. Columns count chars, with tab stops every 4 columns.
+    struct Lexer<'a> {
+        inner: &'a str,
+        line: u64,
+        col: u64,
+        offset: u64,
+    }
+
+    impl<'a> TokenParse<'a> for Lexer<'a> {
+        type Token = Tok;
+        const COLUMNS: ColumnUnit = ColumnUnit::Chars;
+        const TAB_WIDTH: Option<u64> = Some(4);
+
+        fn next(&mut self) -> Option<Spanned<Tok>> {
+            loop {
+                let first = self.inner.chars().next()?;
+                let (token, len) = match first {
+                    'a'..='z' => (Some(Tok::Word), self.inner.find(|c: char| !c.is_ascii_lowercase())),
+                    ' ' | '\n' => (Some(Tok::Ws), self.inner.find(|c| c != ' ' && c != '\n')),
+                    '#' => (Some(Tok::Comment), self.inner.find('\n')),
+                    '-' => (None, self.inner.find(|c| c != '-')),
+                    _ => return None,
+                };
+                let (passed, remaining) = self.inner.split_at(len.unwrap_or(self.inner.len()));
+                self.inner = remaining;
+                let (line_start, col_start, byte_start) = (self.line, self.col, self.offset);
+                self.offset += passed.len() as u64;
+                for c in passed.chars() {
+                    if c == '\n' {
+                        self.line += 1;
+                        self.col = 0;
+                    } else {
+                        self.col += 1;
+                    }
+                }
+                if let Some(token) = token {
+                    return Some(Spanned {
+                        token,
+                        span: Span {
+                            line_start,
+                            col_start,
+                            line_stop: self.line,
+                            col_stop: self.col,
+                            byte_start,
+                            byte_end: self.offset,
+                            file: None,
+                        },
+                    });
+                }
+            }
+        }
+    }
+
+    fn wrap(source: &str) -> TriviaWrap<'_, Lexer<'_>> {
+        TriviaWrap::new(
+            Lexer {
+                inner: source,
+                line: 0,
+                col: 0,
+                offset: 0,
+            },
+            source,
+            [Tok::Ws, Tok::Comment],
+        )
+    }
+
+    fn texts<'a>(trivia: &[Trivia<'a, Tok>]) -> Vec<&'a str> {
+        trivia.iter().map(|x| x.text).collect()
+    }
+
+    #[test]
+    fn round_trips_the_source() {
+        let source = "  # head\nab cd # tail\n\n# doc\nef --gh-- \n";
+        let mut w = wrap(source);
+        let mut out = String::new();
+        let mut count = 0;
+        while let Some(token) = w.next() {
+            token.write_source(&mut out);
+            count += 1;
+        }
+        assert_eq!(count, 4);
+        assert_eq!(out, source);
+    }
+
+    #[test]
+    fn splits_trailing_and_leading_at_line_end() {
+        let mut w = wrap("ab cd # tail\n\n# doc\nef");
+        let ab = w.next().unwrap();
+        assert_eq!((ab.text, texts(&ab.leading), texts(&ab.trailing)), ("ab", vec![], vec![" "]));
+        let cd = w.next().unwrap();
+        assert_eq!(texts(&cd.trailing), vec![" ", "# tail", "\n\n"]);
+        let ef = w.next().unwrap();
+        assert_eq!(texts(&ef.leading), vec!["# doc", "\n"]);
+        assert_eq!(ef.leading_of(&Tok::Comment).map(|x| x.text).collect::<Vec<_>>(), vec!["# doc"]);
+        assert!(w.next().is_none());
+    }
+
+    #[test]
+    fn keeps_skipped_text_as_tokenless_trivia() {
+        let mut w = wrap("ab--\n--cd--");
+        let ab = w.next().unwrap();
+        assert_eq!((texts(&ab.trailing), ab.trailing[0].token), (vec!["--", "\n"], None));
+        let cd = w.next().unwrap();
+        assert_eq!((texts(&cd.leading), cd.leading[0].token), (vec!["--"], None));
+        assert_eq!(texts(&cd.trailing), vec!["--"]);
+    }
+
+    #[test]
+    fn input_without_tokens_is_eof_trivia() {
+        let mut w = wrap(" # only\n");
+        assert!(w.next().is_none());
+        assert_eq!(texts(w.eof_trivia()), vec![" ", "# only", "\n"]);
+    }
+
+    #[test]
+    fn unlexed_tail_ends_in_the_tokenizers_columns() {
+        let mut w = wrap("ab\n\tééx");
+        let ab = w.next().unwrap();
+        assert!(w.next().is_none());
+        let tail = ab.trailing.last().unwrap();
+        assert_eq!(tail.text, "\tééx");
+        assert_eq!((tail.span.line_start, tail.span.col_start, tail.span.line_stop, tail.span.col_stop), (1, 0, 1, 7));
+    }
+}
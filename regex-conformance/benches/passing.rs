@@ -29,12 +29,15 @@ use regex_conformance::{Regex, compiled_lookup, load_corpus, passes, run_search}
 use regex_test::{RegexTest, RegexTests};
 use std::hint::black_box;
 
+/// A generated-Rust matcher as returned by [`compiled_lookup`].
+type CompiledMatcher = fn(&str, Option<char>) -> Option<(&str, &str)>;
+
 /// Everything needed to run one passing test through each engine, with all per-test
 /// compilation done once, up front (outside the timed loop).
 struct Case<'a> {
     test: &'a RegexTest,
     simple: Regex,
-    compiled: fn(&str, Option<char>) -> Option<(&str, &str)>,
+    compiled: CompiledMatcher,
     full: regex::Regex,
     #[cfg(feature = "jit")]
     jit: compiler_tools_regex::JitRegex,
@@ -52,7 +55,7 @@ fn select(corpus: &RegexTests) -> Vec<Case<'_>> {
             if std::str::from_utf8(test.haystack()).is_err() {
                 return None;
             }
-            let simple = Regex::parse(pattern)?;
+            let simple = Regex::parse(pattern).ok()?;
             // JIT-compile once here (heavy), before `simple` is moved into `Case`; the
             // resulting `JitRegex` owns its code, so it outlives the borrow.
             #[cfg(feature = "jit")]
@@ -64,7 +67,7 @@ fn select(corpus: &RegexTests) -> Vec<Case<'_>> {
             // Only keep tests the simple engine genuinely passes. `passes` parses a
             // fresh `Regex` for the check since the one we store is borrowed by
             // `find_prefix` and `Regex` isn't `Clone`.
-            let check = Regex::parse(pattern)?;
+            let check = Regex::parse(pattern).ok()?;
             if !passes(test, Box::new(move |input, prev| check.find_prefix(input, prev))) {
                 return None;
             }
@@ -4,15 +4,17 @@
 //!
 //! For each test with a single, parseable pattern we emit the same matcher
 //! `Regex::generate_parser` produces inside `#[token(regex = ...)]`, keyed
-//! by the test's full name. Patterns the engine can't parse (alternation, etc.)
-//! are simply absent from the table and skipped by the test.
+//! by the test's full name; for each multi-pattern test, the matcher
+//! `RegexSet::generate_parser` produces. Patterns the engine can't parse are
+//! simply absent from the tables and skipped by the test. Tests with
+//! `utf8 = false` get bytes-mode matchers over `&[u8]`, in their own tables.
 
 use std::{
     env, fs,
     path::{Path, PathBuf},
 };
 
-use compiler_tools_regex::{Regex, flatten};
+use compiler_tools_regex::{Regex, RegexSet, flatten};
 use quote::{format_ident, quote};
 use regex_test::{RegexTest, RegexTests};
 
@@ -22,7 +24,14 @@ use regex_test::{RegexTest, RegexTests};
 /// `i` — so the compiled matcher and the runtime interpreter parse the same
 /// pattern. Keep in sync with `src/lib.rs`.
 fn effective_pattern(test: &RegexTest) -> String {
-    let pattern = &test.regexes()[0];
+    with_test_flags(test, &test.regexes()[0])
+}
+
+fn effective_patterns(test: &RegexTest) -> Vec<String> {
+    test.regexes().iter().map(|pattern| with_test_flags(test, pattern)).collect()
+}
+
+fn with_test_flags(test: &RegexTest, pattern: &str) -> String {
     let mut inline = String::new();
     if test.unicode() {
         inline.push('u');
@@ -31,7 +40,7 @@ fn effective_pattern(test: &RegexTest) -> String {
         inline.push('i');
     }
     if inline.is_empty() {
-        pattern.clone()
+        pattern.to_string()
     } else {
         format!("(?{inline}){pattern}")
     }
@@ -63,11 +72,45 @@ fn main() {
 
     let mut fns = vec![];
     let mut entries = vec![];
+    let mut set_entries = vec![];
+    let mut bytes_entries = vec![];
+    let mut bytes_set_entries = vec![];
     for (i, test) in tests.iter().enumerate() {
+        // A test that may match within a UTF-8 sequence runs on the bytes engine, over `&[u8]`.
+        let bytes = !test.utf8();
+        let parse = if bytes { Regex::parse_bytes } else { Regex::parse };
+        let (input, prev) = if bytes {
+            (quote! { [u8] }, quote! { u8 })
+        } else {
+            (quote! { str }, quote! { char })
+        };
         if test.regexes().len() != 1 {
+            let Ok(regexes) = effective_patterns(test).iter().map(|pattern| parse(pattern)).collect::<Result<Vec<_>, _>>() else {
+                continue;
+            };
+            let ident = format_ident!("compiled_set_{}", i);
+            let inner = format_ident!("parse_set");
+            let parser = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| RegexSet::from_regexes(regexes).generate_parser(inner.clone()))) {
+                Ok(parser) => parser,
+                Err(_) => continue,
+            };
+            // The set matcher returns a `[Option<usize>; N]`; widen it to one table type.
+            fns.push(quote! {
+                fn #ident(from: &#input, prev: Option<#prev>) -> Vec<Option<usize>> {
+                    #parser
+                    #inner(from, prev).to_vec()
+                }
+            });
+            let name = test.full_name();
+            let entry = quote! { (#name, #ident as fn(&#input, Option<#prev>) -> Vec<Option<usize>>), };
+            if bytes {
+                bytes_set_entries.push(entry)
+            } else {
+                set_entries.push(entry)
+            }
             continue;
         }
-        let Some(re) = Regex::parse(&effective_pattern(test)) else {
+        let Ok(re) = parse(&effective_pattern(test)) else {
             continue;
         };
         let ident = format_ident!("compiled_{}", i);
@@ -77,13 +120,17 @@ fn main() {
         };
         fns.push(parser);
         let name = test.full_name();
-        entries.push(quote! { (#name, #ident as fn(&str, Option<char>) -> Option<(&str, &str)>), });
+        let entry = quote! { (#name, #ident as fn(&#input, Option<#prev>) -> Option<(&#input, &#input)>), };
+        if bytes { bytes_entries.push(entry) } else { entries.push(entry) }
     }
 
     std::panic::set_hook(prev_hook);
 
     let fns = flatten(fns);
     let entries = flatten(entries);
+    let set_entries = flatten(set_entries);
+    let bytes_entries = flatten(bytes_entries);
+    let bytes_set_entries = flatten(bytes_set_entries);
     let generated = quote! {
         #fns
 
@@ -93,6 +140,25 @@ fn main() {
             const TABLE: &[(&str, fn(&str, Option<char>) -> Option<(&str, &str)>)] = &[ #entries ];
             TABLE.iter().copied().find(|(name, _)| *name == full_name).map(|(_, f)| f)
         }
+
+        /// Returns the compiled set matcher generated for the multi-pattern test with
+        /// the given full name, or `None` if one of its patterns was unsupported.
+        pub fn compiled_set_lookup(full_name: &str) -> Option<fn(&str, Option<char>) -> Vec<Option<usize>>> {
+            const TABLE: &[(&str, fn(&str, Option<char>) -> Vec<Option<usize>>)] = &[ #set_entries ];
+            TABLE.iter().copied().find(|(name, _)| *name == full_name).map(|(_, f)| f)
+        }
+
+        /// [`compiled_lookup`] for the bytes-mode matchers of `utf8 = false` tests.
+        pub fn compiled_bytes_lookup(full_name: &str) -> Option<fn(&[u8], Option<u8>) -> Option<(&[u8], &[u8])>> {
+            const TABLE: &[(&str, fn(&[u8], Option<u8>) -> Option<(&[u8], &[u8])>)] = &[ #bytes_entries ];
+            TABLE.iter().copied().find(|(name, _)| *name == full_name).map(|(_, f)| f)
+        }
+
+        /// [`compiled_set_lookup`] for the bytes-mode set matchers of `utf8 = false` tests.
+        pub fn compiled_bytes_set_lookup(full_name: &str) -> Option<fn(&[u8], Option<u8>) -> Vec<Option<usize>>> {
+            const TABLE: &[(&str, fn(&[u8], Option<u8>) -> Vec<Option<usize>>)] = &[ #bytes_set_entries ];
+            TABLE.iter().copied().find(|(name, _)| *name == full_name).map(|(_, f)| f)
+        }
     };
 
     let out_dir = PathBuf::from(env::var("OUT_DIR").unwrap());
@@ -1,24 +1,41 @@
 //! Test harness that runs the upstream `regex` test corpus (`../testdata`,
 //! parsed with the `regex-test` crate) against compiler-tools' simple-regex
-//! engine, in both of its forms:
+//! engine, in each of its forms:
 //!
 //! * the **runtime interpreter** — [`compiler_tools_regex::Regex::find_prefix`],
-//!   which walks the compiled DFA directly, and
+//!   which walks the compiled DFA directly (or the Pike VM, for the patterns the
+//!   DFA walk can't match exactly),
+//! * the **Pike VM** — [`compiler_tools_regex::PikeVm::find_prefix`], the
+//!   lock-step NFA simulation, run on every pattern, and
 //! * the **compiled-Rust engine** — the `fn(&str) -> Option<(&str, &str)>` matchers
 //!   that `build.rs` emits via `Regex::generate_parser` (the exact code
 //!   `#[token(regex = ...)]` expands to), looked up here via [`compiled_lookup`].
 //!
-//! Both engines are anchored prefix matchers, so [`run_search`] turns one into a
-//! leftmost, non-overlapping search to line up with the corpus' expectations.
+//! Multi-pattern tests run on a [`RegexSet`], both interpreted
+//! ([`RegexSet::find_prefix_all`]) and as the matcher `build.rs` emits via
+//! `RegexSet::generate_parser` ([`compiled_set_lookup`]).
+//!
+//! The engines are anchored prefix matchers, so [`run_search`] (and
+//! [`run_set_search`] for a set) turns one into the search the corpus expects.
+//! [`run_engine_search`] does the same for a whole [`RegexSearch`] engine, which
+//! also answers the earliest and every-match queries, so it can run each test in
+//! the mode its `search-kind`/`match-kind` asks for, and has an unanchored search
+//! of its own ([`RegexSearch::find_at`]) for the leftmost-first tests;
+//! [`run_search`] is leftmost-first only.
+//!
+//! Tests with `utf8 = false` run each engine in bytes mode over the raw haystack,
+//! through the `run_bytes_*` counterparts of these ([`compiled_bytes_lookup`] and
+//! [`compiled_bytes_set_lookup`] for the generated matchers).
 //! The aim is to *exercise* the engines against the corpus; many tests are
 //! expected to fail or be skipped (unsupported syntax, byte/Unicode semantics),
 //! and that's fine — see the test crate for how results are summarized.
 
+use std::cmp::Reverse;
 use std::panic::AssertUnwindSafe;
 use std::path::{Path, PathBuf};
 
-pub use compiler_tools_regex::Regex;
-use regex_test::{CompiledRegex, Match, RegexTest, RegexTests, Span, TestResult, TestRunner, anyhow};
+pub use compiler_tools_regex::{PikeVm, Regex, RegexSearch, RegexSet};
+use regex_test::{CompiledRegex, Match, MatchKind, RegexTest, RegexTests, SearchKind, Span, TestResult, TestRunner, anyhow};
 
 // `compiled_lookup` plus one `compiled_<n>` matcher per supported test. The
 // generated matchers carry the usual dead-`prev`/dead-store warnings from
@@ -27,7 +44,7 @@ use regex_test::{CompiledRegex, Match, RegexTest, RegexTests, Span, TestResult,
 mod compiled {
     include!(concat!(env!("OUT_DIR"), "/compiled.rs"));
 }
-pub use compiled::compiled_lookup;
+pub use compiled::{compiled_bytes_lookup, compiled_bytes_set_lookup, compiled_lookup, compiled_set_lookup};
 
 /// A boxed anchored prefix matcher: `(slice, preceding char) -> (matched, rest)`.
 /// The preceding char seeds the zero-width assertions (`^` under `(?m)`, `\b`) so a
@@ -35,6 +52,73 @@ pub use compiled::compiled_lookup;
 /// interpreter and the generated matcher) are wrapped in this shape.
 pub type BoxedMatcher = Box<dyn Fn(&str, Option<char>) -> Option<(&str, &str)>>;
 
+/// A boxed search engine (the runtime interpreter, the Pike VM or the JIT), for the
+/// searches [`run_engine_search`] needs beyond the anchored prefix match.
+pub type BoxedEngine = Box<dyn RegexSearch>;
+
+/// A boxed anchored set matcher: `(slice, preceding char) -> match length per pattern`,
+/// the shape of [`RegexSet::find_prefix_all`] and of the generated set matcher.
+pub type BoxedSetMatcher = Box<dyn Fn(&str, Option<char>) -> Vec<Option<usize>>>;
+
+/// [`BoxedMatcher`] for a bytes-mode engine: `(slice, preceding byte) -> (matched, rest)`.
+pub type BoxedBytesMatcher = Box<dyn Fn(&[u8], Option<u8>) -> Option<(&[u8], &[u8])>>;
+
+/// [`BoxedEngine`] for a bytes-mode engine.
+pub type BoxedBytesEngine = Box<dyn BytesSearch>;
+
+/// [`BoxedSetMatcher`] for a bytes-mode set, the shape of [`RegexSet::find_prefix_all_bytes`].
+pub type BoxedBytesSetMatcher = Box<dyn Fn(&[u8], Option<u8>) -> Vec<Option<usize>>>;
+
+/// The `&[u8]` searches [`run_bytes_engine_search`] needs from a bytes-mode engine,
+/// mirroring the [`RegexSearch`] primitives. Each engine has them as inherent methods;
+/// this only lets the harness box them.
+pub trait BytesSearch {
+    fn find_prefix_bytes<'h>(&self, from: &'h [u8], prev: Option<u8>) -> Option<(&'h [u8], &'h [u8])>;
+
+    fn find_prefix_ends_bytes(&self, from: &[u8], prev: Option<u8>) -> Vec<usize>;
+
+    fn find_prefix_earliest_bytes<'h>(&self, from: &'h [u8], prev: Option<u8>) -> Option<(&'h [u8], &'h [u8])>;
+
+    /// The leftmost-first match at or after byte offset `start`, as its start and end.
+    /// Without an unanchored search of its own, an engine tries the anchored match at
+    /// each offset in turn, as [`RegexSearch::find_at`] does.
+    fn search_at_bytes(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+        (start..=haystack.len()).find_map(|at| {
+            let (matched, _) = self.find_prefix_bytes(&haystack[at..], at.checked_sub(1).map(|i| haystack[i]))?;
+            Some((at, at + matched.len()))
+        })
+    }
+}
+
+macro_rules! impl_bytes_search {
+    ($engine:ty $(=> $search_at_bytes:ident)?) => {
+        impl BytesSearch for $engine {
+            fn find_prefix_bytes<'h>(&self, from: &'h [u8], prev: Option<u8>) -> Option<(&'h [u8], &'h [u8])> {
+                <$engine>::find_prefix_bytes(self, from, prev)
+            }
+
+            fn find_prefix_ends_bytes(&self, from: &[u8], prev: Option<u8>) -> Vec<usize> {
+                <$engine>::find_prefix_ends_bytes(self, from, prev)
+            }
+
+            fn find_prefix_earliest_bytes<'h>(&self, from: &'h [u8], prev: Option<u8>) -> Option<(&'h [u8], &'h [u8])> {
+                <$engine>::find_prefix_earliest_bytes(self, from, prev)
+            }
+
+            $(
+                fn search_at_bytes(&self, haystack: &[u8], start: usize) -> Option<(usize, usize)> {
+                    <$engine>::$search_at_bytes(self, haystack, start)
+                }
+            )?
+        }
+    };
+}
+
+impl_bytes_search!(Regex => search_at_bytes);
+impl_bytes_search!(PikeVm);
+#[cfg(feature = "jit")]
+impl_bytes_search!(compiler_tools_regex::JitRegex => search_at_bytes);
+
 /// The pattern string to feed the engine for `test`, with the corpus' test-level
 /// options folded into a leading inline-flag group so they behave like the `regex`
 /// crate's builder switches. Maps `unicode = true` (the corpus default) to `u` and
@@ -47,7 +131,15 @@ pub type BoxedMatcher = Box<dyn Fn(&str, Option<char>) -> Option<(&str, &str)>>;
 /// its own crate), so the compiled-engine matcher and the runtime interpreter
 /// parse the *same* effective pattern — keep the two in sync.
 pub fn effective_pattern(test: &RegexTest) -> String {
-    let pattern = &test.regexes()[0];
+    with_test_flags(test, &test.regexes()[0])
+}
+
+/// [`effective_pattern`] for every pattern of a (multi-pattern) test.
+pub fn effective_patterns(test: &RegexTest) -> Vec<String> {
+    test.regexes().iter().map(|pattern| with_test_flags(test, pattern)).collect()
+}
+
+fn with_test_flags(test: &RegexTest, pattern: &str) -> String {
     let mut inline = String::new();
     if test.unicode() {
         inline.push('u');
@@ -56,7 +148,7 @@ pub fn effective_pattern(test: &RegexTest) -> String {
         inline.push('i');
     }
     if inline.is_empty() {
-        pattern.clone()
+        pattern.to_string()
     } else {
         format!("(?{inline}){pattern}")
     }
@@ -105,27 +197,214 @@ pub fn run_search(matcher: impl Fn(&str, Option<char>) -> Option<(&str, &str)>,
     let Ok(haystack) = std::str::from_utf8(test.haystack()) else {
         return TestResult::skip();
     };
+    TestResult::matches(leftmost_search(haystack, test, |input, prev| matcher(input, prev).map(|(matched, _)| (0, matched.len()))))
+}
+
+/// [`run_search`] for a bytes-mode prefix matcher, over the raw haystack.
+pub fn run_bytes_search(matcher: impl Fn(&[u8], Option<u8>) -> Option<(&[u8], &[u8])>, test: &RegexTest) -> TestResult {
+    TestResult::matches(leftmost_search(test.haystack(), test, |input, prev| matcher(input, prev).map(|(matched, _)| (0, matched.len()))))
+}
+
+/// Drive a [`RegexSearch`] engine over a test's haystack in the test's own mode:
+///
+/// * `search-kind = "overlapping"` reports every match from
+///   [`RegexSearch::find_prefix_ends`] at every start, ordered by end and then by
+///   start descending, as the `regex` crate's overlapping search does,
+/// * `search-kind = "earliest"` is a leftmost search cutting each match at
+///   [`RegexSearch::find_prefix_earliest`],
+/// * `match-kind = "all"` is a leftmost search taking the longest match at each start,
+/// * anything else is the leftmost-first search of [`run_search`], run through the
+///   engine's own unanchored [`RegexSearch::find_at`] unless the test is anchored.
+pub fn run_engine_search(engine: &dyn RegexSearch, test: &RegexTest) -> TestResult {
+    let Ok(haystack) = std::str::from_utf8(test.haystack()) else {
+        return TestResult::skip();
+    };
+    TestResult::matches(engine_matches(
+        haystack,
+        test,
+        |input, start| engine.find_at(input, start).map(|m| (m.start(), m.end())),
+        |input, prev| engine.find_prefix(input, prev).map(|(matched, _)| matched.len()),
+        |input, prev| engine.find_prefix_ends(input, prev),
+        |input, prev| engine.find_prefix_earliest(input, prev).map(|(matched, _)| matched.len()),
+    ))
+}
+
+/// [`run_engine_search`] for a bytes-mode engine, over the raw haystack.
+pub fn run_bytes_engine_search(engine: &dyn BytesSearch, test: &RegexTest) -> TestResult {
+    TestResult::matches(engine_matches(
+        test.haystack(),
+        test,
+        |input, start| engine.search_at_bytes(input, start),
+        |input, prev| engine.find_prefix_bytes(input, prev).map(|(matched, _)| matched.len()),
+        |input, prev| engine.find_prefix_ends_bytes(input, prev),
+        |input, prev| engine.find_prefix_earliest_bytes(input, prev).map(|(matched, _)| matched.len()),
+    ))
+}
+
+/// The matches behind [`run_engine_search`], given the engine's unanchored search and
+/// its anchored leftmost-first, every-match and earliest match lengths.
+fn engine_matches<H: Haystack + ?Sized>(
+    haystack: &H,
+    test: &RegexTest,
+    search: impl Fn(&H, usize) -> Option<(usize, usize)>,
+    prefix: impl Fn(&H, Option<H::Unit>) -> Option<usize>,
+    ends: impl Fn(&H, Option<H::Unit>) -> Vec<usize>,
+    earliest: impl Fn(&H, Option<H::Unit>) -> Option<usize>,
+) -> Vec<Match> {
+    match (test.search_kind(), test.match_kind()) {
+        (SearchKind::Overlapping, _) => {
+            let mut matches = vec![];
+            for_each_start(haystack, test, |start, input, prev| {
+                matches.extend(ends(input, prev).into_iter().map(|length| Match {
+                    id: 0,
+                    span: Span {
+                        start,
+                        end: start + length,
+                    },
+                }));
+            });
+            matches.sort_by_key(|m| (m.span.end, Reverse(m.span.start)));
+            if let Some(limit) = test.match_limit() {
+                matches.truncate(limit);
+            }
+            matches
+        }
+        (SearchKind::Earliest, _) => leftmost_search(haystack, test, |input, prev| earliest(input, prev).map(|length| (0, length))),
+        (_, MatchKind::All) => leftmost_search(haystack, test, |input, prev| ends(input, prev).last().map(|length| (0, *length))),
+        _ if test.anchored() => leftmost_search(haystack, test, |input, prev| prefix(input, prev).map(|length| (0, length))),
+        _ => unanchored_search(haystack, test, search),
+    }
+}
+
+/// Drive a set matcher over a test's haystack. An overlapping search reports which
+/// patterns match anywhere (as [`RegexSet::matches`] does); a leftmost search
+/// reports non-overlapping matches, taking at each position the first pattern that
+/// matched (`match-kind = "leftmost-first"`, the set's priority order) or the longest
+/// (`match-kind = "all"`).
+pub fn run_set_search(matcher: impl Fn(&str, Option<char>) -> Vec<Option<usize>>, test: &RegexTest) -> TestResult {
+    let Ok(haystack) = std::str::from_utf8(test.haystack()) else {
+        return TestResult::skip();
+    };
+    set_result(haystack, test, matcher)
+}
+
+/// [`run_set_search`] for a bytes-mode set matcher, over the raw haystack.
+pub fn run_bytes_set_search(matcher: impl Fn(&[u8], Option<u8>) -> Vec<Option<usize>>, test: &RegexTest) -> TestResult {
+    set_result(test.haystack(), test, matcher)
+}
+
+fn set_result<H: Haystack + ?Sized>(haystack: &H, test: &RegexTest, matcher: impl Fn(&H, Option<H::Unit>) -> Vec<Option<usize>>) -> TestResult {
+    if test.search_kind() == SearchKind::Overlapping {
+        let mut which = vec![];
+        for_each_start(haystack, test, |_, input, prev| {
+            for (id, length) in matcher(input, prev).into_iter().enumerate() {
+                if length.is_some() && !which.contains(&id) {
+                    which.push(id);
+                }
+            }
+        });
+        return TestResult::which(which);
+    }
+    let longest = test.match_kind() == MatchKind::All;
+    TestResult::matches(leftmost_search(haystack, test, |input, prev| {
+        let mut found = matcher(input, prev).into_iter().enumerate().filter_map(|(id, length)| Some((id, length?)));
+        if longest {
+            // The earliest id wins a tie, as `max_by_key` would keep the last.
+            found.fold(None, |best: Option<(usize, usize)>, (id, length)| match best {
+                Some((_, best_length)) if best_length >= length => best,
+                _ => Some((id, length)),
+            })
+        } else {
+            found.next()
+        }
+    }))
+}
+
+/// A haystack the searches run over: a `str`, where a match can only start on a char
+/// boundary, or the `[u8]` of a bytes-mode engine, where it can start at any byte.
+trait Haystack {
+    /// What the engine takes as the context preceding a slice: a `char`, or a `u8`.
+    type Unit: Copy;
+
+    fn len(&self) -> usize;
+
+    fn is_start(&self, pos: usize) -> bool;
+
+    fn slice(&self, start: usize, end: usize) -> &Self;
+
+    /// The unit immediately before `pos`.
+    fn unit_before(&self, pos: usize) -> Option<Self::Unit>;
+}
+
+impl Haystack for str {
+    type Unit = char;
+
+    fn len(&self) -> usize {
+        str::len(self)
+    }
+
+    fn is_start(&self, pos: usize) -> bool {
+        self.is_char_boundary(pos)
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &str {
+        &self[start..end]
+    }
+
+    fn unit_before(&self, pos: usize) -> Option<char> {
+        self[..pos].chars().next_back()
+    }
+}
+
+impl Haystack for [u8] {
+    type Unit = u8;
+
+    fn len(&self) -> usize {
+        <[u8]>::len(self)
+    }
+
+    fn is_start(&self, _pos: usize) -> bool {
+        true
+    }
+
+    fn slice(&self, start: usize, end: usize) -> &[u8] {
+        &self[start..end]
+    }
+
+    fn unit_before(&self, pos: usize) -> Option<u8> {
+        pos.checked_sub(1).map(|pos| self[pos])
+    }
+}
+
+/// The leftmost, non-overlapping search behind [`run_search`] and [`run_set_search`]:
+/// `matcher` returns the id and byte length of the match anchored at the start of
+/// the slice it's given.
+fn leftmost_search<H: Haystack + ?Sized>(haystack: &H, test: &RegexTest, matcher: impl Fn(&H, Option<H::Unit>) -> Option<(usize, usize)>) -> Vec<Match> {
     let bounds = test.bounds();
     let anchored = test.anchored();
     let limit = test.match_limit();
 
     let mut matches = vec![];
+    let mut last_end = None;
     let mut pos = bounds.start;
     while pos <= bounds.end && pos <= haystack.len() {
-        if !haystack.is_char_boundary(pos) {
+        if !haystack.is_start(pos) {
             pos += 1;
             continue;
         }
         let end = bounds.end.min(haystack.len());
-        // The char immediately before `pos`, so zero-width assertions in the matcher
+        // The unit immediately before `pos`, so zero-width assertions in the matcher
         // (`^` under `(?m)`, `\b`) see the right preceding context for a mid-haystack
         // slice instead of treating every start position as start-of-text.
-        let prev = haystack[..pos].chars().next_back();
-        match matcher(&haystack[pos..end], prev) {
-            Some((matched, _)) => {
-                let match_end = pos + matched.len();
+        let prev = haystack.unit_before(pos);
+        match matcher(haystack.slice(pos, end), prev) {
+            // As in the `regex` crate, an empty match where the previous match ended isn't reported.
+            Some((_, 0)) if last_end == Some(pos) => pos = next_start(haystack, pos),
+            Some((id, length)) => {
+                let match_end = pos + length;
+                last_end = Some(match_end);
                 matches.push(Match {
-                    id: 0,
+                    id,
                     span: Span {
                         start: pos,
                         end: match_end,
@@ -135,7 +414,7 @@ pub fn run_search(matcher: impl Fn(&str, Option<char>) -> Option<(&str, &str)>,
                     break;
                 }
                 // Advance past the match; a zero-width match must still move forward.
-                pos = if matched.is_empty() { next_char_boundary(haystack, pos) } else { match_end };
+                pos = if length == 0 { next_start(haystack, pos) } else { match_end };
             }
             None => {
                 if anchored {
@@ -145,7 +424,61 @@ pub fn run_search(matcher: impl Fn(&str, Option<char>) -> Option<(&str, &str)>,
             }
         }
     }
-    TestResult::matches(matches)
+    matches
+}
+
+/// [`leftmost_search`] for an engine with an unanchored search of its own: `search`
+/// returns the start and end of the first match at or after an offset of the haystack,
+/// which is cut at the end of the test's bounds.
+fn unanchored_search<H: Haystack + ?Sized>(haystack: &H, test: &RegexTest, search: impl Fn(&H, usize) -> Option<(usize, usize)>) -> Vec<Match> {
+    let bounds = test.bounds();
+    let end = bounds.end.min(haystack.len());
+    // Bounds ending inside a char leave a `str` nothing to search.
+    if !haystack.is_start(end) {
+        return vec![];
+    }
+    let haystack = haystack.slice(0, end);
+    let limit = test.match_limit();
+
+    let mut matches = vec![];
+    let mut last_end = None;
+    let mut pos = bounds.start;
+    while let Some((start, end)) = search(haystack, pos) {
+        // As in `leftmost_search`, an empty match where the previous match ended isn't reported.
+        if start == end && last_end == Some(start) {
+            pos = next_start(haystack, start);
+            continue;
+        }
+        last_end = Some(end);
+        matches.push(Match {
+            id: 0,
+            span: Span {
+                start,
+                end,
+            },
+        });
+        if limit.is_some_and(|limit| matches.len() >= limit) {
+            break;
+        }
+        pos = if start == end { next_start(haystack, end) } else { end };
+    }
+    matches
+}
+
+/// Call `visit` with the position, slice and preceding unit at every start position
+/// within the test's bounds (only the first, for an anchored test).
+fn for_each_start<H: Haystack + ?Sized>(haystack: &H, test: &RegexTest, mut visit: impl FnMut(usize, &H, Option<H::Unit>)) {
+    let bounds = test.bounds();
+    let end = bounds.end.min(haystack.len());
+    for pos in bounds.start..=end {
+        if !haystack.is_start(pos) {
+            continue;
+        }
+        visit(pos, haystack.slice(pos, end), haystack.unit_before(pos));
+        if test.anchored() {
+            break;
+        }
+    }
 }
 
 /// Run a single test through `regex-test`'s comparator and report whether the
@@ -155,11 +488,40 @@ pub fn run_search(matcher: impl Fn(&str, Option<char>) -> Option<(&str, &str)>,
 /// This is how the conformance harness and the criterion benchmark agree on
 /// which tests the engine "passes" — the benchmark times only the passing set.
 pub fn passes(test: &RegexTest, matcher: BoxedMatcher) -> bool {
+    check(test, move |test| run_search(|input, prev| matcher(input, prev), test))
+}
+
+/// [`passes`] for a whole engine, searched with [`run_engine_search`].
+pub fn engine_passes(test: &RegexTest, engine: BoxedEngine) -> bool {
+    check(test, move |test| run_engine_search(&*engine, test))
+}
+
+/// [`passes`] for a set matcher, searched with [`run_set_search`].
+pub fn set_passes(test: &RegexTest, matcher: BoxedSetMatcher) -> bool {
+    check(test, move |test| run_set_search(|input, prev| matcher(input, prev), test))
+}
+
+/// [`passes`] for a bytes-mode matcher, searched with [`run_bytes_search`].
+pub fn bytes_passes(test: &RegexTest, matcher: BoxedBytesMatcher) -> bool {
+    check(test, move |test| run_bytes_search(|input, prev| matcher(input, prev), test))
+}
+
+/// [`passes`] for a bytes-mode engine, searched with [`run_bytes_engine_search`].
+pub fn bytes_engine_passes(test: &RegexTest, engine: BoxedBytesEngine) -> bool {
+    check(test, move |test| run_bytes_engine_search(&*engine, test))
+}
+
+/// [`passes`] for a bytes-mode set matcher, searched with [`run_bytes_set_search`].
+pub fn bytes_set_passes(test: &RegexTest, matcher: BoxedBytesSetMatcher) -> bool {
+    check(test, move |test| run_bytes_set_search(|input, prev| matcher(input, prev), test))
+}
+
+fn check(test: &RegexTest, search: impl FnMut(&RegexTest) -> TestResult + 'static) -> bool {
     let mut runner = TestRunner::new().expect("failed to read REGEX_TEST env");
-    let mut matcher = Some(matcher);
+    let mut search = Some(search);
     runner.test(test, move |_patterns| {
-        let matcher = matcher.take().expect("compile called once per test");
-        Ok::<_, anyhow::Error>(CompiledRegex::compiled(move |test| run_search(|input, prev| matcher(input, prev), test)))
+        let search = search.take().expect("compile called once per test");
+        Ok::<_, anyhow::Error>(CompiledRegex::compiled(search))
     });
 
     // `assert()` panics (with a large report) iff the single test failed. Silence
@@ -172,9 +534,9 @@ pub fn passes(test: &RegexTest, matcher: BoxedMatcher) -> bool {
     result.is_ok()
 }
 
-fn next_char_boundary(haystack: &str, mut pos: usize) -> usize {
+fn next_start<H: Haystack + ?Sized>(haystack: &H, mut pos: usize) -> usize {
     pos += 1;
-    while pos < haystack.len() && !haystack.is_char_boundary(pos) {
+    while pos < haystack.len() && !haystack.is_start(pos) {
         pos += 1;
     }
     pos
@@ -1,4 +1,4 @@
-//! Runs the regex test corpus against both simple-regex engines and prints a
+//! Runs the regex test corpus against every simple-regex engine and prints a
 //! per-engine summary of pass / fail-to-parse / fail-to-pass / skipped.
 //!
 //! This is a bring-up harness, not a gate: the engine implements a small subset
@@ -14,12 +14,23 @@
 //! * **fail-to-pass** — the pattern parsed, but the search produced different
 //!   matches than expected.
 //! * **skipped** — not applicable to this engine (a multi-pattern set, or a
-//!   non-UTF-8 haystack this `&str`-based engine can't represent).
+//!   non-UTF-8 haystack a `&str`-based engine can't represent).
+//!
+//! The multi-pattern tests run separately, on a `RegexSet` interpreted and
+//! compiled; those two summaries count only the multi-pattern tests.
+//!
+//! Tests with `utf8 = false`, whose matches may split a UTF-8 sequence, run on
+//! the bytes-mode form of each engine over the raw haystack.
 
 use std::time::{Duration, Instant};
 
-use regex_conformance::{BoxedMatcher, Regex, compiled_lookup, effective_pattern, load_corpus, passes, run_search};
-use regex_test::{RegexTest, RegexTests};
+use regex_conformance::{
+    BoxedBytesEngine, BoxedBytesMatcher, BoxedBytesSetMatcher, BoxedEngine, BoxedMatcher, BoxedSetMatcher, Regex, RegexSet, bytes_engine_passes, bytes_passes,
+    bytes_set_passes, compiled_bytes_lookup, compiled_bytes_set_lookup, compiled_lookup, compiled_set_lookup, effective_pattern, effective_patterns,
+    engine_passes, load_corpus, passes, run_bytes_engine_search, run_bytes_search, run_bytes_set_search, run_engine_search, run_search, run_set_search,
+    set_passes,
+};
+use regex_test::RegexTest;
 
 /// What an engine could do with a given test before we try to run it.
 enum Prepared {
@@ -28,8 +39,19 @@ enum Prepared {
     /// The engine's parser rejected the pattern.
     FailToParse,
     /// A ready-to-run anchored prefix matcher for the (single) pattern. The second
-    /// argument is the char preceding the slice (for `^`/`\b` context).
+    /// argument is the char preceding the slice (for `^`/`\b` context). Searched
+    /// leftmost-first whatever the test's mode.
     Run(BoxedMatcher),
+    /// A ready-to-run engine for the (single) pattern, searched in the test's mode.
+    RunEngine(BoxedEngine),
+    /// A ready-to-run anchored matcher for every pattern of a set.
+    RunSet(BoxedSetMatcher),
+    /// [`Prepared::Run`] for a bytes-mode matcher.
+    RunBytes(BoxedBytesMatcher),
+    /// [`Prepared::RunEngine`] for a bytes-mode engine.
+    RunBytesEngine(BoxedBytesEngine),
+    /// [`Prepared::RunSet`] for a bytes-mode set.
+    RunBytesSet(BoxedBytesSetMatcher),
 }
 
 struct Summary {
@@ -63,6 +85,23 @@ impl Summary {
         }
     }
 
+    /// Time the engine's search in isolation (the comparison harness in `passes` adds
+    /// its own, unrelated overhead).
+    fn time_search<T>(&mut self, search: impl FnOnce() -> T) {
+        let started = Instant::now();
+        let _ = search();
+        self.match_time += started.elapsed();
+        self.searches += 1;
+    }
+
+    fn record(&mut self, test: &RegexTest, passed: bool) {
+        if passed {
+            self.pass += 1;
+        } else {
+            self.fail_to_pass.push(test.full_name().to_string());
+        }
+    }
+
     fn report(&self) {
         let total = self.pass + self.fail_to_parse.len() as u32 + self.fail_to_pass.len() as u32 + self.skipped;
         println!("\n=== {} ===", self.label);
@@ -79,7 +118,7 @@ impl Summary {
     }
 }
 
-fn summarize(label: &str, tests: &RegexTests, prepare: impl Fn(&RegexTest) -> Prepared) -> Summary {
+fn summarize<'t>(label: &str, tests: impl IntoIterator<Item = &'t RegexTest>, prepare: impl Fn(&RegexTest) -> Prepared) -> Summary {
     let mut summary = Summary {
         label: label.to_string(),
         pass: 0,
@@ -91,28 +130,35 @@ fn summarize(label: &str, tests: &RegexTests, prepare: impl Fn(&RegexTest) -> Pr
         wall_time: Duration::ZERO,
     };
     let started = Instant::now();
-    for test in tests.iter() {
+    for test in tests {
         match prepare(test) {
             Prepared::Skip => summary.skipped += 1,
             Prepared::FailToParse => summary.fail_to_parse.push(test.full_name().to_string()),
+            // These engines work on `&str`; a non-UTF-8 haystack isn't representable.
+            Prepared::Run(_) | Prepared::RunEngine(_) | Prepared::RunSet(_) if std::str::from_utf8(test.haystack()).is_err() => summary.skipped += 1,
             Prepared::Run(matcher) => {
-                // This engine works on `&str`; a non-UTF-8 haystack isn't representable.
-                if std::str::from_utf8(test.haystack()).is_err() {
-                    summary.skipped += 1;
-                    continue;
-                }
-                // Time the engine's search in isolation (the comparison harness in
-                // `test_passes` adds its own, unrelated overhead).
-                let search_started = Instant::now();
-                let _ = run_search(&matcher, test);
-                summary.match_time += search_started.elapsed();
-                summary.searches += 1;
-
-                if passes(test, matcher) {
-                    summary.pass += 1;
-                } else {
-                    summary.fail_to_pass.push(test.full_name().to_string());
-                }
+                summary.time_search(|| run_search(&matcher, test));
+                summary.record(test, passes(test, matcher));
+            }
+            Prepared::RunEngine(engine) => {
+                summary.time_search(|| run_engine_search(&*engine, test));
+                summary.record(test, engine_passes(test, engine));
+            }
+            Prepared::RunSet(matcher) => {
+                summary.time_search(|| run_set_search(&matcher, test));
+                summary.record(test, set_passes(test, matcher));
+            }
+            Prepared::RunBytes(matcher) => {
+                summary.time_search(|| run_bytes_search(&matcher, test));
+                summary.record(test, bytes_passes(test, matcher));
+            }
+            Prepared::RunBytesEngine(engine) => {
+                summary.time_search(|| run_bytes_engine_search(&*engine, test));
+                summary.record(test, bytes_engine_passes(test, engine));
+            }
+            Prepared::RunBytesSet(matcher) => {
+                summary.time_search(|| run_bytes_set_search(&matcher, test));
+                summary.record(test, bytes_set_passes(test, matcher));
             }
         }
     }
@@ -120,50 +166,109 @@ fn summarize(label: &str, tests: &RegexTests, prepare: impl Fn(&RegexTest) -> Pr
     summary
 }
 
-/// Both engines share one `#[test]` so they run sequentially — `test_passes`
+/// The test's single pattern, parsed for the bytes engine if its matches may split a
+/// UTF-8 sequence.
+fn parse(test: &RegexTest) -> Option<Regex> {
+    let pattern = effective_pattern(test);
+    if test.utf8() { Regex::parse(&pattern) } else { Regex::parse_bytes(&pattern) }.ok()
+}
+
+/// The engines share one `#[test]` so they run sequentially — `test_passes`
 /// swaps the global panic hook, which would race across parallel test threads.
 #[test]
 fn conformance_summary() {
     let tests = load_corpus();
+    let sets: Vec<&RegexTest> = tests.iter().filter(|test| test.regexes().len() != 1).collect();
 
-    // Runtime interpreter: `Regex::find_prefix` walks the DFA directly.
-    let runtime = summarize("runtime interpreter", &tests, |test| {
+    // Runtime interpreter: `Regex` walks its DFAs directly, searched in each test's mode.
+    let runtime = summarize("runtime interpreter", tests.iter(), |test| {
         let [_] = test.regexes() else {
             return Prepared::Skip; // regex sets are out of scope for this engine
         };
-        match Regex::parse(&effective_pattern(test)) {
-            Some(regex) => Prepared::Run(Box::new(move |input, prev| regex.find_prefix(input, prev))),
+        match parse(test) {
+            Some(regex) if regex.is_bytes() => Prepared::RunBytesEngine(Box::new(regex)),
+            Some(regex) => Prepared::RunEngine(Box::new(regex)),
             None => Prepared::FailToParse,
         }
     });
 
-    // Compiled-Rust engine: the matchers `build.rs` emitted via `generate_parser`.
-    let compiled = summarize("compiled-rust engine", &tests, |test| {
+    // Pike VM: every pattern run through the lock-step NFA simulation, whether or not
+    // `find_prefix` would pick it for that pattern.
+    let pike_vm = summarize("pike vm", tests.iter(), |test| {
+        let [_] = test.regexes() else {
+            return Prepared::Skip;
+        };
+        match parse(test) {
+            Some(regex) if regex.is_bytes() => Prepared::RunBytesEngine(Box::new(regex.pike_vm().clone())),
+            Some(regex) => Prepared::RunEngine(Box::new(regex.pike_vm().clone())),
+            None => Prepared::FailToParse,
+        }
+    });
+
+    // Compiled-Rust engine: the matchers `build.rs` emitted via `generate_parser`. They
+    // are leftmost-first prefix matchers, so every test is searched leftmost-first.
+    let compiled = summarize("compiled-rust engine", tests.iter(), |test| {
         let [_] = test.regexes() else {
             return Prepared::Skip;
         };
         // Use the parser to tell "couldn't parse" apart from "parsed but no matcher".
-        if Regex::parse(&effective_pattern(test)).is_none() {
+        if parse(test).is_none() {
             return Prepared::FailToParse;
         }
+        if !test.utf8() {
+            return match compiled_bytes_lookup(test.full_name()) {
+                Some(matcher) => Prepared::RunBytes(Box::new(matcher)),
+                None => Prepared::FailToParse,
+            };
+        }
         match compiled_lookup(test.full_name()) {
             Some(matcher) => Prepared::Run(Box::new(matcher)),
             None => Prepared::FailToParse,
         }
     });
 
+    // Regex sets: every multi-pattern test, through `RegexSet::find_prefix_all` and the
+    // matcher `build.rs` emitted via `RegexSet::generate_parser`.
+    let set_runtime = summarize("regex set interpreter", sets.iter().copied(), |test| {
+        let patterns = effective_patterns(test);
+        let patterns = patterns.iter().map(String::as_str);
+        if !test.utf8() {
+            return match RegexSet::new_bytes(patterns) {
+                Ok(set) => Prepared::RunBytesSet(Box::new(move |input, prev| set.find_prefix_all_bytes(input, prev))),
+                Err(_) => Prepared::FailToParse,
+            };
+        }
+        match RegexSet::new(patterns) {
+            Ok(set) => Prepared::RunSet(Box::new(move |input, prev| set.find_prefix_all(input, prev))),
+            Err(_) => Prepared::FailToParse,
+        }
+    });
+    let set_compiled = summarize("regex set compiled-rust engine", sets.iter().copied(), |test| {
+        if !test.utf8() {
+            return match compiled_bytes_set_lookup(test.full_name()) {
+                Some(matcher) => Prepared::RunBytesSet(Box::new(matcher)),
+                None => Prepared::FailToParse,
+            };
+        }
+        match compiled_set_lookup(test.full_name()) {
+            Some(matcher) => Prepared::RunSet(Box::new(matcher)),
+            None => Prepared::FailToParse,
+        }
+    });
+
     // JIT engine (feature-gated): Cranelift compiles the same DFA to native code. It must
     // agree with the other two, since all three are driven from the same DFA.
     #[cfg(feature = "jit")]
-    let jit = summarize("jit engine", &tests, |test| {
+    let jit = summarize("jit engine", tests.iter(), |test| {
         let [_] = test.regexes() else {
             return Prepared::Skip;
         };
-        match Regex::parse(&effective_pattern(test)) {
+        match parse(test) {
             // A JIT build failure (e.g. a non-64-bit host) counts as fail-to-parse, the same
             // bucket the compiled engine uses when it has no matcher for a parsed pattern.
             Some(regex) => match regex.compile_jit() {
-                Ok(jit) => Prepared::Run(Box::new(move |input, prev| jit.find_prefix(input, prev))),
+                Ok(jit) if regex.is_bytes() => Prepared::RunBytesEngine(Box::new(jit)),
+                Ok(jit) => Prepared::RunEngine(Box::new(jit)),
                 Err(_) => Prepared::FailToParse,
             },
             None => Prepared::FailToParse,
@@ -172,20 +277,26 @@ fn conformance_summary() {
 
     // Failing-test names first, so the numeric summaries land at the end.
     runtime.report_failures();
+    pike_vm.report_failures();
     compiled.report_failures();
+    set_runtime.report_failures();
+    set_compiled.report_failures();
     #[cfg(feature = "jit")]
     jit.report_failures();
 
     runtime.report();
+    pike_vm.report();
     compiled.report();
+    set_runtime.report();
+    set_compiled.report();
     #[cfg(feature = "jit")]
     jit.report();
 
-    println!("\n=== total ===");
-    #[cfg(not(feature = "jit"))]
-    println!("  wall time:     {:.3?}", runtime.wall_time + compiled.wall_time);
+    let wall_time = runtime.wall_time + pike_vm.wall_time + compiled.wall_time + set_runtime.wall_time + set_compiled.wall_time;
     #[cfg(feature = "jit")]
-    println!("  wall time:     {:.3?}", runtime.wall_time + compiled.wall_time + jit.wall_time);
+    let wall_time = wall_time + jit.wall_time;
+    println!("\n=== total ===");
+    println!("  wall time:     {:.3?}", wall_time);
 
     // Unlike the interpreter-vs-compiled comparison (a bring-up harness that never fails),
     // the JIT *must* agree with the interpreter exactly — both walk the same DFA — so any